@@ -10,7 +10,7 @@ use time::macros::{date, datetime};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let mut client = HistoricalClient::builder().key_from_env()?.build()?;
+    let client = HistoricalClient::builder().key_from_env()?.build()?;
     let mut decoder = client
         .timeseries()
         .get_range(
@@ -65,7 +65,7 @@ async fn main() -> anyhow::Result<()> {
 }
 
 async fn fetch_symbols_to_parent(metadata: &Metadata) -> anyhow::Result<HashMap<String, String>> {
-    let mut client = HistoricalClient::builder().key_from_env()?.build()?;
+    let client = HistoricalClient::builder().key_from_env()?.build()?;
     let end = metadata.end().ok_or_else(|| {
         anyhow::format_err!("Missing end in metadata. This script is intended for historical data")
     })?;
@@ -0,0 +1,507 @@
+//! Aggregating market-by-order (MBO) records into market-by-price (MBP) records locally,
+//! such as for book-level views when only MBO data is available.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Bound,
+};
+
+use dbn::{
+    enums::{rtype, Action, Side},
+    BidAskPair, MboMsg, Mbp10Msg, Mbp1Msg, RecordHeader, UNDEF_PRICE,
+};
+
+/// Converts MBO records into MBP-1 or MBP-10 records by maintaining an aggregated book
+/// for each instrument. Records must be passed in the order they're received, starting
+/// from a clear book such as the start of a session or a snapshot.
+///
+/// A converter should only be used to produce records of one depth.
+#[derive(Debug, Clone, Default)]
+pub struct MbpConverter {
+    books: HashMap<u32, LevelBook>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct LevelBook {
+    orders: HashMap<u64, Order>,
+    levels: PriceLevels,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Order {
+    side: Side,
+    price: i64,
+    size: u32,
+}
+
+/// The resting orders of a book aggregated by side and price. Shared by
+/// [`MbpConverter`] and the live snapshot `Book` so both aggregate levels the same way.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PriceLevels {
+    bids: BTreeMap<i64, Level>,
+    asks: BTreeMap<i64, Level>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Level {
+    pub size: u64,
+    pub count: u32,
+}
+
+impl MbpConverter {
+    /// Creates a new converter with empty books.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies `mbo` to the book of its instrument and returns the resulting MBP-1
+    /// record if it's a trade or changed the top of the book.
+    ///
+    /// # Errors
+    /// This function returns an error if `mbo` contains an invalid `action` or `side`.
+    pub fn convert_mbp1(&mut self, mbo: &MboMsg) -> crate::Result<Option<Mbp1Msg>> {
+        Ok(self.convert::<1>(mbo)?.map(|(depth, levels)| Mbp1Msg {
+            hd: RecordHeader::new::<Mbp1Msg>(
+                rtype::MBP_1,
+                mbo.hd.publisher_id,
+                mbo.hd.instrument_id,
+                mbo.hd.ts_event,
+            ),
+            price: mbo.price,
+            size: mbo.size,
+            action: mbo.action,
+            side: mbo.side,
+            flags: mbo.flags,
+            depth,
+            ts_recv: mbo.ts_recv,
+            ts_in_delta: mbo.ts_in_delta,
+            sequence: mbo.sequence,
+            levels,
+        }))
+    }
+
+    /// Applies `mbo` to the book of its instrument and returns the resulting MBP-10
+    /// record if it's a trade or changed one of the top 10 levels of the book.
+    ///
+    /// # Errors
+    /// This function returns an error if `mbo` contains an invalid `action` or `side`.
+    pub fn convert_mbp10(&mut self, mbo: &MboMsg) -> crate::Result<Option<Mbp10Msg>> {
+        Ok(self.convert::<10>(mbo)?.map(|(depth, levels)| Mbp10Msg {
+            hd: RecordHeader::new::<Mbp10Msg>(
+                rtype::MBP_10,
+                mbo.hd.publisher_id,
+                mbo.hd.instrument_id,
+                mbo.hd.ts_event,
+            ),
+            price: mbo.price,
+            size: mbo.size,
+            action: mbo.action,
+            side: mbo.side,
+            flags: mbo.flags,
+            depth,
+            ts_recv: mbo.ts_recv,
+            ts_in_delta: mbo.ts_in_delta,
+            sequence: mbo.sequence,
+            levels,
+        }))
+    }
+
+    /// Returns the top `N` aggregated levels of the book of the instrument with
+    /// `instrument_id`. Missing levels have undefined prices and zero sizes.
+    pub fn levels<const N: usize>(&self, instrument_id: u32) -> [BidAskPair; N] {
+        self.books
+            .get(&instrument_id)
+            .map_or_else(|| [empty_level(); N], LevelBook::levels::<N>)
+    }
+
+    /// Applies `mbo` and returns the depth of the change and the top `N` levels if the
+    /// change should be published at depth `N`.
+    fn convert<const N: usize>(
+        &mut self,
+        mbo: &MboMsg,
+    ) -> crate::Result<Option<(u8, [BidAskPair; N])>> {
+        let book = self.books.entry(mbo.hd.instrument_id).or_default();
+        let depth = match mbo.action()? {
+            Action::Add => {
+                let order = Order::try_from(mbo)?;
+                if let Some(prev) = book.orders.insert(mbo.order_id, order) {
+                    book.remove(&prev);
+                }
+                book.add(&order);
+                book.depth(order.side, order.price, N)
+            }
+            Action::Modify => {
+                let order = Order::try_from(mbo)?;
+                if let Some(prev) = book.orders.insert(mbo.order_id, order) {
+                    let prev_depth = book.depth(prev.side, prev.price, N);
+                    book.remove(&prev);
+                    book.add(&order);
+                    prev_depth.min(book.depth(order.side, order.price, N))
+                } else {
+                    book.add(&order);
+                    book.depth(order.side, order.price, N)
+                }
+            }
+            Action::Cancel => {
+                let Some(order) = book.orders.get_mut(&mbo.order_id) else {
+                    return Ok(None);
+                };
+                let prev = *order;
+                order.size = order.size.saturating_sub(mbo.size);
+                let order = *order;
+                let depth = book.depth(prev.side, prev.price, N);
+                book.remove(&prev);
+                if order.size == 0 {
+                    book.orders.remove(&mbo.order_id);
+                } else {
+                    book.add(&order);
+                }
+                depth
+            }
+            Action::Clear => {
+                *book = LevelBook::default();
+                0
+            }
+            Action::Trade => 0,
+            Action::Fill | Action::None => return Ok(None),
+        };
+        Ok((depth < N).then(|| (depth as u8, book.levels())))
+    }
+}
+
+impl LevelBook {
+    fn add(&mut self, order: &Order) {
+        self.levels.add(order.side, order.price, order.size);
+    }
+
+    fn remove(&mut self, order: &Order) {
+        self.levels.remove(order.side, order.price, order.size);
+    }
+
+    fn depth(&self, side: Side, price: i64, max: usize) -> usize {
+        self.levels.depth(side, price, max)
+    }
+
+    fn levels<const N: usize>(&self) -> [BidAskPair; N] {
+        let mut levels = [empty_level(); N];
+        for (pair, (price, level)) in levels.iter_mut().zip(self.levels.bids()) {
+            pair.bid_px = price;
+            pair.bid_sz = u32::try_from(level.size).unwrap_or(u32::MAX);
+            pair.bid_ct = level.count;
+        }
+        for (pair, (price, level)) in levels.iter_mut().zip(self.levels.asks()) {
+            pair.ask_px = price;
+            pair.ask_sz = u32::try_from(level.size).unwrap_or(u32::MAX);
+            pair.ask_ct = level.count;
+        }
+        levels
+    }
+}
+
+impl PriceLevels {
+    fn side_mut(&mut self, side: Side) -> Option<&mut BTreeMap<i64, Level>> {
+        match side {
+            Side::Bid => Some(&mut self.bids),
+            Side::Ask => Some(&mut self.asks),
+            Side::None => None,
+        }
+    }
+
+    /// Adds an order to its level. Orders without a side aren't part of any level.
+    pub fn add(&mut self, side: Side, price: i64, size: u32) {
+        if let Some(levels) = self.side_mut(side) {
+            let level = levels.entry(price).or_default();
+            level.size += u64::from(size);
+            level.count += 1;
+        }
+    }
+
+    /// Removes an order previously passed to [`add()`](Self::add) from its level.
+    pub fn remove(&mut self, side: Side, price: i64, size: u32) {
+        if let Some(levels) = self.side_mut(side) {
+            if let Some(level) = levels.get_mut(&price) {
+                level.size = level.size.saturating_sub(u64::from(size));
+                level.count = level.count.saturating_sub(1);
+                if level.count == 0 {
+                    levels.remove(&price);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of better levels than `price` on `side`, up to `max`. Orders
+    /// without a side are never published.
+    pub fn depth(&self, side: Side, price: i64, max: usize) -> usize {
+        match side {
+            Side::Bid => self
+                .bids
+                .range((Bound::Excluded(price), Bound::Unbounded))
+                .take(max)
+                .count(),
+            Side::Ask => self.asks.range(..price).take(max).count(),
+            Side::None => max,
+        }
+    }
+
+    /// Returns the bid levels from best (highest) to worst price.
+    pub fn bids(&self) -> impl Iterator<Item = (i64, Level)> + '_ {
+        self.bids
+            .iter()
+            .rev()
+            .map(|(&price, &level)| (price, level))
+    }
+
+    /// Returns the ask levels from best (lowest) to worst price.
+    pub fn asks(&self) -> impl Iterator<Item = (i64, Level)> + '_ {
+        self.asks.iter().map(|(&price, &level)| (price, level))
+    }
+}
+
+impl TryFrom<&MboMsg> for Order {
+    type Error = crate::Error;
+
+    fn try_from(mbo: &MboMsg) -> crate::Result<Self> {
+        Ok(Self {
+            side: mbo.side()?,
+            price: mbo.price,
+            size: mbo.size,
+        })
+    }
+}
+
+fn empty_level() -> BidAskPair {
+    BidAskPair {
+        bid_px: UNDEF_PRICE,
+        ask_px: UNDEF_PRICE,
+        bid_sz: 0,
+        ask_sz: 0,
+        bid_ct: 0,
+        ask_ct: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::c_char;
+
+    use dbn::{
+        decode::{DbnDecoder, DecodeRecord},
+        Schema,
+    };
+
+    use super::*;
+    use crate::zst_test_data_path;
+
+    fn mbo(order_id: u64, action: Action, side: Side, price: i64, size: u32) -> MboMsg {
+        MboMsg {
+            hd: RecordHeader::new::<MboMsg>(rtype::MBO, 1, 10, 0),
+            order_id,
+            price,
+            size,
+            action: action as u8 as c_char,
+            side: side as u8 as c_char,
+            ..Default::default()
+        }
+    }
+
+    fn top(rec: &Mbp1Msg) -> (i64, u32, u32, i64, u32, u32) {
+        let level = &rec.levels[0];
+        (
+            level.bid_px,
+            level.bid_sz,
+            level.bid_ct,
+            level.ask_px,
+            level.ask_sz,
+            level.ask_ct,
+        )
+    }
+
+    #[test]
+    fn test_convert_mbp1() {
+        let mut target = MbpConverter::new();
+        let rec = target
+            .convert_mbp1(&mbo(1, Action::Add, Side::Bid, 100, 5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(rec.hd.rtype, rtype::MBP_1);
+        assert_eq!(rec.hd.instrument_id, 10);
+        assert_eq!(rec.depth, 0);
+        assert_eq!(top(&rec), (100, 5, 1, UNDEF_PRICE, 0, 0));
+        let rec = target
+            .convert_mbp1(&mbo(2, Action::Add, Side::Bid, 100, 3))
+            .unwrap()
+            .unwrap();
+        assert_eq!(top(&rec), (100, 8, 2, UNDEF_PRICE, 0, 0));
+        // Below the top of the book
+        assert!(target
+            .convert_mbp1(&mbo(3, Action::Add, Side::Bid, 99, 1))
+            .unwrap()
+            .is_none());
+        let rec = target
+            .convert_mbp1(&mbo(4, Action::Add, Side::Ask, 101, 2))
+            .unwrap()
+            .unwrap();
+        assert_eq!(top(&rec), (100, 8, 2, 101, 2, 1));
+        let rec = target
+            .convert_mbp1(&mbo(2, Action::Cancel, Side::Bid, 100, 1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(top(&rec), (100, 7, 2, 101, 2, 1));
+        // Trades don't change the book
+        let rec = target
+            .convert_mbp1(&mbo(0, Action::Trade, Side::Ask, 100, 5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(rec.action, b'T' as c_char);
+        assert_eq!(top(&rec), (100, 7, 2, 101, 2, 1));
+        assert!(target
+            .convert_mbp1(&mbo(1, Action::Fill, Side::Bid, 100, 5))
+            .unwrap()
+            .is_none());
+        // Moving the best bid away from the top
+        let rec = target
+            .convert_mbp1(&mbo(1, Action::Modify, Side::Bid, 98, 5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(top(&rec), (100, 2, 1, 101, 2, 1));
+        let rec = target
+            .convert_mbp1(&mbo(2, Action::Cancel, Side::Bid, 100, 2))
+            .unwrap()
+            .unwrap();
+        assert_eq!(top(&rec), (99, 1, 1, 101, 2, 1));
+        // Unknown orders are ignored
+        assert!(target
+            .convert_mbp1(&mbo(9, Action::Cancel, Side::Bid, 99, 1))
+            .unwrap()
+            .is_none());
+        let rec = target
+            .convert_mbp1(&mbo(0, Action::Clear, Side::None, UNDEF_PRICE, 0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(top(&rec), (UNDEF_PRICE, 0, 0, UNDEF_PRICE, 0, 0));
+    }
+
+    #[test]
+    fn test_convert_mbp10() {
+        let mut target = MbpConverter::new();
+        for (order_id, price) in (1..=12).zip((90..102).rev()) {
+            target
+                .convert_mbp10(&mbo(order_id, Action::Add, Side::Bid, price, 1))
+                .unwrap();
+        }
+        // Beyond the top 10 levels
+        assert!(target
+            .convert_mbp10(&mbo(13, Action::Add, Side::Bid, 91, 1))
+            .unwrap()
+            .is_none());
+        let rec = target
+            .convert_mbp10(&mbo(14, Action::Add, Side::Bid, 95, 4))
+            .unwrap()
+            .unwrap();
+        assert_eq!(rec.depth, 6);
+        let levels = target.levels::<10>(10);
+        assert_eq!(rec.levels, levels);
+        assert_eq!(
+            levels.map(|level| level.bid_px),
+            [101, 100, 99, 98, 97, 96, 95, 94, 93, 92]
+        );
+        assert_eq!(levels[6].bid_sz, 5);
+        assert_eq!(levels[6].bid_ct, 2);
+        assert!(levels.iter().all(|level| level.ask_px == UNDEF_PRICE));
+        assert_eq!(target.levels::<1>(11)[0], empty_level());
+    }
+
+    #[test]
+    fn test_convert_mbp10_matches_test_data() {
+        let mbos = DbnDecoder::from_zstd_file(zst_test_data_path(Schema::Mbo))
+            .unwrap()
+            .decode_records::<MboMsg>()
+            .unwrap();
+        let mbp10s = DbnDecoder::from_zstd_file(zst_test_data_path(Schema::Mbp10))
+            .unwrap()
+            .decode_records::<Mbp10Msg>()
+            .unwrap();
+        let expected = &mbp10s[0];
+        // Same event as the first MBO record
+        assert_eq!(expected.sequence, mbos[0].sequence);
+        assert_eq!(expected.hd.ts_event, mbos[0].hd.ts_event);
+
+        // Seed the book with the levels from before the event, which are the published
+        // levels plus the order it cancels
+        let mut target = MbpConverter::new();
+        let mut seed = |order_id: u64, side: Side, price: i64, size: u32| {
+            let mut add = mbo(order_id, Action::Add, side, price, size);
+            add.hd.publisher_id = expected.hd.publisher_id;
+            add.hd.instrument_id = expected.hd.instrument_id;
+            target.convert_mbp10(&add).unwrap();
+        };
+        let mut order_id = 1;
+        for level in expected.levels.iter() {
+            for (side, price, size, count) in [
+                (Side::Bid, level.bid_px, level.bid_sz, level.bid_ct),
+                (Side::Ask, level.ask_px, level.ask_sz, level.ask_ct),
+            ] {
+                for i in 0..count {
+                    // All of the remaining size on the last order
+                    let order_size = if i + 1 == count { size - i } else { 1 };
+                    seed(order_id, side, price, order_size);
+                    order_id += 1;
+                }
+            }
+        }
+        for cancel in mbos.iter() {
+            seed(
+                cancel.order_id,
+                cancel.side().unwrap(),
+                cancel.price,
+                cancel.size,
+            );
+        }
+
+        let rec = target.convert_mbp10(&mbos[0]).unwrap().unwrap();
+        assert_eq!(rec, *expected);
+        // The second cancel is beyond the top 10 levels
+        assert!(target.convert_mbp10(&mbos[1]).unwrap().is_none());
+        assert_eq!(
+            target.levels::<10>(expected.hd.instrument_id),
+            expected.levels
+        );
+    }
+
+    #[cfg(feature = "live")]
+    #[test]
+    fn test_matches_snapshot_book() {
+        use crate::live::snapshot::Book;
+
+        let mbos = [
+            mbo(1, Action::Add, Side::Bid, 100, 5),
+            mbo(2, Action::Add, Side::Ask, 103, 2),
+            mbo(3, Action::Add, Side::Bid, 101, 7),
+            mbo(4, Action::Add, Side::Ask, 102, 1),
+            mbo(1, Action::Modify, Side::Bid, 101, 3),
+            mbo(4, Action::Cancel, Side::Ask, 102, 1),
+            mbo(5, Action::Add, Side::Ask, 102, 6),
+        ];
+        let mut target = MbpConverter::new();
+        let mut book = Book::default();
+        for mbo in mbos.iter() {
+            target.convert_mbp10(mbo).unwrap();
+            book.apply(mbo);
+            let levels = target.levels::<10>(10);
+            for (pair, level) in levels.iter().zip(book.bids()) {
+                assert_eq!(
+                    (pair.bid_px, u64::from(pair.bid_sz)),
+                    (level.price, level.size)
+                );
+                assert_eq!(pair.bid_ct, level.count);
+            }
+            for (pair, level) in levels.iter().zip(book.asks()) {
+                assert_eq!(
+                    (pair.ask_px, u64::from(pair.ask_sz)),
+                    (level.price, level.size)
+                );
+                assert_eq!(pair.ask_ct, level.count);
+            }
+        }
+    }
+}
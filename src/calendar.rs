@@ -0,0 +1,309 @@
+//! Trading session calendars of the venues whose data is available from Databento, such
+//! as for skipping days when a venue is closed.
+//!
+//! Built-in calendars only know each venue's weekly trading hours. Holidays can be
+//! added with [`WeeklyCalendar::with_holidays()`], and other calendars can be
+//! registered by implementing [`TradingCalendar`].
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    ops::{Deref, Range},
+    sync::Arc,
+};
+
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time, Weekday};
+
+use crate::timezone::TimeZone;
+
+/// A calendar of the trading sessions of a venue.
+pub trait TradingCalendar: fmt::Debug + Send + Sync {
+    /// Returns the time zone of the venue.
+    fn time_zone(&self) -> TimeZone;
+
+    /// Returns the session with the trade date `date`, or `None` if the venue is
+    /// closed on that date.
+    fn session(&self, date: Date) -> Option<TradingSession>;
+
+    /// Returns `true` if there's a session with the trade date `date`.
+    fn is_trading_day(&self, date: Date) -> bool {
+        self.session(date).is_some()
+    }
+
+    /// Returns the UTC dates in `dates` that don't overlap with any session, including
+    /// extended hours, in ascending order.
+    fn closed_days(&self, dates: Range<Date>) -> Vec<Date> {
+        let mut closed = Vec::new();
+        let mut date = dates.start;
+        while date < dates.end {
+            let Some(next) = date.next_day() else {
+                break;
+            };
+            let day = date.midnight().assume_utc()..next.midnight().assume_utc();
+            // Sessions can start on the previous calendar day or end on the next one
+            let is_open = [date.previous_day(), Some(date), Some(next)]
+                .into_iter()
+                .flatten()
+                .filter_map(|trade_date| self.session(trade_date))
+                .any(|session| {
+                    let hours = session.extended.as_ref().unwrap_or(&session.regular);
+                    hours.start < day.end && day.start < hours.end
+                });
+            if !is_open {
+                closed.push(date);
+            }
+            date = next;
+        }
+        closed
+    }
+}
+
+/// The trading hours of a single session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TradingSession {
+    /// The trade date of the session.
+    pub date: Date,
+    /// The regular trading hours.
+    pub regular: Range<OffsetDateTime>,
+    /// The extended trading hours, including pre- and post-market trading, if the venue
+    /// has any.
+    pub extended: Option<Range<OffsetDateTime>>,
+}
+
+/// The local opening and closing times of a session. If `open` is later than `close`,
+/// the session opens on the calendar day before its trade date, as with CME Globex.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionHours {
+    /// The local opening time.
+    pub open: Time,
+    /// The local closing time.
+    pub close: Time,
+}
+
+/// A calendar with the same trading hours every weekday except holidays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeeklyCalendar {
+    time_zone: TimeZone,
+    regular: SessionHours,
+    extended: Option<SessionHours>,
+    holidays: BTreeSet<Date>,
+}
+
+/// A shared [`TradingCalendar`].
+#[derive(Debug, Clone)]
+pub struct Calendar(Arc<dyn TradingCalendar>);
+
+/// The calendars of a set of datasets. Datasets without a registered calendar fall
+/// back to the built-in calendar of their venue, if any.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CalendarRegistry {
+    calendars: HashMap<String, Calendar>,
+}
+
+impl SessionHours {
+    /// Creates new session hours from the local opening and closing times.
+    pub const fn new(open: Time, close: Time) -> Self {
+        Self { open, close }
+    }
+
+    fn on(&self, time_zone: TimeZone, date: Date) -> Option<Range<OffsetDateTime>> {
+        let open_date = if self.open > self.close {
+            date.previous_day()?
+        } else {
+            date
+        };
+        Some(
+            time_zone.localize(PrimitiveDateTime::new(open_date, self.open))
+                ..time_zone.localize(PrimitiveDateTime::new(date, self.close)),
+        )
+    }
+}
+
+impl WeeklyCalendar {
+    /// Creates a new calendar with sessions from Monday to Friday during `regular`
+    /// hours in `time_zone`.
+    pub fn new(time_zone: TimeZone, regular: SessionHours) -> Self {
+        Self {
+            time_zone,
+            regular,
+            extended: None,
+            holidays: BTreeSet::new(),
+        }
+    }
+
+    /// Sets the extended trading hours.
+    pub fn with_extended(mut self, extended: SessionHours) -> Self {
+        self.extended = Some(extended);
+        self
+    }
+
+    /// Adds `holidays` as trade dates without a session.
+    pub fn with_holidays(mut self, holidays: impl IntoIterator<Item = Date>) -> Self {
+        self.holidays.extend(holidays);
+        self
+    }
+
+    /// Returns the built-in calendar of the venue of `dataset`, such as US equity
+    /// trading hours for `XNAS.ITCH`, or `None` if the venue is unknown.
+    pub fn for_dataset(dataset: &str) -> Option<Self> {
+        let venue = dataset.split_once('.').map_or(dataset, |(venue, _)| venue);
+        let hours = |open: (u8, u8), close: (u8, u8)| {
+            // Always valid times
+            SessionHours::new(
+                Time::from_hms(open.0, open.1, 0).unwrap(),
+                Time::from_hms(close.0, close.1, 0).unwrap(),
+            )
+        };
+        Some(match venue {
+            "GLBX" => Self::new(TimeZone::Chicago, hours((17, 0), (16, 0))),
+            "OPRA" => Self::new(TimeZone::NewYork, hours((9, 30), (16, 15))),
+            "ARCX" | "BATS" | "BATY" | "DBEQ" | "EDGA" | "EDGX" | "EPRL" | "EQUS" | "FINN"
+            | "FINY" | "IEXG" | "MEMX" | "XASE" | "XBOS" | "XCHI" | "XCIS" | "XNAS" | "XNYS"
+            | "XPSX" => Self::new(TimeZone::NewYork, hours((9, 30), (16, 0)))
+                .with_extended(hours((4, 0), (20, 0))),
+            "IFUS" => Self::new(TimeZone::NewYork, hours((20, 0), (18, 0))),
+            "IFEU" | "NDEX" => Self::new(TimeZone::London, hours((1, 0), (23, 0))),
+            "XEEE" | "XEUR" => Self::new(TimeZone::CentralEurope, hours((1, 10), (22, 0))),
+            _ => return None,
+        })
+    }
+
+    /// Returns the regular trading hours.
+    pub fn regular(&self) -> SessionHours {
+        self.regular
+    }
+
+    /// Returns the extended trading hours, if any.
+    pub fn extended(&self) -> Option<SessionHours> {
+        self.extended
+    }
+
+    /// Returns the holidays in ascending order.
+    pub fn holidays(&self) -> impl Iterator<Item = Date> + '_ {
+        self.holidays.iter().copied()
+    }
+}
+
+impl TradingCalendar for WeeklyCalendar {
+    fn time_zone(&self) -> TimeZone {
+        self.time_zone
+    }
+
+    fn session(&self, date: Date) -> Option<TradingSession> {
+        if matches!(date.weekday(), Weekday::Saturday | Weekday::Sunday)
+            || self.holidays.contains(&date)
+        {
+            return None;
+        }
+        Some(TradingSession {
+            date,
+            regular: self.regular.on(self.time_zone, date)?,
+            extended: match self.extended {
+                Some(extended) => Some(extended.on(self.time_zone, date)?),
+                None => None,
+            },
+        })
+    }
+}
+
+impl Calendar {
+    /// Creates a new shared calendar from `calendar`.
+    pub fn new(calendar: impl TradingCalendar + 'static) -> Self {
+        Self(Arc::new(calendar))
+    }
+}
+
+impl Deref for Calendar {
+    type Target = dyn TradingCalendar;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl PartialEq for Calendar {
+    /// Calendars are equal if they share the same instance.
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Calendar {}
+
+impl CalendarRegistry {
+    /// Creates a new registry with only the built-in calendars.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `calendar` for `dataset`, replacing any previous one.
+    pub fn register(&mut self, dataset: impl ToString, calendar: Calendar) -> &mut Self {
+        self.calendars.insert(dataset.to_string(), calendar);
+        self
+    }
+
+    /// Returns the calendar registered for `dataset`, or else the built-in calendar of
+    /// its venue.
+    pub fn get(&self, dataset: &str) -> Option<Calendar> {
+        self.calendars
+            .get(dataset)
+            .cloned()
+            .or_else(|| WeeklyCalendar::for_dataset(dataset).map(Calendar::new))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, datetime, time};
+
+    use super::*;
+
+    #[test]
+    fn test_weekly_calendar() {
+        let target = WeeklyCalendar::for_dataset("GLBX.MDP3")
+            .unwrap()
+            .with_holidays([date!(2024 - 07 - 04)]);
+        let session = target.session(date!(2024 - 07 - 01)).unwrap();
+        assert_eq!(
+            session.regular,
+            datetime!(2024-06-30 22:00 UTC)..datetime!(2024-07-01 21:00 UTC)
+        );
+        assert!(session.extended.is_none());
+        assert!(!target.is_trading_day(date!(2024 - 07 - 04)));
+        assert!(!target.is_trading_day(date!(2024 - 07 - 06)));
+        // The 4th is a holiday but has the open of the 5th's session, and Sunday has
+        // the open of Monday's session
+        assert_eq!(
+            target.closed_days(date!(2024 - 07 - 04)..date!(2024 - 07 - 09)),
+            [date!(2024 - 07 - 06)]
+        );
+
+        let target = WeeklyCalendar::for_dataset("XNAS.ITCH").unwrap();
+        let session = target.session(date!(2024 - 01 - 02)).unwrap();
+        assert_eq!(session.regular.start, datetime!(2024-01-02 14:30 UTC));
+        assert_eq!(
+            session.extended.unwrap(),
+            datetime!(2024-01-02 09:00 UTC)..datetime!(2024-01-03 01:00 UTC)
+        );
+        assert_eq!(
+            target.closed_days(date!(2024 - 01 - 05)..date!(2024 - 01 - 09)),
+            [date!(2024 - 01 - 07)]
+        );
+    }
+
+    #[test]
+    fn test_registry() {
+        let custom = Calendar::new(WeeklyCalendar::new(
+            TimeZone::Utc,
+            SessionHours::new(time!(0:00), time!(12:00)),
+        ));
+        let mut target = CalendarRegistry::new();
+        target.register("XNAS.ITCH", custom.clone());
+        assert_eq!(target.get("XNAS.ITCH"), Some(custom));
+        assert_eq!(
+            target.get("XNYS.PILLAR").unwrap().time_zone(),
+            TimeZone::NewYork
+        );
+        assert!(target.get("UNKNOWN").is_none());
+    }
+}
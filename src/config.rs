@@ -0,0 +1,299 @@
+//! Loading client settings from TOML or JSON configuration files, so connection
+//! settings can be changed without recompiling.
+//!
+//! ```toml
+//! key_env = "DATABENTO_API_KEY"
+//!
+//! [historical]
+//! oversized_request_policy = "split"
+//! pool_idle_timeout_secs = 30
+//!
+//! [live]
+//! dataset = "GLBX.MDP3"
+//! heartbeat_interval_secs = 10
+//!
+//! [[live.subscriptions]]
+//! symbols = ["ESM5"]
+//! schema = "trades"
+//! ```
+
+use std::path::Path;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{ApiKey, Error};
+
+/// The settings of the historical and live clients. Missing fields use the same
+/// defaults as the client builders.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The API key. Prefer [`key_env`](Self::key_env) to avoid storing keys in
+    /// configuration files.
+    #[serde(deserialize_with = "deserialize_key")]
+    pub key: Option<ApiKey>,
+    /// The environment variable to read the API key from when [`key`](Self::key) isn't
+    /// set. Defaults to `DATABENTO_API_KEY`.
+    pub key_env: Option<String>,
+    /// Settings for the historical client.
+    #[cfg(feature = "historical")]
+    pub historical: HistoricalConfig,
+    /// Settings for the live client.
+    #[cfg(feature = "live")]
+    pub live: LiveConfig,
+}
+
+/// Settings for [`HistoricalClient::from_config()`](crate::HistoricalClient::from_config).
+#[cfg(feature = "historical")]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoricalConfig {
+    /// Overrides the base URL of the API.
+    pub base_url: Option<String>,
+    /// Extends the user agent.
+    pub user_agent_extension: Option<String>,
+    /// The maximum number of idle connections kept per host.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// The number of seconds an idle connection is kept.
+    pub pool_idle_timeout_secs: Option<u64>,
+    /// The interval in seconds for sending HTTP/2 keep-alive pings.
+    pub http2_keep_alive_interval_secs: Option<u64>,
+    /// Whether to use HTTP/2 adaptive flow control.
+    pub http2_adaptive_window: bool,
+    /// The HTTP compression accepted for JSON responses: `"any"`, `"gzip"`,
+    /// `"deflate"`, or `"none"`.
+    pub response_compression: crate::historical::ResponseCompression,
+    /// How requests too large to stream are handled: `"error"` or `"split"`.
+    pub oversized_request_policy: crate::historical::timeseries::OversizedRequestPolicy,
+}
+
+/// Settings for [`LiveClient::from_config()`](crate::LiveClient::from_config).
+#[cfg(feature = "live")]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LiveConfig {
+    /// The dataset code. Required to create a live client.
+    pub dataset: Option<String>,
+    /// Whether the gateway should send `ts_out` after every record.
+    pub send_ts_out: bool,
+    /// The interval in seconds at which the gateway sends heartbeats.
+    pub heartbeat_interval_secs: Option<i64>,
+    /// Extends the client identifier sent to the gateway.
+    pub user_agent_extension: Option<String>,
+    /// Whether error messages from the gateway are returned as errors.
+    pub raise_gateway_errors: bool,
+    /// The local address to bind the connection to.
+    pub bind_addr: Option<std::net::IpAddr>,
+    /// The IP version used to connect to the gateway: `"any"`, `"v4"`, or `"v6"`.
+    pub ip_version: crate::live::IpVersion,
+    /// Gateway `host:port` addresses keyed by dataset code, which override the
+    /// default gateway of those datasets.
+    pub gateways: std::collections::HashMap<String, String>,
+    /// The subscriptions to make once connected.
+    pub subscriptions: Vec<crate::live::Subscription>,
+}
+
+impl Config {
+    /// Reads the configuration from the file at `path`, which is parsed as JSON if its
+    /// extension is `json` and as TOML otherwise.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to read the file or its contents are
+    /// invalid.
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json_str(&contents)
+        } else {
+            Self::from_toml_str(&contents)
+        }
+    }
+
+    /// Parses the configuration from TOML.
+    ///
+    /// # Errors
+    /// This function returns an error if `toml` is invalid.
+    pub fn from_toml_str(toml: &str) -> crate::Result<Self> {
+        toml::from_str(toml).map_err(|e| Error::bad_arg("toml", e))
+    }
+
+    /// Parses the configuration from JSON.
+    ///
+    /// # Errors
+    /// This function returns an error if `json` is invalid.
+    pub fn from_json_str(json: &str) -> crate::Result<Self> {
+        serde_json::from_str(json).map_err(|e| Error::bad_arg("json", e))
+    }
+
+    /// Returns the configured API key, reading it from the environment if it's not in
+    /// the configuration.
+    ///
+    /// # Errors
+    /// This function returns an error if the environment variable isn't set or the key
+    /// is invalid.
+    pub fn api_key(&self) -> crate::Result<ApiKey> {
+        if let Some(key) = &self.key {
+            return Ok(key.clone());
+        }
+        let Some(var) = &self.key_env else {
+            return ApiKey::from_env();
+        };
+        let key = std::env::var(var)
+            .map_err(|e| Error::bad_arg("key_env", format!("failed to read {var}: {e}")))?;
+        ApiKey::new(key)
+    }
+}
+
+#[cfg(feature = "historical")]
+impl crate::HistoricalClient {
+    /// Creates a historical client from the [`historical`](Config::historical)
+    /// settings of `config`.
+    ///
+    /// # Errors
+    /// This function returns an error when the API key is missing or invalid, or it
+    /// fails to build the HTTP client.
+    pub fn from_config(config: &Config) -> crate::Result<Self> {
+        use std::time::Duration;
+
+        let settings = &config.historical;
+        let mut builder = Self::builder()
+            .api_key(config.api_key()?)
+            .http2_adaptive_window(settings.http2_adaptive_window)
+            .response_compression(settings.response_compression)
+            .oversized_request_policy(settings.oversized_request_policy);
+        if let Some(url) = &settings.base_url {
+            builder = builder.base_url(
+                url.parse()
+                    .map_err(|e| Error::bad_arg("base_url", format!("{e:?}")))?,
+            );
+        }
+        if let Some(extension) = &settings.user_agent_extension {
+            builder = builder.user_agent_extension(extension);
+        }
+        if let Some(max) = settings.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(secs) = settings.pool_idle_timeout_secs {
+            builder = builder.pool_idle_timeout(Duration::from_secs(secs));
+        }
+        if let Some(secs) = settings.http2_keep_alive_interval_secs {
+            builder = builder.http2_keep_alive_interval(Duration::from_secs(secs));
+        }
+        builder.build()
+    }
+}
+
+#[cfg(feature = "live")]
+impl crate::LiveClient {
+    /// Connects a live client with the [`live`](Config::live) settings of `config` and
+    /// makes its subscriptions. The session still needs to be
+    /// [started](Self::start).
+    ///
+    /// # Errors
+    /// This function returns an error when the API key or dataset are missing or
+    /// invalid, it fails to connect and authenticate with the gateway, or a
+    /// subscription fails.
+    pub async fn from_config(config: &Config) -> crate::Result<Self> {
+        let settings = &config.live;
+        let dataset = settings
+            .dataset
+            .as_deref()
+            .ok_or_else(|| Error::bad_arg("dataset", "must be set to create a live client"))?;
+        let mut builder = Self::builder()
+            .api_key(config.api_key()?)
+            .dataset(dataset)
+            .send_ts_out(settings.send_ts_out)
+            .raise_gateway_errors(settings.raise_gateway_errors)
+            .ip_version(settings.ip_version)
+            .gateway_overrides(&settings.gateways);
+        if let Some(secs) = settings.heartbeat_interval_secs {
+            builder = builder.heartbeat_interval(time::Duration::seconds(secs));
+        }
+        if let Some(extension) = &settings.user_agent_extension {
+            builder = builder.user_agent_extension(extension);
+        }
+        if let Some(addr) = settings.bind_addr {
+            builder = builder.bind_addr(addr);
+        }
+        let mut client = builder.build().await?;
+        for sub in &settings.subscriptions {
+            client.subscribe(sub).await?;
+        }
+        Ok(client)
+    }
+}
+
+fn deserialize_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<ApiKey>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(ApiKey::new)
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(all(test, feature = "historical", feature = "live"))]
+mod tests {
+    use dbn::Schema;
+
+    use super::*;
+
+    const KEY: &str = "32-character-with-lots-of-filler";
+
+    #[test]
+    fn test_from_toml_str() {
+        let target = Config::from_toml_str(&format!(
+            r#"
+key = "{KEY}"
+
+[historical]
+pool_idle_timeout_secs = 30
+oversized_request_policy = "split"
+
+[live]
+dataset = "GLBX.MDP3"
+ip_version = "v4"
+
+[live.gateways]
+"GLBX.MDP3" = "relay.internal:13000"
+
+[[live.subscriptions]]
+symbols = ["ESM5"]
+schema = "trades"
+"#
+        ))
+        .unwrap();
+        assert_eq!(target.api_key().unwrap().as_str(), KEY);
+        assert_eq!(target.historical.pool_idle_timeout_secs, Some(30));
+        assert_eq!(
+            target.historical.oversized_request_policy,
+            crate::historical::timeseries::OversizedRequestPolicy::Split
+        );
+        assert_eq!(target.live.dataset.as_deref(), Some("GLBX.MDP3"));
+        assert_eq!(target.live.ip_version, crate::live::IpVersion::V4);
+        assert_eq!(target.live.gateways["GLBX.MDP3"], "relay.internal:13000");
+        assert_eq!(target.live.subscriptions.len(), 1);
+        assert_eq!(target.live.subscriptions[0].schema, Schema::Trades);
+    }
+
+    #[test]
+    fn test_from_json_str() {
+        let target = Config::from_json_str(
+            r#"{"key_env": "CONFIG_TEST_KEY", "live": {"send_ts_out": true}}"#,
+        )
+        .unwrap();
+        assert!(target.live.send_ts_out);
+        assert!(target.api_key().is_err());
+        assert!(Config::from_json_str(r#"{"unknown": 1}"#).is_err());
+        assert!(Config::from_json_str(r#"{"key": "too-short"}"#).is_err());
+    }
+
+    #[test]
+    fn test_historical_client_from_config() {
+        let config = Config::from_toml_str(&format!(
+            "key = \"{KEY}\"\n[historical]\nbase_url = \"http://localhost:8080\"\n"
+        ))
+        .unwrap();
+        let client = crate::HistoricalClient::from_config(&config).unwrap();
+        assert_eq!(client.key(), KEY);
+    }
+}
@@ -0,0 +1,107 @@
+//! Providers for supplying the API key to the clients, which allow the key to be
+//! rotated without restarting the process.
+
+use std::path::PathBuf;
+#[cfg(any(feature = "historical", feature = "live"))]
+use std::{fmt, sync::Arc};
+
+#[cfg(any(feature = "historical", feature = "live"))]
+use crate::ApiKey;
+
+/// A source of Databento API keys. The key is requested when building a client and
+/// again whenever the client refreshes its credentials, such as after an
+/// authentication failure.
+///
+/// Closures returning `crate::Result<String>` implement this trait.
+pub trait CredentialsProvider: Send + Sync {
+    /// Returns the current API key.
+    ///
+    /// # Errors
+    /// This function returns an error when the key can't be retrieved.
+    fn api_key(&self) -> crate::Result<String>;
+}
+
+impl<F> CredentialsProvider for F
+where
+    F: Fn() -> crate::Result<String> + Send + Sync,
+{
+    fn api_key(&self) -> crate::Result<String> {
+        self()
+    }
+}
+
+/// Reads the API key from the `DATABENTO_API_KEY` environment variable.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvCredentials;
+
+impl CredentialsProvider for EnvCredentials {
+    fn api_key(&self) -> crate::Result<String> {
+        crate::key_from_env()
+    }
+}
+
+/// Reads the API key from a file, ignoring any leading or trailing whitespace. The
+/// file is read again each time the key is requested.
+#[derive(Debug, Clone)]
+pub struct FileCredentials {
+    path: PathBuf,
+}
+
+impl FileCredentials {
+    /// Creates a new provider that reads the API key from the file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl CredentialsProvider for FileCredentials {
+    fn api_key(&self) -> crate::Result<String> {
+        Ok(std::fs::read_to_string(&self.path)?.trim().to_owned())
+    }
+}
+
+/// A type-erased [`CredentialsProvider`] that can be stored in the clients and their
+/// builders.
+#[cfg(any(feature = "historical", feature = "live"))]
+#[derive(Clone)]
+pub(crate) struct SharedCredentials(Arc<dyn CredentialsProvider>);
+
+#[cfg(any(feature = "historical", feature = "live"))]
+impl SharedCredentials {
+    pub fn new(provider: impl CredentialsProvider + 'static) -> Self {
+        Self(Arc::new(provider))
+    }
+
+    /// Requests the API key from the provider and validates it.
+    pub fn api_key(&self) -> crate::Result<ApiKey> {
+        ApiKey::new(self.0.api_key()?)
+    }
+}
+
+#[cfg(any(feature = "historical", feature = "live"))]
+impl fmt::Debug for SharedCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CredentialsProvider")
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_file_credentials() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "  32-character-with-lots-of-filler").unwrap();
+        let target = SharedCredentials::new(FileCredentials::new(file.path()));
+        assert_eq!(
+            target.api_key().unwrap().as_str(),
+            "32-character-with-lots-of-filler"
+        );
+        file.as_file().set_len(0).unwrap();
+        assert!(target.api_key().is_err());
+    }
+}
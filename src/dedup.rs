@@ -0,0 +1,89 @@
+//! Removing duplicate records, such as those at the boundaries of overlapping or
+//! resumed requests.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    num::NonZeroUsize,
+};
+
+/// Detects exact duplicates among the most recent records passed to
+/// [`is_duplicate()`](Self::is_duplicate). Two records are duplicates when all of
+/// their bytes are equal, which implies they share the same `rtype`, `instrument_id`,
+/// `ts_event`, and for record types that have them, `sequence` and `ts_recv`.
+///
+/// Only the last `window` unique records are remembered, which bounds memory usage
+/// when deduplicating long streams where duplicates are expected to be close together.
+#[derive(Debug, Clone)]
+pub struct Deduplicator {
+    window: NonZeroUsize,
+    recent: VecDeque<Box<[u8]>>,
+    seen: HashSet<Box<[u8]>>,
+}
+
+impl Deduplicator {
+    /// Creates a new deduplicator that remembers the last `window` unique records.
+    pub fn new(window: NonZeroUsize) -> Self {
+        Self {
+            window,
+            recent: VecDeque::with_capacity(window.get()),
+            seen: HashSet::with_capacity(window.get()),
+        }
+    }
+
+    /// Returns the number of unique records remembered.
+    pub fn window(&self) -> NonZeroUsize {
+        self.window
+    }
+
+    /// Returns `true` if `rec` is identical to one of the records in the window,
+    /// otherwise adds it to the window and returns `false`. `rec` can be any record
+    /// type, including [`RecordRef`](dbn::RecordRef) and [`RecordEnum`](dbn::RecordEnum).
+    pub fn is_duplicate(&mut self, rec: impl AsRef<[u8]>) -> bool {
+        let bytes = rec.as_ref();
+        if self.seen.contains(bytes) {
+            return true;
+        }
+        if self.recent.len() == self.window.get() {
+            if let Some(oldest) = self.recent.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        let bytes: Box<[u8]> = bytes.into();
+        self.seen.insert(bytes.clone());
+        self.recent.push_back(bytes);
+        false
+    }
+
+    /// Forgets all remembered records.
+    pub fn clear(&mut self) {
+        self.recent.clear();
+        self.seen.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{enums::rtype, record::RecordHeader, TradeMsg};
+
+    use super::*;
+
+    fn trade(instrument_id: u32, ts_event: u64, sequence: u32) -> TradeMsg {
+        TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, instrument_id, ts_event),
+            sequence,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_duplicate() {
+        let mut target = Deduplicator::new(NonZeroUsize::new(2).unwrap());
+        assert!(!target.is_duplicate(trade(1, 1, 1)));
+        assert!(target.is_duplicate(trade(1, 1, 1)));
+        assert!(!target.is_duplicate(trade(1, 1, 2)));
+        assert!(!target.is_duplicate(trade(2, 1, 1)));
+        // Evicted from the window
+        assert!(!target.is_duplicate(trade(1, 1, 1)));
+        assert!(target.is_duplicate(trade(2, 1, 1)));
+    }
+}
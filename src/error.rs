@@ -24,6 +24,16 @@ pub enum Error {
     #[cfg(feature = "historical")]
     #[error("API error: {0}")]
     Api(ApiError),
+    /// An HTTP error from a request whose parameters are known, such as a timeout or
+    /// failed connection while requesting timeseries data.
+    #[cfg(feature = "historical")]
+    #[error("HTTP error for {context}: {source:?}")]
+    Transport {
+        /// A summary of the request that failed.
+        context: Box<RequestContext>,
+        /// The underlying HTTP error.
+        source: reqwest::Error,
+    },
     /// An error internal to the client.
     #[error("internal error: {0}")]
     Internal(String),
@@ -33,6 +43,38 @@ pub enum Error {
     /// An when authentication failed.
     #[error("authentication failed: {0}")]
     Auth(String),
+    /// An operation was cancelled through a
+    /// [`CancellationToken`](crate::historical::CancellationToken).
+    #[cfg(feature = "historical")]
+    #[error("operation was cancelled")]
+    Cancelled,
+    /// A response was larger than the limit set by the caller, such as with
+    /// [`get_range_collect()`](crate::historical::timeseries::TimeseriesClient::get_range_collect).
+    #[cfg(feature = "historical")]
+    #[error("limit exceeded: {0}")]
+    LimitExceeded(String),
+    /// An error message sent by the live gateway. Only returned when
+    /// [`raise_gateway_errors`](crate::live::ClientBuilder::raise_gateway_errors) is
+    /// enabled.
+    #[cfg(feature = "live")]
+    #[error("gateway error: {0}")]
+    Gateway(GatewayError),
+    /// The live gateway's major version is later than the client supports. Only
+    /// returned when
+    /// [`require_compatible_gateway`](crate::live::ClientBuilder::require_compatible_gateway)
+    /// is enabled.
+    #[cfg(feature = "live")]
+    #[error(
+        "live gateway version {version} isn't supported by this client, which supports up \
+        to major version {max_supported_major_version}: upgrade to a newer version of \
+        databento"
+    )]
+    IncompatibleGateway {
+        /// The version of the live gateway.
+        version: String,
+        /// The latest major version supported by the client.
+        max_supported_major_version: u32,
+    },
 }
 /// An alias for a `Result` with [`databento::Error`](crate::Error) as the error type.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -49,6 +91,41 @@ pub struct ApiError {
     pub message: String,
     /// The link to documentation related to the error.
     pub docs_url: Option<String>,
+    /// A summary of the request that failed, if known.
+    pub context: Option<Box<RequestContext>>,
+}
+
+/// A summary of a historical request attached to errors to identify which request
+/// failed. It doesn't contain the API key or the symbols themselves.
+#[cfg(feature = "historical")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestContext {
+    /// The API endpoint, such as `timeseries.get_range`.
+    pub endpoint: String,
+    /// The dataset code.
+    pub dataset: String,
+    /// The data record schema, if the request had one.
+    pub schema: Option<dbn::Schema>,
+    /// The number of symbols in the request, or `None` for all symbols.
+    pub symbol_count: Option<usize>,
+    /// The inclusive start of the request time range.
+    pub start: time::OffsetDateTime,
+    /// The exclusive end of the request time range.
+    pub end: time::OffsetDateTime,
+}
+
+/// An error message sent by the live gateway, converted from an
+/// [`ErrorMsg`](dbn::ErrorMsg) record.
+#[cfg(feature = "live")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewayError {
+    /// The error code, if the gateway sent one.
+    pub code: Option<u8>,
+    /// The error message.
+    pub message: String,
+    /// Whether the error ends the session, such as an authentication or internal
+    /// error, as opposed to a recoverable one like a symbol that failed to resolve.
+    pub is_fatal: bool,
 }
 
 impl Error {
@@ -62,6 +139,51 @@ impl Error {
     pub(crate) fn internal(msg: impl ToString) -> Self {
         Self::Internal(msg.to_string())
     }
+
+    /// Returns the summary of the request that caused the error, if known.
+    #[cfg(feature = "historical")]
+    pub fn request_context(&self) -> Option<&RequestContext> {
+        match self {
+            Self::Api(err) => err.context.as_deref(),
+            Self::Transport { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "historical")]
+    pub(crate) fn with_context(self, context: RequestContext) -> Self {
+        match self {
+            Self::Api(mut err) => {
+                err.context = Some(Box::new(context));
+                Self::Api(err)
+            }
+            Self::Http(source) | Self::Transport { source, .. } => Self::Transport {
+                context: Box::new(context),
+                source,
+            },
+            err => err,
+        }
+    }
+}
+
+#[cfg(feature = "historical")]
+impl RequestContext {
+    pub(crate) fn new(
+        endpoint: &str,
+        dataset: &str,
+        schema: Option<dbn::Schema>,
+        symbols: &crate::Symbols,
+        date_time_range: &crate::historical::DateTimeRange,
+    ) -> Self {
+        Self {
+            endpoint: endpoint.to_owned(),
+            dataset: dataset.to_owned(),
+            schema,
+            symbol_count: symbols.count(),
+            start: date_time_range.start(),
+            end: date_time_range.end(),
+        }
+    }
 }
 
 impl From<dbn::Error> for Error {
@@ -74,6 +196,49 @@ impl From<dbn::Error> for Error {
     }
 }
 
+#[cfg(feature = "live")]
+impl GatewayError {
+    // Error codes sent by the gateway
+    const AUTH_FAILED: u8 = 1;
+    const API_KEY_DEACTIVATED: u8 = 2;
+    const CONNECTION_LIMIT_EXCEEDED: u8 = 3;
+    const INTERNAL_ERROR: u8 = 6;
+    const UNSET: u8 = u8::MAX;
+}
+
+#[cfg(feature = "live")]
+impl TryFrom<&dbn::ErrorMsg> for GatewayError {
+    type Error = Error;
+
+    fn try_from(error_msg: &dbn::ErrorMsg) -> Result<Self> {
+        let code = (error_msg.code != Self::UNSET).then_some(error_msg.code);
+        Ok(Self {
+            code,
+            message: error_msg.err()?.to_owned(),
+            is_fatal: matches!(
+                code,
+                Some(
+                    Self::AUTH_FAILED
+                        | Self::API_KEY_DEACTIVATED
+                        | Self::CONNECTION_LIMIT_EXCEEDED
+                        | Self::INTERNAL_ERROR
+                )
+            ),
+        })
+    }
+}
+
+#[cfg(feature = "live")]
+impl std::fmt::Display for GatewayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(code) = self.code {
+            write!(f, "{} (code {code})", self.message)
+        } else {
+            write!(f, "{}", self.message)
+        }
+    }
+}
+
 #[cfg(feature = "historical")]
 impl std::fmt::Display for ApiError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -85,9 +250,29 @@ impl std::fmt::Display for ApiError {
         let status = self.status_code;
         let msg = &self.message;
         if let Some(ref request_id) = self.request_id {
-            write!(f, "{request_id} failed with {status} {msg}{doc}")
+            write!(f, "{request_id} failed with {status} {msg}{doc}")?;
+        } else {
+            write!(f, "{status} {msg}{doc}")?;
+        }
+        if let Some(ref context) = self.context {
+            write!(f, " Request: {context}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "historical")]
+impl std::fmt::Display for RequestContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} of {}", self.endpoint, self.dataset)?;
+        if let Some(schema) = self.schema {
+            write!(f, " {schema}")?;
+        }
+        if let Some(symbol_count) = self.symbol_count {
+            write!(f, " for {symbol_count} symbols")?;
         } else {
-            write!(f, "{status} {msg}{doc}")
+            write!(f, " for all symbols")?;
         }
+        write!(f, " from {} to {}", self.start, self.end)
     }
 }
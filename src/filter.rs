@@ -0,0 +1,64 @@
+//! Filtering records by instrument, such as when only a few instruments from a broad
+//! subscription or request are consumed.
+
+use std::{collections::HashSet, sync::Arc};
+
+use dbn::{enums::rtype, RecordRef};
+
+/// A set of instrument IDs to keep. Records for other instruments are skipped by
+/// checking only their header, before they're copied into an owned record.
+///
+/// Error and system records apply to the whole session and are always kept.
+///
+/// Cloning is cheap because the set of instrument IDs is shared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstrumentFilter {
+    instrument_ids: Arc<HashSet<u32>>,
+}
+
+impl InstrumentFilter {
+    /// Creates a new filter that keeps records for the instruments in `instrument_ids`.
+    pub fn new(instrument_ids: impl IntoIterator<Item = u32>) -> Self {
+        Self {
+            instrument_ids: Arc::new(instrument_ids.into_iter().collect()),
+        }
+    }
+
+    /// Returns the instrument IDs to keep.
+    pub fn instrument_ids(&self) -> &HashSet<u32> {
+        &self.instrument_ids
+    }
+
+    /// Returns `true` if `rec` should be kept.
+    pub fn matches(&self, rec: &RecordRef) -> bool {
+        let header = rec.header();
+        matches!(header.rtype, rtype::ERROR | rtype::SYSTEM)
+            || self.instrument_ids.contains(&header.instrument_id)
+    }
+}
+
+impl FromIterator<u32> for InstrumentFilter {
+    fn from_iter<T: IntoIterator<Item = u32>>(iter: T) -> Self {
+        Self::new(iter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{record::RecordHeader, ErrorMsg, TradeMsg};
+
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let target = InstrumentFilter::new([1, 2]);
+        let trade = |instrument_id| TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, instrument_id, 0),
+            ..Default::default()
+        };
+        assert!(target.matches(&RecordRef::from(&trade(1))));
+        assert!(target.matches(&RecordRef::from(&trade(2))));
+        assert!(!target.matches(&RecordRef::from(&trade(3))));
+        assert!(target.matches(&RecordRef::from(&ErrorMsg::new(0, "error", true))));
+    }
+}
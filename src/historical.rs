@@ -1,20 +1,46 @@
 //! Historical client and related API types.
 
+pub mod adjustment;
+pub mod audit;
+pub mod backfill;
 pub mod batch;
+pub mod billing;
+pub mod bundle;
 mod client;
 mod deserialize;
+pub mod futures_roll;
+pub mod job_store;
 pub mod metadata;
+mod paginate;
+pub mod progress;
 pub mod symbology;
+pub mod throttle;
 pub mod timeseries;
+pub mod universe;
+mod zstd;
+
+use std::num::NonZeroU32;
 
 pub use client::*;
+// Re-export because they're accepted as parameters or returned.
+pub use reqwest::{Method, RequestBuilder};
+pub use tokio_util::sync::CancellationToken;
+
+use serde::{Deserialize, Serialize};
 use time::{format_description::BorrowedFormatItem, macros::format_description};
 
 use crate::{Error, Symbols};
 
+use self::metadata::DatasetRange;
+
 /// The current Databento historical API version.
 pub const API_VERSION: u32 = 0;
 
+/// The maximum number of symbols the historical API accepts in a single request.
+/// Requests with more symbols are split into several requests and their results
+/// are merged.
+pub const MAX_SYMBOLS_PER_REQUEST: usize = 2_000;
+
 /// The Historical API gateway to use.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum HistoricalGateway {
@@ -25,21 +51,25 @@ pub enum HistoricalGateway {
 
 /// A **half**-closed date interval with an inclusive start date and an exclusive end
 /// date.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DateRange {
     /// The start date (inclusive).
+    #[serde(with = "date_format")]
     start: time::Date,
     /// The end date (exclusive).
+    #[serde(with = "date_format")]
     end: time::Date,
 }
 
 /// A **half**-closed datetime interval with an inclusive start time and an exclusive
 /// end time.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DateTimeRange {
     /// The start date time (inclusive).
+    #[serde(with = "time::serde::rfc3339")]
     start: time::OffsetDateTime,
     /// The end date time (exclusive).
+    #[serde(with = "time::serde::rfc3339")]
     end: time::OffsetDateTime,
 }
 
@@ -64,6 +94,8 @@ impl From<(time::Date, time::Duration)> for DateRange {
 pub(crate) const DATE_FORMAT: &[BorrowedFormatItem<'_>] =
     format_description!("[year]-[month]-[day]");
 
+time::serde::format_description!(date_format, Date, "[year]-[month]-[day]");
+
 impl From<(time::OffsetDateTime, time::OffsetDateTime)> for DateTimeRange {
     fn from(value: (time::OffsetDateTime, time::OffsetDateTime)) -> Self {
         Self {
@@ -122,7 +154,95 @@ impl AddToQuery<Symbols> for reqwest::RequestBuilder {
     }
 }
 
+impl From<DatasetRange> for DateTimeRange {
+    fn from(value: DatasetRange) -> Self {
+        Self {
+            start: value.start,
+            end: value.end,
+        }
+    }
+}
+
+impl From<DatasetRange> for DateRange {
+    /// Converts to the smallest date range containing all of `value`.
+    fn from(value: DatasetRange) -> Self {
+        let end = if value.end.time() == time::Time::MIDNIGHT {
+            value.end.date()
+        } else {
+            value.end.date().next_day().unwrap_or(value.end.date())
+        };
+        Self {
+            start: value.start.date(),
+            end,
+        }
+    }
+}
+
 impl DateRange {
+    /// Returns the start date (inclusive).
+    pub fn start(&self) -> time::Date {
+        self.start
+    }
+
+    /// Returns the end date (exclusive).
+    pub fn end(&self) -> time::Date {
+        self.end
+    }
+
+    /// Returns `true` if the range contains no dates.
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    /// Returns `true` if `date` is within the range.
+    pub fn contains(&self, date: time::Date) -> bool {
+        self.start <= date && date < self.end
+    }
+
+    /// Returns the dates contained in both `self` and `other` or `None` if they don't
+    /// overlap.
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let res = Self {
+            start: self.start.max(other.start),
+            end: self.end.min(other.end),
+        };
+        (!res.is_empty()).then_some(res)
+    }
+
+    /// Returns the range containing the dates in either `self` or `other`, or `None` if
+    /// they neither overlap nor are adjacent, since the result wouldn't be contiguous.
+    pub fn union(&self, other: &Self) -> Option<Self> {
+        if self.start > other.end || other.start > self.end {
+            return None;
+        }
+        Some(Self {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        })
+    }
+
+    /// Splits the range into consecutive chunks of at most `days` days. The last chunk
+    /// may be shorter.
+    pub fn split_days(&self, days: NonZeroU32) -> Vec<Self> {
+        let step = time::Duration::days(i64::from(days.get()));
+        let mut res = Vec::new();
+        let mut start = self.start;
+        while start < self.end {
+            let end = start
+                .checked_add(step)
+                .map_or(self.end, |end| end.min(self.end));
+            res.push(Self { start, end });
+            start = end;
+        }
+        res
+    }
+
+    /// Returns the part of the range within the available range of a dataset or `None`
+    /// if there's no overlap.
+    pub fn clamp(&self, range: &DatasetRange) -> Option<Self> {
+        self.intersection(&Self::from(range.clone()))
+    }
+
     pub(crate) fn add_to_form(&self, form: &mut Vec<(&'static str, String)>) {
         form.push(("start_date", self.start.format(DATE_FORMAT).unwrap()));
         form.push(("end_date", self.end.format(DATE_FORMAT).unwrap()));
@@ -130,6 +250,70 @@ impl DateRange {
 }
 
 impl DateTimeRange {
+    /// Returns the start date time (inclusive).
+    pub fn start(&self) -> time::OffsetDateTime {
+        self.start
+    }
+
+    /// Returns the end date time (exclusive).
+    pub fn end(&self) -> time::OffsetDateTime {
+        self.end
+    }
+
+    /// Returns `true` if the range contains no time.
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    /// Returns `true` if `datetime` is within the range.
+    pub fn contains(&self, datetime: time::OffsetDateTime) -> bool {
+        self.start <= datetime && datetime < self.end
+    }
+
+    /// Returns the time contained in both `self` and `other` or `None` if they don't
+    /// overlap.
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let res = Self {
+            start: self.start.max(other.start),
+            end: self.end.min(other.end),
+        };
+        (!res.is_empty()).then_some(res)
+    }
+
+    /// Returns the range containing the time in either `self` or `other`, or `None` if
+    /// they neither overlap nor are adjacent, since the result wouldn't be contiguous.
+    pub fn union(&self, other: &Self) -> Option<Self> {
+        if self.start > other.end || other.start > self.end {
+            return None;
+        }
+        Some(Self {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        })
+    }
+
+    /// Splits the range into consecutive chunks of at most `days` days. The last chunk
+    /// may be shorter.
+    pub fn split_days(&self, days: NonZeroU32) -> Vec<Self> {
+        let step = time::Duration::days(i64::from(days.get()));
+        let mut res = Vec::new();
+        let mut start = self.start;
+        while start < self.end {
+            let end = start
+                .checked_add(step)
+                .map_or(self.end, |end| end.min(self.end));
+            res.push(Self { start, end });
+            start = end;
+        }
+        res
+    }
+
+    /// Returns the part of the range within the available range of a dataset or `None`
+    /// if there's no overlap.
+    pub fn clamp(&self, range: &DatasetRange) -> Option<Self> {
+        self.intersection(&Self::from(range.clone()))
+    }
+
     pub(crate) fn add_to_form(&self, form: &mut Vec<(&'static str, String)>) {
         form.push(("start", self.start.unix_timestamp_nanos().to_string()));
         form.push(("end", self.end.unix_timestamp_nanos().to_string()));
@@ -140,7 +324,7 @@ impl DateTimeRange {
 mod tests {
     use super::*;
 
-    use time::macros::date;
+    use time::macros::{date, datetime};
 
     #[test]
     fn date_range_from_lt_day_duration() {
@@ -153,4 +337,76 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn date_range_intersection_and_union() {
+        let a = DateRange::from((date!(2024 - 01 - 01), date!(2024 - 01 - 10)));
+        let b = DateRange::from((date!(2024 - 01 - 05), date!(2024 - 01 - 15)));
+        let c = DateRange::from((date!(2024 - 01 - 10), date!(2024 - 01 - 12)));
+        assert_eq!(
+            a.intersection(&b),
+            Some(DateRange::from((
+                date!(2024 - 01 - 05),
+                date!(2024 - 01 - 10)
+            )))
+        );
+        // Adjacent ranges don't intersect because the end is exclusive
+        assert_eq!(a.intersection(&c), None);
+        assert_eq!(
+            a.union(&c),
+            Some(DateRange::from((
+                date!(2024 - 01 - 01),
+                date!(2024 - 01 - 12)
+            )))
+        );
+        let d = DateRange::from((date!(2024 - 01 - 11), date!(2024 - 01 - 12)));
+        assert_eq!(a.union(&d), None);
+    }
+
+    #[test]
+    fn date_range_split_days() {
+        let target = DateRange::from((date!(2024 - 01 - 01), date!(2024 - 01 - 08)));
+        let chunks = target.split_days(NonZeroU32::new(3).unwrap());
+        assert_eq!(
+            chunks,
+            vec![
+                DateRange::from((date!(2024 - 01 - 01), date!(2024 - 01 - 04))),
+                DateRange::from((date!(2024 - 01 - 04), date!(2024 - 01 - 07))),
+                DateRange::from((date!(2024 - 01 - 07), date!(2024 - 01 - 08))),
+            ]
+        );
+        assert!(
+            DateRange::from((date!(2024 - 01 - 01), date!(2024 - 01 - 01)))
+                .split_days(NonZeroU32::new(3).unwrap())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn date_time_range_clamp() {
+        let dataset_range = DatasetRange {
+            start: datetime!(2023 - 03 - 28 00:00 UTC),
+            end: datetime!(2024 - 06 - 10 12:30 UTC),
+        };
+        let target = DateTimeRange::from((
+            datetime!(2023 - 01 - 01 00:00 UTC),
+            datetime!(2025 - 01 - 01 00:00 UTC),
+        ));
+        assert_eq!(
+            target.clamp(&dataset_range),
+            Some(DateTimeRange::from(dataset_range.clone()))
+        );
+        assert_eq!(
+            DateRange::from((date!(2024 - 06 - 01), date!(2024 - 07 - 01))).clamp(&dataset_range),
+            Some(DateRange::from((
+                date!(2024 - 06 - 01),
+                date!(2024 - 06 - 11)
+            )))
+        );
+        let before = DateTimeRange::from((
+            datetime!(2022 - 01 - 01 00:00 UTC),
+            datetime!(2023 - 03 - 28 00:00 UTC),
+        ));
+        assert_eq!(before.clamp(&dataset_range), None);
+    }
 }
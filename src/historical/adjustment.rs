@@ -0,0 +1,198 @@
+//! Post-processing of downloaded OHLCV files to adjust prices for corporate actions
+//! such as splits and dividends.
+
+use std::{collections::HashMap, path::Path};
+
+use async_compression::tokio::{bufread::ZstdDecoder, write::ZstdEncoder};
+use dbn::{decode::AsyncDbnDecoder, encode::AsyncDbnEncoder, OhlcvMsg, Schema, UNDEF_PRICE};
+use time::{Date, OffsetDateTime};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncWrite, BufReader, BufWriter},
+};
+
+use crate::Error;
+
+/// A price adjustment factor for a corporate action of a single instrument.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdjustmentFactor {
+    /// The instrument ID of the security.
+    pub instrument_id: u32,
+    /// The ex-date of the corporate action. Prices of bars before this date are
+    /// adjusted.
+    pub ex_date: Date,
+    /// The factor prices before the ex-date are multiplied by, such as `0.5` for a 2:1
+    /// split.
+    pub factor: f64,
+}
+
+/// Adjusts the prices of the OHLCV bars in the DBN file at `input`, such as one
+/// downloaded with [`BatchClient::download()`](super::batch::BatchClient::download),
+/// and writes the result to `output`. The open, high, low, and close of each bar are
+/// multiplied by the product of the `factors` for its instrument with an ex-date after
+/// the UTC date of the bar. Volumes aren't adjusted. Files ending in `.zst` are read
+/// and written with zstd compression. Returns the number of adjusted bars.
+///
+/// # Errors
+/// This function returns an error if it fails to read `input` or write `output`, or if
+/// `input` isn't an OHLCV file.
+pub async fn adjust_ohlcv_file(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    factors: &[AdjustmentFactor],
+) -> crate::Result<u64> {
+    let input = input.as_ref();
+    let output = output.as_ref();
+    let file = BufReader::new(File::open(input).await?);
+    let reader: Box<dyn AsyncRead + Send + Unpin> = if is_zstd(input) {
+        let mut decoder = ZstdDecoder::new(file);
+        decoder.multiple_members(true);
+        Box::new(decoder)
+    } else {
+        Box::new(file)
+    };
+    let mut decoder = AsyncDbnDecoder::new(reader).await?;
+    if !decoder.metadata().schema.is_some_and(is_ohlcv) {
+        return Err(Error::bad_arg(
+            "input",
+            format!(
+                "expected an OHLCV file, got {:?}",
+                decoder.metadata().schema
+            ),
+        ));
+    }
+    let file = BufWriter::new(File::create(output).await?);
+    let writer: Box<dyn AsyncWrite + Send + Unpin> = if is_zstd(output) {
+        Box::new(ZstdEncoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let mut encoder = AsyncDbnEncoder::new(writer, decoder.metadata()).await?;
+    let mut factors_by_id = HashMap::<u32, Vec<&AdjustmentFactor>>::new();
+    for factor in factors {
+        factors_by_id
+            .entry(factor.instrument_id)
+            .or_default()
+            .push(factor);
+    }
+    let mut adjusted_count = 0;
+    while let Some(rec) = decoder.decode_record_ref().await? {
+        let Some(bar) = rec.get::<OhlcvMsg>() else {
+            encoder.encode_record_ref(rec).await?;
+            continue;
+        };
+        let date = OffsetDateTime::from_unix_timestamp_nanos(i128::from(bar.hd.ts_event))
+            .map_err(|e| Error::internal(format!("invalid bar timestamp: {e:?}")))?
+            .date();
+        let multiplier: f64 = factors_by_id
+            .get(&bar.hd.instrument_id)
+            .into_iter()
+            .flatten()
+            .filter(|factor| factor.ex_date > date)
+            .map(|factor| factor.factor)
+            .product();
+        let mut bar = bar.clone();
+        if multiplier != 1.0 {
+            for px in [&mut bar.open, &mut bar.high, &mut bar.low, &mut bar.close] {
+                if *px != UNDEF_PRICE {
+                    *px = (*px as f64 * multiplier).round() as i64;
+                }
+            }
+            adjusted_count += 1;
+        }
+        encoder.encode_record(&bar).await?;
+    }
+    encoder.shutdown().await?;
+    Ok(adjusted_count)
+}
+
+fn is_zstd(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "zst")
+}
+
+fn is_ohlcv(schema: Schema) -> bool {
+    matches!(
+        schema,
+        Schema::Ohlcv1S | Schema::Ohlcv1M | Schema::Ohlcv1H | Schema::Ohlcv1D | Schema::OhlcvEod
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{enums::rtype, record::RecordHeader, MetadataBuilder, SType, FIXED_PRICE_SCALE};
+    use time::macros::{date, datetime};
+
+    use super::*;
+
+    fn bar(instrument_id: u32, ts: OffsetDateTime, px: i64) -> OhlcvMsg {
+        OhlcvMsg {
+            hd: RecordHeader::new::<OhlcvMsg>(
+                rtype::OHLCV_1D,
+                1,
+                instrument_id,
+                ts.unix_timestamp_nanos() as u64,
+            ),
+            open: px,
+            high: px,
+            low: px,
+            close: px,
+            volume: 100,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_adjust_ohlcv_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.dbn.zst");
+        let output = dir.path().join("output.dbn");
+        let metadata = MetadataBuilder::new()
+            .dataset("XNAS.ITCH".to_owned())
+            .start(0)
+            .schema(Some(Schema::Ohlcv1D))
+            .stype_in(Some(SType::RawSymbol))
+            .stype_out(SType::InstrumentId)
+            .build();
+        let mut encoder =
+            AsyncDbnEncoder::with_zstd(File::create(&input).await.unwrap(), &metadata)
+                .await
+                .unwrap();
+        for bar in [
+            bar(1, datetime!(2024-06-07 00:00 UTC), 100 * FIXED_PRICE_SCALE),
+            bar(2, datetime!(2024-06-07 00:00 UTC), 50 * FIXED_PRICE_SCALE),
+            bar(1, datetime!(2024-06-10 00:00 UTC), 10 * FIXED_PRICE_SCALE),
+        ] {
+            encoder.encode_record(&bar).await.unwrap();
+        }
+        encoder.shutdown().await.unwrap();
+
+        let factors = [
+            AdjustmentFactor {
+                instrument_id: 1,
+                ex_date: date!(2024 - 06 - 10),
+                factor: 0.1,
+            },
+            AdjustmentFactor {
+                instrument_id: 1,
+                ex_date: date!(2024 - 06 - 01),
+                factor: 0.5,
+            },
+        ];
+        assert_eq!(
+            adjust_ohlcv_file(&input, &output, &factors).await.unwrap(),
+            1
+        );
+        let mut decoder = AsyncDbnDecoder::from_file(&output).await.unwrap();
+        let mut closes = Vec::new();
+        while let Some(bar) = decoder.decode_record::<OhlcvMsg>().await.unwrap() {
+            closes.push((bar.hd.instrument_id, bar.close));
+        }
+        assert_eq!(
+            closes,
+            vec![
+                (1, 10 * FIXED_PRICE_SCALE),
+                (2, 50 * FIXED_PRICE_SCALE),
+                (1, 10 * FIXED_PRICE_SCALE)
+            ]
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Hooks for keeping an audit trail of the requests made by the historical client, such
+//! as for regulated users who need a record of data access.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+use super::client::REQUEST_ID_HEADER;
+
+/// Receives an [`AuditEvent`] for every request sent by a
+/// [`HistoricalClient`](super::Client) and every response received, in the order they
+/// occur. Set with [`ClientBuilder::audit_hook()`](super::ClientBuilder::audit_hook).
+///
+/// The hook is called from the task making the request, so it should return quickly,
+/// such as by appending to a file or sending the event over a channel.
+///
+/// Closures taking an `&AuditEvent` implement this trait.
+pub trait AuditHook: Send + Sync {
+    /// Records `event`.
+    fn record(&self, event: &AuditEvent);
+}
+
+impl<F> AuditHook for F
+where
+    F: Fn(&AuditEvent) + Send + Sync,
+{
+    fn record(&self, event: &AuditEvent) {
+        self(event)
+    }
+}
+
+/// An event passed to an [`AuditHook`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditEvent {
+    /// A request is about to be sent.
+    Request(AuditRequest),
+    /// A response was received, or the request failed without one.
+    Response(AuditResponse),
+}
+
+/// The metadata of a request sent by the historical client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRequest {
+    /// Identifies the request among those made by the client and its clones, for
+    /// matching it with its [`AuditResponse`].
+    pub sequence: u64,
+    /// The HTTP method.
+    pub method: Method,
+    /// The path of the endpoint, such as `/v0/timeseries.get_range`.
+    pub endpoint: String,
+    /// The hex-encoded SHA-256 hash of the query string and body of the request, which
+    /// identifies the parameters without recording them.
+    pub params_hash: String,
+    /// When the request was sent.
+    pub timestamp: OffsetDateTime,
+}
+
+/// The outcome of a request sent by the historical client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditResponse {
+    /// The [`sequence`](AuditRequest::sequence) of the request.
+    pub sequence: u64,
+    /// The HTTP status of the response, or `None` if the request failed before a
+    /// response was received, such as due to a connection error.
+    pub status: Option<StatusCode>,
+    /// The ID assigned to the request by the API, if any.
+    pub request_id: Option<String>,
+    /// When the response headers were received or the request failed.
+    pub timestamp: OffsetDateTime,
+    /// How long it took to receive the response headers or fail.
+    pub elapsed: Duration,
+}
+
+/// A type-erased [`AuditHook`] that's shared between a client and its clones.
+#[derive(Clone)]
+pub(crate) struct SharedAuditHook {
+    hook: Arc<dyn AuditHook>,
+    next_sequence: Arc<AtomicU64>,
+}
+
+impl SharedAuditHook {
+    pub fn new(hook: impl AuditHook + 'static) -> Self {
+        Self {
+            hook: Arc::new(hook),
+            next_sequence: Arc::default(),
+        }
+    }
+}
+
+impl fmt::Debug for SharedAuditHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditHook")
+            .field("next_sequence", &self.next_sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sends `builder`, passing the request and its response to `hook` if there is one.
+pub(crate) async fn send(
+    hook: Option<&SharedAuditHook>,
+    builder: RequestBuilder,
+) -> crate::Result<Response> {
+    let Some(hook) = hook else {
+        return Ok(builder.send().await?);
+    };
+    let (client, request) = builder.build_split();
+    let request = request?;
+    let sequence = hook.next_sequence.fetch_add(1, Ordering::Relaxed);
+    let mut hasher = Sha256::new();
+    hasher.update(request.url().query().unwrap_or_default());
+    if let Some(body) = request.body().and_then(reqwest::Body::as_bytes) {
+        hasher.update(body);
+    }
+    hook.hook.record(&AuditEvent::Request(AuditRequest {
+        sequence,
+        method: request.method().clone(),
+        endpoint: request.url().path().to_owned(),
+        params_hash: hex::encode(hasher.finalize()),
+        timestamp: OffsetDateTime::now_utc(),
+    }));
+    let start = Instant::now();
+    let res = client.execute(request).await;
+    let (status, request_id) = match &res {
+        Ok(resp) => (
+            Some(resp.status()),
+            resp.headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|header| header.to_str().ok().map(ToOwned::to_owned)),
+        ),
+        Err(e) => (e.status(), None),
+    };
+    hook.hook.record(&AuditEvent::Response(AuditResponse {
+        sequence,
+        status,
+        request_id,
+        timestamp: OffsetDateTime::now_utc(),
+        elapsed: start.elapsed(),
+    }));
+    Ok(res?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{historical::API_VERSION, HistoricalClient};
+
+    #[tokio::test]
+    async fn test_audit_hook() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/v{API_VERSION}/metadata.list_schemas")))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK.as_u16())
+                    .insert_header(REQUEST_ID_HEADER, "abc")
+                    .set_body_json(json!(["mbo"])),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/v{API_VERSION}/metadata.get_dataset_range")))
+            .respond_with(ResponseTemplate::new(StatusCode::FORBIDDEN.as_u16()))
+            .mount(&mock_server)
+            .await;
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let target = HistoricalClient::builder()
+            .base_url(mock_server.uri().parse().unwrap())
+            .audit_hook({
+                let events = events.clone();
+                move |event: &AuditEvent| events.lock().unwrap().push(event.clone())
+            })
+            .key("32-character-with-lots-of-filler")
+            .unwrap()
+            .build()
+            .unwrap();
+        target.metadata().list_schemas("XNAS.ITCH").await.unwrap();
+        target.metadata().list_schemas("XNAS.ITCH").await.unwrap();
+        target
+            .clone()
+            .metadata()
+            .get_dataset_range("XNAS.ITCH")
+            .await
+            .unwrap_err();
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 6);
+        let requests = events
+            .iter()
+            .filter_map(|event| match event {
+                AuditEvent::Request(req) => Some(req),
+                AuditEvent::Response(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let responses = events
+            .iter()
+            .filter_map(|event| match event {
+                AuditEvent::Response(resp) => Some(resp),
+                AuditEvent::Request(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(matches!(events[0], AuditEvent::Request(_)));
+        assert!(matches!(events[1], AuditEvent::Response(_)));
+        assert_eq!(
+            requests.iter().map(|req| req.sequence).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert_eq!(requests[0].method, Method::GET);
+        assert_eq!(
+            requests[0].endpoint,
+            format!("/v{API_VERSION}/metadata.list_schemas")
+        );
+        assert_eq!(requests[0].params_hash.len(), 64);
+        assert_eq!(requests[0].params_hash, requests[1].params_hash);
+        // Same parameters to a different endpoint
+        assert_eq!(requests[0].params_hash, requests[2].params_hash);
+        assert_eq!(responses[0].sequence, 0);
+        assert_eq!(responses[0].status, Some(StatusCode::OK));
+        assert_eq!(responses[0].request_id.as_deref(), Some("abc"));
+        assert_eq!(responses[2].sequence, 2);
+        assert_eq!(responses[2].status, Some(StatusCode::FORBIDDEN));
+        assert!(responses[2].timestamp >= requests[2].timestamp);
+    }
+}
@@ -0,0 +1,620 @@
+//! Planning and executing large historical backfills across several datasets and
+//! schemas with a mix of streaming requests and batch jobs.
+
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use dbn::{SType, Schema};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use time::{Date, Time, UtcOffset};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use typed_builder::TypedBuilder;
+
+use crate::{calendar::CalendarRegistry, Error, Symbols};
+
+use super::{
+    batch::{BatchClient, DownloadParams, JobState, SubmitJobParams},
+    metadata::{GetBillableSizeParams, GetDatasetConditionParams},
+    timeseries::GetRangeToFileParams,
+    Client, DateTimeRange,
+};
+
+/// The default maximum billable size in bytes of a query that's streamed rather than
+/// submitted as a batch job.
+pub const DEFAULT_MAX_STREAM_SIZE: u64 = 5 << 30;
+
+/// The parameters for [`BatchClient::plan_backfill()`]. Use
+/// [`BackfillParams::builder()`] to get a builder type with all the preset defaults.
+#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
+pub struct BackfillParams {
+    /// The dataset codes.
+    #[builder(setter(transform = |datasets: impl IntoIterator<Item = impl ToString>| {
+        datasets.into_iter().map(|dataset| dataset.to_string()).collect()
+    }))]
+    pub datasets: Vec<String>,
+    /// The symbols to backfill in every dataset.
+    #[builder(setter(into))]
+    pub symbols: Symbols,
+    /// The symbology type of the input `symbols`. Defaults to
+    /// [`RawSymbol`](dbn::enums::SType::RawSymbol).
+    #[builder(default = SType::RawSymbol)]
+    pub stype_in: SType,
+    /// The data record schemas to backfill for every dataset.
+    #[builder(setter(into))]
+    pub schemas: Vec<Schema>,
+    /// The backfill time range.
+    #[builder(setter(into))]
+    pub date_time_range: DateTimeRange,
+    /// The directory to write streamed files and download batch files to.
+    #[builder(setter(into))]
+    pub output_dir: PathBuf,
+    /// The maximum billable size in bytes of a query that's streamed. Larger queries
+    /// are submitted as batch jobs. Defaults to [`DEFAULT_MAX_STREAM_SIZE`].
+    #[builder(default = DEFAULT_MAX_STREAM_SIZE)]
+    pub max_stream_size: u64,
+    /// How days with degraded or missing data are handled. Defaults to
+    /// [`Off`](QualityGate::Off).
+    #[builder(default)]
+    pub quality_gate: QualityGate,
+    /// The trading calendars used to skip the UTC days on which the venue of each
+    /// dataset is closed, such as weekends. Defaults to `None`, where no days are
+    /// skipped.
+    #[builder(default, setter(strip_option))]
+    pub calendars: Option<CalendarRegistry>,
+}
+
+/// How [`BatchClient::plan_backfill()`] handles days where the
+/// [dataset condition](super::metadata::MetadataClient::get_dataset_condition) is
+/// degraded, pending, or missing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QualityGate {
+    /// Don't check the dataset condition.
+    #[default]
+    Off,
+    /// Return an error listing the degraded, pending, and missing days.
+    FailOnMissing,
+    /// Leave out the degraded, pending, and missing days, splitting the time range
+    /// into one task per run of consecutive usable days.
+    SkipMissingDays,
+}
+
+/// The parameters for [`BackfillPlan::execute()`]. Use
+/// [`ExecuteBackfillParams::builder()`] to get a builder type with all the preset
+/// defaults.
+#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
+pub struct ExecuteBackfillParams {
+    /// The path of the JSON manifest recording submitted batch jobs and completed
+    /// tasks. If it exists, completed tasks are skipped and batch jobs that were
+    /// already submitted are resumed rather than resubmitted.
+    #[builder(setter(into))]
+    pub manifest_path: PathBuf,
+    /// The maximum number of tasks run at once. Defaults to 4.
+    #[builder(default = NonZeroUsize::new(4).unwrap())]
+    pub concurrency: NonZeroUsize,
+    /// How often to check whether a batch job is done. Defaults to 30 seconds.
+    #[builder(default = Duration::from_secs(30))]
+    pub poll_interval: Duration,
+}
+
+/// A plan of streaming requests and batch jobs for a backfill created by
+/// [`BatchClient::plan_backfill()`]. It can be inspected, such as to check the total
+/// size, before being [executed](Self::execute).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackfillPlan {
+    /// The directory files are written to.
+    pub output_dir: PathBuf,
+    /// The tasks of the plan, one per dataset and schema.
+    pub tasks: Vec<BackfillTask>,
+}
+
+/// A single query of a [`BackfillPlan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackfillTask {
+    /// The identifier of the task in the manifest, made from the dataset and schema.
+    pub id: String,
+    /// The billable size of the query in bytes.
+    pub billable_size: u64,
+    /// How the data is requested.
+    pub request: BackfillRequest,
+}
+
+/// How the data of a [`BackfillTask`] is requested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackfillRequest {
+    /// Streamed to a single file.
+    Stream(GetRangeToFileParams),
+    /// Submitted as a batch job whose files are downloaded once it's done.
+    Batch(SubmitJobParams),
+}
+
+/// The progress of executing a [`BackfillPlan`], persisted after every change.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    #[serde(skip)]
+    path: PathBuf,
+    /// The ID of the batch job submitted for each task.
+    jobs: HashMap<String, String>,
+    /// The files written for each completed task.
+    completed: HashMap<String, Vec<PathBuf>>,
+}
+
+impl BatchClient<'_> {
+    /// Plans a backfill of every combination of the datasets and schemas in `params`.
+    /// Queries with a billable size up to `max_stream_size` are streamed, and larger
+    /// ones are submitted as batch jobs. Days with degraded or missing data are
+    /// handled according to the [`QualityGate`].
+    ///
+    /// # Errors
+    /// This function returns an error when `params` has no datasets or schemas, the
+    /// quality gate fails, it fails to communicate with the Databento API, or the API
+    /// indicates there's an issue with the request.
+    pub async fn plan_backfill(&self, params: &BackfillParams) -> crate::Result<BackfillPlan> {
+        if params.datasets.is_empty() {
+            return Err(Error::bad_arg(
+                "datasets",
+                "must contain at least one dataset",
+            ));
+        }
+        if params.schemas.is_empty() {
+            return Err(Error::bad_arg(
+                "schemas",
+                "must contain at least one schema",
+            ));
+        }
+        let mut queries = Vec::new();
+        for dataset in &params.datasets {
+            let ranges = self.gated_ranges(dataset, params).await?;
+            let is_split = ranges.len() > 1;
+            for range in ranges {
+                for schema in &params.schemas {
+                    let id = if is_split {
+                        format!("{dataset}_{schema}_{}", range.start().date())
+                    } else {
+                        format!("{dataset}_{schema}")
+                    };
+                    queries.push((id, dataset, *schema, range.clone()));
+                }
+            }
+        }
+        let sizes = self
+            .inner
+            .metadata()
+            .get_billable_sizes(
+                &queries
+                    .iter()
+                    .map(|(_, dataset, schema, range)| {
+                        GetBillableSizeParams::builder()
+                            .dataset(dataset)
+                            .symbols(params.symbols.clone())
+                            .stype_in(params.stype_in)
+                            .schema(*schema)
+                            .date_time_range(range.clone())
+                            .build()
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .await?;
+        let tasks = queries
+            .into_iter()
+            .zip(sizes.per_query)
+            .map(|((id, dataset, schema, range), billable_size)| {
+                let request = if billable_size <= params.max_stream_size {
+                    BackfillRequest::Stream(
+                        GetRangeToFileParams::builder()
+                            .dataset(dataset)
+                            .symbols(params.symbols.clone())
+                            .stype_in(params.stype_in)
+                            .schema(schema)
+                            .date_time_range(range)
+                            .path(params.output_dir.join(format!("{id}.dbn.zst")))
+                            .build(),
+                    )
+                } else {
+                    BackfillRequest::Batch(
+                        SubmitJobParams::builder()
+                            .dataset(dataset)
+                            .symbols(params.symbols.clone())
+                            .stype_in(params.stype_in)
+                            .schema(schema)
+                            .date_time_range(range)
+                            .build(),
+                    )
+                };
+                BackfillTask {
+                    id,
+                    billable_size,
+                    request,
+                }
+            })
+            .collect();
+        Ok(BackfillPlan {
+            output_dir: params.output_dir.clone(),
+            tasks,
+        })
+    }
+
+    /// Returns the parts of the time range of `params` to request from `dataset`
+    /// after skipping closed days and applying the quality gate.
+    async fn gated_ranges(
+        &self,
+        dataset: &str,
+        params: &BackfillParams,
+    ) -> crate::Result<Vec<DateTimeRange>> {
+        let range = &params.date_time_range;
+        let calendar = params
+            .calendars
+            .as_ref()
+            .and_then(|calendars| calendars.get(dataset));
+        if params.quality_gate == QualityGate::Off && calendar.is_none() {
+            return Ok(vec![range.clone()]);
+        }
+        let start = range.start().to_offset(UtcOffset::UTC);
+        let end = range.end().to_offset(UtcOffset::UTC);
+        let end_date = if end.time() == Time::MIDNIGHT {
+            end.date()
+        } else {
+            end.date() + time::Duration::DAY
+        };
+        let mut excluded_days = calendar.map_or_else(Vec::new, |calendar| {
+            calendar.closed_days(start.date()..end_date)
+        });
+        if params.quality_gate != QualityGate::Off {
+            let unusable_days: Vec<_> = self
+                .inner
+                .metadata()
+                .get_dataset_condition_summary(
+                    &GetDatasetConditionParams::builder()
+                        .dataset(dataset)
+                        .date_range((start.date(), end_date))
+                        .build(),
+                )
+                .await?
+                .unusable_days()
+                .into_iter()
+                .filter(|day| !excluded_days.contains(day))
+                .collect();
+            if params.quality_gate == QualityGate::FailOnMissing && !unusable_days.is_empty() {
+                let days: Vec<_> = unusable_days.iter().map(ToString::to_string).collect();
+                return Err(Error::bad_arg(
+                    "date_time_range",
+                    format!(
+                        "{dataset} has degraded or missing data on {}",
+                        days.join(", ")
+                    ),
+                ));
+            }
+            excluded_days.extend(unusable_days);
+            excluded_days.sort_unstable();
+        }
+        if excluded_days.is_empty() {
+            return Ok(vec![range.clone()]);
+        }
+        let ranges = exclude_days(range, &excluded_days);
+        if ranges.is_empty() {
+            warn!(dataset, "Skipping dataset with no open and usable days");
+        }
+        Ok(ranges)
+    }
+}
+
+/// Splits `range` into the parts outside the sorted UTC `days`.
+fn exclude_days(range: &DateTimeRange, days: &[Date]) -> Vec<DateTimeRange> {
+    let mut ranges = Vec::new();
+    let mut start = range.start();
+    for day in days {
+        let day_start = day.midnight().assume_utc();
+        let part_end = day_start.min(range.end());
+        if part_end > start {
+            ranges.push(DateTimeRange::from((start, part_end)));
+        }
+        start = start.max(day_start + time::Duration::DAY);
+    }
+    if start < range.end() {
+        ranges.push(DateTimeRange::from((start, range.end())));
+    }
+    ranges
+}
+
+impl BackfillPlan {
+    /// Returns the total billable size of the plan in bytes.
+    pub fn total_billable_size(&self) -> u64 {
+        self.tasks.iter().map(|task| task.billable_size).sum()
+    }
+
+    /// Executes the plan with `client`, running up to `concurrency` tasks at
+    /// once. Progress is recorded in the manifest so an interrupted backfill can be
+    /// resumed by executing the same plan again. Returns the files written for each
+    /// task, keyed by task ID.
+    ///
+    /// Batch files are downloaded to a subdirectory of the output directory named
+    /// after the job ID.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to read or write the manifest, a
+    /// request fails, a batch job expires before it's downloaded, or it fails to
+    /// write a file. Tasks completed before the error remain recorded in the manifest.
+    pub async fn execute(
+        &self,
+        client: &Client,
+        params: &ExecuteBackfillParams,
+    ) -> crate::Result<HashMap<String, Vec<PathBuf>>> {
+        let manifest = Arc::new(Mutex::new(Manifest::load(&params.manifest_path).await?));
+        let pending: Vec<_> = {
+            let manifest = manifest.lock().await;
+            self.tasks
+                .iter()
+                .filter(|task| !manifest.completed.contains_key(&task.id))
+                .collect()
+        };
+        stream::iter(pending)
+            .map(|task| {
+                let manifest = manifest.clone();
+                async move {
+                    task.execute(client, &manifest, &self.output_dir, params)
+                        .await
+                }
+            })
+            .buffer_unordered(params.concurrency.get())
+            .try_collect::<()>()
+            .await?;
+        let manifest = manifest.lock().await;
+        Ok(self
+            .tasks
+            .iter()
+            .filter_map(|task| {
+                let paths = manifest.completed.get(&task.id)?;
+                Some((task.id.clone(), paths.clone()))
+            })
+            .collect())
+    }
+}
+
+impl BackfillTask {
+    async fn execute(
+        &self,
+        client: &Client,
+        manifest: &Mutex<Manifest>,
+        output_dir: &Path,
+        params: &ExecuteBackfillParams,
+    ) -> crate::Result<()> {
+        info!(id = %self.id, "Starting backfill task");
+        let paths = match &self.request {
+            BackfillRequest::Stream(get_range_params) => {
+                if let Some(dir) = get_range_params.path.parent() {
+                    tokio::fs::create_dir_all(dir).await?;
+                }
+                client
+                    .timeseries()
+                    .get_range_to_file(get_range_params)
+                    .await?;
+                vec![get_range_params.path.clone()]
+            }
+            BackfillRequest::Batch(submit_job_params) => {
+                let existing_job_id = manifest.lock().await.jobs.get(&self.id).cloned();
+                let job_id = if let Some(job_id) = existing_job_id {
+                    info!(id = %self.id, %job_id, "Resuming batch job");
+                    job_id
+                } else {
+                    let job = client.batch().submit_job(submit_job_params).await?;
+                    let mut manifest = manifest.lock().await;
+                    manifest.jobs.insert(self.id.clone(), job.id.clone());
+                    manifest.save().await?;
+                    job.id
+                };
+                self.wait_for_job(client, &job_id, params.poll_interval)
+                    .await?;
+                client
+                    .batch()
+                    .download(
+                        &DownloadParams::builder()
+                            .output_dir(output_dir)
+                            .job_id(&job_id)
+                            .build(),
+                    )
+                    .await?
+            }
+        };
+        let mut manifest = manifest.lock().await;
+        manifest.completed.insert(self.id.clone(), paths);
+        manifest.save().await?;
+        info!(id = %self.id, "Completed backfill task");
+        Ok(())
+    }
+
+    async fn wait_for_job(
+        &self,
+        client: &Client,
+        job_id: &str,
+        poll_interval: Duration,
+    ) -> crate::Result<()> {
+        loop {
+            match client.batch().get_job(job_id).await?.state {
+                JobState::Done => return Ok(()),
+                JobState::Expired => {
+                    return Err(Error::internal(format!(
+                        "batch job {job_id} for {} expired before it was downloaded",
+                        self.id
+                    )))
+                }
+                _ => tokio::time::sleep(poll_interval).await,
+            }
+        }
+    }
+}
+
+impl Manifest {
+    async fn load(path: &Path) -> crate::Result<Self> {
+        let mut manifest: Self = match tokio::fs::read(path).await {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|e| Error::bad_arg("manifest_path", format!("invalid manifest: {e}")))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e.into()),
+        };
+        manifest.path = path.to_owned();
+        Ok(manifest)
+    }
+
+    async fn save(&self) -> crate::Result<()> {
+        let contents = serde_json::to_vec_pretty(self)
+            .map_err(|e| Error::internal(format!("failed to serialize manifest: {e}")))?;
+        tokio::fs::write(&self.path, contents).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use time::macros::{date, datetime};
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        body_contains,
+        historical::{HistoricalGateway, API_VERSION},
+        zst_test_data_path, HistoricalClient,
+    };
+
+    const API_KEY: &str = "test-backfill";
+
+    #[tokio::test]
+    async fn test_plan_and_execute_backfill() {
+        let mock_server = MockServer::start().await;
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for (schema, size) in [(Schema::Trades, 1_000), (Schema::Mbo, 10 << 30)] {
+            Mock::given(method("POST"))
+                .and(path(format!("/v{API_VERSION}/metadata.get_billable_size")))
+                .and(body_contains("schema", schema))
+                .respond_with(
+                    ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!(size)),
+                )
+                .mount(&mock_server)
+                .await;
+        }
+        let bytes = tokio::fs::read(zst_test_data_path(Schema::Trades))
+            .await
+            .unwrap();
+        Mock::given(method("POST"))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .and(body_contains("schema", "trades"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
+            // Not repeated when resumed
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let client = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let mut plan = client
+            .batch()
+            .plan_backfill(
+                &BackfillParams::builder()
+                    .datasets(["XNAS.ITCH"])
+                    .symbols("SPOT")
+                    .schemas(vec![Schema::Trades, Schema::Mbo])
+                    .date_time_range((
+                        datetime!(2023 - 06 - 14 00:00 UTC),
+                        datetime!(2023 - 06 - 17 00:00 UTC),
+                    ))
+                    .output_dir(temp_dir.path())
+                    .build(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(plan.total_billable_size(), 1_000 + (10 << 30));
+        assert_eq!(plan.tasks.len(), 2);
+        assert_eq!(plan.tasks[0].id, "XNAS.ITCH_trades");
+        assert!(matches!(plan.tasks[0].request, BackfillRequest::Stream(_)));
+        assert_eq!(plan.tasks[1].id, "XNAS.ITCH_mbo");
+        assert!(matches!(plan.tasks[1].request, BackfillRequest::Batch(_)));
+
+        plan.tasks.truncate(1);
+        let params = ExecuteBackfillParams::builder()
+            .manifest_path(temp_dir.path().join("manifest.json"))
+            .build();
+        let expected_path = temp_dir.path().join("XNAS.ITCH_trades.dbn.zst");
+        for _ in 0..2 {
+            let files = plan.execute(&client, &params).await.unwrap();
+            assert_eq!(files["XNAS.ITCH_trades"], vec![expected_path.clone()]);
+        }
+        assert!(expected_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_plan_backfill_skips_closed_days() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(format!("/v{API_VERSION}/metadata.get_billable_size")))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!(100)))
+            .mount(&mock_server)
+            .await;
+        let client = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let plan = client
+            .batch()
+            .plan_backfill(
+                &BackfillParams::builder()
+                    .datasets(["XNAS.ITCH"])
+                    .symbols("SPOT")
+                    .schemas(vec![Schema::Trades])
+                    .date_time_range((
+                        datetime!(2023 - 06 - 16 00:00 UTC),
+                        datetime!(2023 - 06 - 21 00:00 UTC),
+                    ))
+                    .output_dir("backfill")
+                    .calendars(CalendarRegistry::new())
+                    .build(),
+            )
+            .await
+            .unwrap();
+        // The weekend is skipped
+        let ids: Vec<_> = plan.tasks.iter().map(|task| task.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            ["XNAS.ITCH_trades_2023-06-16", "XNAS.ITCH_trades_2023-06-19"]
+        );
+    }
+
+    #[test]
+    fn test_exclude_days() {
+        let range = DateTimeRange::from((
+            datetime!(2024-03-04 14:30 UTC),
+            datetime!(2024-03-08 12:00 UTC),
+        ));
+        assert_eq!(exclude_days(&range, &[]), vec![range.clone()]);
+        assert_eq!(
+            exclude_days(&range, &[date!(2024 - 03 - 04), date!(2024 - 03 - 06)]),
+            vec![
+                DateTimeRange::from((
+                    datetime!(2024-03-05 00:00 UTC),
+                    datetime!(2024-03-06 00:00 UTC)
+                )),
+                DateTimeRange::from((
+                    datetime!(2024-03-07 00:00 UTC),
+                    datetime!(2024-03-08 12:00 UTC)
+                )),
+            ]
+        );
+        assert!(exclude_days(&range, &[date!(2024 - 03 - 08)])
+            .last()
+            .is_some_and(|last| last.end() == datetime!(2024-03-08 00:00 UTC)));
+    }
+}
@@ -4,40 +4,55 @@ use core::fmt;
 use std::{
     collections::HashMap,
     fmt::Write,
-    num::NonZeroU64,
+    num::{NonZeroU64, NonZeroUsize},
     path::{Path, PathBuf},
     str::FromStr,
 };
 
 use dbn::{Compression, Encoding, SType, Schema};
-use futures::StreamExt;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use hex::ToHex;
 use reqwest::RequestBuilder;
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
 use time::OffsetDateTime;
-use tokio::io::BufWriter;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
 use tracing::info;
 use typed_builder::TypedBuilder;
 
-use crate::{historical::check_http_error, Error, Symbols};
+use crate::{error::RequestContext, historical::check_http_error, Error, Symbols};
 
 use super::{
     deserialize::{deserialize_date_time, deserialize_opt_date_time},
-    handle_response, DateTimeRange,
+    handle_response,
+    metadata::GetQueryParams,
+    progress::{ProgressHandler, ProgressTracker},
+    CancellationToken, DateRange, DateTimeRange,
 };
 
 /// A client for the batch group of Historical API endpoints.
 #[derive(Debug)]
 pub struct BatchClient<'a> {
-    pub(crate) inner: &'a mut super::Client,
+    pub(crate) inner: &'a super::Client,
 }
 
 impl BatchClient<'_> {
     /// Submits a new batch job and returns a description and identifiers for the job.
     ///
     /// # Errors
-    /// This function returns an error when it fails to communicate with the Databento API
-    /// or the API indicates there's an issue with the request.
-    pub async fn submit_job(&mut self, params: &SubmitJobParams) -> crate::Result<BatchJob> {
+    /// This function returns an error when `pretty_px`, `pretty_ts`, or `map_symbols` is
+    /// set with [`Encoding::Dbn`], it fails to communicate with the Databento API, or the
+    /// API indicates there's an issue with the request.
+    pub async fn submit_job(&self, params: &SubmitJobParams) -> crate::Result<BatchJob> {
+        if params.encoding == Encoding::Dbn
+            && (params.pretty_px || params.pretty_ts || params.map_symbols)
+        {
+            return Err(Error::bad_arg(
+                "encoding",
+                "pretty_px, pretty_ts, and map_symbols are only valid for CSV and JSON",
+            ));
+        }
+        let (stype_in, symbols) = params.symbols.single_stype_group(params.stype_in)?;
         let mut form = vec![
             ("dataset", params.dataset.to_string()),
             ("schema", params.schema.to_string()),
@@ -49,9 +64,9 @@ impl BatchClient<'_> {
             ("split_symbols", params.split_symbols.to_string()),
             ("split_duration", params.split_duration.to_string()),
             ("delivery", params.delivery.to_string()),
-            ("stype_in", params.stype_in.to_string()),
+            ("stype_in", stype_in.to_string()),
             ("stype_out", params.stype_out.to_string()),
-            ("symbols", params.symbols.to_api_string()),
+            ("symbols", symbols.to_api_string()),
         ];
         params.date_time_range.add_to_form(&mut form);
         if let Some(split_size) = params.split_size {
@@ -60,9 +75,37 @@ impl BatchClient<'_> {
         if let Some(limit) = params.limit {
             form.push(("limit", limit.to_string()));
         }
-        let builder = self.post("submit_job")?.form(&form);
-        let resp = builder.send().await?;
-        handle_response(resp).await
+        let res: crate::Result<BatchJob> = async {
+            let billing_entry = self
+                .inner
+                .billing_entry(
+                    "batch.submit_job",
+                    &GetQueryParams::builder()
+                        .dataset(&params.dataset)
+                        .symbols(params.symbols.clone())
+                        .stype_in(params.stype_in)
+                        .schema(params.schema)
+                        .date_time_range(params.date_time_range.clone())
+                        .limit(params.limit)
+                        .build(),
+                )
+                .await?;
+            let builder = self.post("submit_job")?.form(&form);
+            let resp = self.inner.send(builder).await?;
+            let job = handle_response(resp).await?;
+            self.inner.billing.record_job(billing_entry, &job);
+            Ok(job)
+        }
+        .await;
+        res.map_err(|e| {
+            e.with_context(RequestContext::new(
+                "batch.submit_job",
+                &params.dataset,
+                Some(params.schema),
+                &params.symbols,
+                &params.date_time_range,
+            ))
+        })
     }
 
     /// Lists previous batch jobs with filtering by `params`.
@@ -70,7 +113,7 @@ impl BatchClient<'_> {
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request.
-    pub async fn list_jobs(&mut self, params: &ListJobsParams) -> crate::Result<Vec<BatchJob>> {
+    pub async fn list_jobs(&self, params: &ListJobsParams) -> crate::Result<Vec<BatchJob>> {
         let mut builder = self.get("list_jobs")?;
         if let Some(ref states) = params.states {
             let states_str = states.iter().fold(String::new(), |mut acc, s| {
@@ -86,8 +129,28 @@ impl BatchClient<'_> {
         if let Some(ref since) = params.since {
             builder = builder.query(&[("since", &since.unix_timestamp_nanos().to_string())]);
         }
-        let resp = builder.send().await?;
-        handle_response(resp).await
+        let resp = self.inner.send(builder).await?;
+        let jobs = handle_response(resp).await?;
+        self.inner.billing.update_jobs(&jobs);
+        Ok(jobs)
+    }
+
+    /// Gets the details of the batch job with ID `job_id`, including its cost, billed
+    /// size, and record count once it's processed.
+    ///
+    /// The API doesn't have an endpoint for a single job, so this searches the jobs
+    /// returned by [`list_jobs()`](Self::list_jobs) with the default filters.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request. It will also return an
+    /// error if there's no job with ID `job_id`.
+    pub async fn get_job(&self, job_id: &str) -> crate::Result<BatchJob> {
+        self.list_jobs(&ListJobsParams::default())
+            .await?
+            .into_iter()
+            .find(|job| job.id == job_id)
+            .ok_or_else(|| Error::bad_arg("job_id", format!("no job with ID {job_id}")))
     }
 
     /// Lists all files associated with the batch job with ID `job_id`.
@@ -95,22 +158,109 @@ impl BatchClient<'_> {
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request.
-    pub async fn list_files(&mut self, job_id: &str) -> crate::Result<Vec<BatchFileDesc>> {
-        let resp = self
-            .get("list_files")?
-            .query(&[("job_id", job_id)])
-            .send()
-            .await?;
+    pub async fn list_files(&self, job_id: &str) -> crate::Result<Vec<BatchFileDesc>> {
+        let builder = self.get("list_files")?.query(&[("job_id", job_id)]);
+        let resp = self.inner.send(builder).await?;
         handle_response(resp).await
     }
 
+    /// Lists the files associated with a batch job that match the filter in `params`,
+    /// skipping the first `offset` matches and returning at most `limit`. Useful for
+    /// jobs split by day or symbol with thousands of files.
+    ///
+    /// The API returns all files at once, so the filtering and pagination happen in the
+    /// client.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request.
+    pub async fn list_files_filtered(
+        &self,
+        params: &ListFilesParams,
+    ) -> crate::Result<Vec<BatchFileDesc>> {
+        Ok(self
+            .list_files(&params.job_id)
+            .await?
+            .into_iter()
+            .filter(|file| params.filter.matches(file))
+            .skip(params.offset)
+            .take(params.limit.map_or(usize::MAX, NonZeroUsize::get))
+            .collect())
+    }
+
+    /// Lists the files associated with a batch job that match `params` like
+    /// [`list_files_filtered()`](Self::list_files_filtered), but yields them one at a
+    /// time.
+    ///
+    /// # Errors
+    /// The stream yields an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request.
+    pub fn list_files_stream<'s>(
+        &'s self,
+        params: &'s ListFilesParams,
+    ) -> impl Stream<Item = crate::Result<BatchFileDesc>> + 's {
+        stream::once(self.list_files_filtered(params))
+            .map_ok(|files| stream::iter(files.into_iter().map(Ok)))
+            .try_flatten()
+    }
+
     /// Downloads the file specified in `params` or all files associated with the job ID.
     ///
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request. It will also return an
     /// error if it encounters an issue downloading a file.
-    pub async fn download(&mut self, params: &DownloadParams) -> crate::Result<Vec<PathBuf>> {
+    pub async fn download(&self, params: &DownloadParams) -> crate::Result<Vec<PathBuf>> {
+        self.download_with_cancellation(params, &CancellationToken::new())
+            .await
+    }
+
+    /// Downloads the file specified in `params` or all files associated with the job ID,
+    /// stopping early if `cancellation_token` is cancelled.
+    ///
+    /// Files that finished downloading before the cancellation are kept, while the file
+    /// being downloaded at the time is removed so no partially-written files remain.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request. It will also return an
+    /// error if it encounters an issue downloading a file. If the download is cancelled,
+    /// it returns [`Error::Cancelled`].
+    pub async fn download_with_cancellation(
+        &self,
+        params: &DownloadParams,
+        cancellation_token: &CancellationToken,
+    ) -> crate::Result<Vec<PathBuf>> {
+        self.download_impl(params, cancellation_token, None).await
+    }
+
+    /// Downloads the file specified in `params` or all files associated with the job ID
+    /// like [`download()`](Self::download), but periodically reports the number of
+    /// bytes downloaded across all files to `progress_handler`.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request. It will also return an
+    /// error if it encounters an issue downloading a file.
+    pub async fn download_with_progress(
+        &self,
+        params: &DownloadParams,
+        progress_handler: impl ProgressHandler + 'static,
+    ) -> crate::Result<Vec<PathBuf>> {
+        let mut tracker = ProgressTracker::new(progress_handler);
+        let paths = self
+            .download_impl(params, &CancellationToken::new(), Some(&mut tracker))
+            .await?;
+        tracker.finish();
+        Ok(paths)
+    }
+
+    async fn download_impl(
+        &self,
+        params: &DownloadParams,
+        cancellation_token: &CancellationToken,
+        mut progress: Option<&mut ProgressTracker>,
+    ) -> crate::Result<Vec<PathBuf>> {
         let job_dir = params.output_dir.join(&params.job_id);
         if job_dir.exists() {
             if !job_dir.is_dir() {
@@ -122,8 +272,8 @@ impl BatchClient<'_> {
         } else {
             tokio::fs::create_dir_all(&job_dir).await?;
         }
-        let job_files = self.list_files(&params.job_id).await?;
         if let Some(filename_to_download) = params.filename_to_download.as_ref() {
+            let job_files = self.list_files(&params.job_id).await?;
             let Some(file_desc) = job_files
                 .iter()
                 .find(|file| file.filename == *filename_to_download)
@@ -138,9 +288,18 @@ impl BatchClient<'_> {
                 .urls
                 .get("https")
                 .ok_or_else(|| Error::internal("Missing https URL for batch file"))?;
-            self.download_file(https_url, &output_path).await?;
+            self.download_file(https_url, &output_path, cancellation_token, progress)
+                .await?;
             Ok(vec![output_path])
         } else {
+            let job_files = self
+                .list_files_filtered(
+                    &ListFilesParams::builder()
+                        .job_id(&params.job_id)
+                        .filter(params.filter.clone())
+                        .build(),
+                )
+                .await?;
             let mut paths = Vec::new();
             for file_desc in job_files.iter() {
                 let output_path = params
@@ -151,19 +310,38 @@ impl BatchClient<'_> {
                     .urls
                     .get("https")
                     .ok_or_else(|| Error::internal("Missing https URL for batch file"))?;
-                self.download_file(https_url, &output_path).await?;
+                self.download_file(
+                    https_url,
+                    &output_path,
+                    cancellation_token,
+                    progress.as_deref_mut(),
+                )
+                .await?;
                 paths.push(output_path);
             }
             Ok(paths)
         }
     }
 
-    async fn download_file(&mut self, url: &str, path: impl AsRef<Path>) -> crate::Result<()> {
+    async fn download_file(
+        &self,
+        url: &str,
+        path: impl AsRef<Path>,
+        cancellation_token: &CancellationToken,
+        mut progress: Option<&mut ProgressTracker>,
+    ) -> crate::Result<()> {
+        if cancellation_token.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let path = path.as_ref();
         let url = reqwest::Url::parse(url)
             .map_err(|e| Error::internal(format!("Unable to parse URL: {e:?}")))?;
-        let resp = self.inner.get_with_path(url.path())?.send().await?;
+        let resp = self
+            .inner
+            .send(self.inner.get_with_path(url.path())?)
+            .await?;
         let mut stream = check_http_error(resp).await?.bytes_stream();
-        info!(%url, path=%path.as_ref().display(), "Downloading file");
+        info!(%url, path=%path.display(), "Downloading file");
         let mut output = BufWriter::new(
             tokio::fs::OpenOptions::new()
                 .create(true)
@@ -172,19 +350,41 @@ impl BatchClient<'_> {
                 .open(path)
                 .await?,
         );
-        while let Some(chunk) = stream.next().await {
-            tokio::io::copy(&mut chunk?.as_ref(), &mut output).await?;
+        let res: crate::Result<()> = async {
+            loop {
+                let chunk = tokio::select! {
+                    biased;
+                    _ = cancellation_token.cancelled() => return Err(Error::Cancelled),
+                    chunk = stream.next() => chunk,
+                };
+                let Some(chunk) = chunk else {
+                    break;
+                };
+                let chunk = chunk?;
+                tokio::io::copy(&mut chunk.as_ref(), &mut output).await?;
+                if let Some(progress) = progress.as_deref_mut() {
+                    progress.add_downloaded(chunk.len() as u64);
+                }
+            }
+            output.flush().await?;
+            Ok(())
         }
-        Ok(())
+        .await;
+        if res.is_err() {
+            drop(output);
+            // Don't leave a partially-written file
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        res
     }
 
     const PATH_PREFIX: &'static str = "batch";
 
-    fn get(&mut self, slug: &str) -> crate::Result<RequestBuilder> {
+    fn get(&self, slug: &str) -> crate::Result<RequestBuilder> {
         self.inner.get(&format!("{}.{slug}", Self::PATH_PREFIX))
     }
 
-    fn post(&mut self, slug: &str) -> crate::Result<RequestBuilder> {
+    fn post(&self, slug: &str) -> crate::Result<RequestBuilder> {
         self.inner.post(&format!("{}.{slug}", Self::PATH_PREFIX))
     }
 }
@@ -230,7 +430,7 @@ pub enum JobState {
 
 /// The parameters for [`BatchClient::submit_job()`]. Use [`SubmitJobParams::builder()`] to
 /// get a builder type with all the preset defaults.
-#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
+#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SubmitJobParams {
     /// The dataset code.
     #[builder(setter(transform = |dt: impl ToString| dt.to_string()))]
@@ -239,54 +439,79 @@ pub struct SubmitJobParams {
     #[builder(setter(into))]
     pub symbols: Symbols,
     /// The data record schema.
+    #[serde(with = "crate::serialize::display_from_str")]
     pub schema: Schema,
     /// The request time range.
     #[builder(setter(into))]
     pub date_time_range: DateTimeRange,
     /// The data encoding. Defaults to [`Dbn`](Encoding::Dbn).
     #[builder(default = Encoding::Dbn)]
+    #[serde(
+        default = "crate::serialize::default_encoding",
+        with = "crate::serialize::display_from_str"
+    )]
     pub encoding: Encoding,
     /// The data compression mode. Defaults to [`ZStd`](Compression::ZStd).
     #[builder(default = Compression::ZStd)]
+    #[serde(
+        default = "crate::serialize::default_compression",
+        with = "crate::serialize::display_from_str"
+    )]
     pub compression: Compression,
     /// If `true`, prices will be formatted to the correct scale (using the fixed-
     /// precision scalar 1e-9). Only valid for [`Encoding::Csv`] and [`Encoding::Json`].
     #[builder(default)]
+    #[serde(default)]
     pub pretty_px: bool,
     /// If `true`, timestamps will be formatted as ISO 8601 strings. Only valid for
     /// [`Encoding::Csv`] and [`Encoding::Json`].
     #[builder(default)]
+    #[serde(default)]
     pub pretty_ts: bool,
     /// If `true`, a symbol field will be included with each text-encoded
     /// record, reducing the need to look at the `symbology.json`. Only valid for
     /// [`Encoding::Csv`] and [`Encoding::Json`].
     #[builder(default)]
+    #[serde(default)]
     pub map_symbols: bool,
     /// If `true`, files will be split by raw symbol. Cannot be requested with [`Symbols::All`].
     #[builder(default)]
+    #[serde(default)]
     pub split_symbols: bool,
     /// The maximum time duration before batched data is split into multiple files.
     /// Defaults to [`Day`](SplitDuration::Day).
     #[builder(default)]
+    #[serde(default)]
     pub split_duration: SplitDuration,
     /// The optional maximum size (in bytes) of each batched data file before being split.
     /// Must be an integer between 1e9 and 10e9 inclusive (1GB - 10GB). Defaults to `None`.
     #[builder(default, setter(strip_option))]
+    #[serde(default)]
     pub split_size: Option<NonZeroU64>,
     /// The delivery mechanism for the batched data files once processed. Defaults to
     /// [`Download`](Delivery::Download).
     #[builder(default)]
+    #[serde(default)]
     pub delivery: Delivery,
     /// The symbology type of the input `symbols`. Defaults to
     /// [`RawSymbol`](dbn::enums::SType::RawSymbol).
     #[builder(default = SType::RawSymbol)]
+    #[serde(
+        default = "crate::serialize::default_stype_in",
+        with = "crate::serialize::display_from_str"
+    )]
     pub stype_in: SType,
     /// The symbology type of the output `symbols`. Defaults to
     /// [`InstrumentId`](dbn::enums::SType::InstrumentId).
     #[builder(default = SType::InstrumentId)]
+    #[serde(
+        default = "crate::serialize::default_stype_out",
+        with = "crate::serialize::display_from_str"
+    )]
     pub stype_out: SType,
     /// The optional maximum number of records to return. Defaults to no limit.
     #[builder(default)]
+    #[serde(default)]
     pub limit: Option<NonZeroU64>,
 }
 
@@ -379,6 +604,45 @@ pub struct ListJobsParams {
     pub since: Option<OffsetDateTime>,
 }
 
+/// The parameters for [`BatchClient::list_files_filtered()`]. Use
+/// [`ListFilesParams::builder()`] to get a builder type with all the preset defaults.
+#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
+pub struct ListFilesParams {
+    /// The batch job identifier.
+    #[builder(setter(transform = |dt: impl ToString| dt.to_string()))]
+    pub job_id: String,
+    /// The filter for which files to list. Defaults to all files.
+    #[builder(default)]
+    pub filter: BatchFileFilter,
+    /// The number of matching files to skip. Defaults to 0.
+    #[builder(default)]
+    pub offset: usize,
+    /// The optional maximum number of files to return. Defaults to no limit.
+    #[builder(default, setter(strip_option))]
+    pub limit: Option<NonZeroUsize>,
+}
+
+/// A filter for the files of a batch job based on their names, which are of the form
+/// `{dataset}-{date}.{schema}.{encoding}`. Files whose name doesn't contain a schema or
+/// date, such as `metadata.json`, aren't excluded by the `schemas` or `date_range`
+/// filters. Use [`BatchFileFilter::builder()`] to get a builder type with all the
+/// preset defaults.
+#[derive(Debug, Clone, Default, TypedBuilder, PartialEq, Eq)]
+pub struct BatchFileFilter {
+    /// The optional pattern the file name must match, where `*` matches any sequence
+    /// of characters and `?` matches any single character. Defaults to no pattern.
+    #[builder(default, setter(transform = |glob: impl ToString| Some(glob.to_string())))]
+    pub filename_glob: Option<String>,
+    /// The optional schemas of the files to include. Defaults to all schemas.
+    #[builder(default, setter(transform = |schemas: impl IntoIterator<Item = Schema>| {
+        Some(schemas.into_iter().collect())
+    }))]
+    pub schemas: Option<Vec<Schema>>,
+    /// The optional range of the dates of the files to include. Defaults to all dates.
+    #[builder(default, setter(transform = |dr: impl Into<DateRange>| Some(dr.into())))]
+    pub date_range: Option<DateRange>,
+}
+
 /// The file details for a batch job.
 #[derive(Debug, Clone, Deserialize)]
 pub struct BatchFileDesc {
@@ -386,12 +650,58 @@ pub struct BatchFileDesc {
     pub filename: String,
     /// The size of the file in bytes.
     pub size: u64,
-    /// The SHA256 hash of the file.
-    pub hash: String,
+    /// The hash of the file.
+    pub hash: FileHash,
     /// A map of download protocol to URL.
     pub urls: HashMap<String, String>,
 }
 
+/// The hash of a batch file, used for verifying its integrity after downloading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHash {
+    /// The algorithm used to compute the hash.
+    pub algorithm: HashAlgorithm,
+    /// The lowercase hex-encoded hash value.
+    pub value: String,
+}
+
+/// A hash algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// SHA-256.
+    Sha256,
+}
+
+/// The result of verifying a downloaded file against its [`BatchFileDesc`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileVerification {
+    /// The file's size and hash match.
+    Valid,
+    /// The file doesn't exist.
+    Missing,
+    /// The size of the file doesn't match.
+    SizeMismatch {
+        /// The size in bytes according to the manifest.
+        expected: u64,
+        /// The size in bytes of the file on disk.
+        actual: u64,
+    },
+    /// The hash of the file doesn't match.
+    HashMismatch {
+        /// The hash according to the manifest.
+        expected: String,
+        /// The hash of the file on disk.
+        actual: String,
+    },
+}
+
+/// The results of verifying all the files of a batch job with [`verify_all()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationReport {
+    /// The verification result of each file in the manifest and its path.
+    pub files: Vec<(PathBuf, FileVerification)>,
+}
+
 /// The parameters for [`BatchClient::download()`]. Use [`DownloadParams::builder()`] to
 /// get a builder type with all the preset defaults.
 #[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
@@ -405,6 +715,10 @@ pub struct DownloadParams {
     /// `None` means all files associated with the job will be downloaded.
     #[builder(default, setter(strip_option))]
     pub filename_to_download: Option<String>,
+    /// The filter for which files to download when `filename_to_download` is `None`.
+    /// Defaults to all files.
+    #[builder(default)]
+    pub filter: BatchFileFilter,
 }
 
 impl SplitDuration {
@@ -443,6 +757,12 @@ impl FromStr for SplitDuration {
     }
 }
 
+impl Serialize for SplitDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 impl<'de> Deserialize<'de> for SplitDuration {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let str = String::deserialize(deserializer)?;
@@ -486,6 +806,12 @@ impl FromStr for Delivery {
     }
 }
 
+impl Serialize for Delivery {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 impl<'de> Deserialize<'de> for Delivery {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let str = String::deserialize(deserializer)?;
@@ -533,6 +859,12 @@ impl FromStr for JobState {
     }
 }
 
+impl Serialize for JobState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 impl<'de> Deserialize<'de> for JobState {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let str = String::deserialize(deserializer)?;
@@ -540,6 +872,249 @@ impl<'de> Deserialize<'de> for JobState {
     }
 }
 
+impl HashAlgorithm {
+    /// Converts the enum to its `str` representation.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => Err(crate::Error::bad_arg(
+                "s",
+                format!(
+                    "{s} does not correspond with any {} variant",
+                    std::any::type_name::<Self>()
+                ),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FileHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm, self.value)
+    }
+}
+
+impl FromStr for FileHash {
+    type Err = crate::Error;
+
+    /// Parses a hash in the form `<algorithm>:<hex value>`. Hashes without an algorithm
+    /// prefix are assumed to be SHA-256.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, value) = match s.split_once(':') {
+            Some((algorithm, value)) => (algorithm.parse()?, value),
+            None => (HashAlgorithm::Sha256, s),
+        };
+        Ok(Self {
+            algorithm,
+            value: value.to_ascii_lowercase(),
+        })
+    }
+}
+
+impl Serialize for FileHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let str = String::deserialize(deserializer)?;
+        FromStr::from_str(&str).map_err(de::Error::custom)
+    }
+}
+
+impl FileVerification {
+    /// Returns `true` if the file matched the manifest.
+    pub fn is_valid(&self) -> bool {
+        matches!(self, FileVerification::Valid)
+    }
+}
+
+impl VerificationReport {
+    /// Returns `true` if all files matched the manifest.
+    pub fn is_valid(&self) -> bool {
+        self.files.iter().all(|(_, res)| res.is_valid())
+    }
+
+    /// Returns an iterator over the files that didn't match the manifest.
+    pub fn failures(&self) -> impl Iterator<Item = &(PathBuf, FileVerification)> {
+        self.files.iter().filter(|(_, res)| !res.is_valid())
+    }
+}
+
+impl BatchFileFilter {
+    /// Returns `true` if `file` matches the filter.
+    pub fn matches(&self, file: &BatchFileDesc) -> bool {
+        if let Some(glob) = &self.filename_glob {
+            if !glob_matches(glob, &file.filename) {
+                return false;
+            }
+        }
+        if let (Some(schemas), Some(schema)) = (&self.schemas, file_schema(&file.filename)) {
+            if !schemas.contains(&schema) {
+                return false;
+            }
+        }
+        if let (Some(date_range), Some(date)) = (&self.date_range, file_date(&file.filename)) {
+            if !date_range.contains(date) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Returns `true` if `name` matches `glob`, where `*` matches any sequence of characters
+/// and `?` matches any single character.
+fn glob_matches(glob: &str, name: &str) -> bool {
+    let glob = glob.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut g, mut n) = (0, 0);
+    // The position of the last `*` and the position in `name` it was matched up to
+    let mut backtrack = None;
+    while n < name.len() {
+        match glob.get(g) {
+            Some('*') => {
+                backtrack = Some((g, n));
+                g += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            _ => {
+                let Some((star, matched)) = backtrack else {
+                    return false;
+                };
+                // Let the `*` match one more character
+                g = star + 1;
+                n = matched + 1;
+                backtrack = Some((star, n));
+            }
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+/// Returns the schema from a file name like `glbx-mdp3-20240101.trades.dbn.zst`.
+fn file_schema(filename: &str) -> Option<Schema> {
+    filename.split('.').nth(1)?.parse().ok()
+}
+
+/// Returns the date from a file name like `glbx-mdp3-20240101.trades.dbn.zst`.
+fn file_date(filename: &str) -> Option<time::Date> {
+    let (_, date) = filename.split('.').next()?.rsplit_once('-')?;
+    if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let month = time::Month::try_from(date[4..6].parse::<u8>().ok()?).ok()?;
+    time::Date::from_calendar_date(date[..4].parse().ok()?, month, date[6..].parse().ok()?).ok()
+}
+
+impl BatchFileDesc {
+    /// Recomputes the size and hash of the downloaded file at `path` and compares them
+    /// against the manifest.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to read the file for a reason other
+    /// than it not existing.
+    pub async fn verify(&self, path: impl AsRef<Path>) -> crate::Result<FileVerification> {
+        verify_file(path, self.size, &self.hash).await
+    }
+}
+
+impl FileHash {
+    /// Computes the hash of the contents of `file` with `algorithm`.
+    pub(crate) async fn compute(
+        file: &mut tokio::fs::File,
+        algorithm: HashAlgorithm,
+    ) -> crate::Result<Self> {
+        let value = match algorithm {
+            HashAlgorithm::Sha256 => {
+                let mut hasher = Sha256::new();
+                let mut buffer = vec![0; 1 << 16];
+                loop {
+                    let read = file.read(&mut buffer).await?;
+                    if read == 0 {
+                        break;
+                    }
+                    hasher.update(&buffer[..read]);
+                }
+                hasher.finalize().encode_hex::<String>()
+            }
+        };
+        Ok(Self { algorithm, value })
+    }
+}
+
+/// Recomputes the size and hash of the file at `path` and compares them against
+/// `size` and `hash`.
+pub(crate) async fn verify_file(
+    path: impl AsRef<Path>,
+    size: u64,
+    hash: &FileHash,
+) -> crate::Result<FileVerification> {
+    let mut file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(FileVerification::Missing),
+        Err(e) => return Err(e.into()),
+    };
+    let actual_size = file.metadata().await?.len();
+    if actual_size != size {
+        return Ok(FileVerification::SizeMismatch {
+            expected: size,
+            actual: actual_size,
+        });
+    }
+    let actual_hash = FileHash::compute(&mut file, hash.algorithm).await?.value;
+    if actual_hash == hash.value {
+        Ok(FileVerification::Valid)
+    } else {
+        Ok(FileVerification::HashMismatch {
+            expected: hash.value.clone(),
+            actual: actual_hash,
+        })
+    }
+}
+
+/// Verifies every file in `files` against the downloaded copy in `dir`, such as the
+/// job directory created by [`BatchClient::download()`].
+///
+/// # Errors
+/// This function returns an error if it fails to read a file for a reason other than it
+/// not existing.
+pub async fn verify_all(
+    files: &[BatchFileDesc],
+    dir: impl AsRef<Path>,
+) -> crate::Result<VerificationReport> {
+    let mut report = VerificationReport {
+        files: Vec::with_capacity(files.len()),
+    };
+    for file_desc in files {
+        let path = dir.as_ref().join(&file_desc.filename);
+        let res = file_desc.verify(&path).await?;
+        report.files.push((path, res));
+    }
+    Ok(report)
+}
+
 // Handles Compression::None being serialized as null in JSON
 fn deserialize_compression<'de, D: serde::Deserializer<'de>>(
     deserializer: D,
@@ -552,9 +1127,9 @@ fn deserialize_compression<'de, D: serde::Deserializer<'de>>(
 mod tests {
     use reqwest::StatusCode;
     use serde_json::json;
-    use time::macros::datetime;
+    use time::macros::{date, datetime};
     use wiremock::{
-        matchers::{basic_auth, method, path, query_param_is_missing},
+        matchers::{basic_auth, method, path, query_param, query_param_is_missing},
         Mock, MockServer, ResponseTemplate,
     };
 
@@ -624,7 +1199,7 @@ mod tests {
             )
             .mount(&mock_server)
             .await;
-        let mut target = HistoricalClient::with_url(
+        let target = HistoricalClient::with_url(
             mock_server.uri(),
             API_KEY.to_owned(),
             HistoricalGateway::Bo1,
@@ -641,6 +1216,26 @@ mod tests {
             )
             .await?;
         assert_eq!(job_desc.dataset, dbn::Dataset::XnasItch.as_str());
+        let report = target.billing_report();
+        assert_eq!(report.entries.len(), 1);
+        assert_eq!(
+            report.entries[0].job_id.as_deref(),
+            Some(job_desc.id.as_str())
+        );
+        assert_eq!(report.entries[0].cost_usd, job_desc.cost_usd);
+        let res = target
+            .batch()
+            .submit_job(
+                &SubmitJobParams::builder()
+                    .dataset(dbn::Dataset::XnasItch)
+                    .schema(SCHEMA)
+                    .symbols("TSLA")
+                    .date_time_range((START, END))
+                    .pretty_px(true)
+                    .build(),
+            )
+            .await;
+        assert!(matches!(res, Err(Error::BadArgument { .. })));
         Ok(())
     }
 
@@ -688,7 +1283,7 @@ mod tests {
             )
             .mount(&mock_server)
             .await;
-        let mut target = HistoricalClient::with_url(
+        let target = HistoricalClient::with_url(
             mock_server.uri(),
             API_KEY.to_owned(),
             HistoricalGateway::Bo1,
@@ -708,6 +1303,9 @@ mod tests {
         assert!(job_desc.pretty_px);
         assert!(!job_desc.pretty_ts);
         assert!(job_desc.map_symbols);
+        let job = target.batch().get_job("123").await?;
+        assert_eq!(job.cost_usd, Some(10.5));
+        assert!(target.batch().get_job("456").await.is_err());
         Ok(())
     }
 
@@ -727,4 +1325,147 @@ mod tests {
             vec![Compression::None, Compression::None, Compression::ZStd]
         );
     }
+
+    #[tokio::test]
+    async fn test_list_files_filtered() -> crate::Result<()> {
+        let file = |filename: &str| {
+            json!({
+                "filename": filename,
+                "size": 5,
+                "hash": "sha256:abc",
+                "urls": {}
+            })
+        };
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/batch.list_files")))
+            .and(query_param("job_id", "123"))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!([
+                    file("metadata.json"),
+                    file("xnas-itch-20240102.trades.dbn.zst"),
+                    file("xnas-itch-20240102.mbp-1.dbn.zst"),
+                    file("xnas-itch-20240103.trades.dbn.zst"),
+                    file("xnas-itch-20240104.trades.dbn.zst"),
+                ])),
+            )
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )?;
+        let filenames = |files: Vec<BatchFileDesc>| {
+            files
+                .into_iter()
+                .map(|file| file.filename)
+                .collect::<Vec<_>>()
+        };
+        let params = ListFilesParams::builder()
+            .job_id("123")
+            .filter(
+                BatchFileFilter::builder()
+                    .schemas([Schema::Trades])
+                    .date_range((date!(2024 - 01 - 02), date!(2024 - 01 - 04)))
+                    .build(),
+            )
+            .build();
+        assert_eq!(
+            filenames(target.batch().list_files_filtered(&params).await?),
+            [
+                "metadata.json",
+                "xnas-itch-20240102.trades.dbn.zst",
+                "xnas-itch-20240103.trades.dbn.zst"
+            ]
+        );
+        let params = ListFilesParams::builder()
+            .job_id("123")
+            .filter(
+                BatchFileFilter::builder()
+                    .filename_glob("*.dbn.zst")
+                    .build(),
+            )
+            .offset(1)
+            .limit(NonZeroUsize::new(2).unwrap())
+            .build();
+        let files = target
+            .batch()
+            .list_files_stream(&params)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(
+            filenames(files),
+            [
+                "xnas-itch-20240102.mbp-1.dbn.zst",
+                "xnas-itch-20240103.trades.dbn.zst"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches(
+            "*.trades.*",
+            "xnas-itch-20240102.trades.dbn.zst"
+        ));
+        assert!(glob_matches(
+            "xnas-itch-2024010?.*",
+            "xnas-itch-20240102.trades.dbn"
+        ));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("a*b*c", "aXbYbZc"));
+        assert!(!glob_matches("a*b*c", "aXbYbZ"));
+        assert!(!glob_matches("*.json", "xnas-itch-20240102.trades.dbn"));
+        assert!(!glob_matches("?", ""));
+    }
+
+    #[tokio::test]
+    async fn test_verify_all() {
+        // SHA-256 of "hello"
+        const HASH: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let files: Vec<BatchFileDesc> = serde_json::from_value(json!([
+            {"filename": "valid.dbn", "size": 5, "hash": format!("sha256:{HASH}"), "urls": {}},
+            {"filename": "corrupt.dbn", "size": 5, "hash": format!("sha256:{HASH}"), "urls": {}},
+            {"filename": "truncated.dbn", "size": 5, "hash": format!("sha256:{HASH}"), "urls": {}},
+            {"filename": "missing.dbn", "size": 5, "hash": format!("sha256:{HASH}"), "urls": {}},
+        ]))
+        .unwrap();
+        assert_eq!(
+            files[0].hash,
+            FileHash {
+                algorithm: HashAlgorithm::Sha256,
+                value: HASH.to_owned()
+            }
+        );
+        let dir = tempfile::tempdir().unwrap();
+        tokio::fs::write(dir.path().join("valid.dbn"), "hello")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("corrupt.dbn"), "hellp")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("truncated.dbn"), "hell")
+            .await
+            .unwrap();
+
+        let report = verify_all(&files, dir.path()).await.unwrap();
+        assert!(!report.is_valid());
+        let results: Vec<_> = report.files.iter().map(|(_, res)| res.clone()).collect();
+        assert_eq!(results[0], FileVerification::Valid);
+        assert!(
+            matches!(&results[1], FileVerification::HashMismatch { expected, .. } if expected == HASH)
+        );
+        assert_eq!(
+            results[2],
+            FileVerification::SizeMismatch {
+                expected: 5,
+                actual: 4
+            }
+        );
+        assert_eq!(results[3], FileVerification::Missing);
+        assert_eq!(report.failures().count(), 3);
+    }
 }
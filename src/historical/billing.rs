@@ -0,0 +1,307 @@
+//! Tracking the billed sizes and costs of the requests made by a historical client.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use dbn::Schema;
+use serde::Serialize;
+
+use super::{batch::BatchJob, metadata::GetQueryParams, Client, DateTimeRange};
+
+/// A billable request made by a historical client.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BillingEntry {
+    /// The API endpoint, such as `"timeseries.get_range"`.
+    pub endpoint: &'static str,
+    /// The dataset code.
+    pub dataset: String,
+    /// The data record schema.
+    pub schema: Schema,
+    /// The request time range.
+    pub date_time_range: DateTimeRange,
+    /// The ID of the batch job. `None` for streaming requests.
+    pub job_id: Option<String>,
+    /// The billable size quoted by the API before the request was made. Only set when
+    /// the client was built with
+    /// [`estimate_costs()`](super::ClientBuilder::estimate_costs).
+    pub estimated_size: Option<u64>,
+    /// The cost in US dollars quoted by the API before the request was made. Only set
+    /// when the client was built with
+    /// [`estimate_costs()`](super::ClientBuilder::estimate_costs).
+    pub estimated_cost_usd: Option<f64>,
+    /// The size billed for a batch job. `None` until the job is processed and its
+    /// details are fetched with
+    /// [`BatchClient::list_jobs()`](super::batch::BatchClient::list_jobs) or
+    /// [`BatchClient::get_job()`](super::batch::BatchClient::get_job).
+    pub billed_size: Option<u64>,
+    /// The cost in US dollars billed for a batch job. `None` until the job is processed
+    /// and its details are fetched.
+    pub cost_usd: Option<f64>,
+    /// The number of bytes received so far for a streaming request, as sent by the
+    /// API. For compressed responses, this is the compressed size.
+    pub bytes_received: Option<u64>,
+}
+
+impl Client {
+    /// Creates the billing entry for a request to `endpoint`, quoting its billable size
+    /// and cost if the client [estimates costs](Self::estimate_costs).
+    pub(crate) async fn billing_entry(
+        &self,
+        endpoint: &'static str,
+        params: &GetQueryParams,
+    ) -> crate::Result<BillingEntry> {
+        let (estimated_size, estimated_cost_usd) = if self.estimate_costs() {
+            let metadata = self.metadata();
+            let size = metadata.get_billable_size(params).await?;
+            let cost = metadata.get_cost(params).await?;
+            (Some(size), Some(cost))
+        } else {
+            (None, None)
+        };
+        Ok(BillingEntry {
+            endpoint,
+            dataset: params.dataset.clone(),
+            schema: params.schema,
+            date_time_range: params.date_time_range.clone(),
+            job_id: None,
+            estimated_size,
+            estimated_cost_usd,
+            billed_size: None,
+            cost_usd: None,
+            bytes_received: None,
+        })
+    }
+}
+
+/// The totals of the requests for a dataset and schema.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BillingSummary {
+    /// The dataset code.
+    pub dataset: String,
+    /// The data record schema.
+    pub schema: Schema,
+    /// The number of requests.
+    pub request_count: usize,
+    /// The sum of the estimated sizes.
+    pub estimated_size: u64,
+    /// The sum of the estimated costs in US dollars.
+    pub estimated_cost_usd: f64,
+    /// The sum of the billed sizes of processed batch jobs.
+    pub billed_size: u64,
+    /// The sum of the billed costs in US dollars of processed batch jobs.
+    pub cost_usd: f64,
+    /// The sum of the bytes received from streaming requests.
+    pub bytes_received: u64,
+}
+
+/// A snapshot of the billable requests made by a historical client. Returned by
+/// [`HistoricalClient::billing_report()`](super::Client::billing_report).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BillingReport {
+    /// The requests in the order they were made.
+    pub entries: Vec<BillingEntry>,
+}
+
+impl BillingReport {
+    /// Returns the totals for each dataset and schema, sorted by dataset and schema name.
+    pub fn breakdown(&self) -> Vec<BillingSummary> {
+        let mut summaries = BTreeMap::new();
+        for entry in &self.entries {
+            let summary = summaries
+                .entry((entry.dataset.as_str(), entry.schema.as_str()))
+                .or_insert_with(|| BillingSummary {
+                    dataset: entry.dataset.clone(),
+                    schema: entry.schema,
+                    request_count: 0,
+                    estimated_size: 0,
+                    estimated_cost_usd: 0.0,
+                    billed_size: 0,
+                    cost_usd: 0.0,
+                    bytes_received: 0,
+                });
+            summary.request_count += 1;
+            summary.estimated_size += entry.estimated_size.unwrap_or_default();
+            summary.estimated_cost_usd += entry.estimated_cost_usd.unwrap_or_default();
+            summary.billed_size += entry.billed_size.unwrap_or_default();
+            summary.cost_usd += entry.cost_usd.unwrap_or_default();
+            summary.bytes_received += entry.bytes_received.unwrap_or_default();
+        }
+        summaries.into_values().collect()
+    }
+
+    /// Serializes the report as a JSON object with the `breakdown` by dataset and
+    /// schema and the individual `entries`.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to serialize the report.
+    pub fn to_json(&self) -> crate::Result<String> {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            breakdown: Vec<BillingSummary>,
+            entries: &'a [BillingEntry],
+        }
+
+        serde_json::to_string(&Json {
+            breakdown: self.breakdown(),
+            entries: &self.entries,
+        })
+        .map_err(|e| crate::Error::internal(format!("failed to serialize report: {e}")))
+    }
+
+    /// Formats the breakdown by dataset and schema as CSV with a header row.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "dataset,schema,request_count,estimated_size,estimated_cost_usd,billed_size,\
+             cost_usd,bytes_received\n",
+        );
+        for summary in self.breakdown() {
+            // Writing to a `String` can't fail
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                summary.dataset,
+                summary.schema,
+                summary.request_count,
+                summary.estimated_size,
+                summary.estimated_cost_usd,
+                summary.billed_size,
+                summary.cost_usd,
+                summary.bytes_received,
+            );
+        }
+        csv
+    }
+}
+
+/// The record of billable requests shared by clones of a client.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BillingLedger(Arc<Mutex<Vec<LedgerEntry>>>);
+
+#[derive(Debug)]
+struct LedgerEntry {
+    entry: BillingEntry,
+    bytes_received: Option<Arc<AtomicU64>>,
+}
+
+impl BillingLedger {
+    /// Records a streaming request, returning the counter for the bytes received.
+    pub fn record_stream(&self, entry: BillingEntry) -> Arc<AtomicU64> {
+        let bytes_received = Arc::new(AtomicU64::new(0));
+        self.push(LedgerEntry {
+            entry,
+            bytes_received: Some(bytes_received.clone()),
+        });
+        bytes_received
+    }
+
+    /// Records a submitted batch job.
+    pub fn record_job(&self, mut entry: BillingEntry, job: &BatchJob) {
+        entry.job_id = Some(job.id.clone());
+        entry.billed_size = job.billed_size;
+        entry.cost_usd = job.cost_usd;
+        self.push(LedgerEntry {
+            entry,
+            bytes_received: None,
+        });
+    }
+
+    /// Updates the billed size and cost of recorded batch jobs from `jobs`.
+    pub fn update_jobs(&self, jobs: &[BatchJob]) {
+        let mut entries = self.lock();
+        for ledger_entry in entries.iter_mut() {
+            let entry = &mut ledger_entry.entry;
+            let Some(job) = jobs
+                .iter()
+                .find(|job| entry.job_id.as_deref() == Some(job.id.as_str()))
+            else {
+                continue;
+            };
+            entry.billed_size = job.billed_size.or(entry.billed_size);
+            entry.cost_usd = job.cost_usd.or(entry.cost_usd);
+        }
+    }
+
+    pub fn report(&self) -> BillingReport {
+        let entries = self
+            .lock()
+            .iter()
+            .map(|ledger_entry| {
+                let mut entry = ledger_entry.entry.clone();
+                entry.bytes_received = ledger_entry
+                    .bytes_received
+                    .as_ref()
+                    .map(|count| count.load(Ordering::Relaxed));
+                entry
+            })
+            .collect();
+        BillingReport { entries }
+    }
+
+    fn push(&self, entry: LedgerEntry) {
+        self.lock().push(entry);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<LedgerEntry>> {
+        // Entries are only appended or updated in place, so they're still consistent
+        // if another thread panicked while holding the lock
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn entry(dataset: &str, schema: Schema) -> BillingEntry {
+        BillingEntry {
+            endpoint: "timeseries.get_range",
+            dataset: dataset.to_owned(),
+            schema,
+            date_time_range: DateTimeRange::from((
+                datetime!(2024-03-04 00:00 UTC),
+                datetime!(2024-03-05 00:00 UTC),
+            )),
+            job_id: None,
+            estimated_size: Some(100),
+            estimated_cost_usd: Some(0.5),
+            billed_size: None,
+            cost_usd: None,
+            bytes_received: None,
+        }
+    }
+
+    #[test]
+    fn test_report_breakdown() {
+        let target = BillingLedger::default();
+        target
+            .record_stream(entry("XNAS.ITCH", Schema::Trades))
+            .fetch_add(40, Ordering::Relaxed);
+        target.record_stream(entry("XNAS.ITCH", Schema::Trades));
+        target.record_stream(entry("GLBX.MDP3", Schema::Mbo));
+        let report = target.report();
+        assert_eq!(report.entries.len(), 3);
+        assert_eq!(report.entries[0].bytes_received, Some(40));
+        let breakdown = report.breakdown();
+        assert_eq!(breakdown.len(), 2);
+        assert_eq!(breakdown[0].dataset, "GLBX.MDP3");
+        assert_eq!(breakdown[1].request_count, 2);
+        assert_eq!(breakdown[1].estimated_size, 200);
+        assert_eq!(breakdown[1].estimated_cost_usd, 1.0);
+        assert_eq!(breakdown[1].bytes_received, 40);
+        assert_eq!(
+            report.to_csv().lines().nth(2),
+            Some("XNAS.ITCH,trades,2,200,1,0,0,40")
+        );
+        assert!(report.to_json().unwrap().contains(r#""request_count":2"#));
+    }
+}
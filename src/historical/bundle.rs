@@ -0,0 +1,456 @@
+//! Packaging historical data into self-describing bundles: a directory with a JSON
+//! manifest, one DBN file per schema, and the symbology mappings of the requested
+//! symbols. Bundles are a reproducible input format for research pipelines, and are
+//! verified against the manifest when they're opened.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use dbn::{decode::AsyncDbnDecoder, MappingInterval, SType, Schema};
+use serde::{Deserialize, Serialize};
+use time::Date;
+use tokio::io::AsyncRead;
+use tracing::info;
+use typed_builder::TypedBuilder;
+
+use crate::{Error, Symbols};
+
+use super::{
+    batch::{verify_file, FileHash, HashAlgorithm, VerificationReport},
+    symbology::{Resolution, ResolveParams},
+    timeseries::{GetRangeToFileParams, TimeseriesClient},
+    DateRange, DateTimeRange,
+};
+
+/// The version of the bundle format written by
+/// [`TimeseriesClient::export_bundle()`].
+pub const BUNDLE_VERSION: u32 = 1;
+/// The file name of the manifest within a bundle directory.
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+/// The file name of the symbology mappings within a bundle directory.
+pub const SYMBOLOGY_FILENAME: &str = "symbology.json";
+
+/// The parameters for [`TimeseriesClient::export_bundle()`]. Use
+/// [`ExportBundleParams::builder()`] to get a builder type with all the preset
+/// defaults.
+#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
+pub struct ExportBundleParams {
+    /// The dataset code.
+    #[builder(setter(transform = |dt: impl ToString| dt.to_string()))]
+    pub dataset: String,
+    /// The symbols to export.
+    #[builder(setter(into))]
+    pub symbols: Symbols,
+    /// The symbology type of the input `symbols`. Defaults to
+    /// [`RawSymbol`](dbn::enums::SType::RawSymbol).
+    #[builder(default = SType::RawSymbol)]
+    pub stype_in: SType,
+    /// The data record schemas to export, one file per schema.
+    #[builder(setter(into))]
+    pub schemas: Vec<Schema>,
+    /// The request time range.
+    #[builder(setter(into))]
+    pub date_time_range: DateTimeRange,
+    /// The directory to write the bundle to. It's created if it doesn't exist.
+    #[builder(setter(into))]
+    pub dir: PathBuf,
+}
+
+/// The contents of a bundle's manifest, describing how the data was requested and the
+/// files it contains.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    /// The version of the bundle format.
+    pub version: u32,
+    /// The dataset code.
+    pub dataset: String,
+    /// The requested symbols.
+    pub symbols: Symbols,
+    /// The symbology type of `symbols`.
+    #[serde(with = "crate::serialize::display_from_str")]
+    pub stype_in: SType,
+    /// The requested time range.
+    pub date_time_range: DateTimeRange,
+    /// The zstd-compressed DBN file of each schema.
+    pub files: Vec<BundleDataFile>,
+    /// The symbology mappings of `symbols` over the time range.
+    pub symbology: BundleFile,
+}
+
+/// A file within a bundle with the size and hash used for verifying its integrity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleFile {
+    /// The file name relative to the bundle directory.
+    pub filename: String,
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// The hash of the file.
+    pub hash: FileHash,
+}
+
+/// The DBN file of a single schema within a bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleDataFile {
+    /// The data record schema.
+    #[serde(with = "crate::serialize::display_from_str")]
+    pub schema: Schema,
+    /// The file.
+    #[serde(flatten)]
+    pub file: BundleFile,
+}
+
+/// A bundle that was verified when it was [opened](Self::open).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bundle {
+    dir: PathBuf,
+    manifest: BundleManifest,
+}
+
+/// The symbology file, in the same format as the API's resolution response. Mappings
+/// are sorted by symbol so exports of the same data are identical.
+#[derive(Debug, Serialize, Deserialize)]
+struct SymbologyFile<I> {
+    #[serde(with = "crate::serialize::display_from_str")]
+    stype_in: SType,
+    #[serde(with = "crate::serialize::display_from_str")]
+    stype_out: SType,
+    result: BTreeMap<String, Vec<I>>,
+    partial: Vec<String>,
+    not_found: Vec<String>,
+}
+
+/// A [`MappingInterval`] borrowed for serialization.
+#[derive(Debug, Serialize)]
+struct SymbologyInterval<'a> {
+    #[serde(with = "super::date_format")]
+    d0: Date,
+    #[serde(with = "super::date_format")]
+    d1: Date,
+    s: &'a str,
+}
+
+impl TimeseriesClient<'_> {
+    /// Exports the data of every schema in `params` and the symbology mappings of its
+    /// symbols to a bundle in `dir`, writing the manifest last so an interrupted export
+    /// doesn't leave a bundle that can be opened.
+    ///
+    /// # Errors
+    /// This function returns an error when `params` has no schemas, it fails to
+    /// communicate with the Databento API, the API indicates there's an issue with the
+    /// request, or it fails to write a file.
+    pub async fn export_bundle(&self, params: &ExportBundleParams) -> crate::Result<Bundle> {
+        if params.schemas.is_empty() {
+            return Err(Error::bad_arg(
+                "schemas",
+                "must contain at least one schema",
+            ));
+        }
+        tokio::fs::create_dir_all(&params.dir).await?;
+        let mut files = Vec::with_capacity(params.schemas.len());
+        for &schema in &params.schemas {
+            let filename = format!("{schema}.dbn.zst");
+            info!(dataset = %params.dataset, %schema, "Exporting bundle data");
+            self.get_range_to_file(
+                &GetRangeToFileParams::builder()
+                    .dataset(&params.dataset)
+                    .symbols(params.symbols.clone())
+                    .stype_in(params.stype_in)
+                    .schema(schema)
+                    .date_time_range(params.date_time_range.clone())
+                    .path(params.dir.join(&filename))
+                    .build(),
+            )
+            .await?;
+            files.push(BundleDataFile {
+                schema,
+                file: describe_file(&params.dir, filename).await?,
+            });
+        }
+        let stype_out = if params.stype_in == SType::InstrumentId {
+            SType::RawSymbol
+        } else {
+            SType::InstrumentId
+        };
+        let resolution = self
+            .inner
+            .symbology()
+            .resolve(
+                &ResolveParams::builder()
+                    .dataset(&params.dataset)
+                    .symbols(params.symbols.clone())
+                    .stype_in(params.stype_in)
+                    .stype_out(stype_out)
+                    .date_range(covering_dates(&params.date_time_range))
+                    .build(),
+            )
+            .await?;
+        let symbology = SymbologyFile {
+            stype_in: resolution.stype_in,
+            stype_out: resolution.stype_out,
+            result: resolution
+                .mappings
+                .iter()
+                .map(|(symbol, intervals)| {
+                    let intervals = intervals
+                        .iter()
+                        .map(|interval| SymbologyInterval {
+                            d0: interval.start_date,
+                            d1: interval.end_date,
+                            s: &interval.symbol,
+                        })
+                        .collect();
+                    (symbol.clone(), intervals)
+                })
+                .collect(),
+            partial: resolution.partial.clone(),
+            not_found: resolution.not_found.clone(),
+        };
+        tokio::fs::write(params.dir.join(SYMBOLOGY_FILENAME), to_json(&symbology)?).await?;
+        let manifest = BundleManifest {
+            version: BUNDLE_VERSION,
+            dataset: params.dataset.clone(),
+            symbols: params.symbols.clone(),
+            stype_in: params.stype_in,
+            date_time_range: params.date_time_range.clone(),
+            files,
+            symbology: describe_file(&params.dir, SYMBOLOGY_FILENAME.to_owned()).await?,
+        };
+        tokio::fs::write(params.dir.join(MANIFEST_FILENAME), to_json(&manifest)?).await?;
+        Ok(Bundle {
+            dir: params.dir.clone(),
+            manifest,
+        })
+    }
+}
+
+impl Bundle {
+    /// Opens the bundle in `dir`, verifying the size and hash of every file against the
+    /// manifest.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to read or parse the manifest, the
+    /// bundle was written in an unsupported version of the format, or a file is
+    /// missing or doesn't match the manifest.
+    pub async fn open(dir: impl Into<PathBuf>) -> crate::Result<Self> {
+        let dir = dir.into();
+        let manifest: BundleManifest =
+            serde_json::from_slice(&tokio::fs::read(dir.join(MANIFEST_FILENAME)).await?)
+                .map_err(|e| Error::internal(format!("failed to parse bundle manifest: {e}")))?;
+        if manifest.version > BUNDLE_VERSION {
+            return Err(Error::bad_arg(
+                "dir",
+                format!("unsupported bundle version {}", manifest.version),
+            ));
+        }
+        let bundle = Self { dir, manifest };
+        let report = bundle.verify().await?;
+        if let Some((path, verification)) = report.failures().next() {
+            return Err(Error::bad_arg(
+                "dir",
+                format!(
+                    "bundle file {} failed verification: {verification:?}",
+                    path.display()
+                ),
+            ));
+        }
+        Ok(bundle)
+    }
+
+    /// Returns the bundle directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the manifest of the bundle.
+    pub fn manifest(&self) -> &BundleManifest {
+        &self.manifest
+    }
+
+    /// Returns the schemas with data in the bundle.
+    pub fn schemas(&self) -> impl Iterator<Item = Schema> + '_ {
+        self.manifest.files.iter().map(|file| file.schema)
+    }
+
+    /// Returns the path of the DBN file of `schema`, if the bundle contains it.
+    pub fn path(&self, schema: Schema) -> Option<PathBuf> {
+        self.manifest
+            .files
+            .iter()
+            .find(|file| file.schema == schema)
+            .map(|file| self.dir.join(&file.file.filename))
+    }
+
+    /// Recomputes the size and hash of every file in the bundle and compares them
+    /// against the manifest.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to read a file for a reason other
+    /// than it not existing.
+    pub async fn verify(&self) -> crate::Result<VerificationReport> {
+        let mut report = VerificationReport {
+            files: Vec::with_capacity(self.manifest.files.len() + 1),
+        };
+        let files = self
+            .manifest
+            .files
+            .iter()
+            .map(|file| &file.file)
+            .chain(std::iter::once(&self.manifest.symbology));
+        for file in files {
+            let path = self.dir.join(&file.filename);
+            let res = verify_file(&path, file.size, &file.hash).await?;
+            report.files.push((path, res));
+        }
+        Ok(report)
+    }
+
+    /// Opens a decoder for the DBN file of `schema`.
+    ///
+    /// # Errors
+    /// This function returns an error if the bundle doesn't contain `schema` or it
+    /// fails to open the file or decode its metadata.
+    pub async fn decoder(&self, schema: Schema) -> crate::Result<AsyncDbnDecoder<impl AsyncRead>> {
+        let path = self.path(schema).ok_or_else(|| {
+            Error::bad_arg("schema", format!("bundle doesn't contain {schema} data"))
+        })?;
+        Ok(AsyncDbnDecoder::from_zstd_file(path).await?)
+    }
+
+    /// Reads the symbology mappings of the bundle's symbols.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to read or parse the symbology file.
+    pub async fn symbology(&self) -> crate::Result<Resolution> {
+        let bytes = tokio::fs::read(self.dir.join(&self.manifest.symbology.filename)).await?;
+        let symbology: SymbologyFile<MappingInterval> = serde_json::from_slice(&bytes)
+            .map_err(|e| Error::internal(format!("failed to parse bundle symbology: {e}")))?;
+        Ok(Resolution {
+            mappings: symbology.result.into_iter().collect(),
+            partial: symbology.partial,
+            not_found: symbology.not_found,
+            stype_in: symbology.stype_in,
+            stype_out: symbology.stype_out,
+        })
+    }
+}
+
+/// Returns the size and SHA-256 hash of the file `filename` in `dir`.
+async fn describe_file(dir: &Path, filename: String) -> crate::Result<BundleFile> {
+    let mut file = tokio::fs::File::open(dir.join(&filename)).await?;
+    let size = file.metadata().await?.len();
+    let hash = FileHash::compute(&mut file, HashAlgorithm::Sha256).await?;
+    Ok(BundleFile {
+        filename,
+        size,
+        hash,
+    })
+}
+
+/// Returns the smallest date range containing all of `range`.
+fn covering_dates(range: &DateTimeRange) -> DateRange {
+    let end = range.end();
+    let end_date = if end.time() == time::Time::MIDNIGHT {
+        end.date()
+    } else {
+        end.date().next_day().unwrap_or(end.date())
+    };
+    DateRange::from((range.start().date(), end_date))
+}
+
+fn to_json(value: &impl Serialize) -> crate::Result<Vec<u8>> {
+    serde_json::to_vec_pretty(value)
+        .map_err(|e| Error::internal(format!("failed to serialize bundle: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::TradeMsg;
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use time::macros::datetime;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        historical::{HistoricalGateway, API_VERSION},
+        zst_test_data_path, HistoricalClient,
+    };
+
+    #[tokio::test]
+    async fn test_export_and_open_bundle() {
+        let mock_server = MockServer::start().await;
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bytes = tokio::fs::read(zst_test_data_path(Schema::Trades))
+            .await
+            .unwrap();
+        Mock::given(method("POST"))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/v{API_VERSION}/symbology.resolve")))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!({
+                    "result": {
+                        "ESM4": [{ "d0": "2024-05-17", "d1": "2024-05-18", "s": "5482" }]
+                    },
+                    "partial": [],
+                    "not_found": []
+                })),
+            )
+            .mount(&mock_server)
+            .await;
+        let client = HistoricalClient::with_url(
+            mock_server.uri(),
+            "test-API".to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let dir = temp_dir.path().join("bundle");
+        let exported = client
+            .timeseries()
+            .export_bundle(
+                &ExportBundleParams::builder()
+                    .dataset("GLBX.MDP3")
+                    .symbols("ESM4")
+                    .schemas(vec![Schema::Trades])
+                    .date_time_range((
+                        datetime!(2024-05-17 00:00 UTC),
+                        datetime!(2024-05-17 12:00 UTC),
+                    ))
+                    .dir(&dir)
+                    .build(),
+            )
+            .await
+            .unwrap();
+
+        let target = Bundle::open(&dir).await.unwrap();
+        assert_eq!(target, exported);
+        assert_eq!(target.schemas().collect::<Vec<_>>(), [Schema::Trades]);
+        let mut decoder = target.decoder(Schema::Trades).await.unwrap();
+        assert!(decoder.decode_record::<TradeMsg>().await.unwrap().is_some());
+        assert!(matches!(
+            target.decoder(Schema::Mbo).await,
+            Err(Error::BadArgument { .. })
+        ));
+        let symbology = target.symbology().await.unwrap();
+        assert_eq!(symbology.mappings["ESM4"][0].symbol, "5482");
+
+        // Corrupt the data file
+        let data_path = target.path(Schema::Trades).unwrap();
+        let mut data = tokio::fs::read(&data_path).await.unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        tokio::fs::write(&data_path, data).await.unwrap();
+        assert!(matches!(
+            Bundle::open(&dir).await,
+            Err(Error::BadArgument { .. })
+        ));
+    }
+}
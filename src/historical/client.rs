@@ -1,12 +1,26 @@
-use reqwest::{header::ACCEPT, IntoUrl, RequestBuilder, Url};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    time::Duration,
+};
+
+use reqwest::{
+    header::{ACCEPT, AUTHORIZATION},
+    IntoUrl, RequestBuilder, Response, StatusCode, Url,
+};
 use serde::Deserialize;
 use tracing::warn;
 
-use crate::{error::ApiError, ApiKey, Error};
+use crate::{credentials::SharedCredentials, error::ApiError, ApiKey, CredentialsProvider, Error};
 
 use super::{
-    batch::BatchClient, metadata::MetadataClient, symbology::SymbologyClient,
-    timeseries::TimeseriesClient, HistoricalGateway, API_VERSION,
+    audit::{self, AuditHook, SharedAuditHook},
+    batch::BatchClient,
+    billing::{BillingLedger, BillingReport},
+    metadata::{DatasetCapabilities, MetadataClient},
+    symbology::SymbologyClient,
+    timeseries::{OversizedRequestPolicy, TimeseriesClient},
+    HistoricalGateway, API_VERSION,
 };
 
 /// The Historical client. Used for symbology resolutions, metadata requests, Historical
@@ -20,12 +34,44 @@ use super::{
 /// - [`timeseries()`](Self::timeseries)
 /// - [`symbology()`](Self::symbology)
 /// - [`batch()`](Self::batch)
+///
+/// All methods take `&self`, so a client can be shared by reference between
+/// concurrent requests, such as with `futures::join!`. For requests from several tasks,
+/// clone the client: clones share the same HTTP connection pool, API key, cached
+/// [`capabilities()`](MetadataClient::capabilities), and
+/// [`billing_report()`](Self::billing_report), so cloning is cheap and preferred over
+/// building several clients.
+///
+/// ```no_run
+/// # async fn example() -> databento::Result<()> {
+/// use databento::HistoricalClient;
+///
+/// let client = HistoricalClient::builder().key_from_env()?.build()?;
+/// let tasks: Vec<_> = ["XNAS.ITCH", "GLBX.MDP3"]
+///     .into_iter()
+///     .map(|dataset| {
+///         let client = client.clone();
+///         tokio::spawn(async move { client.metadata().get_dataset_range(dataset).await })
+///     })
+///     .collect();
+/// for task in tasks {
+///     println!("{:?}", task.await.unwrap()?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug, Clone)]
 pub struct Client {
-    key: ApiKey,
+    key: Arc<RwLock<ApiKey>>,
     base_url: Url,
     gateway: HistoricalGateway,
+    credentials: Option<SharedCredentials>,
+    audit: Option<SharedAuditHook>,
     client: reqwest::Client,
+    oversized_request_policy: OversizedRequestPolicy,
+    estimate_costs: bool,
+    pub(crate) billing: BillingLedger,
+    capabilities: Arc<Mutex<HashMap<String, DatasetCapabilities>>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,7 +89,7 @@ pub(crate) struct BusinessErrorDetails {
 
 const USER_AGENT: &str = concat!("Databento/", env!("CARGO_PKG_VERSION"), " Rust");
 const WARNING_HEADER: &str = "X-Warning";
-const REQUEST_ID_HEADER: &str = "request-id";
+pub(crate) const REQUEST_ID_HEADER: &str = "request-id";
 
 impl Client {
     /// Returns a type-safe builder for setting the required parameters
@@ -57,10 +103,7 @@ impl Client {
     /// # Errors
     /// This function returns an error when it fails to build the HTTP client.
     pub fn new(key: String, gateway: HistoricalGateway) -> crate::Result<Self> {
-        let url = match gateway {
-            HistoricalGateway::Bo1 => "https://hist.databento.com",
-        };
-        Self::with_url(url, key, gateway)
+        Self::with_url(Self::gateway_url(gateway), key, gateway)
     }
 
     /// Creates a new client with a specific API URL. This is an advanced method and
@@ -72,26 +115,104 @@ impl Client {
         url: impl IntoUrl,
         key: String,
         gateway: HistoricalGateway,
+    ) -> crate::Result<Self> {
+        Self::with_config(
+            url,
+            ApiKey(key),
+            gateway,
+            None,
+            None,
+            &HttpConfig::default(),
+        )
+    }
+
+    fn with_config(
+        url: impl IntoUrl,
+        key: ApiKey,
+        gateway: HistoricalGateway,
+        user_agent_extension: Option<&str>,
+        credentials: Option<SharedCredentials>,
+        http_config: &HttpConfig,
     ) -> crate::Result<Self> {
         let base_url = url
             .into_url()
             .map_err(|e| Error::bad_arg("url", format!("{e:?}")))?;
         let mut headers = reqwest::header::HeaderMap::new();
         headers.insert(ACCEPT, "application/json".parse().unwrap());
+        let user_agent = if let Some(ext) = user_agent_extension {
+            format!("{USER_AGENT} {ext}")
+        } else {
+            USER_AGENT.to_owned()
+        };
         Ok(Self {
-            key: ApiKey(key),
+            key: Arc::new(RwLock::new(key)),
             base_url,
             gateway,
-            client: reqwest::ClientBuilder::new()
-                .user_agent(USER_AGENT)
+            credentials,
+            audit: None,
+            client: http_config
+                .apply(reqwest::ClientBuilder::new())
+                .user_agent(user_agent)
                 .default_headers(headers)
                 .build()?,
+            oversized_request_policy: OversizedRequestPolicy::default(),
+            estimate_costs: false,
+            billing: BillingLedger::default(),
+            capabilities: Arc::default(),
         })
     }
 
-    /// Returns the API key used by the instance of the client.
-    pub fn key(&self) -> &str {
-        &self.key.0
+    fn gateway_url(gateway: HistoricalGateway) -> &'static str {
+        match gateway {
+            HistoricalGateway::Bo1 => "https://hist.databento.com",
+        }
+    }
+
+    /// Returns the API key used by the client and its clones.
+    pub fn key(&self) -> String {
+        self.key
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+            .clone()
+    }
+
+    /// Returns how requests too large to stream are handled.
+    pub fn oversized_request_policy(&self) -> OversizedRequestPolicy {
+        self.oversized_request_policy
+    }
+
+    /// Returns whether the billable size and cost of streaming requests and batch jobs
+    /// are quoted before they're made.
+    pub fn estimate_costs(&self) -> bool {
+        self.estimate_costs
+    }
+
+    /// Returns a snapshot of the streaming requests and batch jobs made by the client
+    /// and its clones, with their estimated and billed sizes and costs, for attributing
+    /// spend to datasets and schemas.
+    pub fn billing_report(&self) -> BillingReport {
+        self.billing.report()
+    }
+
+    /// Requests a new API key from the [`CredentialsProvider`] the client was built
+    /// with and uses it for all subsequent requests by the client and its clones.
+    /// Requests that fail with `401 Unauthorized` already refresh the key and are
+    /// retried once, so this is only needed to rotate the key ahead of time.
+    ///
+    /// # Errors
+    /// This function returns an error when the client wasn't built with a credentials
+    /// provider, or the provider fails to return a valid API key.
+    pub fn refresh_credentials(&self) -> crate::Result<()> {
+        let credentials = self.credentials.as_ref().ok_or_else(|| {
+            Error::bad_arg(
+                "credentials",
+                "client wasn't built with a credentials provider",
+            )
+        })?;
+        let key = credentials.api_key()?;
+        *self.key.write().unwrap_or_else(PoisonError::into_inner) = key;
+        Ok(())
     }
 
     /// Returns the configured Historical gateway.
@@ -100,30 +221,49 @@ impl Client {
     }
 
     /// Returns the batch subclient.
-    pub fn batch(&mut self) -> BatchClient {
+    pub fn batch(&self) -> BatchClient {
         BatchClient { inner: self }
     }
 
     /// Returns the metadata subclient.
-    pub fn metadata(&mut self) -> MetadataClient {
+    pub fn metadata(&self) -> MetadataClient {
         MetadataClient { inner: self }
     }
 
     /// Returns the symbology subclient.
-    pub fn symbology(&mut self) -> SymbologyClient {
+    pub fn symbology(&self) -> SymbologyClient {
         SymbologyClient { inner: self }
     }
 
     /// Returns the timeseries subclient.
-    pub fn timeseries(&mut self) -> TimeseriesClient {
+    pub fn timeseries(&self) -> TimeseriesClient {
         TimeseriesClient { inner: self }
     }
 
-    pub(crate) fn get(&mut self, slug: &str) -> crate::Result<RequestBuilder> {
-        self.request(reqwest::Method::GET, slug)
+    pub(crate) fn cached_capabilities(&self, dataset: &str) -> Option<DatasetCapabilities> {
+        self.capabilities_cache().get(dataset).cloned()
     }
 
-    pub(crate) fn get_with_path(&mut self, path: &str) -> crate::Result<RequestBuilder> {
+    pub(crate) fn cache_capabilities(&self, capabilities: DatasetCapabilities) {
+        self.capabilities_cache()
+            .insert(capabilities.dataset.clone(), capabilities);
+    }
+
+    fn capabilities_cache(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<String, DatasetCapabilities>> {
+        // The cache is only ever inserted into, so it's still valid if another thread
+        // panicked while holding the lock
+        self.capabilities
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    pub(crate) fn get(&self, slug: &str) -> crate::Result<RequestBuilder> {
+        self.request_builder(reqwest::Method::GET, slug)
+    }
+
+    pub(crate) fn get_with_path(&self, path: &str) -> crate::Result<RequestBuilder> {
         Ok(self
             .client
             .get(
@@ -134,11 +274,23 @@ impl Client {
             .basic_auth(self.key(), Option::<&str>::None))
     }
 
-    pub(crate) fn post(&mut self, slug: &str) -> crate::Result<RequestBuilder> {
-        self.request(reqwest::Method::POST, slug)
+    pub(crate) fn post(&self, slug: &str) -> crate::Result<RequestBuilder> {
+        self.request_builder(reqwest::Method::POST, slug)
     }
 
-    fn request(&mut self, method: reqwest::Method, slug: &str) -> crate::Result<RequestBuilder> {
+    /// Returns an authenticated request builder for the API endpoint `slug`, such as
+    /// `"metadata.list_publishers"`, under the current API version. This is an
+    /// advanced method for calling endpoints that don't yet have a method in this
+    /// crate. Send the request with [`send()`](Self::send) and pass the response to
+    /// [`handle_response()`] or [`check_http_error()`] to handle errors from the API.
+    ///
+    /// # Errors
+    /// This function returns an error if `slug` results in an invalid URL.
+    pub fn request_builder(
+        &self,
+        method: reqwest::Method,
+        slug: &str,
+    ) -> crate::Result<RequestBuilder> {
         Ok(self
             .client
             .request(
@@ -149,11 +301,41 @@ impl Client {
             )
             .basic_auth(self.key(), Option::<&str>::None))
     }
+
+    /// Sends a request created with [`request_builder()`](Self::request_builder),
+    /// passing it and its response to the [audit hook](ClientBuilder::audit_hook) if
+    /// one was set. If the client was built with a
+    /// [`credentials_provider()`](ClientBuilder::credentials_provider) and the request
+    /// fails with `401 Unauthorized`, the credentials are refreshed and the request is
+    /// retried once with the new API key.
+    ///
+    /// # Errors
+    /// This function returns an error if the request can't be built or sent, or the
+    /// credentials can't be refreshed. Error statuses aren't converted to errors.
+    pub async fn send(&self, request: RequestBuilder) -> crate::Result<Response> {
+        // Requests with streaming bodies can't be retried
+        let retry = self.credentials.as_ref().and_then(|_| request.try_clone());
+        let resp = audit::send(self.audit.as_ref(), request).await?;
+        let Some(retry) = retry.filter(|_| resp.status() == StatusCode::UNAUTHORIZED) else {
+            return Ok(resp);
+        };
+        warn!("Request was unauthorized, retrying with refreshed credentials");
+        self.refresh_credentials()?;
+        let (client, request) = retry.build_split();
+        let mut request = request?;
+        request.headers_mut().remove(AUTHORIZATION);
+        let retry = RequestBuilder::from_parts(client, request)
+            .basic_auth(self.key(), Option::<&str>::None);
+        audit::send(self.audit.as_ref(), retry).await
+    }
 }
 
-pub(crate) async fn check_http_error(
-    response: reqwest::Response,
-) -> crate::Result<reqwest::Response> {
+/// Checks `response` for an HTTP error status, converting it to an
+/// [`Error::Api`] with the details sent by the API, otherwise returns `response`.
+///
+/// # Errors
+/// This function returns an error if `response` has an error status code.
+pub async fn check_http_error(response: reqwest::Response) -> crate::Result<reqwest::Response> {
     if response.status().is_success() {
         Ok(response)
     } else {
@@ -169,12 +351,14 @@ pub(crate) async fn check_http_error(
                 status_code,
                 message,
                 docs_url: None,
+                context: None,
             },
             Ok(ApiErrorResponse::Business { detail }) => ApiError {
                 request_id,
                 status_code,
                 message: detail.message,
                 docs_url: Some(detail.docs),
+                context: None,
             },
             Err(e) => {
                 warn!("Failed to deserialize error response to expected JSON format: {e:?}");
@@ -183,6 +367,7 @@ pub(crate) async fn check_http_error(
                     status_code,
                     message: body,
                     docs_url: None,
+                    context: None,
                 }
             }
         };
@@ -190,7 +375,13 @@ pub(crate) async fn check_http_error(
     }
 }
 
-pub(crate) async fn handle_response<R: serde::de::DeserializeOwned>(
+/// Logs any warnings sent by the API, checks `response` for an HTTP error status
+/// with [`check_http_error()`], and deserializes the JSON body.
+///
+/// # Errors
+/// This function returns an error if `response` has an error status code or its body
+/// can't be deserialized as `R`.
+pub async fn handle_response<R: serde::de::DeserializeOwned>(
     response: reqwest::Response,
 ) -> crate::Result<R> {
     check_warnings(&response);
@@ -224,6 +415,65 @@ pub struct ClientBuilder<AK> {
     key: AK,
     base_url: Option<Url>,
     gateway: HistoricalGateway,
+    user_agent_extension: Option<String>,
+    credentials: Option<SharedCredentials>,
+    audit: Option<SharedAuditHook>,
+    http_config: HttpConfig,
+    oversized_request_policy: OversizedRequestPolicy,
+    estimate_costs: bool,
+}
+
+/// Tuning options for the HTTP connection pool.
+#[derive(Debug, Clone, Default)]
+struct HttpConfig {
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
+    http2_keep_alive_interval: Option<Duration>,
+    http2_adaptive_window: bool,
+    response_compression: ResponseCompression,
+}
+
+impl HttpConfig {
+    fn apply(&self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(interval) = self.http2_keep_alive_interval {
+            builder = builder
+                .http2_keep_alive_interval(interval)
+                .http2_keep_alive_while_idle(true);
+        }
+        builder
+            .http2_adaptive_window(self.http2_adaptive_window)
+            .gzip(matches!(
+                self.response_compression,
+                ResponseCompression::Any | ResponseCompression::Gzip
+            ))
+            .deflate(matches!(
+                self.response_compression,
+                ResponseCompression::Any | ResponseCompression::Deflate
+            ))
+    }
+}
+
+/// The HTTP compression accepted for JSON responses from the Historical API.
+/// Compressed responses are decompressed transparently based on their
+/// `Content-Encoding`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseCompression {
+    /// Accept gzip or deflate.
+    #[default]
+    Any,
+    /// Accept only gzip.
+    Gzip,
+    /// Accept only deflate.
+    Deflate,
+    /// Request uncompressed responses.
+    None,
 }
 
 impl Default for ClientBuilder<Unset> {
@@ -232,6 +482,12 @@ impl Default for ClientBuilder<Unset> {
             key: Unset,
             base_url: None,
             gateway: HistoricalGateway::default(),
+            user_agent_extension: None,
+            credentials: None,
+            audit: None,
+            http_config: HttpConfig::default(),
+            oversized_request_policy: OversizedRequestPolicy::default(),
+            estimate_costs: false,
         }
     }
 }
@@ -249,6 +505,74 @@ impl<AK> ClientBuilder<AK> {
         self.gateway = gateway;
         self
     }
+
+    /// Extends the user agent sent with every request. Intended for library authors
+    /// to identify traffic from their library.
+    pub fn user_agent_extension(mut self, extension: impl ToString) -> Self {
+        self.user_agent_extension = Some(extension.to_string());
+        self
+    }
+
+    /// Sets the maximum number of idle connections per host kept in the connection
+    /// pool. Defaults to no limit.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.http_config.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Sets how long idle connections are kept in the connection pool before being
+    /// closed. Defaults to 90 seconds.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.http_config.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the interval at which HTTP/2 ping frames are sent to keep connections
+    /// alive, including while idle. Defaults to disabled.
+    pub fn http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.http_config.http2_keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Sets whether to use an adaptive flow control window for HTTP/2 connections,
+    /// which can improve throughput of large downloads. Defaults to `false`.
+    pub fn http2_adaptive_window(mut self, enabled: bool) -> Self {
+        self.http_config.http2_adaptive_window = enabled;
+        self
+    }
+
+    /// Sets the HTTP compression accepted for JSON responses. Timeseries data is
+    /// compressed separately with
+    /// [`GetRangeParams::compression`](super::timeseries::GetRangeParams::compression).
+    /// Defaults to [`ResponseCompression::Any`].
+    pub fn response_compression(mut self, compression: ResponseCompression) -> Self {
+        self.http_config.response_compression = compression;
+        self
+    }
+
+    /// Sets how [`TimeseriesClient::get_range_buffered()`] handles requests the API
+    /// rejects as too large to stream. Defaults to [`OversizedRequestPolicy::Error`].
+    pub fn oversized_request_policy(mut self, policy: OversizedRequestPolicy) -> Self {
+        self.oversized_request_policy = policy;
+        self
+    }
+
+    /// Sets whether the billable size and cost of each streaming request and batch job
+    /// are requested from the metadata API before it's made and included in the
+    /// [`billing_report()`](Client::billing_report). This makes two additional
+    /// requests for each billable request. Defaults to `false`.
+    pub fn estimate_costs(mut self, enabled: bool) -> Self {
+        self.estimate_costs = enabled;
+        self
+    }
+
+    /// Sets a hook that's passed the metadata of every request made by the client and
+    /// its clones and the status of every response, such as for writing an audit trail
+    /// of data access. See [`AuditHook`].
+    pub fn audit_hook(mut self, hook: impl AuditHook + 'static) -> Self {
+        self.audit = Some(SharedAuditHook::new(hook));
+        self
+    }
 }
 
 impl ClientBuilder<Unset> {
@@ -262,11 +586,22 @@ impl ClientBuilder<Unset> {
     /// # Errors
     /// This function returns an error when the API key is invalid.
     pub fn key(self, key: impl ToString) -> crate::Result<ClientBuilder<ApiKey>> {
-        Ok(ClientBuilder {
-            key: ApiKey::new(key.to_string())?,
+        Ok(self.api_key(ApiKey::new(key.to_string())?))
+    }
+
+    /// Sets the API key from an already validated [`ApiKey`].
+    pub fn api_key(self, key: ApiKey) -> ClientBuilder<ApiKey> {
+        ClientBuilder {
+            key,
             base_url: self.base_url,
             gateway: self.gateway,
-        })
+            user_agent_extension: self.user_agent_extension,
+            credentials: self.credentials,
+            audit: self.audit,
+            http_config: self.http_config,
+            oversized_request_policy: self.oversized_request_policy,
+            estimate_costs: self.estimate_costs,
+        }
     }
 
     /// Sets the API key reading it from the `DATABENTO_API_KEY` environment
@@ -276,8 +611,32 @@ impl ClientBuilder<Unset> {
     /// This function returns an error when the environment variable is not set or the
     /// API key is invalid.
     pub fn key_from_env(self) -> crate::Result<ClientBuilder<ApiKey>> {
-        let key = crate::key_from_env()?;
-        self.key(key)
+        Ok(self.api_key(ApiKey::from_env()?))
+    }
+
+    /// Sets the API key by requesting it from `provider`. The provider is retained so
+    /// the key is refreshed when a request fails with `401 Unauthorized`, or with
+    /// [`HistoricalClient::refresh_credentials()`](Client::refresh_credentials).
+    ///
+    /// # Errors
+    /// This function returns an error when the provider fails to return a key or the
+    /// API key is invalid.
+    pub fn credentials_provider(
+        self,
+        provider: impl CredentialsProvider + 'static,
+    ) -> crate::Result<ClientBuilder<ApiKey>> {
+        let credentials = SharedCredentials::new(provider);
+        Ok(ClientBuilder {
+            key: credentials.api_key()?,
+            base_url: self.base_url,
+            gateway: self.gateway,
+            user_agent_extension: self.user_agent_extension,
+            credentials: Some(credentials),
+            audit: self.audit,
+            http_config: self.http_config,
+            oversized_request_policy: self.oversized_request_policy,
+            estimate_costs: self.estimate_costs,
+        })
     }
 }
 
@@ -287,21 +646,233 @@ impl ClientBuilder<ApiKey> {
     /// # Errors
     /// This function returns an error when it fails to build the HTTP client.
     pub fn build(self) -> crate::Result<Client> {
-        if let Some(url) = self.base_url {
-            Client::with_url(url, self.key.0, self.gateway)
+        let user_agent_extension = self.user_agent_extension.as_deref();
+        let mut client = if let Some(url) = self.base_url {
+            Client::with_config(
+                url,
+                self.key,
+                self.gateway,
+                user_agent_extension,
+                self.credentials,
+                &self.http_config,
+            )
         } else {
-            Client::new(self.key.0, self.gateway)
-        }
+            Client::with_config(
+                Client::gateway_url(self.gateway),
+                self.key,
+                self.gateway,
+                user_agent_extension,
+                self.credentials,
+                &self.http_config,
+            )
+        }?;
+        client.oversized_request_policy = self.oversized_request_policy;
+        client.estimate_costs = self.estimate_costs;
+        client.audit = self.audit;
+        Ok(client)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use reqwest::StatusCode;
-    use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+    use serde_json::json;
+    use wiremock::{
+        matchers::{basic_auth, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
 
     use super::*;
 
+    #[tokio::test]
+    async fn test_response_compression() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("accept-encoding", "gzip"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let target = Client::builder()
+            .key("32-character-with-lots-of-filler")
+            .unwrap()
+            .base_url(mock_server.uri().parse().unwrap())
+            .response_compression(ResponseCompression::Gzip)
+            .build()
+            .unwrap();
+        target
+            .get("metadata.list_datasets")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_user_agent_extension() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header(
+                "user-agent",
+                format!("{USER_AGENT} my-library/1.0").as_str(),
+            ))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let target = Client::builder()
+            .key("32-character-with-lots-of-filler")
+            .unwrap()
+            .base_url(mock_server.uri().parse().unwrap())
+            .user_agent_extension("my-library/1.0")
+            .build()
+            .unwrap();
+        let resp = target.get_with_path("/").unwrap().send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_request_builder() {
+        const API_KEY: &str = "32-character-with-lots-of-filler";
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/metadata.new_endpoint")))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!([1, 2])),
+            )
+            .mount(&mock_server)
+            .await;
+        let target = Client::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let resp = target
+            .request_builder(reqwest::Method::GET, "metadata.new_endpoint")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(handle_response::<Vec<u32>>(resp).await.unwrap(), vec![1, 2]);
+        let resp = target
+            .request_builder(reqwest::Method::GET, "metadata.missing")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert!(matches!(
+            check_http_error(resp).await,
+            Err(Error::Api(api_err)) if api_err.status_code == StatusCode::NOT_FOUND
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_config() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let target = Client::builder()
+            .key("32-character-with-lots-of-filler")
+            .unwrap()
+            .base_url(mock_server.uri().parse().unwrap())
+            .pool_max_idle_per_host(1)
+            .pool_idle_timeout(Duration::from_secs(30))
+            .http2_keep_alive_interval(Duration::from_secs(10))
+            .http2_adaptive_window(true)
+            .build()
+            .unwrap();
+        let clone = target.clone();
+        for client in [&target, &clone] {
+            let resp = client.get_with_path("/").unwrap().send().await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+    }
+
+    #[test]
+    fn test_refresh_credentials() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let provider_count = count.clone();
+        let target = Client::builder()
+            .credentials_provider(move || -> crate::Result<String> {
+                Ok(match provider_count.fetch_add(1, Ordering::SeqCst) {
+                    0 => "32-character-with-lots-of-filler",
+                    _ => "32-character-rotated-new-filler!",
+                }
+                .to_owned())
+            })
+            .unwrap()
+            .build()
+            .unwrap();
+        let clone = target.clone();
+        assert_eq!(target.key(), "32-character-with-lots-of-filler");
+        target.refresh_credentials().unwrap();
+        assert_eq!(target.key(), "32-character-rotated-new-filler!");
+        assert_eq!(clone.key(), "32-character-rotated-new-filler!");
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        let target = Client::builder()
+            .key("32-character-with-lots-of-filler")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(target.refresh_credentials().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_credentials_on_unauthorized() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        const OLD_KEY: &str = "32-character-with-lots-of-filler";
+        const NEW_KEY: &str = "32-character-rotated-new-filler!";
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(basic_auth(OLD_KEY, ""))
+            .respond_with(ResponseTemplate::new(StatusCode::UNAUTHORIZED.as_u16()))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(basic_auth(NEW_KEY, ""))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!(["mbo"])),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let count = Arc::new(AtomicUsize::new(0));
+        let target = Client::builder()
+            .base_url(mock_server.uri().parse().unwrap())
+            .credentials_provider({
+                let count = count.clone();
+                move || -> crate::Result<String> {
+                    Ok(match count.fetch_add(1, Ordering::SeqCst) {
+                        0 => OLD_KEY,
+                        _ => NEW_KEY,
+                    }
+                    .to_owned())
+                }
+            })
+            .unwrap()
+            .build()
+            .unwrap();
+        let clone = target.clone();
+        let schemas = target.metadata().list_schemas("XNAS.ITCH").await.unwrap();
+        assert_eq!(schemas, [dbn::Schema::Mbo]);
+        assert_eq!(target.key(), NEW_KEY);
+        // Clones share the refreshed key
+        clone.metadata().list_schemas("XNAS.ITCH").await.unwrap();
+    }
+
     #[tokio::test]
     async fn check_http_error_non_json() {
         const BODY: &str = "<html><body><h1>502 Bad Gateway</h1>
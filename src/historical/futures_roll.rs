@@ -0,0 +1,402 @@
+//! Helpers for working with continuous futures contracts: determining roll schedules
+//! and computing the adjustments for back-adjusting prices from earlier contracts.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use dbn::{OhlcvMsg, SType, Schema, FIXED_PRICE_SCALE};
+use time::{Date, OffsetDateTime};
+use typed_builder::TypedBuilder;
+
+use crate::{Error, Symbols};
+
+use super::{
+    symbology::{ResolveParams, SymbologyClient},
+    timeseries::{GetRangeParams, TimeseriesClient},
+    DateRange,
+};
+
+/// The rule used to determine when a continuous contract rolls to the next contract.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RollRule {
+    /// Rolls based on the expiration calendar.
+    Calendar,
+    /// Rolls to the contract with the highest trading volume.
+    Volume,
+    /// Rolls to the contract with the highest open interest.
+    OpenInterest,
+}
+
+/// A continuous contract symbol such as `ES.v.0`. Requests using this symbol should
+/// use [`SType::Continuous`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ContinuousSymbol {
+    /// The root symbol of the futures product, e.g. `ES`.
+    pub root: String,
+    /// The roll rule.
+    pub rule: RollRule,
+    /// The rank of the contract, where 0 is the front month.
+    pub rank: u32,
+}
+
+/// The parameters for [`SymbologyClient::roll_schedule()`]. Use
+/// [`RollScheduleParams::builder()`] to get a builder type with all the preset
+/// defaults.
+#[derive(Clone, Debug, TypedBuilder, PartialEq, Eq)]
+pub struct RollScheduleParams {
+    /// The dataset code.
+    #[builder(setter(transform = |dt: impl ToString| dt.to_string()))]
+    pub dataset: String,
+    /// The continuous contract.
+    pub symbol: ContinuousSymbol,
+    /// The date range of the schedule.
+    #[builder(setter(into))]
+    pub date_range: DateRange,
+}
+
+/// A period during which a continuous contract maps to a single underlying contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RollPeriod {
+    /// The first date of the period (inclusive).
+    pub start_date: Date,
+    /// The end date of the period (exclusive).
+    pub end_date: Date,
+    /// The instrument ID of the underlying contract.
+    pub instrument_id: u32,
+    /// The raw symbol of the underlying contract.
+    pub raw_symbol: String,
+}
+
+/// How prices are adjusted across rolls.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AdjustmentMethod {
+    /// Prices are shifted by the price difference between contracts at each roll.
+    #[default]
+    Difference,
+    /// Prices are scaled by the price ratio between contracts at each roll.
+    Ratio,
+}
+
+impl SymbologyClient<'_> {
+    /// Determines the contracts a continuous contract maps to over the requested date
+    /// range, returning one [`RollPeriod`] per contract in chronological order.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API,
+    /// the API indicates there's an issue with the request, or the continuous symbol
+    /// couldn't be resolved.
+    pub async fn roll_schedule(
+        &self,
+        params: &RollScheduleParams,
+    ) -> crate::Result<Vec<RollPeriod>> {
+        let symbol = params.symbol.to_string();
+        let resolve_params = |stype_out| {
+            ResolveParams::builder()
+                .dataset(&params.dataset)
+                .symbols(symbol.as_str())
+                .stype_in(SType::Continuous)
+                .stype_out(stype_out)
+                .date_range(params.date_range.clone())
+                .build()
+        };
+        let mut ids = self.resolve(&resolve_params(SType::InstrumentId)).await?;
+        let mut raw_symbols = self.resolve(&resolve_params(SType::RawSymbol)).await?;
+        let mut id_intervals = ids
+            .mappings
+            .remove(&symbol)
+            .ok_or_else(|| Error::bad_arg("symbol", format!("unable to resolve {symbol}")))?;
+        let raw_intervals = raw_symbols.mappings.remove(&symbol).unwrap_or_default();
+        id_intervals.sort_by_key(|interval| interval.start_date);
+
+        let mut periods: Vec<RollPeriod> = Vec::with_capacity(id_intervals.len());
+        for interval in id_intervals {
+            let instrument_id = interval.symbol.parse().map_err(|_| {
+                Error::internal(format!(
+                    "Unable to parse '{}' to an instrument ID",
+                    interval.symbol
+                ))
+            })?;
+            match periods.last_mut() {
+                Some(last)
+                    if last.instrument_id == instrument_id
+                        && last.end_date == interval.start_date =>
+                {
+                    last.end_date = interval.end_date;
+                }
+                _ => {
+                    let raw_symbol = raw_intervals
+                        .iter()
+                        .find(|raw| {
+                            raw.start_date <= interval.start_date
+                                && interval.start_date < raw.end_date
+                        })
+                        .map(|raw| raw.symbol.clone())
+                        .unwrap_or_default();
+                    periods.push(RollPeriod {
+                        start_date: interval.start_date,
+                        end_date: interval.end_date,
+                        instrument_id,
+                        raw_symbol,
+                    });
+                }
+            }
+        }
+        Ok(periods)
+    }
+}
+
+impl TimeseriesClient<'_> {
+    /// Computes the cumulative back adjustment for each period in `schedule`, such that
+    /// applying an adjustment with [`AdjustmentMethod::apply()`] to prices from its
+    /// period makes them continuous with prices from the most recent contract.
+    ///
+    /// At each roll, the adjustment is based on the daily closing prices of the
+    /// outgoing and incoming contracts on the last day before the roll that both
+    /// traded.
+    ///
+    /// # Errors
+    /// This function returns an error when `method` is [`AdjustmentMethod::Ratio`] and
+    /// the closing price of an outgoing contract is zero, it fails to communicate with
+    /// the Databento API, or the API indicates there's an issue with the request.
+    pub async fn roll_adjustments(
+        &self,
+        dataset: &str,
+        schedule: &[RollPeriod],
+        method: AdjustmentMethod,
+    ) -> crate::Result<Vec<f64>> {
+        let (Some(first), Some(last)) = (schedule.first(), schedule.last()) else {
+            return Ok(Vec::new());
+        };
+        let mut decoder = self
+            .get_range(
+                &GetRangeParams::builder()
+                    .dataset(dataset)
+                    .schema(Schema::Ohlcv1D)
+                    .stype_in(SType::InstrumentId)
+                    .symbols(
+                        schedule
+                            .iter()
+                            .map(|period| period.instrument_id)
+                            .collect::<Symbols>(),
+                    )
+                    .date_time_range((
+                        first.start_date.midnight().assume_utc(),
+                        last.end_date.midnight().assume_utc(),
+                    ))
+                    .build(),
+            )
+            .await?;
+        let mut closes = HashMap::<u32, BTreeMap<Date, i64>>::new();
+        while let Some(bar) = decoder.decode_record::<OhlcvMsg>().await? {
+            let date = OffsetDateTime::from_unix_timestamp_nanos(bar.hd.ts_event as i128)
+                .map_err(|e| Error::internal(format!("invalid bar timestamp: {e:?}")))?
+                .date();
+            closes
+                .entry(bar.hd.instrument_id)
+                .or_default()
+                .insert(date, bar.close);
+        }
+        compute_adjustments(schedule, &closes, method)
+    }
+}
+
+fn compute_adjustments(
+    schedule: &[RollPeriod],
+    closes: &HashMap<u32, BTreeMap<Date, i64>>,
+    method: AdjustmentMethod,
+) -> crate::Result<Vec<f64>> {
+    let identity = match method {
+        AdjustmentMethod::Difference => 0.0,
+        AdjustmentMethod::Ratio => 1.0,
+    };
+    let mut adjustments = vec![identity; schedule.len()];
+    // Walk backwards from the most recent contract, accumulating adjustments
+    for i in (1..schedule.len()).rev() {
+        let (old, new) = (&schedule[i - 1], &schedule[i]);
+        let roll_closes = closes
+            .get(&old.instrument_id)
+            .zip(closes.get(&new.instrument_id))
+            .and_then(|(old_closes, new_closes)| {
+                old_closes
+                    .range(..new.start_date)
+                    .rev()
+                    .find_map(|(date, old_close)| {
+                        new_closes
+                            .get(date)
+                            .map(|new_close| (*date, *old_close, *new_close))
+                    })
+            });
+        let roll_adjustment = match roll_closes {
+            Some((date, 0, _)) if method == AdjustmentMethod::Ratio => {
+                return Err(Error::bad_arg(
+                    "method",
+                    format!(
+                        "can't compute a ratio adjustment for the roll from {raw_symbol} \
+                        because it closed at zero on {date}",
+                        raw_symbol = old.raw_symbol
+                    ),
+                ));
+            }
+            Some((_, old_close, new_close)) => {
+                let (old_close, new_close) = (px_to_f64(old_close), px_to_f64(new_close));
+                match method {
+                    AdjustmentMethod::Difference => new_close - old_close,
+                    AdjustmentMethod::Ratio => new_close / old_close,
+                }
+            }
+            None => identity,
+        };
+        adjustments[i - 1] = match method {
+            AdjustmentMethod::Difference => adjustments[i] + roll_adjustment,
+            AdjustmentMethod::Ratio => adjustments[i] * roll_adjustment,
+        };
+    }
+    Ok(adjustments)
+}
+
+fn px_to_f64(px: i64) -> f64 {
+    px as f64 / FIXED_PRICE_SCALE as f64
+}
+
+impl RollRule {
+    /// Converts the enum to the character used in continuous symbology.
+    pub const fn as_char(&self) -> char {
+        match self {
+            RollRule::Calendar => 'c',
+            RollRule::Volume => 'v',
+            RollRule::OpenInterest => 'n',
+        }
+    }
+}
+
+impl ContinuousSymbol {
+    /// Creates a new continuous contract symbol.
+    pub fn new(root: impl ToString, rule: RollRule, rank: u32) -> Self {
+        Self {
+            root: root.to_string(),
+            rule,
+            rank,
+        }
+    }
+}
+
+impl Display for ContinuousSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.root, self.rule.as_char(), self.rank)
+    }
+}
+
+impl FromStr for ContinuousSymbol {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::bad_arg("symbol", format!("'{s}' is not a valid continuous symbol"));
+        let mut parts = s.rsplitn(3, '.');
+        let (Some(rank), Some(rule), Some(root)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(err());
+        };
+        let rule = match rule {
+            "c" => RollRule::Calendar,
+            "v" => RollRule::Volume,
+            "n" => RollRule::OpenInterest,
+            _ => return Err(err()),
+        };
+        Ok(Self {
+            root: root.to_owned(),
+            rule,
+            rank: rank.parse().map_err(|_| err())?,
+        })
+    }
+}
+
+impl From<ContinuousSymbol> for Symbols {
+    fn from(value: ContinuousSymbol) -> Self {
+        Symbols::Symbols(vec![value.to_string()])
+    }
+}
+
+impl AdjustmentMethod {
+    /// Applies `adjustment` as returned by
+    /// [`TimeseriesClient::roll_adjustments()`] to `price`.
+    pub fn apply(&self, price: f64, adjustment: f64) -> f64 {
+        match self {
+            AdjustmentMethod::Difference => price + adjustment,
+            AdjustmentMethod::Ratio => price * adjustment,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::date;
+
+    use super::*;
+
+    #[test]
+    fn test_continuous_symbol_round_trip() {
+        let target: ContinuousSymbol = "ES.v.0".parse().unwrap();
+        assert_eq!(target, ContinuousSymbol::new("ES", RollRule::Volume, 0));
+        assert_eq!(target.to_string(), "ES.v.0");
+        assert!("ES.x.0".parse::<ContinuousSymbol>().is_err());
+        assert!("ES.FUT".parse::<ContinuousSymbol>().is_err());
+    }
+
+    #[test]
+    fn test_compute_adjustments() {
+        let schedule = vec![
+            RollPeriod {
+                start_date: date!(2024 - 01 - 01),
+                end_date: date!(2024 - 03 - 15),
+                instrument_id: 1,
+                raw_symbol: "ESH4".to_owned(),
+            },
+            RollPeriod {
+                start_date: date!(2024 - 03 - 15),
+                end_date: date!(2024 - 06 - 14),
+                instrument_id: 2,
+                raw_symbol: "ESM4".to_owned(),
+            },
+            RollPeriod {
+                start_date: date!(2024 - 06 - 14),
+                end_date: date!(2024 - 07 - 01),
+                instrument_id: 3,
+                raw_symbol: "ESU4".to_owned(),
+            },
+        ];
+        let px = |p: i64| p * FIXED_PRICE_SCALE;
+        let closes = HashMap::from([
+            (1, BTreeMap::from([(date!(2024 - 03 - 14), px(100))])),
+            (
+                2,
+                BTreeMap::from([
+                    (date!(2024 - 03 - 14), px(110)),
+                    (date!(2024 - 06 - 13), px(120)),
+                ]),
+            ),
+            (3, BTreeMap::from([(date!(2024 - 06 - 13), px(125))])),
+        ]);
+        assert_eq!(
+            compute_adjustments(&schedule, &closes, AdjustmentMethod::Difference).unwrap(),
+            vec![15.0, 5.0, 0.0]
+        );
+        let ratios = compute_adjustments(&schedule, &closes, AdjustmentMethod::Ratio).unwrap();
+        assert_eq!(ratios[2], 1.0);
+        assert!((ratios[1] - 125.0 / 120.0).abs() < f64::EPSILON);
+        assert!((AdjustmentMethod::Ratio.apply(100.0, ratios[0]) - 114.583_333).abs() < 1e-6);
+
+        let mut zero_closes = closes.clone();
+        zero_closes.insert(1, BTreeMap::from([(date!(2024 - 03 - 14), 0)]));
+        assert_eq!(
+            compute_adjustments(&schedule, &zero_closes, AdjustmentMethod::Difference).unwrap(),
+            vec![115.0, 5.0, 0.0]
+        );
+        assert!(matches!(
+            compute_adjustments(&schedule, &zero_closes, AdjustmentMethod::Ratio),
+            Err(Error::BadArgument { ref param_name, .. }) if param_name == "method"
+        ));
+    }
+}
@@ -0,0 +1,252 @@
+//! Tracking which batch jobs have been processed across runs, such as for a scheduled
+//! pipeline that only acts on new or newly-completed jobs.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::Error;
+
+use super::batch::{BatchClient, BatchJob, JobState, ListJobsParams};
+
+/// A local store of the batch jobs seen and processed by a pipeline, persisted as JSON
+/// at a path. Use [`BatchClient::list_new_jobs()`] to list the completed jobs that
+/// haven't been processed yet, then [`mark_processed()`](Self::mark_processed) and
+/// [`save()`](Self::save) after acting on each one.
+///
+/// Only jobs submitted since the [watermark](Self::since) are listed, which is the
+/// submission time of the oldest job that's still processing, so jobs that complete
+/// out of order aren't missed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct JobStore {
+    #[serde(skip)]
+    path: PathBuf,
+    jobs: BTreeMap<String, TrackedJob>,
+}
+
+/// The last known state of a batch job in a [`JobStore`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackedJob {
+    /// The state of the job when it was last listed.
+    pub state: JobState,
+    /// The timestamp of when Databento received the job.
+    #[serde(with = "time::serde::rfc3339")]
+    pub ts_received: OffsetDateTime,
+    /// Whether the job has been processed.
+    pub is_processed: bool,
+}
+
+impl JobStore {
+    /// Loads the store from `path`, or creates an empty one if the file doesn't exist.
+    /// The store isn't written until [`save()`](Self::save) is called.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to read the file or its contents
+    /// are invalid.
+    pub async fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let mut store: Self = match tokio::fs::read(path).await {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|e| Error::bad_arg("path", format!("invalid job store: {e}")))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e.into()),
+        };
+        store.path = path.to_owned();
+        Ok(store)
+    }
+
+    /// Writes the store to its path.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to write the file.
+    pub async fn save(&self) -> crate::Result<()> {
+        let contents = serde_json::to_vec_pretty(self)
+            .map_err(|e| Error::internal(format!("failed to serialize job store: {e}")))?;
+        tokio::fs::write(&self.path, contents).await?;
+        Ok(())
+    }
+
+    /// Returns the path of the store.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the watermark for listing jobs: the submission time of the oldest job
+    /// that's still processing, or else of the most recent job. Returns `None` if no
+    /// jobs have been seen.
+    pub fn since(&self) -> Option<OffsetDateTime> {
+        self.jobs
+            .values()
+            .filter(|job| !job.is_finished())
+            .map(|job| job.ts_received)
+            .min()
+            .or_else(|| self.jobs.values().map(|job| job.ts_received).max())
+    }
+
+    /// Returns the parameters for listing the jobs submitted since the watermark.
+    pub fn list_jobs_params(&self) -> ListJobsParams {
+        ListJobsParams {
+            since: self.since(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the tracked state of the job with ID `job_id`, if it's been seen.
+    pub fn get(&self, job_id: &str) -> Option<&TrackedJob> {
+        self.jobs.get(job_id)
+    }
+
+    /// Returns `true` if the job with ID `job_id` has been processed.
+    pub fn is_processed(&self, job_id: &str) -> bool {
+        self.get(job_id).is_some_and(|job| job.is_processed)
+    }
+
+    /// Marks the job with ID `job_id` as processed so it's no longer returned by
+    /// [`update()`](Self::update).
+    ///
+    /// # Errors
+    /// This function returns an error if there's no job with ID `job_id` in the store.
+    pub fn mark_processed(&mut self, job_id: &str) -> crate::Result<()> {
+        let job = self
+            .jobs
+            .get_mut(job_id)
+            .ok_or_else(|| Error::bad_arg("job_id", format!("no job with ID {job_id}")))?;
+        job.is_processed = true;
+        Ok(())
+    }
+
+    /// Records the latest state of `jobs` and returns the jobs that are done but
+    /// haven't been processed. Jobs that are finished and older than the new watermark
+    /// are dropped from the store since they'll no longer be listed.
+    pub fn update<'j>(&mut self, jobs: &'j [BatchJob]) -> Vec<&'j BatchJob> {
+        for job in jobs {
+            self.jobs
+                .entry(job.id.clone())
+                .and_modify(|tracked| tracked.state = job.state)
+                .or_insert(TrackedJob {
+                    state: job.state,
+                    ts_received: job.ts_received,
+                    is_processed: false,
+                });
+        }
+        if let Some(since) = self.since() {
+            self.jobs
+                .retain(|_, job| !job.is_finished() || job.ts_received >= since);
+        }
+        jobs.iter()
+            .filter(|job| job.state == JobState::Done && !self.is_processed(&job.id))
+            .collect()
+    }
+}
+
+impl TrackedJob {
+    /// Returns `true` if the job won't change state again, either because it's been
+    /// processed or it's expired.
+    fn is_finished(&self) -> bool {
+        self.is_processed || self.state == JobState::Expired
+    }
+}
+
+impl BatchClient<'_> {
+    /// Lists the jobs submitted since the watermark of `store` and returns those that
+    /// are done but haven't been processed, after recording the latest state of each
+    /// job in `store`. The store isn't saved.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request.
+    pub async fn list_new_jobs(&self, store: &mut JobStore) -> crate::Result<Vec<BatchJob>> {
+        let jobs = self.list_jobs(&store.list_jobs_params()).await?;
+        Ok(store.update(&jobs).into_iter().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn job(id: &str, state: JobState, ts_received: OffsetDateTime) -> BatchJob {
+        let ts_received = ts_received
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap();
+        serde_json::from_value(json!({
+            "id": id,
+            "dataset": "XNAS.ITCH",
+            "symbols": "TSLA",
+            "stype_in": "raw_symbol",
+            "stype_out": "instrument_id",
+            "schema": "trades",
+            "start": "2023-06-14T00:00:00Z",
+            "end": "2023-06-17T00:00:00Z",
+            "limit": null,
+            "encoding": "dbn",
+            "compression": "zstd",
+            "pretty_px": false,
+            "pretty_ts": false,
+            "map_symbols": false,
+            "split_symbols": false,
+            "split_duration": "day",
+            "split_size": null,
+            "delivery": "download",
+            "state": state.as_str(),
+            "ts_received": ts_received,
+            "ts_queued": null,
+            "ts_process_start": null,
+            "ts_process_done": null,
+            "ts_expiration": null
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_job_store() {
+        const T1: OffsetDateTime = datetime!(2024-01-01 00:00 UTC);
+        const T2: OffsetDateTime = datetime!(2024-01-02 00:00 UTC);
+        const T3: OffsetDateTime = datetime!(2024-01-03 00:00 UTC);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jobs.json");
+        let mut target = JobStore::open(&path).await.unwrap();
+        assert!(target.since().is_none());
+        let jobs = [
+            job("1", JobState::Done, T1),
+            job("2", JobState::Processing, T2),
+            job("3", JobState::Done, T3),
+        ];
+        let new_jobs = target.update(&jobs);
+        assert_eq!(
+            new_jobs
+                .iter()
+                .map(|job| job.id.as_str())
+                .collect::<Vec<_>>(),
+            ["1", "3"]
+        );
+        // Job 1 hasn't been processed yet
+        assert_eq!(target.since(), Some(T1));
+        target.mark_processed("1").unwrap();
+        target.mark_processed("3").unwrap();
+        assert!(target.mark_processed("4").is_err());
+        assert_eq!(target.since(), Some(T2));
+        target.save().await.unwrap();
+
+        let mut target = JobStore::open(&path).await.unwrap();
+        assert!(target.is_processed("3"));
+        assert_eq!(target.list_jobs_params().since, Some(T2));
+        // Job 2 completed and job 1 is no longer listed
+        let jobs = [job("2", JobState::Done, T2), job("3", JobState::Done, T3)];
+        let new_jobs = target.update(&jobs);
+        assert_eq!(new_jobs.len(), 1);
+        assert_eq!(new_jobs[0].id, "2");
+        assert!(target.get("1").is_none());
+        target.mark_processed("2").unwrap();
+        assert_eq!(target.since(), Some(T3));
+        assert!(target.update(&jobs).is_empty());
+    }
+}
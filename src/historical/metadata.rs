@@ -1,22 +1,27 @@
 //! The historical metadata download API.
 
-use std::{collections::HashMap, num::NonZeroU64, str::FromStr};
+use std::{collections::HashMap, iter::Sum, mem, num::NonZeroU64, str::FromStr};
 
-use dbn::{Encoding, SType, Schema};
+use dbn::{
+    BboMsg, CbboMsg, Cmbp1Msg, Encoding, ImbalanceMsg, InstrumentDefMsg, MboMsg, Mbp10Msg, Mbp1Msg,
+    OhlcvMsg, SType, Schema, StatMsg, StatusMsg, TradeMsg,
+};
+use futures::future::try_join_all;
 use reqwest::RequestBuilder;
-use serde::{Deserialize, Deserializer};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
 use typed_builder::TypedBuilder;
 
-use crate::Symbols;
+use crate::{Error, Symbols};
 
 use super::{
-    deserialize::deserialize_date_time, handle_response, AddToQuery, DateRange, DateTimeRange,
+    deserialize::deserialize_date_time, handle_response, paginate::Paginated, AddToQuery,
+    DateRange, DateTimeRange, MAX_SYMBOLS_PER_REQUEST,
 };
 
 /// A client for the metadata group of Historical API endpoints.
 #[derive(Debug)]
 pub struct MetadataClient<'a> {
-    pub(crate) inner: &'a mut super::Client,
+    pub(crate) inner: &'a super::Client,
 }
 
 impl MetadataClient<'_> {
@@ -24,8 +29,8 @@ impl MetadataClient<'_> {
     ///
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API.
-    pub async fn list_publishers(&mut self) -> crate::Result<Vec<PublisherDetail>> {
-        let resp = self.get("list_publishers")?.send().await?;
+    pub async fn list_publishers(&self) -> crate::Result<Vec<PublisherDetail>> {
+        let resp = self.inner.send(self.get("list_publishers")?).await?;
         handle_response(resp).await
     }
 
@@ -34,15 +39,12 @@ impl MetadataClient<'_> {
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request.
-    pub async fn list_datasets(
-        &mut self,
-        date_range: Option<DateRange>,
-    ) -> crate::Result<Vec<String>> {
+    pub async fn list_datasets(&self, date_range: Option<DateRange>) -> crate::Result<Vec<String>> {
         let mut builder = self.get("list_datasets")?;
         if let Some(date_range) = date_range {
             builder = builder.add_to_query(&date_range);
         }
-        let resp = builder.send().await?;
+        let resp = self.inner.send(builder).await?;
         handle_response(resp).await
     }
 
@@ -51,29 +53,58 @@ impl MetadataClient<'_> {
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request.
-    pub async fn list_schemas(&mut self, dataset: &str) -> crate::Result<Vec<Schema>> {
-        let resp = self
-            .get("list_schemas")?
-            .query(&[("dataset", dataset)])
-            .send()
-            .await?;
+    pub async fn list_schemas(&self, dataset: &str) -> crate::Result<Vec<Schema>> {
+        let builder = self.get("list_schemas")?.query(&[("dataset", dataset)]);
+        let resp = self.inner.send(builder).await?;
         handle_response(resp).await
     }
 
+    /// Lists all available schemas for the given `dataset` with their record size,
+    /// description, and DBN fields, such as for displaying schema documentation and
+    /// projecting data sizes without joining the results of
+    /// [`list_schemas()`](Self::list_schemas) and [`list_fields()`](Self::list_fields).
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request.
+    pub async fn describe_schemas(&self, dataset: &str) -> crate::Result<Vec<SchemaDetail>> {
+        let schemas = self.list_schemas(dataset).await?;
+        let requests = schemas
+            .iter()
+            .map(|schema| {
+                Ok(self.get("list_fields")?.query(&[
+                    ("encoding", Encoding::Dbn.as_str()),
+                    ("schema", schema.as_str()),
+                ]))
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        let fields = try_join_all(requests.into_iter().map(|req| async move {
+            handle_response::<Vec<FieldDetail>>(self.inner.send(req).await?).await
+        }))
+        .await?;
+        Ok(schemas
+            .into_iter()
+            .zip(fields)
+            .map(|(schema, fields)| SchemaDetail {
+                schema,
+                record_size: record_size(schema),
+                description: description(schema),
+                fields,
+            })
+            .collect())
+    }
+
     /// Lists all fields for a schema and encoding.
     ///
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request.
-    pub async fn list_fields(
-        &mut self,
-        params: &ListFieldsParams,
-    ) -> crate::Result<Vec<FieldDetail>> {
+    pub async fn list_fields(&self, params: &ListFieldsParams) -> crate::Result<Vec<FieldDetail>> {
         let builder = self.get("list_fields")?.query(&[
             ("encoding", params.encoding.as_str()),
             ("schema", params.schema.as_str()),
         ]);
-        let resp = builder.send().await?;
+        let resp = self.inner.send(builder).await?;
         handle_response(resp).await
     }
 
@@ -82,14 +113,11 @@ impl MetadataClient<'_> {
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request.
-    pub async fn list_unit_prices(
-        &mut self,
-        dataset: &str,
-    ) -> crate::Result<Vec<UnitPricesForMode>> {
+    pub async fn list_unit_prices(&self, dataset: &str) -> crate::Result<Vec<UnitPricesForMode>> {
         let builder = self
             .get("list_unit_prices")?
             .query(&[("dataset", &dataset)]);
-        let resp = builder.send().await?;
+        let resp = self.inner.send(builder).await?;
         handle_response(resp).await
     }
 
@@ -101,7 +129,7 @@ impl MetadataClient<'_> {
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request.
     pub async fn get_dataset_condition(
-        &mut self,
+        &self,
         params: &GetDatasetConditionParams,
     ) -> crate::Result<Vec<DatasetConditionDetail>> {
         let mut builder = self
@@ -110,10 +138,25 @@ impl MetadataClient<'_> {
         if let Some(ref date_range) = params.date_range {
             builder = builder.add_to_query(date_range);
         }
-        let resp = builder.send().await?;
+        let resp = self.inner.send(builder).await?;
         handle_response(resp).await
     }
 
+    /// Gets the dataset condition from Databento like
+    /// [`get_dataset_condition()`](Self::get_dataset_condition) and summarizes it,
+    /// such as to check for days with degraded or missing data before requesting data.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request.
+    pub async fn get_dataset_condition_summary(
+        &self,
+        params: &GetDatasetConditionParams,
+    ) -> crate::Result<DatasetConditionSummary> {
+        let details = self.get_dataset_condition(params).await?;
+        Ok(DatasetConditionSummary::new(&details))
+    }
+
     /// Gets the available range for the dataset from Databento.
     ///
     /// Use this method to discover data availability.
@@ -121,61 +164,217 @@ impl MetadataClient<'_> {
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request.
-    pub async fn get_dataset_range(&mut self, dataset: &str) -> crate::Result<DatasetRange> {
-        let resp = self
+    pub async fn get_dataset_range(&self, dataset: &str) -> crate::Result<DatasetRange> {
+        let builder = self
             .get("get_dataset_range")?
-            .query(&[("dataset", dataset)])
-            .send()
-            .await?;
+            .query(&[("dataset", dataset)]);
+        let resp = self.inner.send(builder).await?;
         handle_response(resp).await
     }
 
+    /// Gets the schemas, available range, daily conditions, and feed modes of `dataset`
+    /// in a single [`DatasetCapabilities`], such as for validating requests before
+    /// they're made. The result is cached for the lifetime of the client and shared
+    /// with its clones.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request.
+    pub async fn capabilities(&self, dataset: &str) -> crate::Result<DatasetCapabilities> {
+        if let Some(capabilities) = self.inner.cached_capabilities(dataset) {
+            return Ok(capabilities);
+        }
+        let schemas = self.list_schemas(dataset).await?;
+        let range = self.get_dataset_range(dataset).await?;
+        let conditions = self
+            .get_dataset_condition_summary(
+                &GetDatasetConditionParams::builder()
+                    .dataset(dataset)
+                    .build(),
+            )
+            .await?
+            .counts;
+        let modes = self
+            .list_unit_prices(dataset)
+            .await?
+            .into_iter()
+            .map(|prices| {
+                let mut schemas: Vec<_> = prices.unit_prices.into_keys().collect();
+                schemas.sort_by_key(|schema| *schema as u16);
+                (prices.mode, schemas)
+            })
+            .collect();
+        let capabilities = DatasetCapabilities {
+            dataset: dataset.to_owned(),
+            schemas,
+            range,
+            conditions,
+            modes,
+        };
+        self.inner.cache_capabilities(capabilities.clone());
+        Ok(capabilities)
+    }
+
     /// Gets the record count of the time series data query.
     ///
+    /// Queries with more than [`MAX_SYMBOLS_PER_REQUEST`] symbols are split into
+    /// several requests whose counts are summed.
+    ///
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request.
-    pub async fn get_record_count(&mut self, params: &GetRecordCountParams) -> crate::Result<u64> {
-        let mut form = Vec::new();
-        params.add_to_form(&mut form);
-        let resp = self.post("get_record_count")?.form(&form).send().await?;
-        handle_response(resp).await
+    pub async fn get_record_count(&self, params: &GetRecordCountParams) -> crate::Result<u64> {
+        let count: u64 = self
+            .post_chunked::<u64>("get_record_count", params, true)
+            .await?
+            .into_iter()
+            .sum();
+        Ok(params
+            .limit
+            .map(|limit| count.min(limit.get()))
+            .unwrap_or(count))
     }
 
     /// Gets the billable uncompressed raw binary size for historical streaming or
     /// batched files.
     ///
+    /// Queries with more than [`MAX_SYMBOLS_PER_REQUEST`] symbols are split into
+    /// several requests whose sizes are summed.
+    ///
     /// # Errors
-    /// This function returns an error when it fails to communicate with the Databento API
-    /// or the API indicates there's an issue with the request.
-    pub async fn get_billable_size(
-        &mut self,
-        params: &GetBillableSizeParams,
-    ) -> crate::Result<u64> {
-        let mut form = Vec::new();
-        params.add_to_form(&mut form);
-        let resp = self.post("get_billable_size")?.form(&form).send().await?;
-        handle_response(resp).await
+    /// This function returns an error when the query has a `limit` and would be split
+    /// into several requests, it fails to communicate with the Databento API, or the API
+    /// indicates there's an issue with the request.
+    pub async fn get_billable_size(&self, params: &GetBillableSizeParams) -> crate::Result<u64> {
+        Ok(self
+            .post_chunked::<u64>("get_billable_size", params, false)
+            .await?
+            .into_iter()
+            .sum())
     }
 
     /// Gets the cost in US dollars for a historical streaming or batch download
     /// request.
     ///
+    /// Queries with more than [`MAX_SYMBOLS_PER_REQUEST`] symbols are split into
+    /// several requests whose costs are summed.
+    ///
     /// # Errors
-    /// This function returns an error when it fails to communicate with the Databento API
-    /// or the API indicates there's an issue with the request.
-    pub async fn get_cost(&mut self, params: &GetCostParams) -> crate::Result<f64> {
-        let mut form = Vec::new();
-        params.add_to_form(&mut form);
-        let resp = self.post("get_cost")?.form(&form).send().await?;
-        handle_response(resp).await
+    /// This function returns an error when the query has a `limit` and would be split
+    /// into several requests, it fails to communicate with the Databento API, or the API
+    /// indicates there's an issue with the request.
+    pub async fn get_cost(&self, params: &GetCostParams) -> crate::Result<f64> {
+        Ok(self
+            .post_chunked::<f64>("get_cost", params, false)
+            .await?
+            .into_iter()
+            .sum())
     }
 
-    fn get(&mut self, slug: &str) -> crate::Result<RequestBuilder> {
+    /// Gets the billable uncompressed raw binary size of several queries, such as the
+    /// datasets and schemas of a planned backfill. The requests are made concurrently.
+    ///
+    /// # Errors
+    /// This function returns an error when a query has a `limit` and would be split into
+    /// several requests, it fails to communicate with the Databento API, or the API
+    /// indicates there's an issue with any of the requests.
+    pub async fn get_billable_sizes(
+        &self,
+        params: &[GetBillableSizeParams],
+    ) -> crate::Result<QueryBreakdown<u64>> {
+        self.post_chunked_concurrently("get_billable_size", params)
+            .await
+    }
+
+    /// Gets the cost in US dollars of several queries, such as the datasets and schemas
+    /// of a planned backfill. The requests are made concurrently.
+    ///
+    /// # Errors
+    /// This function returns an error when a query has a `limit` and would be split into
+    /// several requests, it fails to communicate with the Databento API, or the API
+    /// indicates there's an issue with any of the requests.
+    pub async fn get_costs(&self, params: &[GetCostParams]) -> crate::Result<QueryBreakdown<f64>> {
+        self.post_chunked_concurrently("get_cost", params).await
+    }
+
+    /// Makes one request per `SType` and chunk of at most [`MAX_SYMBOLS_PER_REQUEST`]
+    /// symbols. See [`chunked_requests()`](Self::chunked_requests) for
+    /// `can_split_limit`.
+    async fn post_chunked<R: DeserializeOwned>(
+        &self,
+        slug: &str,
+        params: &GetQueryParams,
+        can_split_limit: bool,
+    ) -> crate::Result<Vec<R>> {
+        Paginated::new(self.chunked_requests(slug, params, can_split_limit)?)
+            .sent_by(self.inner)
+            .collect_all()
+            .await
+    }
+
+    /// Like [`post_chunked()`](Self::post_chunked) for several queries, but with all
+    /// requests made concurrently and the chunks of each query summed.
+    async fn post_chunked_concurrently<R>(
+        &self,
+        slug: &str,
+        params: &[GetQueryParams],
+    ) -> crate::Result<QueryBreakdown<R>>
+    where
+        R: DeserializeOwned + Sum + Copy,
+    {
+        let requests = params
+            .iter()
+            .map(|params| self.chunked_requests(slug, params, false))
+            .collect::<crate::Result<Vec<_>>>()?;
+        let per_query = try_join_all(requests.into_iter().map(|reqs| async move {
+            let chunks =
+                try_join_all(reqs.into_iter().map(|req| async move {
+                    handle_response::<R>(self.inner.send(req).await?).await
+                }))
+                .await?;
+            Ok::<R, crate::Error>(chunks.into_iter().sum())
+        }))
+        .await?;
+        let total = per_query.iter().copied().sum();
+        Ok(QueryBreakdown { per_query, total })
+    }
+
+    /// Returns one request per `SType` and chunk of at most [`MAX_SYMBOLS_PER_REQUEST`]
+    /// symbols. Unless `can_split_limit` is `true`, such as when the caller clamps the
+    /// summed results to the limit, returns an error if the query has a `limit` and
+    /// would be split, since the result of each request would include up to `limit`
+    /// records.
+    fn chunked_requests(
+        &self,
+        slug: &str,
+        params: &GetQueryParams,
+        can_split_limit: bool,
+    ) -> crate::Result<Vec<RequestBuilder>> {
+        let mut res = Vec::new();
+        for (stype_in, symbols) in params.symbols.group_by_stype(params.stype_in) {
+            for symbols in symbols.chunks(MAX_SYMBOLS_PER_REQUEST) {
+                let mut form = Vec::new();
+                params.add_to_form(stype_in, &symbols, &mut form);
+                res.push(self.post(slug)?.form(&form));
+            }
+        }
+        if !can_split_limit && params.limit.is_some() && res.len() > 1 {
+            return Err(Error::bad_arg(
+                "limit",
+                format!(
+                    "isn't supported for queries split into several requests, such as those \
+                    with more than {MAX_SYMBOLS_PER_REQUEST} symbols"
+                ),
+            ));
+        }
+        Ok(res)
+    }
+
+    fn get(&self, slug: &str) -> crate::Result<RequestBuilder> {
         self.inner.get(&format!("metadata.{slug}"))
     }
 
-    fn post(&mut self, slug: &str) -> crate::Result<RequestBuilder> {
+    fn post(&self, slug: &str) -> crate::Result<RequestBuilder> {
         self.inner.post(&format!("metadata.{slug}"))
     }
 }
@@ -240,6 +439,20 @@ pub struct FieldDetail {
     pub type_name: String,
 }
 
+/// The details about a schema returned by [`MetadataClient::describe_schemas()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaDetail {
+    /// The data record schema.
+    pub schema: Schema,
+    /// The size of each record of the schema in bytes, excluding the optional
+    /// `ts_out` field.
+    pub record_size: usize,
+    /// A brief description of the schema.
+    pub description: &'static str,
+    /// The fields of the schema's DBN record.
+    pub fields: Vec<FieldDetail>,
+}
+
 /// The unit prices for a particular [`FeedMode`].
 #[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct UnitPricesForMode {
@@ -277,6 +490,83 @@ pub struct DatasetConditionDetail {
     pub last_modified_date: time::Date,
 }
 
+/// A summary of the condition of a dataset over a range of days. Returned by
+/// [`MetadataClient::get_dataset_condition_summary()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatasetConditionSummary {
+    /// The number of days with each condition.
+    pub counts: HashMap<DatasetCondition, usize>,
+    /// The [`Degraded`](DatasetCondition::Degraded) days in chronological order.
+    pub degraded_days: Vec<time::Date>,
+    /// The [`Missing`](DatasetCondition::Missing) and
+    /// [`Pending`](DatasetCondition::Pending) days in chronological order.
+    pub missing_days: Vec<time::Date>,
+}
+
+impl DatasetConditionSummary {
+    /// Summarizes the condition of each day in `details`.
+    pub fn new(details: &[DatasetConditionDetail]) -> Self {
+        let mut summary = Self::default();
+        for detail in details {
+            *summary.counts.entry(detail.condition).or_insert(0) += 1;
+            match detail.condition {
+                DatasetCondition::Degraded => summary.degraded_days.push(detail.date),
+                DatasetCondition::Missing | DatasetCondition::Pending => {
+                    summary.missing_days.push(detail.date)
+                }
+                DatasetCondition::Available | DatasetCondition::Intraday => {}
+            }
+        }
+        summary.degraded_days.sort_unstable();
+        summary.missing_days.sort_unstable();
+        summary
+    }
+
+    /// Returns `true` if there are no degraded, pending, or missing days.
+    pub fn is_clean(&self) -> bool {
+        self.degraded_days.is_empty() && self.missing_days.is_empty()
+    }
+
+    /// Returns the degraded, pending, and missing days in chronological order.
+    pub fn unusable_days(&self) -> Vec<time::Date> {
+        let mut days = [self.degraded_days.as_slice(), self.missing_days.as_slice()].concat();
+        days.sort_unstable();
+        days
+    }
+}
+
+/// The capabilities of a dataset. Returned by [`MetadataClient::capabilities()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetCapabilities {
+    /// The dataset code.
+    pub dataset: String,
+    /// The available schemas.
+    pub schemas: Vec<Schema>,
+    /// The available range of the dataset.
+    pub range: DatasetRange,
+    /// The number of days with each condition.
+    pub conditions: HashMap<DatasetCondition, usize>,
+    /// The schemas available in each feed mode.
+    pub modes: HashMap<FeedMode, Vec<Schema>>,
+}
+
+impl DatasetCapabilities {
+    /// Returns `true` if `schema` is available in the feed `mode`.
+    pub fn supports(&self, schema: Schema, mode: FeedMode) -> bool {
+        self.modes
+            .get(&mode)
+            .is_some_and(|schemas| schemas.contains(&schema))
+    }
+
+    /// Returns `true` if all days in the dataset's history are
+    /// [`Available`](DatasetCondition::Available).
+    pub fn is_fully_available(&self) -> bool {
+        self.conditions
+            .keys()
+            .all(|condition| *condition == DatasetCondition::Available)
+    }
+}
+
 /// The available range for a dataset.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DatasetRange {
@@ -343,6 +633,62 @@ pub type GetBillableSizeParams = GetQueryParams;
 /// defaults.
 pub type GetCostParams = GetQueryParams;
 
+/// The result of [`MetadataClient::get_costs()`] and
+/// [`MetadataClient::get_billable_sizes()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryBreakdown<T> {
+    /// The value for each query in the same order as the parameters.
+    pub per_query: Vec<T>,
+    /// The sum of the values of all queries.
+    pub total: T,
+}
+
+fn record_size(schema: Schema) -> usize {
+    match schema {
+        Schema::Mbo => mem::size_of::<MboMsg>(),
+        Schema::Mbp1 | Schema::Tbbo => mem::size_of::<Mbp1Msg>(),
+        Schema::Mbp10 => mem::size_of::<Mbp10Msg>(),
+        Schema::Trades => mem::size_of::<TradeMsg>(),
+        Schema::Ohlcv1S
+        | Schema::Ohlcv1M
+        | Schema::Ohlcv1H
+        | Schema::Ohlcv1D
+        | Schema::OhlcvEod => mem::size_of::<OhlcvMsg>(),
+        Schema::Definition => mem::size_of::<InstrumentDefMsg>(),
+        Schema::Statistics => mem::size_of::<StatMsg>(),
+        Schema::Status => mem::size_of::<StatusMsg>(),
+        Schema::Imbalance => mem::size_of::<ImbalanceMsg>(),
+        Schema::Cmbp1 => mem::size_of::<Cmbp1Msg>(),
+        Schema::Cbbo1S | Schema::Cbbo1M | Schema::Tcbbo => mem::size_of::<CbboMsg>(),
+        Schema::Bbo1S | Schema::Bbo1M => mem::size_of::<BboMsg>(),
+    }
+}
+
+fn description(schema: Schema) -> &'static str {
+    match schema {
+        Schema::Mbo => "Market by order with every order book event",
+        Schema::Mbp1 => "Market by price with the top price level",
+        Schema::Mbp10 => "Market by price with the top 10 price levels",
+        Schema::Tbbo => "Every trade with the top of book before it",
+        Schema::Trades => "Every trade event",
+        Schema::Ohlcv1S => "Open, high, low, close, and volume at a 1-second interval",
+        Schema::Ohlcv1M => "Open, high, low, close, and volume at a 1-minute interval",
+        Schema::Ohlcv1H => "Open, high, low, close, and volume at an hourly interval",
+        Schema::Ohlcv1D => "Open, high, low, close, and volume at a UTC daily interval",
+        Schema::OhlcvEod => "Open, high, low, close, and volume at an end-of-day interval",
+        Schema::Definition => "Instrument definitions",
+        Schema::Statistics => "Venue statistics such as settlement prices and open interest",
+        Schema::Status => "Trading status events such as halts and session changes",
+        Schema::Imbalance => "Auction imbalance events",
+        Schema::Cmbp1 => "Consolidated market by price with the top price level",
+        Schema::Cbbo1S => "Consolidated best bid and offer at a 1-second interval",
+        Schema::Cbbo1M => "Consolidated best bid and offer at a 1-minute interval",
+        Schema::Tcbbo => "Every trade with the consolidated best bid and offer before it",
+        Schema::Bbo1S => "Best bid and offer at a 1-second interval",
+        Schema::Bbo1M => "Best bid and offer at a 1-minute interval",
+    }
+}
+
 impl AsRef<str> for FeedMode {
     fn as_ref(&self) -> &str {
         self.as_str()
@@ -432,11 +778,16 @@ fn deserialize_date<'de, D: serde::Deserializer<'de>>(
     time::Date::parse(&dt_str, super::DATE_FORMAT).map_err(serde::de::Error::custom)
 }
 impl GetQueryParams {
-    fn add_to_form(&self, form: &mut Vec<(&'static str, String)>) {
+    fn add_to_form(
+        &self,
+        stype_in: SType,
+        symbols: &Symbols,
+        form: &mut Vec<(&'static str, String)>,
+    ) {
         form.push(("dataset", self.dataset.to_string()));
         form.push(("schema", self.schema.to_string()));
-        form.push(("stype_in", self.stype_in.to_string()));
-        form.push(("symbols", self.symbols.to_api_string()));
+        form.push(("stype_in", stype_in.to_string()));
+        form.push(("symbols", symbols.to_api_string()));
         self.date_time_range.add_to_form(form);
         if let Some(limit) = self.limit {
             form.push(("limit", limit.get().to_string()))
@@ -456,6 +807,7 @@ mod tests {
 
     use super::*;
     use crate::{
+        body_contains,
         historical::{HistoricalGateway, API_VERSION},
         HistoricalClient,
     };
@@ -485,7 +837,7 @@ mod tests {
             )
             .mount(&mock_server)
             .await;
-        let mut target = HistoricalClient::with_url(
+        let target = HistoricalClient::with_url(
             mock_server.uri(),
             API_KEY.to_owned(),
             HistoricalGateway::Bo1,
@@ -534,6 +886,63 @@ mod tests {
         assert_eq!(*fields, exp);
     }
 
+    #[tokio::test]
+    async fn test_describe_schemas() {
+        const DATASET: &str = "XNAS.ITCH";
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/metadata.list_schemas")))
+            .and(query_param("dataset", DATASET))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK.as_u16())
+                    .set_body_json(json!([Schema::Trades.as_str(), Schema::Ohlcv1D.as_str()])),
+            )
+            .mount(&mock_server)
+            .await;
+        for (schema, fields) in [
+            (
+                Schema::Trades,
+                json!([{"name": "price", "type": "int64_t"}]),
+            ),
+            (
+                Schema::Ohlcv1D,
+                json!([{"name": "volume", "type": "uint64_t"}]),
+            ),
+        ] {
+            Mock::given(method("GET"))
+                .and(basic_auth(API_KEY, ""))
+                .and(path(format!("/v{API_VERSION}/metadata.list_fields")))
+                .and(query_param("encoding", Encoding::Dbn.as_str()))
+                .and(query_param("schema", schema.as_str()))
+                .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(fields))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let details = target.metadata().describe_schemas(DATASET).await.unwrap();
+        assert_eq!(details.len(), 2);
+        assert_eq!(details[0].schema, Schema::Trades);
+        assert_eq!(details[0].record_size, 48);
+        assert_eq!(
+            details[0].fields,
+            vec![FieldDetail {
+                name: "price".to_owned(),
+                type_name: "int64_t".to_owned(),
+            }]
+        );
+        assert_eq!(details[1].schema, Schema::Ohlcv1D);
+        assert_eq!(details[1].record_size, 56);
+        assert_eq!(details[1].fields[0].name, "volume");
+        assert!(!details[1].description.is_empty());
+    }
+
     #[tokio::test]
     async fn test_list_unit_prices() {
         const SCHEMA: Schema = Schema::Tbbo;
@@ -561,7 +970,7 @@ mod tests {
             )
             .mount(&mock_server)
             .await;
-        let mut target = HistoricalClient::with_url(
+        let target = HistoricalClient::with_url(
             mock_server.uri(),
             API_KEY.to_owned(),
             HistoricalGateway::Bo1,
@@ -611,7 +1020,7 @@ mod tests {
             )
             .mount(&mock_server)
             .await;
-        let mut target = HistoricalClient::with_url(
+        let target = HistoricalClient::with_url(
             mock_server.uri(),
             API_KEY.to_owned(),
             HistoricalGateway::Bo1,
@@ -646,6 +1055,221 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_dataset_condition_summary() {
+        let detail = |date, condition| DatasetConditionDetail {
+            date,
+            condition,
+            last_modified_date: date,
+        };
+        let target = DatasetConditionSummary::new(&[
+            detail(date!(2024 - 06 - 10), DatasetCondition::Available),
+            detail(date!(2024 - 06 - 13), DatasetCondition::Pending),
+            detail(date!(2024 - 06 - 11), DatasetCondition::Missing),
+            detail(date!(2024 - 06 - 12), DatasetCondition::Degraded),
+            detail(date!(2024 - 06 - 14), DatasetCondition::Available),
+        ]);
+        assert!(!target.is_clean());
+        assert_eq!(target.counts[&DatasetCondition::Available], 2);
+        assert_eq!(target.counts[&DatasetCondition::Missing], 1);
+        assert_eq!(target.degraded_days, vec![date!(2024 - 06 - 12)]);
+        assert_eq!(
+            target.missing_days,
+            vec![date!(2024 - 06 - 11), date!(2024 - 06 - 13)]
+        );
+        assert_eq!(
+            target.unusable_days(),
+            vec![
+                date!(2024 - 06 - 11),
+                date!(2024 - 06 - 12),
+                date!(2024 - 06 - 13)
+            ]
+        );
+        assert!(DatasetConditionSummary::new(&[]).is_clean());
+    }
+
+    #[tokio::test]
+    async fn test_get_record_count_chunked() {
+        const DATASET: &str = "XNAS.ITCH";
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/metadata.get_record_count")))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!(1000)))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let count = target
+            .metadata()
+            .get_record_count(
+                &GetRecordCountParams::builder()
+                    .dataset(DATASET)
+                    .symbols((0..MAX_SYMBOLS_PER_REQUEST as u32 + 1).collect::<Symbols>())
+                    .schema(Schema::Trades)
+                    .stype_in(SType::InstrumentId)
+                    .date_time_range((
+                        datetime!(2023 - 06 - 14 00:00 UTC),
+                        datetime!(2023 - 06 - 15 00:00 UTC),
+                    ))
+                    .build(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 2000);
+    }
+
+    #[tokio::test]
+    async fn test_get_cost_chunked_with_limit() {
+        let target = HistoricalClient::with_url(
+            "http://127.0.0.1:0",
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let params = GetCostParams::builder()
+            .dataset("XNAS.ITCH")
+            .symbols((0..MAX_SYMBOLS_PER_REQUEST as u32 + 1).collect::<Symbols>())
+            .schema(Schema::Trades)
+            .stype_in(SType::InstrumentId)
+            .date_time_range((
+                datetime!(2023 - 06 - 14 00:00 UTC),
+                datetime!(2023 - 06 - 15 00:00 UTC),
+            ))
+            .limit(NonZeroU64::new(10))
+            .build();
+        let res = target.metadata().get_cost(&params).await;
+        assert!(
+            matches!(res, Err(Error::BadArgument { ref param_name, .. }) if param_name == "limit")
+        );
+        let res = target.metadata().get_costs(&[params]).await;
+        assert!(
+            matches!(res, Err(Error::BadArgument { ref param_name, .. }) if param_name == "limit")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_costs() {
+        let mock_server = MockServer::start().await;
+        for (dataset, cost) in [("XNAS.ITCH", 1.5), ("GLBX.MDP3", 2.25)] {
+            Mock::given(method("POST"))
+                .and(basic_auth(API_KEY, ""))
+                .and(path(format!("/v{API_VERSION}/metadata.get_cost")))
+                .and(body_contains("dataset", dataset))
+                .respond_with(
+                    ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!(cost)),
+                )
+                .mount(&mock_server)
+                .await;
+        }
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let params = |dataset: &str| {
+            GetCostParams::builder()
+                .dataset(dataset)
+                .symbols(vec!["ESM4"])
+                .schema(Schema::Trades)
+                .date_time_range((
+                    datetime!(2024 - 06 - 14 00:00 UTC),
+                    datetime!(2024 - 06 - 15 00:00 UTC),
+                ))
+                .build()
+        };
+        let costs = target
+            .metadata()
+            .get_costs(&[
+                params("GLBX.MDP3"),
+                params("XNAS.ITCH"),
+                params("GLBX.MDP3"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(
+            costs,
+            QueryBreakdown {
+                per_query: vec![2.25, 1.5, 2.25],
+                total: 6.0
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capabilities() {
+        const DATASET: &str = "XNAS.ITCH";
+        let mock_server = MockServer::start().await;
+        for (slug, body) in [
+            ("list_schemas", json!(["mbo", "trades"])),
+            (
+                "get_dataset_range",
+                json!({
+                    "start": "2018-05-01T00:00:00.000000000Z",
+                    "end": "2023-07-20T00:00:00.000000000Z",
+                }),
+            ),
+            (
+                "get_dataset_condition",
+                json!([
+                    {"date": "2023-07-18", "condition": "available", "last_modified_date": "2023-07-19"},
+                    {"date": "2023-07-19", "condition": "degraded", "last_modified_date": "2023-07-20"},
+                    {"date": "2023-07-20", "condition": "available", "last_modified_date": "2023-07-21"},
+                ]),
+            ),
+            (
+                "list_unit_prices",
+                json!([
+                    {"mode": "historical", "unit_prices": {"trades": 1.0, "mbo": 2.0}},
+                    {"mode": "live", "unit_prices": {"trades": 3.0}},
+                ]),
+            ),
+        ] {
+            Mock::given(method("GET"))
+                .and(basic_auth(API_KEY, ""))
+                .and(path(format!("/v{API_VERSION}/metadata.{slug}")))
+                .and(query_param("dataset", DATASET))
+                .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(body))
+                // Second call is cached
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let capabilities = target.metadata().capabilities(DATASET).await.unwrap();
+        assert_eq!(capabilities.schemas, vec![Schema::Mbo, Schema::Trades]);
+        assert_eq!(
+            capabilities.range.start,
+            datetime!(2018 - 05 - 01 00:00 UTC)
+        );
+        assert_eq!(
+            capabilities.conditions,
+            HashMap::from([
+                (DatasetCondition::Available, 2),
+                (DatasetCondition::Degraded, 1)
+            ])
+        );
+        assert!(!capabilities.is_fully_available());
+        assert!(capabilities.supports(Schema::Mbo, FeedMode::Historical));
+        assert!(!capabilities.supports(Schema::Mbo, FeedMode::Live));
+        assert!(!capabilities.supports(Schema::Mbo, FeedMode::HistoricalStreaming));
+        assert_eq!(
+            target.metadata().capabilities(DATASET).await.unwrap(),
+            capabilities
+        );
+    }
+
     #[tokio::test]
     async fn test_get_dataset_range() {
         const DATASET: &str = "XNAS.ITCH";
@@ -663,7 +1287,7 @@ mod tests {
             )
             .mount(&mock_server)
             .await;
-        let mut target = HistoricalClient::with_url(
+        let target = HistoricalClient::with_url(
             mock_server.uri(),
             API_KEY.to_owned(),
             HistoricalGateway::Bo1,
@@ -690,7 +1314,7 @@ mod tests {
             )
             .mount(&mock_server)
             .await;
-        let mut target = HistoricalClient::with_url(
+        let target = HistoricalClient::with_url(
             mock_server.uri(),
             API_KEY.to_owned(),
             HistoricalGateway::Bo1,
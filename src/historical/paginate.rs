@@ -0,0 +1,116 @@
+//! Requests whose results are split across several responses.
+
+use std::{collections::VecDeque, marker::PhantomData};
+
+use futures::{stream, Stream, TryStreamExt};
+use reqwest::RequestBuilder;
+use serde::de::DeserializeOwned;
+
+use super::{handle_response, Client};
+
+/// A sequence of requests to a JSON endpoint where the response to each request is a
+/// page of `T`, such as when a request is split into chunks of at most
+/// [`MAX_SYMBOLS_PER_REQUEST`](super::MAX_SYMBOLS_PER_REQUEST) symbols. Requests are
+/// sent one at a time and no further requests are sent after an error.
+pub(crate) struct Paginated<T> {
+    requests: VecDeque<RequestBuilder>,
+    client: Option<Client>,
+    _page: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Paginated<T> {
+    pub fn new(requests: impl IntoIterator<Item = RequestBuilder>) -> Self {
+        Self {
+            requests: requests.into_iter().collect(),
+            client: None,
+            _page: PhantomData,
+        }
+    }
+
+    /// Sends the requests with [`Client::send()`] on `client`, such as the client that
+    /// created them, so they're audited and retried with refreshed credentials.
+    pub fn sent_by(mut self, client: &Client) -> Self {
+        self.client = Some(client.clone());
+        self
+    }
+
+    /// Returns a stream that yields each page as it's received.
+    pub fn into_stream(self) -> impl Stream<Item = crate::Result<T>> {
+        stream::try_unfold(
+            (self.requests, self.client),
+            |(mut requests, client)| async move {
+                let Some(req) = requests.pop_front() else {
+                    return Ok(None);
+                };
+                let resp = match client.as_ref() {
+                    Some(client) => client.send(req).await?,
+                    None => req.send().await?,
+                };
+                let page = handle_response(resp).await?;
+                Ok(Some((page, (requests, client))))
+            },
+        )
+    }
+
+    /// Requests all pages and returns them in order.
+    pub async fn collect_all(self) -> crate::Result<Vec<T>> {
+        self.into_stream().try_collect().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_paginated() {
+        let mock_server = MockServer::start().await;
+        for page in 0..2 {
+            Mock::given(method("GET"))
+                .and(path("/list"))
+                .and(query_param("page", page.to_string()))
+                .respond_with(
+                    ResponseTemplate::new(StatusCode::OK.as_u16())
+                        .set_body_json(json!([page * 2, page * 2 + 1])),
+                )
+                // Once for `collect_all()` and once for `into_stream()`
+                .expect(2)
+                .mount(&mock_server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/list"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(StatusCode::BAD_REQUEST.as_u16()))
+            .mount(&mock_server)
+            .await;
+        let client = reqwest::Client::new();
+        let requests = |pages| {
+            (0..pages)
+                .map(|page| {
+                    client
+                        .get(format!("{}/list", mock_server.uri()))
+                        .query(&[("page", page)])
+                })
+                .collect::<Vec<_>>()
+        };
+        let pages = Paginated::<Vec<u32>>::new(requests(2))
+            .collect_all()
+            .await
+            .unwrap();
+        assert_eq!(pages, vec![vec![0, 1], vec![2, 3]]);
+        let mut stream = Box::pin(Paginated::<Vec<u32>>::new(requests(4)).into_stream());
+        assert_eq!(stream.next().await.unwrap().unwrap(), vec![0, 1]);
+        assert_eq!(stream.next().await.unwrap().unwrap(), vec![2, 3]);
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,227 @@
+//! Progress reporting for long-running historical requests.
+
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use dbn::{decode::AsyncDbnDecoder, Metadata, RecordRef};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, ReadBuf};
+
+/// The minimum amount of time between calls to a [`ProgressHandler`], except for the
+/// final call once the request completes.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A snapshot of the progress of a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of bytes received from the API. For compressed data, this is
+    /// the compressed size.
+    pub bytes_downloaded: u64,
+    /// The number of bytes of decoded records.
+    pub bytes_decoded: u64,
+    /// The number of records decoded.
+    pub records_decoded: u64,
+    /// The time since the request was made.
+    pub elapsed: Duration,
+}
+
+/// A callback for the progress of a request, such as for displaying a progress bar
+/// or detecting a stalled download. It's called at most once every
+/// [`PROGRESS_INTERVAL`] while data is being received and once more when the request
+/// completes.
+///
+/// Closures taking a `&Progress` implement this trait.
+pub trait ProgressHandler: Send + Sync {
+    /// Called with the current progress of the request.
+    fn on_progress(&self, progress: &Progress);
+}
+
+impl<F> ProgressHandler for F
+where
+    F: Fn(&Progress) + Send + Sync,
+{
+    fn on_progress(&self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// Tracks the progress of a request and periodically reports it to a
+/// [`ProgressHandler`].
+pub(crate) struct ProgressTracker {
+    handler: Box<dyn ProgressHandler>,
+    bytes_downloaded: Arc<AtomicU64>,
+    progress: Progress,
+    start: Instant,
+    last_report: Instant,
+}
+
+impl ProgressTracker {
+    pub fn new(handler: impl ProgressHandler + 'static) -> Self {
+        let now = Instant::now();
+        Self {
+            handler: Box::new(handler),
+            bytes_downloaded: Arc::default(),
+            progress: Progress::default(),
+            start: now,
+            last_report: now,
+        }
+    }
+
+    /// Returns a counter for the bytes received from the API, which can be shared with
+    /// a [`CountingReader`].
+    pub fn bytes_downloaded(&self) -> Arc<AtomicU64> {
+        self.bytes_downloaded.clone()
+    }
+
+    pub fn add_downloaded(&mut self, bytes: u64) {
+        self.bytes_downloaded.fetch_add(bytes, Ordering::Relaxed);
+        self.maybe_report();
+    }
+
+    pub fn add_decoded(&mut self, rec: &RecordRef) {
+        self.progress.bytes_decoded += rec.record_size() as u64;
+        self.progress.records_decoded += 1;
+        self.maybe_report();
+    }
+
+    /// Reports the final progress.
+    pub fn finish(&mut self) {
+        self.report(Instant::now());
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            bytes_downloaded: self.bytes_downloaded.load(Ordering::Relaxed),
+            elapsed: self.start.elapsed(),
+            ..self.progress
+        }
+    }
+
+    fn maybe_report(&mut self) {
+        let now = Instant::now();
+        if now.duration_since(self.last_report) >= PROGRESS_INTERVAL {
+            self.report(now);
+        }
+    }
+
+    fn report(&mut self, now: Instant) {
+        self.last_report = now;
+        self.handler.on_progress(&self.progress());
+    }
+}
+
+impl std::fmt::Debug for ProgressTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressTracker")
+            .field("progress", &self.progress())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A reader that counts the bytes read from the inner reader.
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R> CountingReader<R> {
+    pub fn new(inner: R, count: Arc<AtomicU64>) -> Self {
+        Self { inner, count }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CountingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+        self.count
+            .fetch_add((buf.filled().len() - filled) as u64, Ordering::Relaxed);
+        res
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for CountingReader<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().inner).poll_fill_buf(cx)
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        self.count.fetch_add(amt as u64, Ordering::Relaxed);
+        Pin::new(&mut self.inner).consume(amt);
+    }
+}
+
+/// A decoder that reports its progress to a [`ProgressHandler`]. Returned by
+/// [`TimeseriesClient::get_range_with_progress()`](super::timeseries::TimeseriesClient::get_range_with_progress).
+#[derive(Debug)]
+pub struct ProgressDecoder<R> {
+    decoder: AsyncDbnDecoder<R>,
+    tracker: ProgressTracker,
+    is_finished: bool,
+}
+
+impl<R> ProgressDecoder<R>
+where
+    R: AsyncReadExt + Unpin,
+{
+    pub(crate) fn new(decoder: AsyncDbnDecoder<R>, tracker: ProgressTracker) -> Self {
+        Self {
+            decoder,
+            tracker,
+            is_finished: false,
+        }
+    }
+
+    /// Returns the DBN metadata of the inner decoder.
+    pub fn metadata(&self) -> &Metadata {
+        self.decoder.metadata()
+    }
+
+    /// Returns the current progress.
+    pub fn progress(&self) -> Progress {
+        self.tracker.progress()
+    }
+
+    /// Decodes the next record and updates the progress.
+    ///
+    /// Returns `Ok(None)` once all records have been decoded, after reporting the
+    /// final progress.
+    ///
+    /// # Errors
+    /// This function returns an error if it's unable to decode the next record.
+    ///
+    /// # Cancel safety
+    /// This method is cancel safe. It can be used within a [`tokio::select!`] statement
+    /// without the potential for corrupting the input stream.
+    pub async fn decode_record_ref(&mut self) -> crate::Result<Option<RecordRef>> {
+        match self.decoder.decode_record_ref().await? {
+            Some(rec) => {
+                self.tracker.add_decoded(&rec);
+                Ok(Some(rec))
+            }
+            None => {
+                if !self.is_finished {
+                    self.is_finished = true;
+                    self.tracker.finish();
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Consumes the progress decoder and returns the inner decoder.
+    pub fn into_inner(self) -> AsyncDbnDecoder<R> {
+        self.decoder
+    }
+}
@@ -3,18 +3,19 @@
 use std::{collections::HashMap, sync::Arc};
 
 use dbn::{MappingInterval, SType, TsSymbolMap};
+use futures::{future, stream, Stream, StreamExt};
 use reqwest::RequestBuilder;
 use serde::Deserialize;
 use typed_builder::TypedBuilder;
 
 use crate::Symbols;
 
-use super::{handle_response, DateRange};
+use super::{paginate::Paginated, DateRange, MAX_SYMBOLS_PER_REQUEST};
 
 /// A client for the symbology group of Historical API endpoints.
 #[derive(Debug)]
 pub struct SymbologyClient<'a> {
-    pub(crate) inner: &'a mut super::Client,
+    pub(crate) inner: &'a super::Client,
 }
 
 impl SymbologyClient<'_> {
@@ -22,37 +23,139 @@ impl SymbologyClient<'_> {
     ///
     /// For example, resolves a raw symbol to an instrument ID: `ESM2` → `3403`.
     ///
+    /// Requests with more than [`MAX_SYMBOLS_PER_REQUEST`] symbols or
+    /// [`Smart`](crate::Symbols::Smart) symbols with several `SType`s are split into
+    /// several requests whose resolutions are merged. To process the resolutions of
+    /// large requests incrementally, use [`resolve_pages()`](Self::resolve_pages).
+    ///
     /// # Errors
     /// This function returns an error when it fails to communicate with the Databento API
     /// or the API indicates there's an issue with the request.
-    pub async fn resolve(&mut self, params: &ResolveParams) -> crate::Result<Resolution> {
-        let mut form = vec![
-            ("dataset", params.dataset.to_string()),
-            ("stype_in", params.stype_in.to_string()),
-            ("stype_out", params.stype_out.to_string()),
-            ("symbols", params.symbols.to_api_string()),
-        ];
-        params.date_range.add_to_form(&mut form);
-        let resp = self.post("resolve")?.form(&form).send().await?;
-        let ResolutionResp {
-            mappings,
-            partial,
-            not_found,
-        } = handle_response(resp).await?;
-        Ok(Resolution {
-            mappings,
-            partial,
-            not_found,
+    pub async fn resolve(&self, params: &ResolveParams) -> crate::Result<Resolution> {
+        let mut resolution = Resolution {
+            mappings: HashMap::new(),
+            partial: Vec::new(),
+            not_found: Vec::new(),
             stype_in: params.stype_in,
             stype_out: params.stype_out,
-        })
+        };
+        let (_, requests) = resolve_requests(self.inner, params, MAX_SYMBOLS_PER_REQUEST)?;
+        for page in Paginated::<ResolutionResp>::new(requests)
+            .sent_by(self.inner)
+            .collect_all()
+            .await?
+        {
+            resolution.mappings.extend(page.mappings);
+            resolution.partial.extend(page.partial);
+            resolution.not_found.extend(page.not_found);
+        }
+        Ok(resolution)
     }
 
-    fn post(&mut self, slug: &str) -> crate::Result<RequestBuilder> {
-        self.inner.post(&format!("symbology.{slug}"))
+    /// Resolves a list of symbols like [`resolve()`](Self::resolve), but makes one
+    /// request per page of at most `page_size` symbols and yields the [`Resolution`] of
+    /// each page as it's received instead of merging them. `page_size` is clamped to
+    /// be between 1 and [`MAX_SYMBOLS_PER_REQUEST`].
+    ///
+    /// # Errors
+    /// The stream yields an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request. No further pages are
+    /// requested after an error.
+    pub fn resolve_pages<'s>(
+        &'s mut self,
+        params: &'s ResolveParams,
+        page_size: usize,
+    ) -> impl Stream<Item = crate::Result<Resolution>> + 's {
+        let page_size = page_size.clamp(1, MAX_SYMBOLS_PER_REQUEST);
+        let (stypes_in, requests) = match resolve_requests(self.inner, params, page_size) {
+            Ok(requests) => requests,
+            Err(e) => return stream::once(future::ready(Err(e))).left_stream(),
+        };
+        stream::iter(stypes_in)
+            .zip(
+                Paginated::<ResolutionResp>::new(requests)
+                    .sent_by(self.inner)
+                    .into_stream(),
+            )
+            .map(|(stype_in, page)| -> crate::Result<Resolution> {
+                let ResolutionResp {
+                    mappings,
+                    partial,
+                    not_found,
+                } = page?;
+                Ok(Resolution {
+                    mappings,
+                    partial,
+                    not_found,
+                    stype_in,
+                    stype_out: params.stype_out,
+                })
+            })
+            .right_stream()
+    }
+
+    /// Resolves `instrument_ids` to the raw symbols they were assigned to over
+    /// `date_range`, keyed by instrument ID. Instrument IDs that weren't found are
+    /// omitted.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request.
+    pub async fn reverse_resolve(
+        &self,
+        dataset: &str,
+        instrument_ids: Vec<u32>,
+        date_range: impl Into<DateRange>,
+    ) -> crate::Result<HashMap<u32, Vec<MappingInterval>>> {
+        let resolution = self
+            .resolve(
+                &ResolveParams::builder()
+                    .dataset(dataset)
+                    .symbols(instrument_ids)
+                    .stype_in(SType::InstrumentId)
+                    .stype_out(SType::RawSymbol)
+                    .date_range(date_range)
+                    .build(),
+            )
+            .await?;
+        resolution
+            .mappings
+            .into_iter()
+            .map(|(iid, intervals)| {
+                let iid = iid.parse().map_err(|_| {
+                    crate::Error::internal(format!("Unable to parse '{iid}' to an instrument ID"))
+                })?;
+                Ok((iid, intervals))
+            })
+            .collect()
     }
 }
 
+/// Creates one request per `SType` and page of at most `page_size` symbols, returning
+/// the input `SType` of each request alongside the requests.
+fn resolve_requests(
+    client: &mut super::Client,
+    params: &ResolveParams,
+    page_size: usize,
+) -> crate::Result<(Vec<SType>, Vec<RequestBuilder>)> {
+    let mut stypes_in = Vec::new();
+    let mut requests = Vec::new();
+    for (stype_in, symbols) in params.symbols.group_by_stype(params.stype_in) {
+        for symbols in symbols.chunks(page_size) {
+            let mut form = vec![
+                ("dataset", params.dataset.to_string()),
+                ("stype_in", stype_in.to_string()),
+                ("stype_out", params.stype_out.to_string()),
+                ("symbols", symbols.to_api_string()),
+            ];
+            params.date_range.add_to_form(&mut form);
+            stypes_in.push(stype_in);
+            requests.push(client.post("symbology.resolve")?.form(&form));
+        }
+    }
+    Ok((stypes_in, requests))
+}
+
 /// The parameters for [`SymbologyClient::resolve()`]. Use [`ResolveParams::builder()`]
 /// to get a builder type with all the preset defaults.
 #[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
@@ -136,6 +239,63 @@ impl Resolution {
         }
         Ok(map)
     }
+
+    /// Returns the mappings where either the input or output symbol matches the glob
+    /// `pattern`, sorted by input symbol and start date. In the pattern, `*` matches any
+    /// sequence of characters and `?` matches any single character. Matching is case
+    /// sensitive.
+    ///
+    /// For example, `ES*` matches all input symbols starting with `ES`, such as the
+    /// resolution of a parent symbol to its children.
+    pub fn search(&self, pattern: &str) -> Vec<(&str, &MappingInterval)> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let pattern = pattern.as_slice();
+        let mut matches: Vec<_> = self
+            .mappings
+            .iter()
+            .flat_map(|(symbol, intervals)| {
+                let is_symbol_match = glob_match(pattern, symbol);
+                intervals
+                    .iter()
+                    .filter(move |interval| {
+                        is_symbol_match || glob_match(pattern, &interval.symbol)
+                    })
+                    .map(move |interval| (symbol.as_str(), interval))
+            })
+            .collect();
+        matches.sort_by_key(|(symbol, interval)| (*symbol, interval.start_date));
+        matches
+    }
+}
+
+/// Returns `true` if `text` matches the glob `pattern` with `*` and `?` wildcards.
+fn glob_match(pattern: &[char], text: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` and the text position it was tried at
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => {
+                let Some((star_p, star_t)) = backtrack else {
+                    return false;
+                };
+                // Let the `*` consume one more character
+                backtrack = Some((star_p, star_t + 1));
+                p = star_p + 1;
+                t = star_t + 1;
+            }
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -148,6 +308,7 @@ struct ResolutionResp {
 
 #[cfg(test)]
 mod tests {
+    use futures::TryStreamExt;
     use reqwest::StatusCode;
     use serde_json::json;
     use time::macros::date;
@@ -200,7 +361,7 @@ mod tests {
             )
             .mount(&mock_server)
             .await;
-        let mut target = HistoricalClient::with_url(
+        let target = HistoricalClient::with_url(
             mock_server.uri(),
             API_KEY.to_owned(),
             HistoricalGateway::Bo1,
@@ -236,4 +397,133 @@ mod tests {
         assert!(res.partial.is_empty());
         assert_eq!(res.not_found, vec!["ES.d.0"]);
     }
+
+    #[tokio::test]
+    async fn test_reverse_resolve() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/symbology.resolve")))
+            .and(body_contains("symbols", "10245%2C10248"))
+            .and(body_contains("stype_in", "instrument_id"))
+            .and(body_contains("stype_out", "raw_symbol"))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!({
+                    "result": {
+                        "10245": [{"d0": "2023-06-14", "d1": "2023-06-15", "s": "ESM3"}]
+                    },
+                    "partial": [],
+                    "not_found": ["10248"]
+                })),
+            )
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let res = target
+            .symbology()
+            .reverse_resolve(
+                "GLBX.MDP3",
+                vec![10245, 10248],
+                (date!(2023 - 06 - 14), date!(2023 - 06 - 15)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[&10245][0].symbol, "ESM3");
+    }
+
+    #[test]
+    fn test_search() {
+        let interval = |symbol: &str| MappingInterval {
+            start_date: date!(2023 - 06 - 14),
+            end_date: date!(2023 - 06 - 15),
+            symbol: symbol.to_owned(),
+        };
+        let target = Resolution {
+            mappings: HashMap::from([
+                ("ESM3".to_owned(), vec![interval("1")]),
+                ("ESU3".to_owned(), vec![interval("2")]),
+                ("NQM3".to_owned(), vec![interval("3")]),
+            ]),
+            partial: Vec::new(),
+            not_found: Vec::new(),
+            stype_in: SType::RawSymbol,
+            stype_out: SType::InstrumentId,
+        };
+        let symbols = |pattern| {
+            target
+                .search(pattern)
+                .into_iter()
+                .map(|(symbol, _)| symbol)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(symbols("ES*"), vec!["ESM3", "ESU3"]);
+        assert_eq!(symbols("??M3"), vec!["ESM3", "NQM3"]);
+        assert_eq!(symbols("*M*3"), vec!["ESM3", "NQM3"]);
+        assert_eq!(symbols("3"), vec!["NQM3"]);
+        assert!(symbols("ES").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_pages() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/symbology.resolve")))
+            .and(body_contains("symbols", "ESM3%2CNQM3"))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!({
+                    "result": {
+                        "ESM3": [{"d0": "2023-06-14", "d1": "2023-06-15", "s": "1"}],
+                        "NQM3": [{"d0": "2023-06-14", "d1": "2023-06-15", "s": "2"}]
+                    },
+                    "partial": [],
+                    "not_found": []
+                })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/symbology.resolve")))
+            .and(body_contains("symbols", "CLN3"))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_json(json!({
+                    "result": {},
+                    "partial": [],
+                    "not_found": ["CLN3"]
+                })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let params = ResolveParams::builder()
+            .dataset(dbn::Dataset::GlbxMdp3)
+            .symbols(vec!["ESM3", "NQM3", "CLN3"])
+            .date_range((date!(2023 - 06 - 14), date!(2023 - 06 - 15)))
+            .build();
+        let symbology = target.symbology();
+        let pages: Vec<Resolution> = symbology
+            .resolve_pages(&params, 2)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].mappings.len(), 2);
+        assert!(pages[0].not_found.is_empty());
+        assert!(pages[1].mappings.is_empty());
+        assert_eq!(pages[1].not_found, vec!["CLN3"]);
+    }
 }
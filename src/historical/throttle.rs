@@ -0,0 +1,124 @@
+//! An adapter for replaying historical data at the pace it was originally published.
+
+use dbn::{decode::AsyncDbnDecoder, Metadata, RecordRef, UNDEF_TIMESTAMP};
+use tokio::{
+    io::AsyncReadExt,
+    time::{sleep_until, Duration, Instant},
+};
+
+use crate::Error;
+
+/// Wraps an [`AsyncDbnDecoder`], such as one returned from
+/// [`TimeseriesClient::get_range()`](super::timeseries::TimeseriesClient::get_range),
+/// and delays each record based on the difference between its `ts_event` and that of
+/// the first record, scaled by a speed multiplier.
+///
+/// Timing is based on [`tokio::time`], so it respects [`tokio::time::pause()`] in tests.
+pub struct ThrottledDecoder<R> {
+    decoder: AsyncDbnDecoder<R>,
+    speed: f64,
+    start: Option<(u64, Instant)>,
+}
+
+impl<R> ThrottledDecoder<R>
+where
+    R: AsyncReadExt + Unpin,
+{
+    /// Creates a new throttled decoder that replays the records from `decoder` at
+    /// `speed` times the original pace, e.g. `2.0` replays twice as fast.
+    ///
+    /// # Errors
+    /// This function returns an error if `speed` isn't a positive, finite number.
+    pub fn new(decoder: AsyncDbnDecoder<R>, speed: f64) -> crate::Result<Self> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err(Error::bad_arg("speed", "must be a positive, finite number"));
+        }
+        Ok(Self {
+            decoder,
+            speed,
+            start: None,
+        })
+    }
+
+    /// Returns the replay speed multiplier.
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Returns the DBN metadata of the inner decoder.
+    pub fn metadata(&self) -> &Metadata {
+        self.decoder.metadata()
+    }
+
+    /// Decodes the next record, waiting until its time in the replay. Records with an
+    /// undefined or out-of-order `ts_event` are returned without waiting.
+    ///
+    /// Returns `Ok(None)` once all records have been decoded.
+    ///
+    /// # Errors
+    /// This function returns an error if it's unable to decode the next record.
+    ///
+    /// # Cancel safety
+    /// This method is not cancellation safe. If this method is used in a
+    /// [`tokio::select!`] statement and another branch completes first, the record
+    /// being waited on will be lost.
+    pub async fn decode_record_ref(&mut self) -> crate::Result<Option<RecordRef>> {
+        let Some(rec) = self.decoder.decode_record_ref().await? else {
+            return Ok(None);
+        };
+        let ts_event = rec.header().ts_event;
+        if ts_event != UNDEF_TIMESTAMP {
+            let (first_ts_event, start) =
+                *self.start.get_or_insert_with(|| (ts_event, Instant::now()));
+            let delay = ts_event.saturating_sub(first_ts_event) as f64 / self.speed;
+            sleep_until(start + Duration::from_nanos(delay as u64)).await;
+        }
+        Ok(Some(rec))
+    }
+
+    /// Consumes the throttled decoder and returns the inner decoder.
+    pub fn into_inner(self) -> AsyncDbnDecoder<R> {
+        self.decoder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{Schema, TradeMsg};
+
+    use super::*;
+    use crate::zst_test_data_path;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttled_decoder() {
+        let decoder = AsyncDbnDecoder::from_zstd_file(zst_test_data_path(Schema::Trades))
+            .await
+            .unwrap();
+        assert!(ThrottledDecoder::new(decoder, 0.0).is_err());
+        let decoder = AsyncDbnDecoder::from_zstd_file(zst_test_data_path(Schema::Trades))
+            .await
+            .unwrap();
+        let mut target = ThrottledDecoder::new(decoder, 2.0).unwrap();
+        let first = target
+            .decode_record_ref()
+            .await
+            .unwrap()
+            .unwrap()
+            .get::<TradeMsg>()
+            .unwrap()
+            .hd
+            .ts_event;
+        let start = Instant::now();
+        let second = target
+            .decode_record_ref()
+            .await
+            .unwrap()
+            .unwrap()
+            .get::<TradeMsg>()
+            .unwrap()
+            .hd
+            .ts_event;
+        assert!(start.elapsed() >= Duration::from_nanos((second - first) / 2));
+        assert!(target.decode_record_ref().await.unwrap().is_none());
+    }
+}
@@ -1,20 +1,55 @@
 //! The historical timeseries API.
 
-use std::{num::NonZeroU64, path::PathBuf};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    mem,
+    num::{NonZeroU64, NonZeroUsize},
+    path::PathBuf,
+    sync::Arc,
+};
 
-use dbn::{encode::AsyncDbnEncoder, Compression, Encoding, SType, Schema, VersionUpgradePolicy};
+use dbn::{
+    decode::{DbnDecoder, DbnMetadata, DecodeRecordRef, MergeDecoder},
+    encode::AsyncDbnEncoder,
+    record::HasRType,
+    Compression, Encoding, InstrumentDefMsg, Metadata, RecordEnum, RecordRefEnum, SType, Schema,
+    VersionUpgradePolicy,
+};
 use futures::{Stream, TryStreamExt};
-use reqwest::{header::ACCEPT, RequestBuilder};
+use reqwest::{header::ACCEPT, RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
 use tokio::{
     fs::File,
-    io::{AsyncReadExt, AsyncWriteExt, BufWriter},
+    io::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWriteExt, BufWriter},
+    sync::{mpsc, oneshot},
 };
-use tokio_util::{bytes::Bytes, io::StreamReader};
+use tokio_util::{
+    bytes::Bytes,
+    either::Either,
+    io::{StreamReader, SyncIoBridge},
+};
+use tracing::info;
 use typed_builder::TypedBuilder;
 
-use crate::Symbols;
+use crate::{
+    dedup::Deduplicator,
+    error::{ApiError, RequestContext},
+    filter::InstrumentFilter,
+    Error, Symbols,
+};
+
+use super::{
+    check_http_error,
+    metadata::GetQueryParams,
+    progress::{CountingReader, ProgressDecoder, ProgressHandler, ProgressTracker},
+    zstd, CancellationToken, DateTimeRange, MAX_SYMBOLS_PER_REQUEST,
+};
 
-use super::{check_http_error, DateTimeRange};
+/// The number of unique records remembered when removing duplicates from the merged
+/// results of several requests.
+const DEDUP_WINDOW: usize = 10_000;
+/// The shortest time range of a request split with [`OversizedRequestPolicy::Split`].
+const MIN_SPLIT_DURATION: time::Duration = time::Duration::MINUTE;
 
 // Re-export because it's returned.
 pub use dbn::decode::AsyncDbnDecoder;
@@ -22,36 +57,75 @@ pub use dbn::decode::AsyncDbnDecoder;
 /// A client for the timeseries group of Historical API endpoints.
 #[derive(Debug)]
 pub struct TimeseriesClient<'a> {
-    pub(crate) inner: &'a mut super::Client,
+    pub(crate) inner: &'a super::Client,
 }
 
 impl TimeseriesClient<'_> {
     /// Makes a streaming request for timeseries data from Databento.
     ///
     /// This method returns a stream decoder. For larger requests, consider using
-    /// [`BatchClient::submit_job()`](super::batch::BatchClient::submit_job()).
+    /// [`BatchClient::submit_job()`](super::batch::BatchClient::submit_job()). Dropping
+    /// the decoder cancels the request.
+    ///
+    /// Requests can have at most [`MAX_SYMBOLS_PER_REQUEST`] symbols. Use
+    /// [`get_range_buffered()`](Self::get_range_buffered) to split larger requests into
+    /// several requests whose records are merged.
     ///
     /// # Errors
-    /// This function returns an error when it fails to communicate with the Databento API
-    /// or the API indicates there's an issue with the request.
+    /// This function returns an error when `symbols` has more than
+    /// [`MAX_SYMBOLS_PER_REQUEST`] symbols, it fails to communicate with the Databento
+    /// API, or the API indicates there's an issue with the request.
     pub async fn get_range(
-        &mut self,
+        &self,
         params: &GetRangeParams,
     ) -> crate::Result<AsyncDbnDecoder<impl AsyncReadExt>> {
+        let (stype_in, symbols) = params.symbols.single_stype_group(params.stype_in)?;
         let reader = self
             .get_range_impl(
                 &params.dataset,
                 params.schema,
-                params.stype_in,
+                stype_in,
                 params.stype_out,
-                &params.symbols,
+                &symbols,
                 &params.date_time_range,
                 params.limit,
+                params.compression,
+                None,
             )
             .await?;
-        let mut decoder: AsyncDbnDecoder<_> = AsyncDbnDecoder::with_zstd_buffer(reader).await?;
-        decoder.set_upgrade_policy(params.upgrade_policy);
-        Ok(decoder)
+        decode_response(reader, params.compression, params.upgrade_policy).await
+    }
+
+    /// Makes a streaming request for timeseries data from Databento like
+    /// [`get_range()`](Self::get_range), but periodically reports the number of bytes
+    /// downloaded and records decoded to `progress_handler`.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request.
+    pub async fn get_range_with_progress(
+        &self,
+        params: &GetRangeParams,
+        progress_handler: impl ProgressHandler + 'static,
+    ) -> crate::Result<ProgressDecoder<impl AsyncReadExt>> {
+        let tracker = ProgressTracker::new(progress_handler);
+        let (stype_in, symbols) = params.symbols.single_stype_group(params.stype_in)?;
+        let reader = self
+            .get_range_impl(
+                &params.dataset,
+                params.schema,
+                stype_in,
+                params.stype_out,
+                &symbols,
+                &params.date_time_range,
+                params.limit,
+                params.compression,
+                None,
+            )
+            .await?;
+        let reader = CountingReader::new(reader, tracker.bytes_downloaded());
+        let decoder = decode_response(reader, params.compression, params.upgrade_policy).await?;
+        Ok(ProgressDecoder::new(decoder, tracker))
     }
 
     /// Makes a streaming request for timeseries data from Databento.
@@ -64,34 +138,368 @@ impl TimeseriesClient<'_> {
     /// or the API indicates there's an issue with the request. An error will also be returned
     /// if it fails to create a new file at `path`.
     pub async fn get_range_to_file(
-        &mut self,
+        &self,
         params: &GetRangeToFileParams,
     ) -> crate::Result<AsyncDbnDecoder<impl AsyncReadExt>> {
+        self.get_range_to_file_with_cancellation(params, &CancellationToken::new())
+            .await
+    }
+
+    /// Makes a streaming request for timeseries data from Databento and writes it to
+    /// the file at `path`, stopping early if `cancellation_token` is cancelled. If the
+    /// request is cancelled or fails, the partially-written file is removed.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request. An error will also be returned
+    /// if it fails to create a new file at `path`. If the request is cancelled, it returns
+    /// [`Error::Cancelled`].
+    pub async fn get_range_to_file_with_cancellation(
+        &self,
+        params: &GetRangeToFileParams,
+        cancellation_token: &CancellationToken,
+    ) -> crate::Result<AsyncDbnDecoder<impl AsyncReadExt>> {
+        let (stype_in, symbols) = params.symbols.single_stype_group(params.stype_in)?;
         let reader = self
             .get_range_impl(
                 &params.dataset,
                 params.schema,
-                params.stype_in,
+                stype_in,
                 params.stype_out,
-                &params.symbols,
+                &symbols,
                 &params.date_time_range,
                 params.limit,
+                params.compression,
+                None,
             )
             .await?;
-        let mut http_decoder = AsyncDbnDecoder::with_zstd_buffer(reader).await?;
-        http_decoder.set_upgrade_policy(params.upgrade_policy);
+        let mut http_decoder =
+            decode_response(reader, params.compression, params.upgrade_policy).await?;
         let file = BufWriter::new(File::create(&params.path).await?);
         let mut encoder = AsyncDbnEncoder::with_zstd(file, http_decoder.metadata()).await?;
-        while let Some(rec_ref) = http_decoder.decode_record_ref().await? {
-            encoder.encode_record_ref(rec_ref).await?;
+        let res: crate::Result<()> = async {
+            loop {
+                let rec_ref = tokio::select! {
+                    biased;
+                    _ = cancellation_token.cancelled() => return Err(Error::Cancelled),
+                    rec_ref = http_decoder.decode_record_ref() => rec_ref?,
+                };
+                let Some(rec_ref) = rec_ref else {
+                    break;
+                };
+                encoder.encode_record_ref(rec_ref).await?;
+            }
+            encoder.get_mut().shutdown().await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = res {
+            drop(encoder);
+            // Don't leave a partially-written file
+            let _ = tokio::fs::remove_file(&params.path).await;
+            return Err(e);
         }
-        encoder.get_mut().shutdown().await?;
         Ok(AsyncDbnDecoder::from_zstd_file(&params.path).await?)
     }
 
+    /// Makes a request for timeseries data from Databento and spills the
+    /// zstd-compressed response to an anonymous temporary file, which is memory-mapped
+    /// so it can be decoded any number of times with [`CachedRange::decoder()`]
+    /// without downloading it again, such as for multi-pass backtests. The file is
+    /// removed once the returned [`CachedRange`] and all its clones are dropped.
+    ///
+    /// The response is always zstd-compressed, so
+    /// [`compression`](GetRangeParams::compression) is ignored.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request. An error will also be
+    /// returned if it fails to create, write, or map the temporary file.
+    pub async fn get_range_cached_local(
+        &self,
+        params: &GetRangeParams,
+    ) -> crate::Result<CachedRange> {
+        let (stype_in, symbols) = params.symbols.single_stype_group(params.stype_in)?;
+        let mut reader = self
+            .get_range_impl(
+                &params.dataset,
+                params.schema,
+                stype_in,
+                params.stype_out,
+                &symbols,
+                &params.date_time_range,
+                params.limit,
+                Compression::ZStd,
+                None,
+            )
+            .await?;
+        let mut file = File::from_std(tempfile::tempfile()?);
+        tokio::io::copy_buf(&mut reader, &mut file).await?;
+        file.flush().await?;
+        let file = file.into_std().await;
+        // SAFETY: the file is anonymous and this is the only handle to it, so it can't be
+        // modified or truncated while it's mapped
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(CachedRange {
+            mmap: Arc::new(mmap),
+            upgrade_policy: params.upgrade_policy,
+        })
+    }
+
+    /// Makes a streaming request for timeseries data from Databento encoded as CSV or
+    /// JSON, such as for exporting to other tools, and returns a reader of the
+    /// decompressed text. `text` controls the encoding and formatting of the response.
+    ///
+    /// # Errors
+    /// This function returns an error when the encoding in `text` isn't a text
+    /// encoding, it fails to communicate with the Databento API, or the API indicates
+    /// there's an issue with the request.
+    pub async fn get_range_text(
+        &self,
+        params: &GetRangeParams,
+        text: &TextEncodingParams,
+    ) -> crate::Result<impl AsyncRead + Unpin> {
+        text.validate()?;
+        let (stype_in, symbols) = params.symbols.single_stype_group(params.stype_in)?;
+        let reader = self
+            .get_range_impl(
+                &params.dataset,
+                params.schema,
+                stype_in,
+                params.stype_out,
+                &symbols,
+                &params.date_time_range,
+                params.limit,
+                params.compression,
+                Some(text),
+            )
+            .await?;
+        Ok(decompress(reader, params.compression))
+    }
+
+    /// Requests the instrument definitions for the symbols in `params` and returns the
+    /// latest definition of each instrument keyed by instrument ID. A new definition is
+    /// published whenever an instrument is modified, so when there are several for an
+    /// instrument, only the one with the most recent `ts_recv` is kept.
+    ///
+    /// # Errors
+    /// This function returns an error when it fails to communicate with the Databento API
+    /// or the API indicates there's an issue with the request.
+    pub async fn get_definitions(
+        &self,
+        params: &GetDefinitionsParams,
+    ) -> crate::Result<HashMap<u32, InstrumentDefMsg>> {
+        const CHANNEL_CAPACITY: usize = 1024;
+
+        let mut decoder = self
+            .get_range_buffered(
+                &GetRangeParams::builder()
+                    .dataset(&params.dataset)
+                    .symbols(params.symbols.clone())
+                    .schema(Schema::Definition)
+                    .date_time_range(params.date_time_range.clone())
+                    .stype_in(params.stype_in)
+                    .build(),
+                CHANNEL_CAPACITY,
+            )
+            .await?;
+        let mut definitions = HashMap::new();
+        while let Some(rec) = decoder.decode_record().await? {
+            let RecordEnum::InstrumentDef(definition) = rec else {
+                continue;
+            };
+            match definitions.entry(definition.hd.instrument_id) {
+                Entry::Occupied(mut entry) => {
+                    if definition.ts_recv >= entry.get().ts_recv {
+                        entry.insert(definition);
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(definition);
+                }
+            }
+        }
+        Ok(definitions)
+    }
+
+    /// Makes a streaming request for timeseries data like [`get_range()`](Self::get_range)
+    /// and collects the records into a vector, such as for looking up a day of OHLCV
+    /// bars. The records must be of type `T`, which should match the schema of `params`.
+    ///
+    /// To avoid unexpectedly buffering a large response, the response may contain at
+    /// most `limit` records. The `limit` of the request is lowered to one record more
+    /// than that so the rest of an oversized response isn't downloaded.
+    ///
+    /// # Errors
+    /// This function returns [`Error::LimitExceeded`] when the response is larger than
+    /// `limit`. It also returns an error when it fails to communicate with the
+    /// Databento API, the API indicates there's an issue with the request, or the
+    /// records aren't of type `T`.
+    pub async fn get_range_collect<T>(
+        &self,
+        params: &GetRangeParams,
+        limit: CollectLimit,
+    ) -> crate::Result<Vec<T>>
+    where
+        T: HasRType + Clone,
+    {
+        let max_records = limit.max_records::<T>();
+        let request_limit = NonZeroU64::MIN.saturating_add(max_records as u64);
+        let params = GetRangeParams {
+            limit: Some(params.limit.map_or(request_limit, |l| l.min(request_limit))),
+            ..params.clone()
+        };
+        let mut decoder = self.get_range(&params).await?;
+        let mut records = Vec::new();
+        while let Some(rec) = decoder.decode_record::<T>().await? {
+            if records.len() == max_records {
+                return Err(Error::LimitExceeded(match limit {
+                    CollectLimit::Records(max) => {
+                        format!("response contains more than {max} records")
+                    }
+                    CollectLimit::Bytes(max) => format!("response is larger than {max} bytes"),
+                }));
+            }
+            records.push(rec.clone());
+        }
+        Ok(records)
+    }
+
+    /// Makes a streaming request for timeseries data from Databento, decompressing and
+    /// decoding the response on a separate blocking thread.
+    ///
+    /// Decoded records are handed to the returned [`BufferedDecoder`] through a bounded
+    /// channel with room for `channel_capacity` records. For large responses this can
+    /// improve throughput on multi-core machines compared to [`get_range()`](Self::get_range),
+    /// at the cost of copying each record.
+    ///
+    /// Requests with more than [`MAX_SYMBOLS_PER_REQUEST`] symbols or
+    /// [`Smart`](Symbols::Smart) symbols with several `SType`s are split into several
+    /// requests whose records are merged in timestamp order, with `limit` applied to
+    /// the merged records. Records returned by more than one of the requests are only
+    /// included once.
+    ///
+    /// Requests the API rejects as too large to stream are handled according to the
+    /// client's [`OversizedRequestPolicy`].
+    ///
+    /// # Errors
+    /// This function returns an error when `channel_capacity` is 0, it fails to
+    /// communicate with the Databento API, the API indicates there's an issue with the
+    /// request, or it fails to decode the DBN metadata.
+    pub async fn get_range_buffered(
+        &self,
+        params: &GetRangeParams,
+        channel_capacity: usize,
+    ) -> crate::Result<BufferedDecoder> {
+        if channel_capacity == 0 {
+            return Err(Error::bad_arg("channel_capacity", "must be greater than 0"));
+        }
+        let mut readers = Vec::new();
+        for (stype_in, symbols) in params.symbols.group_by_stype(params.stype_in) {
+            for symbols in symbols.chunks(MAX_SYMBOLS_PER_REQUEST) {
+                let mut date_time_ranges = vec![params.date_time_range.clone()];
+                while let Some(date_time_range) = date_time_ranges.pop() {
+                    let res = self
+                        .get_range_impl(
+                            &params.dataset,
+                            params.schema,
+                            stype_in,
+                            params.stype_out,
+                            &symbols,
+                            &date_time_range,
+                            params.limit,
+                            // Decompressed on the blocking thread
+                            Compression::ZStd,
+                            None,
+                        )
+                        .await;
+                    match res {
+                        // Must be created from within the runtime
+                        Ok(reader) => readers.push(SyncIoBridge::new(reader)),
+                        Err(Error::Api(err)) if is_oversized(&err) => {
+                            let [first, second] = self.split_oversized(&date_time_range, err)?;
+                            date_time_ranges.push(second);
+                            date_time_ranges.push(first);
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+        // Overlapping requests, such as from smart symbols with a parent and one of its
+        // children, can return the same records
+        let mut deduplicator = (readers.len() > 1)
+            .then(|| Deduplicator::new(NonZeroUsize::new(DEDUP_WINDOW).unwrap()));
+        let upgrade_policy = params.upgrade_policy;
+        let instrument_filter = params.instrument_filter.clone();
+        let limit = params.limit.map_or(u64::MAX, NonZeroU64::get);
+        let (metadata_tx, metadata_rx) = oneshot::channel();
+        let (record_tx, record_rx) = mpsc::channel(channel_capacity);
+        tokio::task::spawn_blocking(move || {
+            let decoders = readers
+                .into_iter()
+                .map(|reader| -> dbn::Result<_> {
+                    #[cfg(not(feature = "ruzstd"))]
+                    let mut decoder = DbnDecoder::with_zstd_buffer(reader)?;
+                    #[cfg(feature = "ruzstd")]
+                    let mut decoder = DbnDecoder::new(zstd::sync_decoder(reader))?;
+                    decoder.set_upgrade_policy(upgrade_policy);
+                    Ok(decoder)
+                })
+                .collect::<dbn::Result<Vec<_>>>();
+            let mut decoder = match decoders.and_then(MergeDecoder::new) {
+                Ok(decoder) => decoder,
+                Err(e) => {
+                    let _ = metadata_tx.send(Err(Error::from(e)));
+                    return;
+                }
+            };
+            if metadata_tx.send(Ok(decoder.metadata().clone())).is_err() {
+                return;
+            }
+            let mut count = 0;
+            while count < limit {
+                let res = match decoder.decode_record_ref() {
+                    Ok(Some(rec_ref))
+                        if instrument_filter
+                            .as_ref()
+                            .is_some_and(|filter| !filter.matches(&rec_ref)) =>
+                    {
+                        continue;
+                    }
+                    Ok(Some(rec_ref))
+                        if deduplicator
+                            .as_mut()
+                            .is_some_and(|dedup| dedup.is_duplicate(&rec_ref)) =>
+                    {
+                        continue;
+                    }
+                    Ok(Some(rec_ref)) => RecordRefEnum::try_from(rec_ref)
+                        .map(|rec| rec.to_owned())
+                        .map_err(Error::from),
+                    Ok(None) => break,
+                    Err(e) => Err(Error::from(e)),
+                };
+                count += 1;
+                let is_err = res.is_err();
+                // Receiver was dropped or hit a fatal error
+                if record_tx.blocking_send(res).is_err() || is_err {
+                    break;
+                }
+            }
+        });
+        let metadata = metadata_rx
+            .await
+            .map_err(|_| Error::internal("decoding thread exited before sending metadata"))??;
+        Ok(BufferedDecoder {
+            metadata,
+            receiver: record_rx,
+        })
+    }
+
     #[allow(clippy::too_many_arguments)] // private method
     async fn get_range_impl(
-        &mut self,
+        &self,
         dataset: &str,
         schema: Schema,
         stype_in: SType,
@@ -99,12 +507,43 @@ impl TimeseriesClient<'_> {
         symbols: &Symbols,
         date_time_range: &DateTimeRange,
         limit: Option<NonZeroU64>,
-    ) -> crate::Result<StreamReader<impl Stream<Item = std::io::Result<Bytes>>, Bytes>> {
+        compression: Compression,
+        text: Option<&TextEncodingParams>,
+    ) -> crate::Result<
+        CountingReader<StreamReader<impl Stream<Item = std::io::Result<Bytes>>, Bytes>>,
+    > {
+        if symbols
+            .count()
+            .is_some_and(|count| count > MAX_SYMBOLS_PER_REQUEST)
+        {
+            return Err(Error::bad_arg(
+                "symbols",
+                format!(
+                    "can't contain more than {MAX_SYMBOLS_PER_REQUEST} symbols in a single \
+                    request: use `get_range_buffered()` to split the request automatically"
+                ),
+            ));
+        }
+        let billing_entry = self
+            .inner
+            .billing_entry(
+                "timeseries.get_range",
+                &GetQueryParams::builder()
+                    .dataset(dataset)
+                    .symbols(symbols.clone())
+                    .stype_in(stype_in)
+                    .schema(schema)
+                    .date_time_range(date_time_range.clone())
+                    .limit(limit)
+                    .build(),
+            )
+            .await?;
+        let encoding = text.map_or(Encoding::Dbn, |text| text.encoding);
         let mut form = vec![
             ("dataset", dataset.to_owned()),
             ("schema", schema.to_string()),
-            ("encoding", Encoding::Dbn.to_string()),
-            ("compression", Compression::ZStd.to_string()),
+            ("encoding", encoding.to_string()),
+            ("compression", compression.to_string()),
             ("stype_in", stype_in.to_string()),
             ("stype_out", stype_out.to_string()),
             ("symbols", symbols.to_api_string()),
@@ -113,29 +552,233 @@ impl TimeseriesClient<'_> {
         if let Some(limit) = limit {
             form.push(("limit", limit.to_string()));
         }
-        let resp = self
-            .post("get_range")?
-            // unlike almost every other request, it's not JSON
-            .header(ACCEPT, "application/octet-stream")
-            .form(&form)
-            .send()
-            .await?;
-        let stream = check_http_error(resp)
-            .await?
-            .error_for_status()?
+        if let Some(text) = text {
+            text.add_to_form(&mut form);
+        }
+        let res: crate::Result<_> = async {
+            let builder = self
+                .post("get_range")?
+                // unlike almost every other request, it's not JSON
+                .header(ACCEPT, "application/octet-stream")
+                .form(&form);
+            let resp = self.inner.send(builder).await?;
+            Ok(check_http_error(resp).await?.error_for_status()?)
+        }
+        .await;
+        let stream = res
+            .map_err(|e| {
+                e.with_context(RequestContext::new(
+                    "timeseries.get_range",
+                    dataset,
+                    Some(schema),
+                    symbols,
+                    date_time_range,
+                ))
+            })?
             .bytes_stream()
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e));
-        Ok(tokio_util::io::StreamReader::new(stream))
+        let bytes_received = self.inner.billing.record_stream(billing_entry);
+        Ok(CountingReader::new(
+            tokio_util::io::StreamReader::new(stream),
+            bytes_received,
+        ))
     }
 
-    fn post(&mut self, slug: &str) -> crate::Result<RequestBuilder> {
+    /// Splits `date_time_range` of a request rejected as too large in half when the
+    /// client's policy allows it.
+    fn split_oversized(
+        &self,
+        date_time_range: &DateTimeRange,
+        mut err: ApiError,
+    ) -> crate::Result<[DateTimeRange; 2]> {
+        let duration = date_time_range.end() - date_time_range.start();
+        match self.inner.oversized_request_policy() {
+            OversizedRequestPolicy::Split if duration >= 2 * MIN_SPLIT_DURATION => {
+                let mid = date_time_range.start() + duration / 2;
+                info!(
+                    start = %date_time_range.start(),
+                    end = %date_time_range.end(),
+                    "Splitting request that's too large to stream"
+                );
+                Ok([
+                    DateTimeRange::from((date_time_range.start(), mid)),
+                    DateTimeRange::from((mid, date_time_range.end())),
+                ])
+            }
+            OversizedRequestPolicy::Split => Err(Error::Api(err)),
+            OversizedRequestPolicy::Error => {
+                err.message.push_str(
+                    ". Submit it as a batch job with BatchClient::submit_job() or set the \
+                     client's oversized request policy to split it",
+                );
+                Err(Error::Api(err))
+            }
+        }
+    }
+
+    fn post(&self, slug: &str) -> crate::Result<RequestBuilder> {
         self.inner.post(&format!("timeseries.{slug}"))
     }
 }
 
+/// A decoder for records that are decoded on a blocking thread. Returned by
+/// [`TimeseriesClient::get_range_buffered()`].
+#[derive(Debug)]
+pub struct BufferedDecoder {
+    metadata: Metadata,
+    receiver: mpsc::Receiver<crate::Result<RecordEnum>>,
+}
+
+impl BufferedDecoder {
+    /// Returns a reference to the decoded DBN metadata.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the next decoded record. Returns `Ok(None)` once the end of the
+    /// response has been reached.
+    ///
+    /// # Errors
+    /// This function returns an error if the decoding thread encountered an error
+    /// reading or decoding the response.
+    ///
+    /// # Cancel safety
+    /// This method is cancel safe. It can be used within a [`tokio::select!`] statement
+    /// without the potential for losing records.
+    pub async fn decode_record(&mut self) -> crate::Result<Option<RecordEnum>> {
+        self.receiver.recv().await.transpose()
+    }
+}
+
+/// A `get_range` response held in a memory-mapped temporary file. Returned by
+/// [`TimeseriesClient::get_range_cached_local()`].
+///
+/// Cloning is cheap because the mapping is shared, so each clone can be decoded on a
+/// different thread.
+#[derive(Debug, Clone)]
+pub struct CachedRange {
+    mmap: Arc<memmap2::Mmap>,
+    upgrade_policy: VersionUpgradePolicy,
+}
+
+impl CachedRange {
+    /// Returns the size of the compressed response in bytes.
+    pub fn len(&self) -> usize {
+        self.mmap.len()
+    }
+
+    /// Returns `true` if the response is empty.
+    pub fn is_empty(&self) -> bool {
+        self.mmap.is_empty()
+    }
+
+    /// Returns a new decoder that reads the response from the start. Decoding is
+    /// synchronous because the response is read from memory.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to decode the DBN metadata.
+    pub fn decoder(&self) -> crate::Result<DbnDecoder<impl std::io::Read + '_>> {
+        #[cfg(not(feature = "ruzstd"))]
+        let mut decoder = DbnDecoder::with_zstd_buffer(&self.mmap[..])?;
+        #[cfg(feature = "ruzstd")]
+        let mut decoder = DbnDecoder::new(zstd::sync_decoder(&self.mmap[..]))?;
+        decoder.set_upgrade_policy(self.upgrade_policy);
+        Ok(decoder)
+    }
+}
+
+/// The parameters for [`TimeseriesClient::get_definitions()`]. Use
+/// [`GetDefinitionsParams::builder()`] to get a builder type with all the preset
+/// defaults.
+#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GetDefinitionsParams {
+    /// The dataset code.
+    #[builder(setter(transform = |dt: impl ToString| dt.to_string()))]
+    pub dataset: String,
+    /// The symbols of the instruments whose definitions to request.
+    #[builder(setter(into))]
+    pub symbols: Symbols,
+    /// The request time range.
+    #[builder(setter(into))]
+    pub date_time_range: DateTimeRange,
+    /// The symbology type of the input `symbols`. Defaults to
+    /// [`RawSymbol`](dbn::enums::SType::RawSymbol).
+    #[builder(default = SType::RawSymbol)]
+    #[serde(
+        default = "crate::serialize::default_stype_in",
+        with = "crate::serialize::display_from_str"
+    )]
+    pub stype_in: SType,
+}
+
+/// How [`TimeseriesClient::get_range_buffered()`] handles requests the API rejects as
+/// too large to stream. Set with
+/// [`ClientBuilder::oversized_request_policy()`](super::ClientBuilder::oversized_request_policy).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizedRequestPolicy {
+    /// Return the API error with a suggestion to submit a batch job instead.
+    #[default]
+    Error,
+    /// Split the time range of the request in half until each request is accepted,
+    /// down to a minimum of one minute, and merge the results.
+    Split,
+}
+
+/// The maximum size of the response collected by
+/// [`TimeseriesClient::get_range_collect()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectLimit {
+    /// The maximum number of records.
+    Records(usize),
+    /// The maximum total size of the records in bytes.
+    Bytes(usize),
+}
+
+impl CollectLimit {
+    fn max_records<T>(&self) -> usize {
+        match *self {
+            Self::Records(max) => max,
+            Self::Bytes(max) => max / mem::size_of::<T>(),
+        }
+    }
+}
+
+/// Returns `true` if the API rejected a streaming request for being too large, in which
+/// case it recommends a batch download.
+fn is_oversized(err: &ApiError) -> bool {
+    err.status_code == StatusCode::UNPROCESSABLE_ENTITY
+        && err.message.to_ascii_lowercase().contains("batch")
+}
+
+/// Creates a decoder for a `get_range` response with `compression`.
+async fn decode_response<R>(
+    reader: R,
+    compression: Compression,
+    upgrade_policy: VersionUpgradePolicy,
+) -> crate::Result<AsyncDbnDecoder<impl AsyncRead + Unpin + Send>>
+where
+    R: AsyncBufRead + Unpin + Send + 'static,
+{
+    let mut decoder = AsyncDbnDecoder::new(decompress(reader, compression)).await?;
+    decoder.set_upgrade_policy(upgrade_policy);
+    Ok(decoder)
+}
+
+fn decompress<R>(reader: R, compression: Compression) -> impl AsyncRead + Unpin + Send
+where
+    R: AsyncBufRead + Unpin + Send + 'static,
+{
+    if compression == Compression::None {
+        Either::Right(reader)
+    } else {
+        Either::Left(zstd::async_decoder(reader))
+    }
+}
+
 /// The parameters for [`TimeseriesClient::get_range()`]. Use
 /// [`GetRangeParams::builder()`] to get a builder type with all the preset defaults.
-#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
+#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GetRangeParams {
     /// The dataset code.
     #[builder(setter(transform = |dt: impl ToString| dt.to_string()))]
@@ -144,6 +787,7 @@ pub struct GetRangeParams {
     #[builder(setter(into))]
     pub symbols: Symbols,
     /// The data record schema.
+    #[serde(with = "crate::serialize::display_from_str")]
     pub schema: Schema,
     /// The request time range.
     #[builder(setter(into))]
@@ -151,22 +795,85 @@ pub struct GetRangeParams {
     /// The symbology type of the input `symbols`. Defaults to
     /// [`RawSymbol`](dbn::enums::SType::RawSymbol).
     #[builder(default = SType::RawSymbol)]
+    #[serde(
+        default = "crate::serialize::default_stype_in",
+        with = "crate::serialize::display_from_str"
+    )]
     pub stype_in: SType,
     /// The symbology type of the output `symbols`. Defaults to
     /// [`InstrumentId`](dbn::enums::SType::InstrumentId).
     #[builder(default = SType::InstrumentId)]
+    #[serde(
+        default = "crate::serialize::default_stype_out",
+        with = "crate::serialize::display_from_str"
+    )]
     pub stype_out: SType,
     /// The optional maximum number of records to return. Defaults to no limit.
     #[builder(default)]
+    #[serde(default)]
     pub limit: Option<NonZeroU64>,
     /// How to decode DBN from prior versions. Defaults to upgrade.
     #[builder(default = VersionUpgradePolicy::UpgradeToV2)]
+    #[serde(
+        default = "crate::serialize::default_upgrade_policy",
+        with = "crate::serialize::upgrade_policy"
+    )]
     pub upgrade_policy: VersionUpgradePolicy,
+    /// The compression of the response. Uncompressed DBN can have lower latency for
+    /// very small requests. Not used by [`TimeseriesClient::get_range_buffered()`],
+    /// which always requests zstd. Defaults to [`ZStd`](Compression::ZStd).
+    #[builder(default = Compression::ZStd)]
+    #[serde(
+        default = "crate::serialize::default_compression",
+        with = "crate::serialize::display_from_str"
+    )]
+    pub compression: Compression,
+    /// The optional filter for skipping records for other instruments while decoding
+    /// by checking only their header. Only used by
+    /// [`TimeseriesClient::get_range_buffered()`]. Defaults to no filter.
+    #[builder(default, setter(strip_option))]
+    #[serde(skip)]
+    pub instrument_filter: Option<InstrumentFilter>,
+}
+
+/// The text encoding and formatting options for
+/// [`TimeseriesClient::get_range_text()`]. Use [`TextEncodingParams::builder()`] to get
+/// a builder type with all the preset defaults.
+#[derive(Debug, Clone, Copy, TypedBuilder, PartialEq, Eq)]
+pub struct TextEncodingParams {
+    /// The text encoding of the response. Must be [`Encoding::Csv`] or
+    /// [`Encoding::Json`].
+    pub encoding: Encoding,
+    /// If `true`, prices will be formatted to the correct scale (using the fixed-
+    /// precision scalar 1e-9).
+    #[builder(default)]
+    pub pretty_px: bool,
+    /// If `true`, timestamps will be formatted as ISO 8601 strings.
+    #[builder(default)]
+    pub pretty_ts: bool,
+    /// If `true`, a symbol field will be included with each record.
+    #[builder(default)]
+    pub map_symbols: bool,
+}
+
+impl TextEncodingParams {
+    fn validate(&self) -> crate::Result<()> {
+        match self.encoding {
+            Encoding::Csv | Encoding::Json => Ok(()),
+            _ => Err(Error::bad_arg("encoding", "must be CSV or JSON")),
+        }
+    }
+
+    fn add_to_form(&self, form: &mut Vec<(&'static str, String)>) {
+        form.push(("pretty_px", self.pretty_px.to_string()));
+        form.push(("pretty_ts", self.pretty_ts.to_string()));
+        form.push(("map_symbols", self.map_symbols.to_string()));
+    }
 }
 
 /// The parameters for [`TimeseriesClient::get_range_to_file()`]. Use
 /// [`GetRangeToFileParams::builder()`] to get a builder type with all the preset defaults.
-#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
+#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GetRangeToFileParams {
     /// The dataset code.
     #[builder(setter(transform = |dt: impl ToString| dt.to_string()))]
@@ -175,6 +882,7 @@ pub struct GetRangeToFileParams {
     #[builder(setter(into))]
     pub symbols: Symbols,
     /// The data record schema.
+    #[serde(with = "crate::serialize::display_from_str")]
     pub schema: Schema,
     /// The request time range.
     #[builder(setter(into))]
@@ -182,19 +890,41 @@ pub struct GetRangeToFileParams {
     /// The symbology type of the input `symbols`. Defaults to
     /// [`RawSymbol`](dbn::enums::SType::RawSymbol).
     #[builder(default = SType::RawSymbol)]
+    #[serde(
+        default = "crate::serialize::default_stype_in",
+        with = "crate::serialize::display_from_str"
+    )]
     pub stype_in: SType,
     /// The symbology type of the output `symbols`. Defaults to
     /// [`InstrumentId`](dbn::enums::SType::InstrumentId).
     #[builder(default = SType::InstrumentId)]
+    #[serde(
+        default = "crate::serialize::default_stype_out",
+        with = "crate::serialize::display_from_str"
+    )]
     pub stype_out: SType,
     /// The optional maximum number of records to return. Defaults to no limit.
     #[builder(default)]
+    #[serde(default)]
     pub limit: Option<NonZeroU64>,
     /// How to decode DBN from prior versions. Defaults to upgrade.
     #[builder(default = VersionUpgradePolicy::UpgradeToV2)]
+    #[serde(
+        default = "crate::serialize::default_upgrade_policy",
+        with = "crate::serialize::upgrade_policy"
+    )]
     pub upgrade_policy: VersionUpgradePolicy,
+    /// The compression of the response. The file is always written with zstd
+    /// compression. Defaults to [`ZStd`](Compression::ZStd).
+    #[builder(default = Compression::ZStd)]
+    #[serde(
+        default = "crate::serialize::default_compression",
+        with = "crate::serialize::display_from_str"
+    )]
+    pub compression: Compression,
     /// The file path to persist the stream data to.
     #[builder(default, setter(transform = |p: impl Into<PathBuf>| p.into()))]
+    #[serde(default)]
     pub path: PathBuf,
 }
 
@@ -209,6 +939,8 @@ impl From<GetRangeToFileParams> for GetRangeParams {
             stype_out: value.stype_out,
             limit: value.limit,
             upgrade_policy: value.upgrade_policy,
+            compression: value.compression,
+            instrument_filter: None,
         }
     }
 }
@@ -226,6 +958,7 @@ impl GetRangeParams {
             stype_out: self.stype_out,
             limit: self.limit,
             upgrade_policy: self.upgrade_policy,
+            compression: self.compression,
             path: path.into(),
         }
     }
@@ -233,8 +966,12 @@ impl GetRangeParams {
 
 #[cfg(test)]
 mod tests {
-    use dbn::{record::TradeMsg, Dataset};
+    use std::sync::{Arc, Mutex};
+
+    use async_compression::tokio::bufread::ZstdDecoder;
+    use dbn::{decode::DecodeRecord, record::TradeMsg, Dataset};
     use reqwest::StatusCode;
+    use serde_json::json;
     use time::macros::datetime;
     use wiremock::{
         matchers::{basic_auth, method, path},
@@ -244,12 +981,68 @@ mod tests {
     use super::*;
     use crate::{
         body_contains,
-        historical::{HistoricalGateway, API_VERSION},
+        historical::{progress::Progress, HistoricalGateway, API_VERSION},
         zst_test_data_path, HistoricalClient,
     };
 
     const API_KEY: &str = "test-API";
 
+    #[test]
+    fn test_get_range_params_serde() {
+        const JSON: &str = r#"{
+            "dataset": "XNAS.ITCH",
+            "symbols": ["SPOT", "AAPL"],
+            "schema": "trades",
+            "date_time_range": {
+                "start": "2023-06-14T00:00:00Z",
+                "end": "2023-06-17T00:00:00Z"
+            }
+        }"#;
+        let target: GetRangeParams = serde_json::from_str(JSON).unwrap();
+        let expected = GetRangeParams::builder()
+            .dataset(Dataset::XnasItch)
+            .schema(Schema::Trades)
+            .symbols(vec!["SPOT", "AAPL"])
+            .date_time_range((
+                datetime!(2023 - 06 - 14 00:00 UTC),
+                datetime!(2023 - 06 - 17 00:00 UTC),
+            ))
+            .build();
+        assert_eq!(target, expected);
+        let round_trip: GetRangeParams =
+            serde_json::from_str(&serde_json::to_string(&target).unwrap()).unwrap();
+        assert_eq!(round_trip, expected);
+    }
+
+    #[tokio::test]
+    async fn test_get_range_too_many_symbols() {
+        let target = HistoricalClient::with_url(
+            "http://127.0.0.1:0",
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let res = target
+            .timeseries()
+            .get_range(
+                &GetRangeParams::builder()
+                    .dataset(dbn::Dataset::XnasItch)
+                    .schema(Schema::Trades)
+                    .symbols((0..=MAX_SYMBOLS_PER_REQUEST as u32).collect::<Vec<_>>())
+                    .stype_in(SType::InstrumentId)
+                    .date_time_range((
+                        datetime!(2023 - 06 - 14 00:00 UTC),
+                        datetime!(2023 - 06 - 17 00:00 UTC),
+                    ))
+                    .build(),
+            )
+            .await;
+        assert!(matches!(
+            res,
+            Err(Error::BadArgument { ref desc, .. }) if desc.contains("get_range_buffered")
+        ));
+    }
+
     #[tokio::test]
     async fn test_get_range() {
         const START: time::OffsetDateTime = datetime!(2023 - 06 - 14 00:00 UTC);
@@ -275,7 +1068,7 @@ mod tests {
             .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
             .mount(&mock_server)
             .await;
-        let mut target = HistoricalClient::with_url(
+        let target = HistoricalClient::with_url(
             mock_server.uri(),
             API_KEY.to_owned(),
             HistoricalGateway::Bo1,
@@ -300,6 +1093,463 @@ mod tests {
         assert!(decoder.decode_record::<TradeMsg>().await.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn test_get_range_collect() {
+        const SCHEMA: Schema = Schema::Trades;
+
+        let mock_server = MockServer::start().await;
+        let bytes = tokio::fs::read(zst_test_data_path(SCHEMA)).await.unwrap();
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let params = GetRangeParams::builder()
+            .dataset(dbn::Dataset::XnasItch)
+            .schema(SCHEMA)
+            .symbols(vec!["SPOT", "AAPL"])
+            .date_time_range((
+                datetime!(2023 - 06 - 14 00:00 UTC),
+                datetime!(2023 - 06 - 17 00:00 UTC),
+            ))
+            .build();
+        let records = target
+            .timeseries()
+            .get_range_collect::<TradeMsg>(&params, CollectLimit::Records(2))
+            .await
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        let res = target
+            .timeseries()
+            .get_range_collect::<TradeMsg>(&params, CollectLimit::Bytes(mem::size_of::<TradeMsg>()))
+            .await;
+        assert!(matches!(res, Err(Error::LimitExceeded(msg)) if msg.contains("bytes")));
+        let requests = mock_server.received_requests().await.unwrap();
+        let limits = requests
+            .iter()
+            .map(|req| String::from_utf8_lossy(&req.body).contains("limit=3"))
+            .collect::<Vec<_>>();
+        assert_eq!(limits, [true, false]);
+    }
+
+    #[tokio::test]
+    async fn test_get_range_cached_local() {
+        const SCHEMA: Schema = Schema::Trades;
+
+        let mock_server = MockServer::start().await;
+        let bytes = tokio::fs::read(zst_test_data_path(SCHEMA)).await.unwrap();
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .and(body_contains("compression", "zstd"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let cached = target
+            .timeseries()
+            .get_range_cached_local(
+                &GetRangeParams::builder()
+                    .dataset(dbn::Dataset::XnasItch)
+                    .schema(SCHEMA)
+                    .symbols(vec!["SPOT", "AAPL"])
+                    .date_time_range((
+                        datetime!(2023 - 06 - 14 00:00 UTC),
+                        datetime!(2023 - 06 - 17 00:00 UTC),
+                    ))
+                    .build(),
+            )
+            .await
+            .unwrap();
+        assert!(!cached.is_empty());
+        // Each pass decodes the whole response
+        for cached in [cached.clone(), cached] {
+            let mut decoder = cached.decoder().unwrap();
+            assert_eq!(decoder.metadata().schema.unwrap(), SCHEMA);
+            let mut count = 0;
+            while decoder.decode_record::<TradeMsg>().unwrap().is_some() {
+                count += 1;
+            }
+            assert_eq!(count, 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_range_uncompressed() {
+        const SCHEMA: Schema = Schema::Trades;
+
+        let mock_server = MockServer::start().await;
+        let mut bytes = Vec::new();
+        ZstdDecoder::new(tokio::io::BufReader::new(
+            File::open(zst_test_data_path(SCHEMA)).await.unwrap(),
+        ))
+        .read_to_end(&mut bytes)
+        .await
+        .unwrap();
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .and(body_contains("compression", "none"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let mut decoder = target
+            .timeseries()
+            .get_range(
+                &GetRangeParams::builder()
+                    .dataset(dbn::Dataset::XnasItch)
+                    .schema(SCHEMA)
+                    .symbols(vec!["SPOT", "AAPL"])
+                    .date_time_range((
+                        datetime!(2023 - 06 - 14 00:00 UTC),
+                        datetime!(2023 - 06 - 17 00:00 UTC),
+                    ))
+                    .compression(Compression::None)
+                    .build(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(decoder.metadata().schema.unwrap(), SCHEMA);
+        decoder.decode_record::<TradeMsg>().await.unwrap().unwrap();
+        decoder.decode_record::<TradeMsg>().await.unwrap().unwrap();
+        assert!(decoder.decode_record::<TradeMsg>().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_range_text() {
+        const CSV: &str = "ts_recv,price,symbol\n2023-06-14T13:30:00.000000000Z,10.5,SPOT\n";
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .and(body_contains("encoding", "csv"))
+            .and(body_contains("compression", "none"))
+            .and(body_contains("pretty_px", "true"))
+            .and(body_contains("pretty_ts", "true"))
+            .and(body_contains("map_symbols", "true"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_string(CSV))
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let params = GetRangeParams::builder()
+            .dataset(dbn::Dataset::XnasItch)
+            .schema(Schema::Trades)
+            .symbols(vec!["SPOT"])
+            .date_time_range((
+                datetime!(2023 - 06 - 14 00:00 UTC),
+                datetime!(2023 - 06 - 17 00:00 UTC),
+            ))
+            .compression(Compression::None)
+            .build();
+        let mut reader = target
+            .timeseries()
+            .get_range_text(
+                &params,
+                &TextEncodingParams::builder()
+                    .encoding(Encoding::Csv)
+                    .pretty_px(true)
+                    .pretty_ts(true)
+                    .map_symbols(true)
+                    .build(),
+            )
+            .await
+            .unwrap();
+        let mut res = String::new();
+        reader.read_to_string(&mut res).await.unwrap();
+        assert_eq!(res, CSV);
+        let res = target
+            .timeseries()
+            .get_range_text(
+                &params,
+                &TextEncodingParams::builder()
+                    .encoding(Encoding::Dbn)
+                    .build(),
+            )
+            .await;
+        assert!(matches!(res, Err(Error::BadArgument { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_get_range_error_context() {
+        const START: time::OffsetDateTime = datetime!(2023 - 06 - 14 00:00 UTC);
+        const END: time::OffsetDateTime = datetime!(2023 - 06 - 17 00:00 UTC);
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::BAD_REQUEST.as_u16())
+                    .set_body_json(json!({"detail": "Invalid symbol"})),
+            )
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let err = target
+            .timeseries()
+            .get_range(
+                &GetRangeParams::builder()
+                    .dataset(dbn::Dataset::XnasItch)
+                    .schema(Schema::Trades)
+                    .symbols(vec!["SPOT", "AAPL"])
+                    .date_time_range((START, END))
+                    .build(),
+            )
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::Api(_)));
+        let context = err.request_context().unwrap();
+        assert_eq!(context.endpoint, "timeseries.get_range");
+        assert_eq!(context.dataset, "XNAS.ITCH");
+        assert_eq!(context.schema, Some(Schema::Trades));
+        assert_eq!(context.symbol_count, Some(2));
+        assert_eq!((context.start, context.end), (START, END));
+        assert!(!err.to_string().contains(API_KEY));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_range_buffered() {
+        const START: time::OffsetDateTime = datetime!(2023 - 06 - 14 00:00 UTC);
+        const END: time::OffsetDateTime = datetime!(2023 - 06 - 17 00:00 UTC);
+        const SCHEMA: Schema = Schema::Trades;
+
+        let mock_server = MockServer::start().await;
+        let bytes = tokio::fs::read(zst_test_data_path(SCHEMA)).await.unwrap();
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .and(body_contains("dataset", "XNAS.ITCH"))
+            .and(body_contains("schema", "trades"))
+            .and(body_contains("symbols", "SPOT%2CAAPL"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let mut decoder = target
+            .timeseries()
+            .get_range_buffered(
+                &GetRangeParams::builder()
+                    .dataset(dbn::Dataset::XnasItch)
+                    .schema(SCHEMA)
+                    .symbols(vec!["SPOT", "AAPL"])
+                    .date_time_range((START, END))
+                    .build(),
+                1,
+            )
+            .await
+            .unwrap();
+        assert_eq!(decoder.metadata().schema.unwrap(), SCHEMA);
+        // Two records
+        assert!(matches!(
+            decoder.decode_record().await.unwrap(),
+            Some(RecordEnum::Trade(_))
+        ));
+        assert!(matches!(
+            decoder.decode_record().await.unwrap(),
+            Some(RecordEnum::Trade(_))
+        ));
+        assert!(decoder.decode_record().await.unwrap().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_range_buffered_split_oversized() {
+        const START: time::OffsetDateTime = datetime!(2023 - 06 - 14 00:00 UTC);
+        const MID: time::OffsetDateTime = datetime!(2023 - 06 - 15 12:00 UTC);
+        const END: time::OffsetDateTime = datetime!(2023 - 06 - 17 00:00 UTC);
+        const SCHEMA: Schema = Schema::Trades;
+
+        let mock_server = MockServer::start().await;
+        let bytes = tokio::fs::read(zst_test_data_path(SCHEMA)).await.unwrap();
+        Mock::given(method("POST"))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .and(body_contains("start", START.unix_timestamp_nanos()))
+            .and(body_contains("end", END.unix_timestamp_nanos()))
+            .respond_with(
+                ResponseTemplate::new(StatusCode::UNPROCESSABLE_ENTITY.as_u16()).set_body_json(
+                    json!({"detail": "Request is too large to stream, use a batch download"}),
+                ),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        for (start, end) in [(START, MID), (MID, END)] {
+            Mock::given(method("POST"))
+                .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+                .and(body_contains("start", start.unix_timestamp_nanos()))
+                .and(body_contains("end", end.unix_timestamp_nanos()))
+                .respond_with(
+                    ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes.clone()),
+                )
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+        let params = GetRangeParams::builder()
+            .dataset(dbn::Dataset::XnasItch)
+            .schema(SCHEMA)
+            .symbols(vec!["SPOT", "AAPL"])
+            .date_time_range((START, END))
+            .build();
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let Err(Error::Api(err)) = target.timeseries().get_range_buffered(&params, 1).await else {
+            panic!("expected API error");
+        };
+        assert!(err.message.contains("submit_job()"));
+        let target = HistoricalClient::builder()
+            .key(API_KEY)
+            .unwrap()
+            .base_url(mock_server.uri().parse().unwrap())
+            .oversized_request_policy(OversizedRequestPolicy::Split)
+            .build()
+            .unwrap();
+        let mut decoder = target
+            .timeseries()
+            .get_range_buffered(&params, 1)
+            .await
+            .unwrap();
+        // Both halves return the same records, which are removed as duplicates
+        assert!(decoder.decode_record().await.unwrap().is_some());
+        assert!(decoder.decode_record().await.unwrap().is_some());
+        assert!(decoder.decode_record().await.unwrap().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_definitions() {
+        const START: time::OffsetDateTime = datetime!(2021 - 10 - 04 00:00 UTC);
+        const END: time::OffsetDateTime = datetime!(2021 - 10 - 06 00:00 UTC);
+
+        let mock_server = MockServer::start().await;
+        let bytes = tokio::fs::read(zst_test_data_path(Schema::Definition))
+            .await
+            .unwrap();
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .and(body_contains("dataset", "GLBX.MDP3"))
+            .and(body_contains("schema", "definition"))
+            .and(body_contains("symbols", "ESZ1%2CESH2"))
+            .and(body_contains("stype_in", "raw_symbol"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let definitions = target
+            .timeseries()
+            .get_definitions(
+                &GetDefinitionsParams::builder()
+                    .dataset(dbn::Dataset::GlbxMdp3)
+                    .symbols(vec!["ESZ1", "ESH2"])
+                    .date_time_range((START, END))
+                    .build(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(definitions.len(), 2);
+        assert!(definitions.contains_key(&6819));
+        assert!(definitions.contains_key(&6830));
+        assert!(definitions
+            .iter()
+            .all(|(id, def)| def.hd.instrument_id == *id));
+    }
+
+    #[tokio::test]
+    async fn test_get_range_with_progress() {
+        const SCHEMA: Schema = Schema::Trades;
+
+        let mock_server = MockServer::start().await;
+        let bytes = tokio::fs::read(zst_test_data_path(SCHEMA)).await.unwrap();
+        let len = bytes.len() as u64;
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let handler_reports = reports.clone();
+        let mut decoder = target
+            .timeseries()
+            .get_range_with_progress(
+                &GetRangeParams::builder()
+                    .dataset(dbn::Dataset::XnasItch)
+                    .schema(SCHEMA)
+                    .symbols(vec!["SPOT", "AAPL"])
+                    .date_time_range((
+                        datetime!(2023 - 06 - 14 00:00 UTC),
+                        datetime!(2023 - 06 - 17 00:00 UTC),
+                    ))
+                    .build(),
+                move |progress: &Progress| handler_reports.lock().unwrap().push(*progress),
+            )
+            .await
+            .unwrap();
+        while decoder.decode_record_ref().await.unwrap().is_some() {}
+        assert!(decoder.decode_record_ref().await.unwrap().is_none());
+        let reports = reports.lock().unwrap();
+        // Final progress is only reported once
+        let progress = reports.last().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(progress.bytes_downloaded, len);
+        assert_eq!(progress.records_decoded, 2);
+        assert_eq!(
+            progress.bytes_decoded,
+            2 * std::mem::size_of::<TradeMsg>() as u64
+        );
+        assert_eq!(decoder.progress().records_decoded, 2);
+    }
+
     #[tokio::test]
     async fn test_get_range_to_file() {
         const START: time::OffsetDateTime = datetime!(2024 - 05 - 17 00:00 UTC);
@@ -327,7 +1577,7 @@ mod tests {
             .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
             .mount(&mock_server)
             .await;
-        let mut target = HistoricalClient::with_url(
+        let target = HistoricalClient::with_url(
             mock_server.uri(),
             API_KEY.to_owned(),
             HistoricalGateway::Bo1,
@@ -354,4 +1604,47 @@ mod tests {
         decoder.decode_record::<TradeMsg>().await.unwrap().unwrap();
         assert!(decoder.decode_record::<TradeMsg>().await.unwrap().is_none());
     }
+
+    #[tokio::test]
+    async fn test_get_range_to_file_cancelled() {
+        const SCHEMA: Schema = Schema::Trades;
+
+        let mock_server = MockServer::start().await;
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bytes = tokio::fs::read(zst_test_data_path(SCHEMA)).await.unwrap();
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let path = temp_dir.path().join("test.dbn.zst");
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        let res = target
+            .timeseries()
+            .get_range_to_file_with_cancellation(
+                &GetRangeToFileParams::builder()
+                    .dataset(Dataset::IfeuImpact)
+                    .schema(SCHEMA)
+                    .symbols(vec!["BRN.FUT"])
+                    .stype_in(SType::Parent)
+                    .date_time_range((
+                        datetime!(2024 - 05 - 17 00:00 UTC),
+                        datetime!(2024 - 05 - 18 00:00 UTC),
+                    ))
+                    .path(path.clone())
+                    .build(),
+                &cancellation_token,
+            )
+            .await;
+        assert!(matches!(res, Err(Error::Cancelled)), "{res:?}");
+        assert!(!path.exists());
+    }
 }
@@ -0,0 +1,172 @@
+//! Expanding parent symbols such as `ES.FUT` into the contracts listed on a date, for
+//! building explicit symbol universes.
+
+use std::cmp::Ordering;
+
+use dbn::{enums::InstrumentClass, InstrumentDefMsg, SType, FIXED_PRICE_SCALE, UNDEF_PRICE};
+use time::{Date, OffsetDateTime};
+use typed_builder::TypedBuilder;
+
+use crate::{Error, Symbols};
+
+use super::timeseries::{GetDefinitionsParams, TimeseriesClient};
+
+/// The parameters for [`TimeseriesClient::expand_parents()`]. Use
+/// [`ExpandParentsParams::builder()`] to get a builder type with all the preset
+/// defaults.
+#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
+pub struct ExpandParentsParams {
+    /// The dataset code.
+    #[builder(setter(transform = |dt: impl ToString| dt.to_string()))]
+    pub dataset: String,
+    /// The parent symbols to expand, such as `ES.FUT` or `SPY.OPT`.
+    #[builder(setter(into))]
+    pub symbols: Symbols,
+    /// The UTC date on which the contracts are listed.
+    pub date: Date,
+}
+
+/// A contract belonging to a parent symbol, derived from its instrument definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contract {
+    /// The instrument ID assigned by the publisher.
+    pub instrument_id: u32,
+    /// The instrument name (symbol) provided by the publisher.
+    pub raw_symbol: String,
+    /// The underlying asset code (product code) of the instrument.
+    pub asset: String,
+    /// The underlying instrument's symbol, such as for options.
+    pub underlying: String,
+    /// The classification of the instrument.
+    pub instrument_class: InstrumentClass,
+    /// The last eligible trade time, if defined.
+    pub expiration: Option<OffsetDateTime>,
+    /// The strike price of options, if defined.
+    pub strike_price: Option<f64>,
+}
+
+impl TimeseriesClient<'_> {
+    /// Expands the parent symbols in `params` into the contracts listed on `date`,
+    /// ordered by expiration, strike price, and raw symbol. Uses the definition schema,
+    /// so each contract includes its expiration and strike price.
+    ///
+    /// # Errors
+    /// This function returns an error when any symbol isn't a valid parent symbol, it
+    /// fails to communicate with the Databento API, or the API indicates there's an
+    /// issue with the request.
+    pub async fn expand_parents(
+        &self,
+        params: &ExpandParentsParams,
+    ) -> crate::Result<Vec<Contract>> {
+        let symbols = params.symbols.normalize(SType::Parent)?;
+        let definitions = self
+            .get_definitions(
+                &GetDefinitionsParams::builder()
+                    .dataset(&params.dataset)
+                    .symbols(symbols)
+                    .stype_in(SType::Parent)
+                    .date_time_range((params.date.midnight().assume_utc(), time::Duration::DAY))
+                    .build(),
+            )
+            .await?;
+        let mut contracts = definitions
+            .values()
+            .map(Contract::try_from)
+            .collect::<crate::Result<Vec<_>>>()?;
+        contracts.sort_by(|a, b| {
+            a.expiration
+                .cmp(&b.expiration)
+                .then_with(|| {
+                    a.strike_price
+                        .partial_cmp(&b.strike_price)
+                        .unwrap_or(Ordering::Equal)
+                })
+                .then_with(|| a.raw_symbol.cmp(&b.raw_symbol))
+        });
+        Ok(contracts)
+    }
+}
+
+impl TryFrom<&InstrumentDefMsg> for Contract {
+    type Error = Error;
+
+    fn try_from(definition: &InstrumentDefMsg) -> crate::Result<Self> {
+        let expiration = if definition.expiration == dbn::UNDEF_TIMESTAMP {
+            None
+        } else {
+            OffsetDateTime::from_unix_timestamp_nanos(i128::from(definition.expiration)).ok()
+        };
+        Ok(Self {
+            instrument_id: definition.hd.instrument_id,
+            raw_symbol: definition.raw_symbol()?.to_owned(),
+            asset: definition.asset()?.to_owned(),
+            underlying: definition.underlying()?.to_owned(),
+            instrument_class: definition.instrument_class()?,
+            expiration,
+            strike_price: (definition.strike_price != UNDEF_PRICE)
+                .then(|| definition.strike_price as f64 / FIXED_PRICE_SCALE as f64),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+    use time::macros::date;
+    use wiremock::{
+        matchers::{basic_auth, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::{
+        body_contains,
+        historical::{HistoricalGateway, API_VERSION},
+        zst_test_data_path, HistoricalClient,
+    };
+
+    const API_KEY: &str = "test-universe";
+
+    #[tokio::test]
+    async fn test_expand_parents() {
+        let mock_server = MockServer::start().await;
+        let bytes = tokio::fs::read(zst_test_data_path(dbn::Schema::Definition))
+            .await
+            .unwrap();
+        Mock::given(method("POST"))
+            .and(basic_auth(API_KEY, ""))
+            .and(path(format!("/v{API_VERSION}/timeseries.get_range")))
+            .and(body_contains("symbols", "ES.FUT"))
+            .and(body_contains("stype_in", "parent"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK.as_u16()).set_body_bytes(bytes))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let target = HistoricalClient::with_url(
+            mock_server.uri(),
+            API_KEY.to_owned(),
+            HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let params = ExpandParentsParams::builder()
+            .dataset(dbn::Dataset::GlbxMdp3)
+            .symbols("es.fut")
+            .date(date!(2021 - 10 - 04))
+            .build();
+        let contracts = target.timeseries().expand_parents(&params).await.unwrap();
+        assert_eq!(
+            contracts
+                .iter()
+                .map(|contract| contract.raw_symbol.as_str())
+                .collect::<Vec<_>>(),
+            ["ESZ1", "ESH2"]
+        );
+        assert!(contracts[0].expiration < contracts[1].expiration);
+        assert_eq!(contracts[0].instrument_class, InstrumentClass::Future);
+        let invalid = ExpandParentsParams {
+            symbols: Symbols::from("ESZ1"),
+            ..params
+        };
+        assert!(target.timeseries().expand_parents(&invalid).await.is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Decompression of zstd-compressed historical responses with the backend selected by
+//! the `ruzstd` feature: the zstd C library by default, or a pure-Rust decoder.
+
+#[cfg(feature = "ruzstd")]
+use std::io::BufRead;
+
+use tokio::io::{AsyncBufRead, AsyncRead};
+
+/// Returns an async reader of the decompressed contents of `reader`, which can contain
+/// several zstd frames.
+#[cfg(not(feature = "ruzstd"))]
+pub(crate) fn async_decoder<R>(reader: R) -> impl AsyncRead + Unpin + Send
+where
+    R: AsyncBufRead + Unpin + Send + 'static,
+{
+    let mut decoder = async_compression::tokio::bufread::ZstdDecoder::new(reader);
+    // Responses can contain several zstd frames
+    decoder.multiple_members(true);
+    decoder
+}
+
+/// Returns an async reader of the decompressed contents of `reader`, which can contain
+/// several zstd frames. The pure-Rust decoder is synchronous, so it runs on a blocking
+/// thread and sends decompressed chunks back through a channel.
+#[cfg(feature = "ruzstd")]
+pub(crate) fn async_decoder<R>(reader: R) -> impl AsyncRead + Unpin + Send
+where
+    R: AsyncBufRead + Unpin + Send + 'static,
+{
+    use std::io::Read;
+
+    use tokio::sync::mpsc;
+    use tokio_util::{bytes::Bytes, io::StreamReader};
+
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    let (tx, rx) = mpsc::channel::<std::io::Result<Bytes>>(4);
+    tokio::task::spawn_blocking(move || {
+        let mut decoder = sync_decoder(tokio_util::io::SyncIoBridge::new(reader));
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            let res = match decoder.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => Ok(Bytes::copy_from_slice(&buffer[..n])),
+                Err(e) => Err(e),
+            };
+            let is_err = res.is_err();
+            // Reader was dropped or hit a fatal error
+            if tx.blocking_send(res).is_err() || is_err {
+                break;
+            }
+        }
+    });
+    StreamReader::new(Box::pin(futures::stream::unfold(rx, |mut rx| async {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    })))
+}
+
+/// Returns a reader of the decompressed contents of `reader`, which can contain several
+/// zstd frames.
+#[cfg(feature = "ruzstd")]
+pub(crate) fn sync_decoder<R: BufRead>(reader: R) -> impl std::io::Read {
+    FramesDecoder {
+        source: reader,
+        frame: ruzstd::decoding::FrameDecoder::new(),
+        is_in_frame: false,
+    }
+}
+
+/// Decodes consecutive zstd frames with the pure-Rust decoder.
+#[cfg(feature = "ruzstd")]
+struct FramesDecoder<R> {
+    source: R,
+    frame: ruzstd::decoding::FrameDecoder,
+    is_in_frame: bool,
+}
+
+#[cfg(feature = "ruzstd")]
+impl<R: BufRead> std::io::Read for FramesDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use ruzstd::decoding::BlockDecodingStrategy;
+
+        let invalid_data = |e| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if !self.is_in_frame {
+                if self.source.fill_buf()?.is_empty() {
+                    return Ok(0);
+                }
+                self.frame.reset(&mut self.source).map_err(invalid_data)?;
+                self.is_in_frame = true;
+            }
+            if !self.frame.is_finished() && self.frame.can_collect() < buf.len() {
+                self.frame
+                    .decode_blocks(
+                        &mut self.source,
+                        BlockDecodingStrategy::UptoBytes(buf.len()),
+                    )
+                    .map_err(invalid_data)?;
+            }
+            let read = self.frame.read(buf)?;
+            if read > 0 {
+                return Ok(read);
+            }
+            if self.frame.is_finished() {
+                self.is_in_frame = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::Schema;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::zst_test_data_path;
+
+    #[tokio::test]
+    async fn test_async_decoder_multiple_frames() {
+        let compressed = std::fs::read(zst_test_data_path(Schema::Trades)).unwrap();
+        let compressed = [compressed.clone(), compressed].concat();
+        let mut output = Vec::new();
+        async_decoder(std::io::Cursor::new(compressed))
+            .read_to_end(&mut output)
+            .await
+            .unwrap();
+        assert_eq!(&output[..3], b"DBN");
+        let half = output.len() / 2;
+        assert_eq!(output[..half], output[half..]);
+    }
+
+    #[cfg(feature = "ruzstd")]
+    #[test]
+    fn test_sync_decoder_multiple_frames() {
+        use std::io::Read;
+
+        let compressed = std::fs::read(zst_test_data_path(Schema::Trades)).unwrap();
+        let compressed = [compressed.clone(), compressed].concat();
+        let mut output = Vec::new();
+        sync_decoder(compressed.as_slice())
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(&output[..3], b"DBN");
+        let half = output.len() / 2;
+        assert_eq!(output[..half], output[half..]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Helpers for analyzing auction imbalances, such as how much of the auction interest
+//! is paired and how long until the auction, from [`ImbalanceMsg`] records received
+//! from either client.
+
+use std::{collections::HashMap, time::Duration};
+
+use dbn::{enums::Side, ImbalanceMsg, FIXED_PRICE_SCALE, UNDEF_PRICE, UNDEF_TIMESTAMP};
+
+/// The latest imbalance of a set of instruments, updated by passing [`ImbalanceMsg`]
+/// records to [`update()`](Self::update) in the order they're received.
+#[derive(Debug, Clone, Default)]
+pub struct Imbalances {
+    instruments: HashMap<u32, Imbalance>,
+}
+
+/// An auction imbalance with prices converted from fixed-precision. Prices are `None`
+/// when the venue didn't provide them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Imbalance {
+    /// The instrument ID.
+    pub instrument_id: u32,
+    /// The matching-engine-received timestamp expressed as the number of nanoseconds
+    /// since the UNIX epoch.
+    pub ts_event: u64,
+    /// The capture-server-received timestamp expressed as the number of nanoseconds
+    /// since the UNIX epoch.
+    pub ts_recv: u64,
+    /// The time of the auction expressed as the number of nanoseconds since the UNIX
+    /// epoch, if the venue provided it.
+    pub auction_time: Option<u64>,
+    /// The price at which the imbalance shares are calculated.
+    pub ref_price: Option<f64>,
+    /// The hypothetical auction-clearing price for both cross and continuous orders.
+    pub cont_book_clr_price: Option<f64>,
+    /// The hypothetical auction-clearing price for cross orders only.
+    pub auct_interest_clr_price: Option<f64>,
+    /// The indicative match price.
+    pub ind_match_price: Option<f64>,
+    /// The quantity of shares that are eligible to be matched at `ref_price`.
+    pub paired_qty: u32,
+    /// The quantity of shares that are not paired at `ref_price`.
+    pub total_imbalance_qty: u32,
+    /// The total market order imbalance.
+    pub market_imbalance_qty: u32,
+    /// The side of the imbalance.
+    pub side: Side,
+}
+
+impl Imbalances {
+    /// Creates a new empty set of imbalances.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the imbalance of the instrument of `imbalance` and returns the
+    /// converted imbalance.
+    ///
+    /// # Errors
+    /// This function returns an error if `imbalance` contains an invalid `side`.
+    pub fn update(&mut self, imbalance: &ImbalanceMsg) -> crate::Result<&Imbalance> {
+        let imbalance = Imbalance::try_from(imbalance)?;
+        Ok(self
+            .instruments
+            .entry(imbalance.instrument_id)
+            .and_modify(|latest| *latest = imbalance)
+            .or_insert(imbalance))
+    }
+
+    /// Returns the latest imbalance of the instrument with `instrument_id`, if any has
+    /// been received.
+    pub fn get(&self, instrument_id: u32) -> Option<&Imbalance> {
+        self.instruments.get(&instrument_id)
+    }
+
+    /// Returns the latest imbalances of all instruments keyed by instrument ID.
+    pub fn instruments(&self) -> &HashMap<u32, Imbalance> {
+        &self.instruments
+    }
+
+    /// Returns the latest imbalances whose auction is at or after `ts`, a number of
+    /// nanoseconds since the UNIX epoch, ordered by auction time.
+    pub fn upcoming(&self, ts: u64) -> Vec<&Imbalance> {
+        let mut upcoming = self
+            .instruments
+            .values()
+            .filter(|imbalance| imbalance.auction_time.is_some_and(|at| at >= ts))
+            .collect::<Vec<_>>();
+        upcoming.sort_by_key(|imbalance| (imbalance.auction_time, imbalance.instrument_id));
+        upcoming
+    }
+}
+
+impl Imbalance {
+    /// Returns the fraction of the auction interest that's paired, between 0 and 1, or
+    /// `None` if there's no interest.
+    pub fn paired_ratio(&self) -> Option<f64> {
+        let total = u64::from(self.paired_qty) + u64::from(self.total_imbalance_qty);
+        (total > 0).then(|| f64::from(self.paired_qty) / total as f64)
+    }
+
+    /// Returns the size of the imbalance relative to the paired quantity, or `None` if
+    /// nothing is paired.
+    pub fn imbalance_ratio(&self) -> Option<f64> {
+        (self.paired_qty > 0)
+            .then(|| f64::from(self.total_imbalance_qty) / f64::from(self.paired_qty))
+    }
+
+    /// Returns the time remaining until the auction as of `ts_event`, or `None` if the
+    /// auction time is unknown.
+    pub fn time_to_auction(&self) -> Option<Duration> {
+        self.time_to_auction_at(self.ts_event)
+    }
+
+    /// Returns the time remaining until the auction as of `ts`, a number of nanoseconds
+    /// since the UNIX epoch, or `None` if the auction time is unknown. Returns zero once
+    /// the auction time has passed.
+    pub fn time_to_auction_at(&self, ts: u64) -> Option<Duration> {
+        self.auction_time
+            .map(|auction_time| Duration::from_nanos(auction_time.saturating_sub(ts)))
+    }
+}
+
+impl TryFrom<&ImbalanceMsg> for Imbalance {
+    type Error = crate::Error;
+
+    fn try_from(imbalance: &ImbalanceMsg) -> crate::Result<Self> {
+        Ok(Self {
+            instrument_id: imbalance.hd.instrument_id,
+            ts_event: imbalance.hd.ts_event,
+            ts_recv: imbalance.ts_recv,
+            auction_time: (imbalance.auction_time != 0
+                && imbalance.auction_time != UNDEF_TIMESTAMP)
+                .then_some(imbalance.auction_time),
+            ref_price: price(imbalance.ref_price),
+            cont_book_clr_price: price(imbalance.cont_book_clr_price),
+            auct_interest_clr_price: price(imbalance.auct_interest_clr_price),
+            ind_match_price: price(imbalance.ind_match_price),
+            paired_qty: imbalance.paired_qty,
+            total_imbalance_qty: imbalance.total_imbalance_qty,
+            market_imbalance_qty: imbalance.market_imbalance_qty,
+            side: imbalance.side()?,
+        })
+    }
+}
+
+fn price(price: i64) -> Option<f64> {
+    (price != UNDEF_PRICE).then(|| price as f64 / FIXED_PRICE_SCALE as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{enums::rtype, record::RecordHeader};
+
+    use super::*;
+
+    const SEC: u64 = 1_000_000_000;
+
+    fn imbalance(instrument_id: u32, ts_event: u64, auction_time: u64) -> ImbalanceMsg {
+        ImbalanceMsg {
+            hd: RecordHeader::new::<ImbalanceMsg>(rtype::IMBALANCE, 1, instrument_id, ts_event),
+            ts_recv: ts_event + 1,
+            auction_time,
+            ref_price: 100 * FIXED_PRICE_SCALE,
+            paired_qty: 300,
+            total_imbalance_qty: 100,
+            side: Side::Bid as u8 as std::ffi::c_char,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let mut target = Imbalances::new();
+        let res = target.update(&imbalance(10, 10 * SEC, 70 * SEC)).unwrap();
+        assert_eq!(res.ref_price, Some(100.0));
+        assert!(res.ind_match_price.is_none());
+        assert_eq!(res.side, Side::Bid);
+        assert_eq!(res.paired_ratio(), Some(0.75));
+        assert_eq!(res.imbalance_ratio(), Some(1.0 / 3.0));
+        assert_eq!(res.time_to_auction(), Some(Duration::from_secs(60)));
+        target.update(&imbalance(10, 40 * SEC, 70 * SEC)).unwrap();
+        target.update(&imbalance(11, 40 * SEC, 50 * SEC)).unwrap();
+        target.update(&imbalance(12, 40 * SEC, 0)).unwrap();
+        let latest = target.get(10).unwrap();
+        assert_eq!(latest.time_to_auction(), Some(Duration::from_secs(30)));
+        assert_eq!(latest.time_to_auction_at(80 * SEC), Some(Duration::ZERO));
+        assert!(target.get(12).unwrap().time_to_auction().is_none());
+        let upcoming = target
+            .upcoming(45 * SEC)
+            .into_iter()
+            .map(|imbalance| imbalance.instrument_id)
+            .collect::<Vec<_>>();
+        assert_eq!(upcoming, [11, 10]);
+        assert_eq!(target.upcoming(60 * SEC).len(), 1);
+    }
+
+    #[test]
+    fn test_ratios_without_interest() {
+        let target = Imbalance::try_from(&ImbalanceMsg {
+            paired_qty: 0,
+            total_imbalance_qty: 0,
+            ..imbalance(10, 0, 0)
+        })
+        .unwrap();
+        assert!(target.paired_ratio().is_none());
+        assert!(target.imbalance_ratio().is_none());
+    }
+}
@@ -12,6 +12,11 @@
 //! - `historical`: enables the [historical client](HistoricalClient) for data older than 24 hours
 //! - `live`: enables the [live client](LiveClient) for real-time and intraday
 //!   historical data
+//! - `serde`: enables serializing and deserializing request parameters such as
+//!   [`Symbols`] and [`Subscription`](live::Subscription). Enabled by `historical`
+//! - `zeroize`: clears [`ApiKey`]s from memory when they're dropped
+//! - `config`: enables loading client settings from TOML and JSON files with
+//!   [`config::Config`]
 
 // Experimental feature to allow docs.rs to display features
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
@@ -19,28 +24,57 @@
 #![deny(rustdoc::broken_intra_doc_links)]
 #![deny(clippy::missing_errors_doc)]
 
+pub mod book;
+pub mod calendar;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod credentials;
+pub mod dedup;
 pub mod error;
+pub mod filter;
 #[cfg(feature = "historical")]
 pub mod historical;
+pub mod imbalance;
 #[cfg(feature = "live")]
 pub mod live;
+pub mod options;
+pub mod prelude;
+pub mod quality;
+pub mod sampling;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod statistics;
+pub mod status;
+pub mod timezone;
+pub mod vwap;
 
+pub use credentials::CredentialsProvider;
 pub use error::{Error, Result};
 #[cfg(feature = "historical")]
 pub use historical::Client as HistoricalClient;
 #[cfg(feature = "live")]
 pub use live::Client as LiveClient;
+#[cfg(feature = "live")]
+pub use live::{Receiver as LiveReceiver, Sender as LiveSender};
 // Re-export to keep versions synchronized
 pub use dbn;
 
-use std::fmt::{self, Display, Write};
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fmt::{self, Display, Write},
+    hash::Hash,
+};
 
-#[cfg(feature = "historical")]
-use serde::{Deserialize, Deserializer};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tracing::error;
 
 /// A set of symbols for a particular [`SType`](dbn::enums::SType).
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Symbols are compared in order, so use [`sorted()`](Self::sorted) to compare the
+/// members of two sets of symbols regardless of order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Symbols {
     /// Sentinel value for all symbols in a dataset.
     All,
@@ -48,6 +82,10 @@ pub enum Symbols {
     Ids(Vec<u32>),
     /// A set of symbols.
     Symbols(Vec<String>),
+    /// A set of symbols, each with its own [`SType`](dbn::enums::SType), such as parent
+    /// symbols mixed with raw symbols. These override the `stype_in` of a request or
+    /// subscription, which will be split into one per `SType`.
+    Smart(Vec<(dbn::SType, String)>),
 }
 
 const ALL_SYMBOLS: &str = "ALL_SYMBOLS";
@@ -67,7 +105,63 @@ impl Symbols {
                 }
             }),
             Symbols::Symbols(symbols) => symbols.join(","),
+            Symbols::Smart(symbols) => symbols
+                .iter()
+                .map(|(_, symbol)| symbol.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+
+    /// Groups the symbols by their [`SType`](dbn::enums::SType) in order of first
+    /// appearance. For all variants other than [`Smart`](Self::Smart), returns a single
+    /// group with `stype_in`.
+    pub fn group_by_stype(&self, stype_in: dbn::SType) -> Vec<(dbn::SType, Symbols)> {
+        let Symbols::Smart(smart_symbols) = self else {
+            return vec![(stype_in, self.clone())];
+        };
+        let mut groups: Vec<(dbn::SType, Vec<String>)> = Vec::new();
+        for (stype, symbol) in smart_symbols {
+            if let Some((_, symbols)) = groups.iter_mut().find(|(s, _)| s == stype) {
+                symbols.push(symbol.clone());
+            } else {
+                groups.push((*stype, vec![symbol.clone()]));
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(stype, symbols)| (stype, Symbols::Symbols(symbols)))
+            .collect()
+    }
+
+    /// Returns the number of symbols, or `None` for [`All`](Self::All).
+    #[cfg(feature = "historical")]
+    pub(crate) fn count(&self) -> Option<usize> {
+        match self {
+            Symbols::All => None,
+            Symbols::Ids(ids) => Some(ids.len()),
+            Symbols::Symbols(symbols) => Some(symbols.len()),
+            Symbols::Smart(symbols) => Some(symbols.len()),
+        }
+    }
+
+    /// Returns the single symbology group for endpoints that can only make one
+    /// request.
+    #[cfg(feature = "historical")]
+    pub(crate) fn single_stype_group(
+        &self,
+        stype_in: dbn::SType,
+    ) -> crate::Result<(dbn::SType, Symbols)> {
+        let mut groups = self.group_by_stype(stype_in);
+        if groups.len() > 1 {
+            return Err(Error::bad_arg(
+                "symbols",
+                "smart symbols with more than one stype aren't supported by this method",
+            ));
         }
+        groups
+            .pop()
+            .ok_or_else(|| Error::bad_arg("symbols", "must contain at least one symbol"))
     }
 
     #[cfg(feature = "live")]
@@ -94,6 +188,324 @@ impl Symbols {
                 .chunks(CHUNK_SIZE)
                 .map(|chunk| chunk.join(","))
                 .collect(),
+            Symbols::Smart(symbols) => symbols
+                .chunks(CHUNK_SIZE)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .map(|(_, symbol)| symbol.as_str())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .collect(),
+        }
+    }
+
+    #[cfg(feature = "historical")]
+    /// Splits the symbols into groups of at most `chunk_size` symbols to stay within
+    /// the request limits of the historical API.
+    pub(crate) fn chunks(&self, chunk_size: usize) -> Vec<Symbols> {
+        match self {
+            Symbols::Ids(ids) if ids.len() > chunk_size => ids
+                .chunks(chunk_size)
+                .map(|chunk| Symbols::Ids(chunk.to_vec()))
+                .collect(),
+            Symbols::Symbols(symbols) if symbols.len() > chunk_size => symbols
+                .chunks(chunk_size)
+                .map(|chunk| Symbols::Symbols(chunk.to_vec()))
+                .collect(),
+            Symbols::Smart(symbols) if symbols.len() > chunk_size => symbols
+                .chunks(chunk_size)
+                .map(|chunk| Symbols::Smart(chunk.to_vec()))
+                .collect(),
+            _ => vec![self.clone()],
+        }
+    }
+
+    /// Validates the symbols against the format expected for `stype_in` and returns
+    /// them in a normalized form, for catching malformed symbols before sending a
+    /// request. Leading and trailing whitespace is removed from all symbols and:
+    /// - [`InstrumentId`](dbn::SType::InstrumentId) symbols must be valid `u32`s and are
+    ///   converted to [`Ids`](Self::Ids)
+    /// - [`RawSymbol`](dbn::SType::RawSymbol) symbols must be printable ASCII without
+    ///   commas and fit in a DBN symbol field
+    /// - [`Parent`](dbn::SType::Parent) symbols must be of the form `ROOT.FUT` or
+    ///   `ROOT.OPT` and are converted to uppercase
+    /// - [`Continuous`](dbn::SType::Continuous) symbols must be of the form
+    ///   `ROOT.c.0`, where the rule is one of `c`, `n`, or `v`, and the root is
+    ///   converted to uppercase
+    ///
+    /// [`Smart`](Self::Smart) symbols are validated against their own `SType` and
+    /// `stype_in` is ignored.
+    ///
+    /// # Errors
+    /// This function returns an error if there are no symbols, if any symbol is
+    /// malformed for its `SType`, or if [`Ids`](Self::Ids) are passed with an `SType`
+    /// other than `InstrumentId`.
+    pub fn normalize(&self, stype_in: dbn::SType) -> crate::Result<Symbols> {
+        match self {
+            Symbols::All => Ok(Symbols::All),
+            Symbols::Ids(ids) if ids.is_empty() => Err(Error::bad_arg(
+                "symbols",
+                "must contain at least one symbol",
+            )),
+            Symbols::Ids(_) if stype_in != dbn::SType::InstrumentId => Err(Error::bad_arg(
+                "symbols",
+                format!("instrument IDs can't be used with stype_in {stype_in}"),
+            )),
+            Symbols::Ids(ids) => Ok(Symbols::Ids(ids.clone())),
+            Symbols::Symbols(symbols) if symbols.is_empty() => Err(Error::bad_arg(
+                "symbols",
+                "must contain at least one symbol",
+            )),
+            Symbols::Symbols(symbols) if symbols.len() == 1 && symbols[0].trim() == ALL_SYMBOLS => {
+                Ok(Symbols::All)
+            }
+            Symbols::Symbols(symbols) if stype_in == dbn::SType::InstrumentId => symbols
+                .iter()
+                .map(|symbol| {
+                    symbol.trim().parse::<u32>().map_err(|_| {
+                        Error::bad_arg(
+                            "symbols",
+                            format!("invalid instrument ID `{symbol}`: expected a u32"),
+                        )
+                    })
+                })
+                .collect::<crate::Result<Vec<_>>>()
+                .map(Symbols::Ids),
+            Symbols::Symbols(symbols) => symbols
+                .iter()
+                .map(|symbol| normalize_symbol(stype_in, symbol))
+                .collect::<crate::Result<Vec<_>>>()
+                .map(Symbols::Symbols),
+            Symbols::Smart(symbols) if symbols.is_empty() => Err(Error::bad_arg(
+                "symbols",
+                "must contain at least one symbol",
+            )),
+            Symbols::Smart(symbols) => symbols
+                .iter()
+                .map(|(stype, symbol)| Ok((*stype, normalize_symbol(*stype, symbol)?)))
+                .collect::<crate::Result<Vec<_>>>()
+                .map(Symbols::Smart),
+        }
+    }
+
+    /// Returns the symbols sorted and without duplicates, for comparing or diffing
+    /// sets of symbols regardless of order.
+    pub fn sorted(&self) -> Symbols {
+        let mut sorted = self.clone();
+        match &mut sorted {
+            Symbols::All => {}
+            Symbols::Ids(ids) => {
+                ids.sort_unstable();
+                ids.dedup();
+            }
+            Symbols::Symbols(symbols) => {
+                symbols.sort_unstable();
+                symbols.dedup();
+            }
+            Symbols::Smart(symbols) => {
+                symbols.sort_unstable_by(|a, b| smart_key(a).cmp(&smart_key(b)));
+                symbols.dedup();
+            }
+        }
+        sorted
+    }
+
+    /// Removes duplicate symbols, keeping the first occurrence of each.
+    pub fn dedup(&mut self) {
+        match self {
+            Symbols::All => {}
+            Symbols::Ids(ids) => dedup_in_order(ids),
+            Symbols::Symbols(symbols) => dedup_in_order(symbols),
+            Symbols::Smart(symbols) => dedup_in_order(symbols),
+        }
+    }
+
+    /// Returns the symbols in either `self` or `other` without duplicates, in order of
+    /// first appearance. The union with [`All`](Self::All) is always `All`.
+    ///
+    /// # Errors
+    /// This function returns an error if `self` and `other` are different variants
+    /// other than `All`.
+    pub fn union(&self, other: &Symbols) -> crate::Result<Symbols> {
+        let mut union = match (self, other) {
+            (Symbols::All, _) | (_, Symbols::All) => return Ok(Symbols::All),
+            (Symbols::Ids(ids), Symbols::Ids(other_ids)) => {
+                Symbols::Ids([ids.as_slice(), other_ids].concat())
+            }
+            (Symbols::Symbols(symbols), Symbols::Symbols(other_symbols)) => {
+                Symbols::Symbols([symbols.as_slice(), other_symbols].concat())
+            }
+            (Symbols::Smart(symbols), Symbols::Smart(other_symbols)) => {
+                Symbols::Smart([symbols.as_slice(), other_symbols].concat())
+            }
+            _ => return Err(self.variant_mismatch(other)),
+        };
+        union.dedup();
+        Ok(union)
+    }
+
+    /// Returns the symbols in `self` that aren't in `other`, in their original order.
+    /// Removing [`All`](Self::All) leaves no symbols.
+    ///
+    /// # Errors
+    /// This function returns an error if `self` is `All` and `other` isn't, since the
+    /// symbols of a dataset aren't known, or if `self` and `other` are different
+    /// variants.
+    pub fn difference(&self, other: &Symbols) -> crate::Result<Symbols> {
+        fn retain_missing<T: Clone + Eq + Hash>(items: &[T], other: &[T]) -> Vec<T> {
+            let other = other.iter().collect::<HashSet<_>>();
+            items
+                .iter()
+                .filter(|item| !other.contains(item))
+                .cloned()
+                .collect()
+        }
+
+        Ok(match (self, other) {
+            (Symbols::All, Symbols::All) => Symbols::Symbols(Vec::new()),
+            (Symbols::All, _) => {
+                return Err(Error::bad_arg(
+                    "other",
+                    format!("can't be removed from {ALL_SYMBOLS}"),
+                ))
+            }
+            (Symbols::Ids(_), Symbols::All) => Symbols::Ids(Vec::new()),
+            (Symbols::Symbols(_), Symbols::All) => Symbols::Symbols(Vec::new()),
+            (Symbols::Smart(_), Symbols::All) => Symbols::Smart(Vec::new()),
+            (Symbols::Ids(ids), Symbols::Ids(other_ids)) => {
+                Symbols::Ids(retain_missing(ids, other_ids))
+            }
+            (Symbols::Symbols(symbols), Symbols::Symbols(other_symbols)) => {
+                Symbols::Symbols(retain_missing(symbols, other_symbols))
+            }
+            (Symbols::Smart(symbols), Symbols::Smart(other_symbols)) => {
+                Symbols::Smart(retain_missing(symbols, other_symbols))
+            }
+            _ => return Err(self.variant_mismatch(other)),
+        })
+    }
+
+    fn variant_index(&self) -> u8 {
+        match self {
+            Symbols::All => 0,
+            Symbols::Ids(_) => 1,
+            Symbols::Symbols(_) => 2,
+            Symbols::Smart(_) => 3,
+        }
+    }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Symbols::All => "All",
+            Symbols::Ids(_) => "Ids",
+            Symbols::Symbols(_) => "Symbols",
+            Symbols::Smart(_) => "Smart",
+        }
+    }
+
+    fn variant_mismatch(&self, other: &Symbols) -> Error {
+        Error::bad_arg(
+            "other",
+            format!(
+                "can't combine {} with {} symbols",
+                other.variant_name(),
+                self.variant_name()
+            ),
+        )
+    }
+}
+
+fn smart_key((stype, symbol): &(dbn::SType, String)) -> (u8, &str) {
+    (*stype as u8, symbol.as_str())
+}
+
+fn dedup_in_order<T: Clone + Eq + Hash>(items: &mut Vec<T>) {
+    let mut seen = HashSet::with_capacity(items.len());
+    items.retain(|item| seen.insert(item.clone()));
+}
+
+fn normalize_symbol(stype: dbn::SType, symbol: &str) -> crate::Result<String> {
+    let symbol = symbol.trim();
+    let invalid = |expected: &str| {
+        Error::bad_arg(
+            "symbols",
+            format!("invalid {stype} symbol `{symbol}`: {expected}"),
+        )
+    };
+    let is_root = |root: &str| !root.is_empty() && root.chars().all(|c| c.is_ascii_alphanumeric());
+    if symbol.is_empty() {
+        return Err(invalid("must not be empty"));
+    }
+    match stype {
+        dbn::SType::InstrumentId => symbol
+            .parse::<u32>()
+            .map(|id| id.to_string())
+            .map_err(|_| invalid("expected a u32")),
+        dbn::SType::RawSymbol => {
+            if !symbol.chars().all(|c| c.is_ascii_graphic() || c == ' ') || symbol.contains(',') {
+                Err(invalid("must be printable ASCII without commas"))
+            } else if symbol.len() >= dbn::SYMBOL_CSTR_LEN {
+                Err(invalid(&format!(
+                    "must be shorter than {} characters",
+                    dbn::SYMBOL_CSTR_LEN
+                )))
+            } else {
+                Ok(symbol.to_owned())
+            }
+        }
+        dbn::SType::Parent => match symbol.rsplit_once('.') {
+            Some((root, asset))
+                if is_root(root)
+                    && (asset.eq_ignore_ascii_case("FUT") || asset.eq_ignore_ascii_case("OPT")) =>
+            {
+                Ok(symbol.to_ascii_uppercase())
+            }
+            _ => Err(invalid("expected `ROOT.FUT` or `ROOT.OPT`")),
+        },
+        dbn::SType::Continuous => {
+            let mut parts = symbol.split('.');
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(root), Some(rule), Some(rank), None)
+                    if is_root(root)
+                        && matches!(rule, "c" | "n" | "v" | "C" | "N" | "V")
+                        && rank.parse::<u32>().is_ok() =>
+                {
+                    Ok(format!(
+                        "{}.{}.{rank}",
+                        root.to_ascii_uppercase(),
+                        rule.to_ascii_lowercase()
+                    ))
+                }
+                _ => Err(invalid(
+                    "expected `ROOT.RULE.RANK` with a rule of `c`, `n`, or `v`",
+                )),
+            }
+        }
+        _ => Ok(symbol.to_owned()),
+    }
+}
+
+impl PartialOrd for Symbols {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbols {
+    /// Orders by variant, with [`All`](Self::All) first, then by the symbols in order.
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Symbols::Ids(ids), Symbols::Ids(other_ids)) => ids.cmp(other_ids),
+            (Symbols::Symbols(symbols), Symbols::Symbols(other_symbols)) => {
+                symbols.cmp(other_symbols)
+            }
+            (Symbols::Smart(symbols), Symbols::Smart(other_symbols)) => symbols
+                .iter()
+                .map(smart_key)
+                .cmp(other_symbols.iter().map(smart_key)),
+            _ => self.variant_index().cmp(&other.variant_index()),
         }
     }
 }
@@ -122,6 +534,16 @@ impl Display for Symbols {
                 }
                 Ok(())
             }
+            Symbols::Smart(symbols) => {
+                for (i, (stype, sym)) in symbols.iter().enumerate() {
+                    if i == 0 {
+                        write!(f, "{sym} ({stype})")?;
+                    } else {
+                        write!(f, ", {sym} ({stype})")?;
+                    }
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -162,6 +584,24 @@ impl From<Vec<&str>> for Symbols {
     }
 }
 
+impl FromIterator<u32> for Symbols {
+    fn from_iter<T: IntoIterator<Item = u32>>(iter: T) -> Self {
+        Symbols::Ids(iter.into_iter().collect())
+    }
+}
+
+impl FromIterator<String> for Symbols {
+    fn from_iter<T: IntoIterator<Item = String>>(iter: T) -> Self {
+        Symbols::Symbols(iter.into_iter().collect())
+    }
+}
+
+impl<'a> FromIterator<&'a str> for Symbols {
+    fn from_iter<T: IntoIterator<Item = &'a str>>(iter: T) -> Self {
+        Symbols::Symbols(iter.into_iter().map(ToOwned::to_owned).collect())
+    }
+}
+
 pub(crate) fn key_from_env() -> crate::Result<String> {
     std::env::var("DATABENTO_API_KEY").map_err(|e| {
         Error::bad_arg(
@@ -176,7 +616,38 @@ pub(crate) fn key_from_env() -> crate::Result<String> {
     })
 }
 
-#[cfg(feature = "historical")]
+#[cfg(feature = "serde")]
+impl Serialize for Symbols {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Symbols::All => serializer.serialize_str(ALL_SYMBOLS),
+            Symbols::Ids(ids) => ids.serialize(serializer),
+            Symbols::Symbols(symbols) => symbols.serialize(serializer),
+            Symbols::Smart(symbols) => symbols
+                .iter()
+                .map(|(stype, symbol)| SmartSymbol {
+                    stype: *stype,
+                    symbol: symbol.clone(),
+                })
+                .collect::<Vec<_>>()
+                .serialize(serializer),
+        }
+    }
+}
+
+/// The serialized form of an entry of [`Symbols::Smart`].
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SmartSymbol {
+    #[serde(with = "crate::serialize::display_from_str")]
+    stype: dbn::SType,
+    symbol: String,
+}
+
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for Symbols {
     fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
@@ -189,6 +660,7 @@ impl<'de> Deserialize<'de> for Symbols {
             Ids(Vec<u32>),
             Symbol(String),
             Symbols(Vec<String>),
+            Smart(Vec<SmartSymbol>),
         }
         let ir = Helper::deserialize(deserializer)?;
         Ok(match ir {
@@ -197,27 +669,50 @@ impl<'de> Deserialize<'de> for Symbols {
             Helper::Symbol(symbol) if symbol == ALL_SYMBOLS => Symbols::All,
             Helper::Symbol(symbol) => Symbols::Symbols(vec![symbol]),
             Helper::Symbols(symbols) => Symbols::Symbols(symbols),
+            Helper::Smart(symbols) => Symbols::Smart(
+                symbols
+                    .into_iter()
+                    .map(|SmartSymbol { stype, symbol }| (stype, symbol))
+                    .collect(),
+            ),
         })
     }
 }
 
-/// A struct for holding an API key that implements Debug, but will only print the last
-/// five characters of the key.
+/// A validated Databento API key. Its [`Debug`](fmt::Debug) and [`Display`]
+/// implementations only print the last five characters of the key so it can't be
+/// accidentally logged. With the `zeroize` feature, the key is cleared from memory when
+/// dropped.
+///
+/// Can be passed to the `api_key()` methods of the historical and live client builders.
 #[derive(Clone)]
 pub struct ApiKey(String);
 
 pub(crate) const BUCKET_ID_LENGTH: usize = 5;
 
 impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{self}\"")
+    }
+}
+
+impl Display for ApiKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "\"…{}\"",
+            "…{}",
             &self.0[self.0.len().saturating_sub(BUCKET_ID_LENGTH)..]
         )
     }
 }
 
+#[cfg(feature = "zeroize")]
+impl Drop for ApiKey {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.0);
+    }
+}
+
 impl ApiKey {
     /// Validates `key` meets requirements of an API key.
     ///
@@ -248,6 +743,16 @@ impl ApiKey {
         }
     }
 
+    /// Reads and validates the API key from the `DATABENTO_API_KEY` environment
+    /// variable.
+    ///
+    /// # Errors
+    /// This function returns an error when the environment variable is not set or the
+    /// key is invalid.
+    pub fn from_env() -> crate::Result<ApiKey> {
+        Self::new(key_from_env()?)
+    }
+
     /// Returns a slice of the last 5 characters of the key.
     #[cfg(feature = "live")]
     pub fn bucket_id(&self) -> &str {
@@ -277,6 +782,8 @@ pub(crate) fn body_contains(
 
 #[cfg(test)]
 mod tests {
+    use dbn::SType;
+
     use super::*;
 
     #[test]
@@ -294,6 +801,166 @@ mod tests {
         assert_eq!(symbol_res[4], Symbols::Ids(vec![1001]));
     }
 
+    #[test]
+    fn test_serialize_symbols() {
+        let symbols = vec![
+            Symbols::All,
+            Symbols::Ids(vec![1, 2, 3]),
+            Symbols::Symbols(vec!["ESZ3".to_owned(), "CLZ3".to_owned()]),
+        ];
+        let json = serde_json::to_string(&symbols).unwrap();
+        assert_eq!(json, r#"["ALL_SYMBOLS",[1,2,3],["ESZ3","CLZ3"]]"#);
+        let round_trip: Vec<Symbols> = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_trip, symbols);
+    }
+
+    #[test]
+    fn test_smart_symbols_group_by_stype() {
+        let target = Symbols::Smart(vec![
+            (SType::Parent, "ES.FUT".to_owned()),
+            (SType::RawSymbol, "CLZ4".to_owned()),
+            (SType::Parent, "NQ.FUT".to_owned()),
+        ]);
+        assert_eq!(
+            target.group_by_stype(SType::InstrumentId),
+            vec![
+                (
+                    SType::Parent,
+                    Symbols::Symbols(vec!["ES.FUT".to_owned(), "NQ.FUT".to_owned()])
+                ),
+                (SType::RawSymbol, Symbols::Symbols(vec!["CLZ4".to_owned()])),
+            ]
+        );
+        assert_eq!(
+            Symbols::All.group_by_stype(SType::Parent),
+            vec![(SType::Parent, Symbols::All)]
+        );
+        let json = serde_json::to_string(&target).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"stype":"parent","symbol":"ES.FUT"},{"stype":"raw_symbol","symbol":"CLZ4"},{"stype":"parent","symbol":"NQ.FUT"}]"#
+        );
+        assert_eq!(serde_json::from_str::<Symbols>(&json).unwrap(), target);
+    }
+
+    #[test]
+    fn test_symbols_from_iter() {
+        assert_eq!(
+            ["ESZ3", "CLZ3"].into_iter().collect::<Symbols>(),
+            Symbols::Symbols(vec!["ESZ3".to_owned(), "CLZ3".to_owned()])
+        );
+        assert_eq!((1..=3).collect::<Symbols>(), Symbols::Ids(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_symbols_set_operations() {
+        let target = Symbols::from(vec!["ESZ3", "CLZ3", "ESZ3"]);
+        let sorted = target.sorted();
+        assert_eq!(sorted, Symbols::from(vec!["CLZ3", "ESZ3"]));
+        assert_ne!(target, sorted);
+        let mut deduped = target.clone();
+        deduped.dedup();
+        assert_eq!(deduped, Symbols::from(vec!["ESZ3", "CLZ3"]));
+        let other = Symbols::from(vec!["NQZ3", "ESZ3"]);
+        assert_eq!(
+            target.union(&other).unwrap(),
+            Symbols::from(vec!["ESZ3", "CLZ3", "NQZ3"])
+        );
+        assert_eq!(
+            target.difference(&other).unwrap(),
+            Symbols::from(vec!["CLZ3"])
+        );
+        assert_eq!(target.union(&Symbols::All).unwrap(), Symbols::All);
+        assert_eq!(
+            target.difference(&Symbols::All).unwrap(),
+            Symbols::Symbols(Vec::new())
+        );
+        assert!(Symbols::All.difference(&other).is_err());
+        assert!(target.union(&Symbols::Ids(vec![1])).is_err());
+
+        let smart = Symbols::Smart(vec![
+            (SType::RawSymbol, "ESZ3".to_owned()),
+            (SType::Parent, "ES.FUT".to_owned()),
+            (SType::RawSymbol, "ESZ3".to_owned()),
+        ]);
+        assert_eq!(smart.sorted(), smart.sorted().sorted());
+        assert_eq!(smart.sorted().to_api_string().split(',').count(), 2);
+
+        let mut ordered = vec![Symbols::Ids(vec![2]), sorted.clone(), Symbols::All];
+        ordered.sort();
+        assert_eq!(
+            ordered,
+            [Symbols::All, Symbols::Ids(vec![2]), sorted.clone()]
+        );
+        let keys = HashSet::from([sorted, target.sorted()]);
+        assert_eq!(keys.len(), 1);
+    }
+
+    #[test]
+    fn test_symbols_chunks() {
+        assert_eq!(Symbols::All.chunks(2), vec![Symbols::All]);
+        assert_eq!(
+            Symbols::Ids(vec![1, 2, 3]).chunks(2),
+            vec![Symbols::Ids(vec![1, 2]), Symbols::Ids(vec![3])]
+        );
+        assert_eq!(
+            Symbols::from(vec!["A", "B"]).chunks(2),
+            vec![Symbols::from(vec!["A", "B"])]
+        );
+    }
+
+    #[test]
+    fn test_symbols_normalize() {
+        assert_eq!(
+            Symbols::from(vec![" es.fut", "CL.opt "])
+                .normalize(SType::Parent)
+                .unwrap(),
+            Symbols::from(vec!["ES.FUT", "CL.OPT"])
+        );
+        assert!(Symbols::from("ES")
+            .normalize(SType::Parent)
+            .unwrap_err()
+            .to_string()
+            .contains("ROOT.FUT"));
+        assert_eq!(
+            Symbols::from("es.C.0")
+                .normalize(SType::Continuous)
+                .unwrap(),
+            Symbols::from("ES.c.0")
+        );
+        assert!(Symbols::from("ES.x.0")
+            .normalize(SType::Continuous)
+            .is_err());
+        assert_eq!(
+            Symbols::from(vec!["1", " 2"])
+                .normalize(SType::InstrumentId)
+                .unwrap(),
+            Symbols::Ids(vec![1, 2])
+        );
+        assert!(Symbols::Ids(vec![1]).normalize(SType::RawSymbol).is_err());
+        assert!(Symbols::from("ESM4,NQM4")
+            .normalize(SType::RawSymbol)
+            .is_err());
+        assert_eq!(
+            Symbols::from("ALL_SYMBOLS")
+                .normalize(SType::RawSymbol)
+                .unwrap(),
+            Symbols::All
+        );
+        assert_eq!(
+            Symbols::Smart(vec![
+                (SType::Parent, "es.fut".to_owned()),
+                (SType::RawSymbol, "ESM4 C5000".to_owned())
+            ])
+            .normalize(SType::RawSymbol)
+            .unwrap(),
+            Symbols::Smart(vec![
+                (SType::Parent, "ES.FUT".to_owned()),
+                (SType::RawSymbol, "ESM4 C5000".to_owned())
+            ])
+        );
+    }
+
     #[test]
     fn test_key_debug_truncates() {
         assert_eq!(
@@ -306,4 +973,11 @@ mod tests {
     fn test_key_debug_doesnt_underflow() {
         assert_eq!(format!("{:?}", ApiKey("test".to_owned())), "\"…test\"");
     }
+
+    #[test]
+    fn test_key_display_truncates() {
+        let key = ApiKey::new("32-character-with-lots-of-filler".to_owned()).unwrap();
+        assert_eq!(key.to_string(), "…iller");
+        assert_eq!(key.as_str(), "32-character-with-lots-of-filler");
+    }
 }
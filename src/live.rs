@@ -1,9 +1,22 @@
 //! The Live client and related API types. Used for both real-time data and intraday historical.
 
+pub mod buffer;
 mod client;
+pub mod demux;
+pub mod groups;
+pub mod json_lines;
+pub mod latency;
 pub mod protocol;
+pub mod rate_limit;
+pub mod recorder;
+pub mod shutdown;
+pub mod snapshot;
 
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
 
 use dbn::{SType, Schema, VersionUpgradePolicy};
 use time::{Duration, OffsetDateTime};
@@ -11,20 +24,36 @@ use tokio::net::{lookup_host, ToSocketAddrs};
 use tracing::warn;
 use typed_builder::TypedBuilder;
 
-use crate::{ApiKey, Symbols};
+use crate::{
+    credentials::SharedCredentials, filter::InstrumentFilter, ApiKey, CredentialsProvider, Symbols,
+};
 
-pub use client::Client;
+pub use buffer::{BufferedReceiver, OverflowPolicy};
+pub use client::{Client, Receiver, Sender};
+pub use groups::SubscriptionGroups;
+pub use rate_limit::RateLimit;
+pub use shutdown::{run_until_shutdown, RecordHandler};
+pub use snapshot::Snapshot;
 
 /// A subscription for real-time or intraday historical data.
 #[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Subscription {
     /// The symbols of the instruments to subscribe to.
     #[builder(setter(into))]
     pub symbols: Symbols,
     /// The data record schema of data to subscribe to.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::display_from_str"))]
     pub schema: Schema,
     /// The symbology type of the symbols in [`symbols`](Self::symbols).
     #[builder(default = SType::RawSymbol)]
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default = "crate::serialize::default_stype_in",
+            with = "crate::serialize::display_from_str"
+        )
+    )]
     pub stype_in: SType,
     /// If specified, requests available data since that time (inclusive), based on
     /// [`ts_event`](dbn::RecordHeader::ts_event). When `None`, only real-time data is sent.
@@ -32,13 +61,77 @@ pub struct Subscription {
     /// Setting this field is not supported once the session has been started with
     /// [`LiveClient::start`](crate::LiveClient::start).
     #[builder(default, setter(strip_option))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "time::serde::rfc3339::option")
+    )]
     pub start: Option<OffsetDateTime>,
+    /// If specified, the client will stop returning records once it receives a data
+    /// record with a [`ts_event`](dbn::RecordHeader::ts_event) at or after this time
+    /// (exclusive). The gateway doesn't support ending subscriptions, so this is
+    /// enforced by [`LiveClient::next_record()`](crate::LiveClient::next_record),
+    /// which returns `Ok(None)` once the end of all subscriptions has been reached. It
+    /// has no effect if any subscription has no end.
+    ///
+    /// This is primarily useful with [`start`](Self::start) for bounded intraday
+    /// replays.
+    #[builder(default, setter(strip_option))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "time::serde::rfc3339::option")
+    )]
+    pub end: Option<OffsetDateTime>,
     #[doc(hidden)]
     /// Request subscription with snapshot. Defaults to `false`. Conflicts with the `start` parameter.
     #[builder(setter(strip_bool))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub use_snapshot: bool,
+    /// The name of the group of related subscriptions this subscription is part of,
+    /// which can be removed together with
+    /// [`LiveClient::unsubscribe_group()`](crate::LiveClient::unsubscribe_group).
+    /// Defaults to `None`. Not sent to the gateway.
+    #[builder(default, setter(strip_option, into))]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub group: Option<String>,
+    /// The priority of the records of this subscription, where higher values are more
+    /// important. Used by [`OverflowPolicy::DropLowestPriority`] to decide which records
+    /// to drop first. Defaults to 0, the lowest priority. Not sent to the gateway.
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: u8,
+}
+
+/// The state of a live session needed to resume it in a new client with
+/// [`LiveClient::restore()`](Client::restore), such as after restarting a consumer
+/// process. Created with [`LiveClient::session_snapshot()`](Client::session_snapshot).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionSnapshot {
+    /// The dataset of the session.
+    pub dataset: String,
+    /// The subscriptions of the session.
+    pub subscriptions: Vec<Subscription>,
+    /// The `ts_event` of the last data record received in the session, if any.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "time::serde::rfc3339::option")
+    )]
+    pub last_ts_event: Option<OffsetDateTime>,
 }
 
+/// The environment variable read by [`ClientBuilder::dataset_from_env()`].
+pub const DATASET_ENV_VAR: &str = "DATABENTO_DATASET";
+/// The environment variable read by [`ClientBuilder::settings_from_env()`] to set
+/// [`send_ts_out`](ClientBuilder::send_ts_out). Either `true` or `false`.
+pub const SEND_TS_OUT_ENV_VAR: &str = "DATABENTO_SEND_TS_OUT";
+/// The environment variable read by [`ClientBuilder::settings_from_env()`] to set the
+/// [`heartbeat_interval`](ClientBuilder::heartbeat_interval) in seconds.
+pub const HEARTBEAT_INTERVAL_ENV_VAR: &str = "DATABENTO_HEARTBEAT_INTERVAL_SECS";
+/// The environment variable read by [`ClientBuilder::settings_from_env()`] to
+/// override the [address](ClientBuilder::addr) of the gateway, such as
+/// `"127.0.0.1:13000"`.
+pub const GATEWAY_ENV_VAR: &str = "DATABENTO_LIVE_GATEWAY";
+
 #[doc(hidden)]
 #[derive(Debug, Copy, Clone)]
 pub struct Unset;
@@ -55,6 +148,35 @@ pub struct ClientBuilder<AK, D> {
     send_ts_out: bool,
     upgrade_policy: VersionUpgradePolicy,
     heartbeat_interval: Option<Duration>,
+    user_agent_extension: Option<String>,
+    raise_gateway_errors: bool,
+    rate_limit: Option<RateLimit>,
+    instrument_filter: Option<InstrumentFilter>,
+    bind_addr: Option<IpAddr>,
+    ip_version: IpVersion,
+    connect_timeout: Option<std::time::Duration>,
+    connect_retries: u32,
+    credentials: Option<SharedCredentials>,
+    /// Gateway addresses keyed by uppercase dataset code.
+    gateway_overrides: HashMap<String, String>,
+    require_compatible_gateway: bool,
+}
+
+/// The IP version to use when connecting to the gateway.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum IpVersion {
+    /// Use whichever address the gateway resolves to first.
+    #[default]
+    Any,
+    /// Only connect over IPv4.
+    V4,
+    /// Only connect over IPv6.
+    V6,
 }
 
 impl Default for ClientBuilder<Unset, Unset> {
@@ -66,6 +188,17 @@ impl Default for ClientBuilder<Unset, Unset> {
             send_ts_out: false,
             upgrade_policy: VersionUpgradePolicy::UpgradeToV2,
             heartbeat_interval: None,
+            user_agent_extension: None,
+            raise_gateway_errors: false,
+            rate_limit: None,
+            instrument_filter: None,
+            bind_addr: None,
+            ip_version: IpVersion::Any,
+            connect_timeout: None,
+            connect_retries: 0,
+            credentials: None,
+            gateway_overrides: HashMap::new(),
+            require_compatible_gateway: false,
         }
     }
 }
@@ -103,8 +236,113 @@ impl<AK, D> ClientBuilder<AK, D> {
         self
     }
 
+    /// Extends the client identifier sent to the gateway during authentication.
+    /// Intended for library authors to identify traffic from their library. Must only
+    /// contain printable ASCII characters other than `|`, which is checked in
+    /// [`build()`](ClientBuilder::build).
+    pub fn user_agent_extension(mut self, extension: impl ToString) -> Self {
+        self.user_agent_extension = Some(extension.to_string());
+        self
+    }
+
+    /// Sets `raise_gateway_errors`, which when enabled causes error messages sent by
+    /// the gateway, such as for an invalid subscription, to be returned as
+    /// [`Error::Gateway`](crate::Error::Gateway) from
+    /// [`LiveClient::next_record()`](Client::next_record) instead of being passed
+    /// through as [`ErrorMsg`](dbn::ErrorMsg) records. Defaults to `false`.
+    pub fn raise_gateway_errors(mut self, raise_gateway_errors: bool) -> Self {
+        self.raise_gateway_errors = raise_gateway_errors;
+        self
+    }
+
+    /// Sets `rate_limit`, which limits the rate at which records are read from the
+    /// gateway. Intended for development and simulation environments. Reading slower
+    /// than the gateway sends data will eventually cause the gateway to disconnect
+    /// the client. Defaults to `None`, no limit.
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Sets `instrument_filter`, which causes
+    /// [`LiveClient::next_record_owned()`](Client::next_record_owned) and
+    /// [`BufferedReceiver`] to skip records for other instruments by checking only
+    /// their header. Useful when consuming a few instruments from a broad
+    /// subscription, such as a parent symbol. Defaults to `None`, no filter.
+    pub fn instrument_filter(mut self, instrument_filter: InstrumentFilter) -> Self {
+        self.instrument_filter = Some(instrument_filter);
+        self
+    }
+
+    /// Sets `require_compatible_gateway`, which when enabled causes connecting to fail
+    /// with [`Error::IncompatibleGateway`](crate::Error::IncompatibleGateway) if the
+    /// gateway's major version is later than the client supports. Otherwise a warning
+    /// is logged and the session continues. Defaults to `false`.
+    pub fn require_compatible_gateway(mut self, require_compatible_gateway: bool) -> Self {
+        self.require_compatible_gateway = require_compatible_gateway;
+        self
+    }
+
+    /// Sets the local address the connection to the gateway is bound to, such as to
+    /// send market data over a specific network interface on a multi-homed server. Only
+    /// gateway addresses of the same IP version are used. Defaults to `None`, where the
+    /// operating system chooses.
+    pub fn bind_addr(mut self, bind_addr: IpAddr) -> Self {
+        self.bind_addr = Some(bind_addr);
+        self
+    }
+
+    /// Sets the IP version used to connect to the gateway. Defaults to
+    /// [`IpVersion::Any`].
+    pub fn ip_version(mut self, ip_version: IpVersion) -> Self {
+        self.ip_version = ip_version;
+        self
+    }
+
+    /// Sets the maximum time to wait for the TCP connection to each gateway address
+    /// to be established. Defaults to `None`, where the operating system's timeout
+    /// applies.
+    pub fn connect_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the number of times connecting is retried when the gateway refuses the
+    /// connection, such as while it's restarting. The delay between attempts starts at
+    /// 250 milliseconds and doubles after each retry. Defaults to 0.
+    pub fn connect_retries(mut self, retries: u32) -> Self {
+        self.connect_retries = retries;
+        self
+    }
+
+    /// Overrides the gateway address used for each dataset in `overrides`, a mapping of
+    /// dataset codes to `host:port` addresses, such as for routing datasets through
+    /// different internal relays. Datasets without an override use the default
+    /// Databento gateway, and an address set with [`addr()`](Self::addr) takes
+    /// precedence over any override. Can be called several times to add more
+    /// overrides.
+    pub fn gateway_overrides<K, V>(mut self, overrides: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: AsRef<str>,
+        V: ToString,
+    {
+        self.gateway_overrides.extend(
+            overrides
+                .into_iter()
+                .map(|(dataset, addr)| (dataset.as_ref().to_ascii_uppercase(), addr.to_string())),
+        );
+        self
+    }
+
+    /// Returns the gateway addresses set with [`addr()`](Self::addr) or
+    /// [`resolve_gateway()`](ClientBuilder::resolve_gateway), if any.
+    pub fn gateway_addrs(&self) -> Option<&[SocketAddr]> {
+        self.addr.as_deref().map(Vec::as_slice)
+    }
+
     /// Overrides the address of the gateway the client will connect to. This is an
-    /// advanced method.
+    /// advanced method. Pass a single [`SocketAddr`] to pin the session to a specific
+    /// IP address.
     ///
     /// # Errors
     /// This function returns an error when `addr` fails to resolve.
@@ -117,6 +355,33 @@ impl<AK, D> ClientBuilder<AK, D> {
         self.addr = Some(Arc::new(addrs));
         Ok(self)
     }
+
+    /// Applies the optional settings set in the environment, leaving the others
+    /// unchanged:
+    /// - [`SEND_TS_OUT_ENV_VAR`]
+    /// - [`HEARTBEAT_INTERVAL_ENV_VAR`]
+    /// - [`GATEWAY_ENV_VAR`]
+    ///
+    /// # Errors
+    /// This function returns an error when one of the environment variables is invalid
+    /// or the gateway address fails to resolve.
+    pub async fn settings_from_env(mut self) -> crate::Result<Self> {
+        if let Some(send_ts_out) = env_var(SEND_TS_OUT_ENV_VAR)? {
+            self.send_ts_out = send_ts_out
+                .parse()
+                .map_err(|_| bad_env_var(SEND_TS_OUT_ENV_VAR, "true or false"))?;
+        }
+        if let Some(secs) = env_var(HEARTBEAT_INTERVAL_ENV_VAR)? {
+            let secs = secs
+                .parse()
+                .map_err(|_| bad_env_var(HEARTBEAT_INTERVAL_ENV_VAR, "a number of seconds"))?;
+            self = self.heartbeat_interval(Duration::seconds(secs));
+        }
+        if let Some(addr) = env_var(GATEWAY_ENV_VAR)? {
+            self = self.addr(addr).await?;
+        }
+        Ok(self)
+    }
 }
 
 impl ClientBuilder<Unset, Unset> {
@@ -124,6 +389,22 @@ impl ClientBuilder<Unset, Unset> {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Creates a builder configured entirely from the environment, such as for
+    /// containerized deployments. The API key is read from `DATABENTO_API_KEY`, the
+    /// dataset from [`DATASET_ENV_VAR`], and any optional
+    /// [settings](Self::settings_from_env).
+    ///
+    /// # Errors
+    /// This function returns an error when the API key or dataset isn't set, one of the
+    /// environment variables is invalid, or the gateway address fails to resolve.
+    pub async fn from_env() -> crate::Result<ClientBuilder<ApiKey, String>> {
+        Self::new()
+            .key_from_env()?
+            .dataset_from_env()?
+            .settings_from_env()
+            .await
+    }
 }
 
 impl<D> ClientBuilder<Unset, D> {
@@ -132,14 +413,30 @@ impl<D> ClientBuilder<Unset, D> {
     /// # Errors
     /// This function returns an error when the API key is invalid.
     pub fn key(self, key: impl ToString) -> crate::Result<ClientBuilder<ApiKey, D>> {
-        Ok(ClientBuilder {
+        Ok(self.api_key(ApiKey::new(key.to_string())?))
+    }
+
+    /// Sets the API key from an already validated [`ApiKey`].
+    pub fn api_key(self, key: ApiKey) -> ClientBuilder<ApiKey, D> {
+        ClientBuilder {
             addr: self.addr,
-            key: ApiKey::new(key.to_string())?,
+            key,
             dataset: self.dataset,
             send_ts_out: self.send_ts_out,
             upgrade_policy: self.upgrade_policy,
             heartbeat_interval: self.heartbeat_interval,
-        })
+            user_agent_extension: self.user_agent_extension,
+            raise_gateway_errors: self.raise_gateway_errors,
+            rate_limit: self.rate_limit,
+            instrument_filter: self.instrument_filter,
+            bind_addr: self.bind_addr,
+            ip_version: self.ip_version,
+            connect_timeout: self.connect_timeout,
+            connect_retries: self.connect_retries,
+            credentials: self.credentials,
+            gateway_overrides: self.gateway_overrides,
+            require_compatible_gateway: self.require_compatible_gateway,
+        }
     }
 
     /// Sets the API key reading it from the `DATABENTO_API_KEY` environment
@@ -149,8 +446,40 @@ impl<D> ClientBuilder<Unset, D> {
     /// This function returns an error when the environment variable is not set or the
     /// API key is invalid.
     pub fn key_from_env(self) -> crate::Result<ClientBuilder<ApiKey, D>> {
-        let key = crate::key_from_env()?;
-        self.key(key)
+        Ok(self.api_key(ApiKey::from_env()?))
+    }
+
+    /// Sets the API key by requesting it from `provider`. If the gateway rejects the
+    /// key during [`build()`](Self::build), a new key will be requested from the
+    /// provider and authentication retried once, which allows keys to be rotated.
+    ///
+    /// # Errors
+    /// This function returns an error when the provider fails to return a key or the
+    /// API key is invalid.
+    pub fn credentials_provider(
+        self,
+        provider: impl CredentialsProvider + 'static,
+    ) -> crate::Result<ClientBuilder<ApiKey, D>> {
+        let credentials = SharedCredentials::new(provider);
+        Ok(ClientBuilder {
+            addr: self.addr,
+            key: credentials.api_key()?,
+            dataset: self.dataset,
+            send_ts_out: self.send_ts_out,
+            upgrade_policy: self.upgrade_policy,
+            heartbeat_interval: self.heartbeat_interval,
+            user_agent_extension: self.user_agent_extension,
+            raise_gateway_errors: self.raise_gateway_errors,
+            rate_limit: self.rate_limit,
+            instrument_filter: self.instrument_filter,
+            bind_addr: self.bind_addr,
+            ip_version: self.ip_version,
+            connect_timeout: self.connect_timeout,
+            connect_retries: self.connect_retries,
+            credentials: Some(credentials),
+            gateway_overrides: self.gateway_overrides,
+            require_compatible_gateway: self.require_compatible_gateway,
+        })
     }
 }
 
@@ -164,36 +493,195 @@ impl<AK> ClientBuilder<AK, Unset> {
             send_ts_out: self.send_ts_out,
             upgrade_policy: self.upgrade_policy,
             heartbeat_interval: self.heartbeat_interval,
+            user_agent_extension: self.user_agent_extension,
+            raise_gateway_errors: self.raise_gateway_errors,
+            rate_limit: self.rate_limit,
+            instrument_filter: self.instrument_filter,
+            bind_addr: self.bind_addr,
+            ip_version: self.ip_version,
+            connect_timeout: self.connect_timeout,
+            connect_retries: self.connect_retries,
+            credentials: self.credentials,
+            gateway_overrides: self.gateway_overrides,
+            require_compatible_gateway: self.require_compatible_gateway,
         }
     }
+
+    /// Sets the dataset reading it from the [`DATASET_ENV_VAR`] environment variable.
+    ///
+    /// # Errors
+    /// This function returns an error when the environment variable is not set or
+    /// contains invalid unicode.
+    pub fn dataset_from_env(self) -> crate::Result<ClientBuilder<AK, String>> {
+        let dataset = env_var(DATASET_ENV_VAR)?
+            .ok_or_else(|| bad_env_var(DATASET_ENV_VAR, "a dataset code"))?;
+        Ok(self.dataset(dataset))
+    }
+}
+
+impl<AK> ClientBuilder<AK, String> {
+    /// Resolves the gateway of the dataset up-front, so resolution failures are
+    /// reported before connecting and the addresses can be inspected with
+    /// [`gateway_addrs()`](Self::gateway_addrs), such as for pinning the session to
+    /// one of them with [`addr()`](Self::addr). Does nothing if the address was already
+    /// overridden.
+    ///
+    /// # Errors
+    /// This function returns an error when the gateway fails to resolve.
+    pub async fn resolve_gateway(self) -> crate::Result<Self> {
+        if self.addr.is_some() {
+            return Ok(self);
+        }
+        let gateway = self.gateway();
+        self.addr(gateway).await
+    }
+
+    /// Returns the address of the gateway for the dataset, taking into account any
+    /// [overrides](Self::gateway_overrides).
+    pub fn gateway(&self) -> String {
+        self.gateway_overrides
+            .get(&self.dataset.to_ascii_uppercase())
+            .cloned()
+            .unwrap_or_else(|| protocol::determine_gateway(&self.dataset))
+    }
 }
 
 impl ClientBuilder<ApiKey, String> {
     /// Initializes the client and attempts to connect to the gateway.
     ///
     /// # Errors
-    /// This function returns an error when its unable
-    /// to connect and authenticate with the Live gateway.
+    /// This function returns an error when the user agent extension is invalid or it's
+    /// unable to connect and authenticate with the Live gateway.
     pub async fn build(self) -> crate::Result<Client> {
-        if let Some(addr) = self.addr {
-            Client::connect_with_addr(
-                addr.as_slice(),
-                self.key.0,
-                self.dataset,
-                self.send_ts_out,
-                self.upgrade_policy,
-                self.heartbeat_interval,
-            )
-            .await
+        if let Some(extension) = &self.user_agent_extension {
+            validate_user_agent_extension(extension)?;
+        }
+        let Some(credentials) = self.credentials.clone() else {
+            return self.connect().await;
+        };
+        match self.clone().connect().await {
+            Err(crate::Error::Auth(msg)) => {
+                warn!(
+                    msg,
+                    "Authentication failed, retrying with refreshed credentials"
+                );
+                let key = credentials.api_key()?;
+                Self { key, ..self }.connect().await
+            }
+            res => res,
+        }
+    }
+
+    async fn connect(self) -> crate::Result<Client> {
+        if let Some(addr) = self.addr.clone() {
+            Client::connect_with_builder(addr.as_slice(), self).await
         } else {
-            Client::connect(
-                self.key.0,
-                self.dataset,
-                self.send_ts_out,
-                self.upgrade_policy,
-                self.heartbeat_interval,
-            )
-            .await
+            let addr = self.gateway();
+            Client::connect_with_builder(addr, self).await
+        }
+    }
+}
+
+/// Checks `extension` can't alter the fields of the authentication request it's
+/// appended to, which are separated by `|` and terminated by a newline.
+fn validate_user_agent_extension(extension: &str) -> crate::Result<()> {
+    match extension
+        .chars()
+        .find(|&c| !(' '..='~').contains(&c) || c == '|')
+    {
+        Some(c) => Err(crate::Error::bad_arg(
+            "user_agent_extension",
+            format!("must only contain printable ASCII characters other than '|', found {c:?}"),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Reads the environment variable `name`, returning `None` if it's not set.
+fn env_var(name: &'static str) -> crate::Result<Option<String>> {
+    match std::env::var(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(std::env::VarError::NotUnicode(_)) => Err(crate::Error::bad_arg(
+            name,
+            format!("environment variable {name} contains invalid unicode"),
+        )),
+    }
+}
+
+fn bad_env_var(name: &'static str, expected: &str) -> crate::Error {
+    crate::Error::bad_arg(
+        name,
+        format!("environment variable {name} must be set to {expected}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_settings_from_env() {
+        std::env::set_var(SEND_TS_OUT_ENV_VAR, "true");
+        std::env::set_var(HEARTBEAT_INTERVAL_ENV_VAR, "15");
+        std::env::set_var(GATEWAY_ENV_VAR, "127.0.0.1:13000");
+        let target = ClientBuilder::new().settings_from_env().await.unwrap();
+        assert!(target.send_ts_out);
+        assert_eq!(target.heartbeat_interval, Some(Duration::seconds(15)));
+        assert_eq!(
+            target.addr.as_deref(),
+            Some(&vec!["127.0.0.1:13000".parse().unwrap()])
+        );
+        std::env::set_var(HEARTBEAT_INTERVAL_ENV_VAR, "soon");
+        assert!(ClientBuilder::new().settings_from_env().await.is_err());
+        std::env::remove_var(SEND_TS_OUT_ENV_VAR);
+        std::env::remove_var(HEARTBEAT_INTERVAL_ENV_VAR);
+        std::env::remove_var(GATEWAY_ENV_VAR);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_user_agent_extension() {
+        assert!(validate_user_agent_extension("my-lib/1.0 (linux)").is_ok());
+        for extension in [
+            "my-lib|ts_out=1",
+            "my-lib\ndataset=OPRA.PILLAR",
+            "my-lib\t",
+            "é",
+        ] {
+            let res = ClientBuilder::new()
+                .key("32-character-with-lots-of-filler")
+                .unwrap()
+                .dataset("XNAS.ITCH")
+                .user_agent_extension(extension)
+                .build()
+                .await;
+            assert!(
+                matches!(
+                    res,
+                    Err(crate::Error::BadArgument { ref param_name, .. })
+                        if param_name == "user_agent_extension"
+                ),
+                "{extension:?}"
+            );
         }
     }
+
+    #[test]
+    fn test_gateway_overrides() {
+        let target = ClientBuilder::new()
+            .gateway_overrides([("glbx.mdp3", "relay-1.internal:13000")])
+            .gateway_overrides([("OPRA.PILLAR".to_owned(), "relay-2.internal:13000")]);
+        assert_eq!(
+            target.clone().dataset(dbn::Dataset::GlbxMdp3).gateway(),
+            "relay-1.internal:13000"
+        );
+        assert_eq!(
+            target.clone().dataset("OPRA.PILLAR").gateway(),
+            "relay-2.internal:13000"
+        );
+        assert_eq!(
+            target.dataset("XNAS.ITCH").gateway(),
+            protocol::determine_gateway("XNAS.ITCH")
+        );
+    }
 }
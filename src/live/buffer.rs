@@ -0,0 +1,242 @@
+//! Buffering records between the live gateway connection and a slow consumer.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use dbn::RecordEnum;
+use tokio::{sync::Notify, task::JoinHandle};
+use tracing::warn;
+
+use super::Receiver;
+
+/// What a [`BufferedReceiver`] does with a new record when its queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Stop reading from the gateway until there's space in the queue. No records are
+    /// dropped, but the gateway may disconnect a client that falls too far behind.
+    #[default]
+    Block,
+    /// Drop the oldest record in the queue to make space for the new one.
+    DropOldest,
+    /// Drop the new record.
+    DropNewest,
+    /// Drop the oldest record with the lowest [priority](super::Subscription::priority)
+    /// in the queue to make space for the new one, or drop the new record if its
+    /// priority is lower than every queued record.
+    DropLowestPriority,
+}
+
+/// A [`Receiver`] that reads records from the gateway on a separate task into a
+/// bounded queue, so bursts of data don't stall the TCP connection or grow memory
+/// without bound. Created with [`Receiver::buffered()`].
+///
+/// The background task is stopped when the `BufferedReceiver` is dropped.
+pub struct BufferedReceiver {
+    queue: Arc<Queue>,
+    task: JoinHandle<()>,
+}
+
+struct Queue {
+    state: Mutex<QueueState>,
+    capacity: NonZeroUsize,
+    policy: OverflowPolicy,
+    dropped: AtomicU64,
+    not_empty: Notify,
+    not_full: Notify,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// The queued records and their priorities.
+    records: VecDeque<(u8, RecordEnum)>,
+    is_closed: bool,
+    error: Option<crate::Error>,
+}
+
+impl Receiver {
+    /// Converts the receiver into a [`BufferedReceiver`] that reads records into a
+    /// queue of at most `capacity` records on a separate task, handling a full queue
+    /// according to `policy`.
+    pub fn buffered(self, capacity: NonZeroUsize, policy: OverflowPolicy) -> BufferedReceiver {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(QueueState {
+                records: VecDeque::with_capacity(capacity.get()),
+                ..Default::default()
+            }),
+            capacity,
+            policy,
+            dropped: AtomicU64::new(0),
+            not_empty: Notify::new(),
+            not_full: Notify::new(),
+        });
+        let task = tokio::spawn(Self::fill_queue(self, queue.clone()));
+        BufferedReceiver { queue, task }
+    }
+
+    async fn fill_queue(mut self, queue: Arc<Queue>) {
+        let with_priority = queue.policy == OverflowPolicy::DropLowestPriority;
+        loop {
+            match self.next_prioritized_record(with_priority).await {
+                Ok(Some((priority, rec))) => queue.push(priority, rec).await,
+                Ok(None) => {
+                    queue.close(None);
+                    break;
+                }
+                Err(e) => {
+                    queue.close(Some(e));
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl BufferedReceiver {
+    /// Fetches the next record from the queue, waiting for one to be received if
+    /// the queue is empty.
+    ///
+    /// Returns `Ok(None)` once all queued records have been returned and the gateway
+    /// closed the connection or the [`end`](super::Subscription::end) of all
+    /// subscriptions has been reached.
+    ///
+    /// # Errors
+    /// This function returns an error once all queued records have been returned if
+    /// the background task encountered an error reading from the gateway. See
+    /// [`Receiver::next_record()`].
+    ///
+    /// # Cancel safety
+    /// This method is cancel safe. It can be used within a [`tokio::select!`] statement
+    /// without the potential for losing records.
+    pub async fn next_record(&mut self) -> crate::Result<Option<RecordEnum>> {
+        loop {
+            {
+                let mut state = self.queue.lock();
+                if let Some((_, rec)) = state.records.pop_front() {
+                    drop(state);
+                    self.queue.not_full.notify_one();
+                    return Ok(Some(rec));
+                }
+                if state.is_closed {
+                    return state.error.take().map_or(Ok(None), Err);
+                }
+            }
+            self.queue.not_empty.notified().await;
+        }
+    }
+
+    /// Returns the number of records currently in the queue.
+    pub fn len(&self) -> usize {
+        self.queue.lock().records.len()
+    }
+
+    /// Returns `true` if there are no records in the queue.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of records in the queue.
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.queue.capacity
+    }
+
+    /// Returns the policy for handling a full queue.
+    pub fn policy(&self) -> OverflowPolicy {
+        self.queue.policy
+    }
+
+    /// Returns the number of records dropped because the queue was full. Always 0 with
+    /// [`OverflowPolicy::Block`].
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for BufferedReceiver {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl fmt::Debug for BufferedReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferedReceiver")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .field("policy", &self.policy())
+            .field("dropped", &self.dropped())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Queue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        // The lock is never held across a panic
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn push(&self, priority: u8, rec: RecordEnum) {
+        loop {
+            {
+                let mut state = self.lock();
+                if state.records.len() < self.capacity.get() {
+                    state.records.push_back((priority, rec));
+                    break;
+                }
+                match self.policy {
+                    OverflowPolicy::Block => {}
+                    OverflowPolicy::DropOldest => {
+                        state.records.pop_front();
+                        state.records.push_back((priority, rec));
+                        self.record_drop();
+                        break;
+                    }
+                    OverflowPolicy::DropNewest => {
+                        self.record_drop();
+                        return;
+                    }
+                    OverflowPolicy::DropLowestPriority => {
+                        self.record_drop();
+                        let lowest = state
+                            .records
+                            .iter()
+                            .enumerate()
+                            .min_by_key(|(_, (priority, _))| *priority)
+                            .filter(|(_, (lowest, _))| *lowest <= priority)
+                            .map(|(idx, _)| idx);
+                        let Some(idx) = lowest else {
+                            return;
+                        };
+                        state.records.remove(idx);
+                        state.records.push_back((priority, rec));
+                        break;
+                    }
+                }
+            }
+            self.not_full.notified().await;
+        }
+        self.not_empty.notify_one();
+    }
+
+    fn record_drop(&self) {
+        let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        // Avoid flooding the logs
+        if dropped.is_power_of_two() {
+            warn!(dropped, "Dropped records because the buffer is full");
+        }
+    }
+
+    fn close(&self, error: Option<crate::Error>) {
+        let mut state = self.lock();
+        state.is_closed = true;
+        state.error = error;
+        drop(state);
+        self.not_empty.notify_one();
+    }
+}
@@ -1,21 +1,37 @@
-use std::fmt;
+use std::{
+    collections::HashMap,
+    fmt, io, mem,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use dbn::{
+    compat::{ErrorMsgV1, InstrumentDefMsgV1, SystemMsgV1},
     decode::dbn::{AsyncMetadataDecoder, AsyncRecordDecoder},
-    Metadata, RecordRef, VersionUpgradePolicy,
+    enums::rtype,
+    record::HasRType,
+    ErrorMsg, InstrumentDefMsg, MboMsg, Metadata, RecordEnum, RecordRef, RecordRefEnum, SType,
+    Schema, SystemMsg, VersionUpgradePolicy,
 };
-use time::Duration;
+use time::{Duration, OffsetDateTime};
 use tokio::{
     io::{AsyncWriteExt, BufReader, ReadHalf, WriteHalf},
-    net::{TcpStream, ToSocketAddrs},
+    net::{lookup_host, TcpSocket, TcpStream, ToSocketAddrs},
 };
-use tracing::{info, info_span, instrument, Span};
+use tracing::{error, info, info_span, instrument, warn, Span};
 
-use crate::ApiKey;
+use crate::{error::GatewayError, filter::InstrumentFilter, ApiKey, Error, Symbols};
 
 use super::{
-    protocol::{self, Protocol},
-    ClientBuilder, Subscription, Unset,
+    groups::SubscriptionGroups,
+    latency::{LatencySummary, LatencyTracker},
+    protocol::{self, Greeting, Protocol},
+    rate_limit::RateLimiter,
+    ClientBuilder, IpVersion, RateLimit, SessionSnapshot, Snapshot, Subscription, Unset,
 };
 
 /// The Live client. Used for subscribing to real-time and intraday historical market data.
@@ -25,12 +41,23 @@ use super::{
 pub struct Client {
     key: ApiKey,
     dataset: String,
+    gateway_addr: SocketAddr,
     send_ts_out: bool,
     upgrade_policy: VersionUpgradePolicy,
     heartbeat_interval: Option<Duration>,
+    raise_gateway_errors: bool,
+    rate_limiter: Option<RateLimiter>,
+    instrument_filter: Option<InstrumentFilter>,
     protocol: Protocol<WriteHalf<TcpStream>>,
     decoder: AsyncRecordDecoder<BufReader<ReadHalf<TcpStream>>>,
     session_id: String,
+    greeting: Greeting,
+    subscriptions: Vec<Subscription>,
+    groups: SubscriptionGroups,
+    metadata: Option<Metadata>,
+    dbn_version: Option<u8>,
+    progress: Progress,
+    latency: Option<LatencyTracker>,
     span: Span,
 }
 
@@ -74,27 +101,80 @@ impl Client {
         upgrade_policy: VersionUpgradePolicy,
         heartbeat_interval: Option<Duration>,
     ) -> crate::Result<Self> {
-        let key = ApiKey::new(key)?;
-        let stream = TcpStream::connect(addr).await?;
+        let builder = Self::builder()
+            .key(key)?
+            .dataset(dataset)
+            .send_ts_out(send_ts_out)
+            .upgrade_policy(upgrade_policy);
+        let builder = if let Some(heartbeat_interval) = heartbeat_interval {
+            builder.heartbeat_interval(heartbeat_interval)
+        } else {
+            builder
+        };
+        Self::connect_with_builder(addr, builder).await
+    }
+
+    pub(super) async fn connect_with_builder(
+        addr: impl ToSocketAddrs,
+        builder: ClientBuilder<ApiKey, String>,
+    ) -> crate::Result<Self> {
+        let ClientBuilder {
+            key,
+            dataset,
+            send_ts_out,
+            upgrade_policy,
+            heartbeat_interval,
+            user_agent_extension,
+            raise_gateway_errors,
+            rate_limit,
+            instrument_filter,
+            bind_addr,
+            ip_version,
+            connect_timeout,
+            connect_retries,
+            require_compatible_gateway,
+            ..
+        } = builder;
+        let stream = connect_tcp(
+            addr,
+            &ConnectOptions {
+                bind_addr,
+                ip_version,
+                timeout: connect_timeout,
+                retries: connect_retries,
+            },
+        )
+        .await?;
+        let gateway_addr = stream.peer_addr()?;
         let (recver, sender) = tokio::io::split(stream);
         let mut recver = BufReader::new(recver);
         let mut protocol = Protocol::new(sender);
-        let session_id = protocol
+        let (session_id, greeting) = protocol
             .authenticate(
                 &mut recver,
                 &key,
                 &dataset,
                 send_ts_out,
                 heartbeat_interval.map(|i| i.whole_seconds()),
+                user_agent_extension.as_deref(),
             )
             .await?;
+        match greeting.check_compatible() {
+            Err(e) if require_compatible_gateway => return Err(e),
+            Err(e) => warn!(%e, "Connected to a live gateway that may be incompatible"),
+            Ok(()) => {}
+        }
         let span = info_span!("LiveClient", %dataset, session_id);
         Ok(Self {
             key,
             dataset,
+            gateway_addr,
             send_ts_out,
             upgrade_policy,
             heartbeat_interval,
+            raise_gateway_errors,
+            rate_limiter: rate_limit.map(RateLimiter::new),
+            instrument_filter,
             protocol,
             // Pass a placeholder DBN version and should never fail because DBN_VERSION
             // is a valid DBN version. Correct version set in `start()`.
@@ -106,6 +186,13 @@ impl Client {
             )
             .unwrap(),
             session_id,
+            greeting,
+            subscriptions: Vec::new(),
+            groups: SubscriptionGroups::default(),
+            metadata: None,
+            dbn_version: None,
+            progress: Progress::default(),
+            latency: None,
             span,
         })
     }
@@ -128,11 +215,22 @@ impl Client {
         &self.dataset
     }
 
+    /// Returns the address of the gateway the client connected to.
+    pub fn gateway_addr(&self) -> SocketAddr {
+        self.gateway_addr
+    }
+
     /// Returns an identifier for the current Live session.
     pub fn session_id(&self) -> &str {
         &self.session_id
     }
 
+    /// Returns the version of the live gateway from the greeting it sent when the
+    /// client connected.
+    pub fn gateway_version(&self) -> &str {
+        self.greeting.lsg_version()
+    }
+
     /// Returns whether client is configured to request that the gateway send `ts_out`
     /// with each message.
     pub fn send_ts_out(&self) -> bool {
@@ -144,11 +242,103 @@ impl Client {
         self.upgrade_policy
     }
 
+    /// Changes the upgrade policy for decoding DBN from previous versions. Applies to
+    /// the metadata returned by [`start()`](Self::start) if it hasn't been called yet,
+    /// and to all records read afterward.
+    ///
+    /// Whether records are followed by `ts_out` is negotiated during authentication, so
+    /// it can only be changed on a new client with
+    /// [`ClientBuilder::send_ts_out()`].
+    pub fn set_upgrade_policy(&mut self, upgrade_policy: VersionUpgradePolicy) {
+        self.upgrade_policy = upgrade_policy;
+        self.decoder.set_upgrade_policy(upgrade_policy);
+    }
+
+    /// Returns the DBN version of the data sent by the gateway, or `None` if the
+    /// session hasn't been started. With an upgrade policy other than
+    /// [`AsIs`](VersionUpgradePolicy::AsIs), records may be upgraded to a later version
+    /// when decoded.
+    pub fn dbn_version(&self) -> Option<u8> {
+        self.dbn_version
+    }
+
     /// Returns the heartbeat interval override if there is one, otherwise `None`.
     pub fn heartbeat_interval(&self) -> Option<Duration> {
         self.heartbeat_interval
     }
 
+    /// Returns whether error messages sent by the gateway are returned as
+    /// [`Error::Gateway`](crate::Error::Gateway) instead of being passed through as
+    /// records.
+    pub fn raise_gateway_errors(&self) -> bool {
+        self.raise_gateway_errors
+    }
+
+    /// Returns the limit on the rate at which records are read, if any.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limiter.as_ref().map(RateLimiter::limit)
+    }
+
+    /// Returns the filter for the instruments whose records are returned by
+    /// [`next_record_owned()`](Self::next_record_owned), if any.
+    pub fn instrument_filter(&self) -> Option<&InstrumentFilter> {
+        self.instrument_filter.as_ref()
+    }
+
+    /// Returns the subscriptions that have been sent to the gateway in this session.
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    /// Returns the DBN metadata returned by [`start()`](Self::start), or `None` if the
+    /// session hasn't been started.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Returns the `ts_event` of the most recent data record returned by
+    /// [`next_record()`](Self::next_record), if any.
+    pub fn last_ts_event(&self) -> Option<OffsetDateTime> {
+        self.progress.last_ts_event()
+    }
+
+    /// Starts measuring the latency of each record read from the gateway by comparing
+    /// its `ts_out` to the local time, keeping the `window` most recent measurements.
+    /// The measurements are carried over to the [`Receiver`] when the client is
+    /// [split](Self::split).
+    ///
+    /// # Errors
+    /// This function returns an error if the client wasn't built with
+    /// [`send_ts_out`](ClientBuilder::send_ts_out) enabled.
+    pub fn measure_latency(&mut self, window: NonZeroUsize) -> crate::Result<()> {
+        if !self.send_ts_out {
+            return Err(Error::bad_arg(
+                "send_ts_out",
+                "must be enabled to measure latency",
+            ));
+        }
+        self.latency = Some(LatencyTracker::new(window));
+        Ok(())
+    }
+
+    /// Returns a summary of the latencies measured since
+    /// [`measure_latency()`](Self::measure_latency) was called, or `None` if it wasn't
+    /// called or no records have been read since.
+    pub fn latency(&self) -> Option<LatencySummary> {
+        self.latency.as_ref().and_then(LatencyTracker::summary)
+    }
+
+    /// Returns a [`SessionSnapshot`] of the session's dataset, subscriptions, and last
+    /// `ts_event`, which can be persisted and later passed to
+    /// [`restore()`](Self::restore) on a new client.
+    pub fn session_snapshot(&self) -> SessionSnapshot {
+        SessionSnapshot {
+            dataset: self.dataset.clone(),
+            subscriptions: self.subscriptions.clone(),
+            last_ts_event: self.last_ts_event(),
+        }
+    }
+
     /// Closes the connection with the gateway, ending the session and all subscriptions. Consumes
     /// the client.
     ///
@@ -179,14 +369,164 @@ impl Client {
     /// subscription, sending an error, and closing the connection.
     #[instrument(parent = &self.span, skip_all)]
     pub async fn subscribe(&mut self, sub: &Subscription) -> crate::Result<()> {
-        self.protocol.subscribe(sub).await
+        self.protocol.subscribe(sub).await?;
+        self.progress.end.add_subscription(sub);
+        self.groups.add(sub);
+        self.subscriptions.push(sub.clone());
+        Ok(())
+    }
+
+    /// Removes the subscriptions in `group` from the session. The gateway doesn't
+    /// support ending subscriptions, so their records are still sent, but those for
+    /// instruments that aren't part of any other subscription are skipped by
+    /// [`next_record_owned()`](Self::next_record_owned) and
+    /// [`BufferedReceiver`](super::BufferedReceiver), and the subscriptions are excluded
+    /// from the [`session_snapshot()`](Self::session_snapshot).
+    ///
+    /// # Errors
+    /// This function returns an error if there are no subscriptions in `group`.
+    pub fn unsubscribe_group(&mut self, group: &str) -> crate::Result<()> {
+        unsubscribe_group(&mut self.subscriptions, &self.groups, group)
+    }
+
+    /// Returns the groups and priorities of the session's subscriptions, which can be
+    /// used to look up the priority of records.
+    pub fn subscription_groups(&self) -> &SubscriptionGroups {
+        &self.groups
+    }
+
+    /// Checks the symbols of `sub` exist in the client's dataset by resolving them
+    /// with the historical symbology API before subscribing, since the gateway only
+    /// reports invalid symbols once the session has started. Symbols are resolved over
+    /// the week before the start of `sub`, or the current date if it has no start, so
+    /// symbols that were first listed more recently may be reported as invalid.
+    ///
+    /// # Errors
+    /// This function returns an error listing the symbols that failed to resolve, or
+    /// when it fails to communicate with the Databento API.
+    #[cfg(feature = "historical")]
+    pub async fn validate_subscription(
+        &self,
+        sub: &Subscription,
+        historical: &crate::HistoricalClient,
+    ) -> crate::Result<()> {
+        const LOOKBACK_DAYS: i64 = 7;
+
+        if matches!(sub.symbols, crate::Symbols::All) {
+            return Ok(());
+        }
+        let end = sub.start.unwrap_or_else(OffsetDateTime::now_utc).date();
+        let resolution = historical
+            .symbology()
+            .resolve(
+                &crate::historical::symbology::ResolveParams::builder()
+                    .dataset(&self.dataset)
+                    .symbols(sub.symbols.clone())
+                    .stype_in(sub.stype_in)
+                    .date_range((end - Duration::days(LOOKBACK_DAYS), end))
+                    .build(),
+            )
+            .await?;
+        if resolution.not_found.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::bad_arg(
+                "sub",
+                format!(
+                    "symbols not found in {}: {}",
+                    self.dataset,
+                    resolution.not_found.join(", ")
+                ),
+            ))
+        }
+    }
+
+    /// Resubscribes to the subscriptions of a previous session from `snapshot`. If
+    /// `backfill` is `true` and the snapshot has a last `ts_event`, the subscriptions
+    /// will request intraday replay starting immediately after it to fill the gap
+    /// between the sessions. Otherwise, the subscriptions start from the current time
+    /// without their original `start` or snapshot. This method should be called before
+    /// the session is [started](Self::start).
+    ///
+    /// # Errors
+    /// This function returns an error if the snapshot is for a different dataset or
+    /// it's unable to communicate with the gateway.
+    ///
+    /// # Cancel safety
+    /// This method is not cancellation safe. If this method is used in a
+    /// [`tokio::select!`] statement and another branch completes first, some of the
+    /// subscriptions may not have been sent.
+    pub async fn restore(
+        &mut self,
+        snapshot: &SessionSnapshot,
+        backfill: bool,
+    ) -> crate::Result<()> {
+        let start = snapshot
+            .last_ts_event
+            .filter(|_| backfill)
+            .map(|last_ts_event| last_ts_event + Duration::NANOSECOND);
+        self.resubscribe(snapshot, start).await
+    }
+
+    /// Resubscribes to the subscriptions of a previous session from `snapshot` like
+    /// [`restore()`](Self::restore) with backfill, but bounds the replayed interval to
+    /// at most `max_gap` before the current time. Useful for reconnecting after a long
+    /// outage where replaying the entire gap isn't wanted or the start would be beyond
+    /// the intraday replay window. If the snapshot has no last `ts_event`, the
+    /// subscriptions start from the current time. This method should be called before
+    /// the session is [started](Self::start).
+    ///
+    /// # Errors
+    /// This function returns an error if the snapshot is for a different dataset or
+    /// it's unable to communicate with the gateway.
+    ///
+    /// # Cancel safety
+    /// This method is not cancellation safe. If this method is used in a
+    /// [`tokio::select!`] statement and another branch completes first, some of the
+    /// subscriptions may not have been sent.
+    pub async fn restore_with_max_gap(
+        &mut self,
+        snapshot: &SessionSnapshot,
+        max_gap: Duration,
+    ) -> crate::Result<()> {
+        let start = snapshot.last_ts_event.map(|last_ts_event| {
+            bounded_backfill_start(last_ts_event, max_gap, OffsetDateTime::now_utc())
+        });
+        self.resubscribe(snapshot, start).await
+    }
+
+    /// Sends the subscriptions of `snapshot` starting at `start`, or from the current
+    /// time if `start` is `None`.
+    async fn resubscribe(
+        &mut self,
+        snapshot: &SessionSnapshot,
+        start: Option<OffsetDateTime>,
+    ) -> crate::Result<()> {
+        if snapshot.dataset != self.dataset {
+            return Err(Error::bad_arg(
+                "snapshot",
+                format!(
+                    "dataset {} doesn't match the client's dataset {}",
+                    snapshot.dataset, self.dataset
+                ),
+            ));
+        }
+        for sub in &snapshot.subscriptions {
+            let mut sub = sub.clone();
+            // The original start or snapshot would replay stale data
+            sub.start = start;
+            sub.use_snapshot = false;
+            self.subscribe(&sub).await?;
+        }
+        Ok(())
     }
 
     /// Instructs the gateway to start sending data, starting the session. This method
     /// should only be called once on a given instance.
     ///
     /// Returns the DBN metadata associated with this session. This is primarily useful
-    /// when saving the data to a file to replay it later.
+    /// when saving the data to a file to replay it later. It's also retained by the
+    /// client and available from [`metadata()`](Self::metadata).
     ///
     /// # Errors
     /// This function returns an error if it's unable to communicate with
@@ -205,9 +545,11 @@ impl Client {
             .decode()
             .await?;
         self.decoder.set_version(metadata.version)?;
+        self.dbn_version = Some(metadata.version);
         // Should match `send_ts_out` but set again here for safety
         self.decoder.set_ts_out(metadata.ts_out);
         metadata.upgrade(self.upgrade_policy);
+        self.metadata = Some(metadata.clone());
         Ok(metadata)
     }
 
@@ -215,134 +557,831 @@ impl Client {
     /// been [started](Self::start).
     ///
     /// Returns `Ok(None)` if the gateway closed the connection and no more records
-    /// can be read.
+    /// can be read, or the [`end`](Subscription::end) of all subscriptions has been
+    /// reached.
     ///
     /// # Errors
     /// This function returns an error when it's unable to decode the next record
-    /// or it's unable to read from the TCP stream.
+    /// or it's unable to read from the TCP stream. If
+    /// [`raise_gateway_errors`](ClientBuilder::raise_gateway_errors) is enabled, it
+    /// will also return an error when the gateway sends an error message.
     ///
     /// # Cancel safety
     /// This method is cancel safe. It can be used within a [`tokio::select!`] statement
     /// without the potential for corrupting the input stream.
     #[instrument(parent = &self.span, skip_all)]
     pub async fn next_record(&mut self) -> crate::Result<Option<RecordRef>> {
-        Ok(self.decoder.decode_ref().await?)
+        if self.progress.is_ended {
+            return Ok(None);
+        }
+        if let Some(rate_limiter) = self.rate_limiter.as_mut() {
+            rate_limiter.wait().await;
+        }
+        let rec = check_gateway_error(self.decoder.decode_ref().await?, self.raise_gateway_errors)?;
+        if let (Some(rate_limiter), Some(rec)) = (self.rate_limiter.as_mut(), rec.as_ref()) {
+            rate_limiter.consume(rec);
+        }
+        if let (Some(latency), Some(rec)) = (self.latency.as_mut(), rec.as_ref()) {
+            latency.observe(rec);
+        }
+        if let Some(rec) = rec.as_ref() {
+            self.groups.observe(rec);
+        }
+        Ok(rec.and_then(|rec| self.progress.update(rec)))
+    }
+
+    /// Fetches the next record and copies it into an owned [`RecordEnum`], which can be
+    /// sent across channels and threads. This method should only be called after the
+    /// session has been [started](Self::start). Records for instruments not in the
+    /// [`instrument_filter`](ClientBuilder::instrument_filter) are skipped without
+    /// being copied.
+    ///
+    /// Returns `Ok(None)` if the gateway closed the connection and no more records
+    /// can be read, or the [`end`](Subscription::end) of all subscriptions has been
+    /// reached.
+    ///
+    /// # Errors
+    /// This function returns an error when it's unable to decode the next record
+    /// or it's unable to read from the TCP stream. If
+    /// [`raise_gateway_errors`](ClientBuilder::raise_gateway_errors) is enabled, it
+    /// will also return an error when the gateway sends an error message.
+    ///
+    /// # Cancel safety
+    /// This method is cancel safe. It can be used within a [`tokio::select!`] statement
+    /// without the potential for corrupting the input stream.
+    pub async fn next_record_owned(&mut self) -> crate::Result<Option<RecordEnum>> {
+        let instrument_filter = self.instrument_filter.clone();
+        let groups = self.groups.clone();
+        loop {
+            let Some(rec) = self.next_record().await? else {
+                return Ok(None);
+            };
+            if instrument_filter
+                .as_ref()
+                .is_some_and(|filter| !filter.matches(&rec))
+                || groups.is_unsubscribed(&rec)
+            {
+                continue;
+            }
+            return Ok(Some(RecordRefEnum::try_from(rec)?.to_owned()));
+        }
+    }
+
+    /// Reads the snapshot records sent by the gateway for MBO subscriptions with
+    /// [`use_snapshot`](Subscription::use_snapshot) and returns the materialized book
+    /// state. This method should only be called after the session has been
+    /// [started](Self::start), and all subscriptions must be MBO with snapshot.
+    ///
+    /// Collection stops at the snapshot record with the `F_LAST` flag, so the next
+    /// record returned by [`next_record()`](Self::next_record) is the first incremental
+    /// update. Records are read like with `next_record()`, so the rate limit,
+    /// [`instrument_filter`](ClientBuilder::instrument_filter), and subscription
+    /// [`end`](Subscription::end) apply.
+    ///
+    /// # Errors
+    /// This function returns an error if the session hasn't been started, a subscription
+    /// isn't MBO with snapshot, it's unable to decode a record, it's unable to read from
+    /// the TCP stream, or the gateway closes the connection before the end of the
+    /// snapshot. If [`raise_gateway_errors`](ClientBuilder::raise_gateway_errors) is
+    /// enabled, it will also return an error when the gateway sends an error message.
+    ///
+    /// # Cancel safety
+    /// This method is not cancellation safe. If this method is used in a
+    /// [`tokio::select!`] statement and another branch completes first, the snapshot
+    /// records read so far will be lost.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn collect_snapshot(&mut self) -> crate::Result<Snapshot> {
+        if self.subscriptions.is_empty()
+            || self
+                .subscriptions
+                .iter()
+                .any(|sub| sub.schema != Schema::Mbo || !sub.use_snapshot)
+        {
+            return Err(Error::bad_arg(
+                "self",
+                "snapshots can only be collected when all subscriptions are MBO with snapshot",
+            ));
+        }
+        if self.metadata.is_none() {
+            return Err(Error::bad_arg(
+                "self",
+                "snapshots can only be collected after starting the session",
+            ));
+        }
+        let instrument_filter = self.instrument_filter.clone();
+        let groups = self.groups.clone();
+        let mut snapshot = Snapshot::default();
+        while let Some(rec) = self.next_record().await? {
+            let is_skipped = instrument_filter
+                .as_ref()
+                .is_some_and(|filter| !filter.matches(&rec))
+                || groups.is_unsubscribed(&rec);
+            if let Some(mbo) = rec.get::<MboMsg>() {
+                if !is_skipped {
+                    snapshot.apply(mbo);
+                }
+                if mbo.flags.is_snapshot() && mbo.flags.is_last() {
+                    return Ok(snapshot);
+                }
+            } else if !is_skipped {
+                snapshot.push_other(RecordRefEnum::try_from(rec)?.to_owned());
+            }
+        }
+        Err(crate::Error::internal(
+            "gateway closed the connection or the subscriptions ended before the end of \
+            the snapshot",
+        ))
+    }
+
+    /// Subscribes to the definitions of `symbols` with a snapshot, starts the session,
+    /// and collects the definitions sent by the gateway keyed by instrument ID. This
+    /// resolves the metadata of contracts such as futures and options at the start of a
+    /// session without a separate temporary subscription. It must be called before the
+    /// session is [started](Self::start) and before any other subscriptions are added,
+    /// which can be added afterwards with [`subscribe()`](Self::subscribe).
+    ///
+    /// Collection stops when the gateway reports the end of the definition replay.
+    ///
+    /// # Errors
+    /// This function returns an error if the session has already been started or has
+    /// other subscriptions, it's unable to subscribe or start the session, it's unable
+    /// to decode a record or read from the TCP stream, or the gateway closes the
+    /// connection before the end of the definitions. If
+    /// [`raise_gateway_errors`](ClientBuilder::raise_gateway_errors) is enabled, it
+    /// will also return an error when the gateway sends an error message.
+    ///
+    /// # Cancel safety
+    /// This method is not cancellation safe. If this method is used in a
+    /// [`tokio::select!`] statement and another branch completes first, the definitions
+    /// read so far will be lost.
+    pub async fn request_definitions(
+        &mut self,
+        symbols: impl Into<Symbols>,
+        stype_in: SType,
+    ) -> crate::Result<HashMap<u32, InstrumentDefMsg>> {
+        if self.metadata.is_some() {
+            return Err(Error::bad_arg(
+                "self",
+                "definitions must be requested before starting the session",
+            ));
+        }
+        if !self.subscriptions.is_empty() {
+            return Err(Error::bad_arg(
+                "self",
+                "definitions must be requested before adding other subscriptions",
+            ));
+        }
+        let subscription = Subscription::builder()
+            .symbols(symbols)
+            .schema(Schema::Definition)
+            .stype_in(stype_in)
+            .use_snapshot()
+            .build();
+        self.subscribe(&subscription).await?;
+        self.start().await?;
+        self.collect_definitions().await
+    }
+
+    #[instrument(parent = &self.span, skip_all)]
+    async fn collect_definitions(&mut self) -> crate::Result<HashMap<u32, InstrumentDefMsg>> {
+        let mut definitions = HashMap::new();
+        while let Some(rec) =
+            check_gateway_error(self.decoder.decode_ref().await?, self.raise_gateway_errors)?
+        {
+            if let Some(definition) = get_upgraded::<InstrumentDefMsg, InstrumentDefMsgV1>(&rec) {
+                definitions.insert(definition.hd.instrument_id, definition);
+            } else if get_upgraded::<SystemMsg, SystemMsgV1>(&rec).is_some_and(|msg| {
+                msg.msg()
+                    .is_ok_and(|msg| msg.starts_with(DEFINITION_REPLAY_END_MSG))
+            }) {
+                info!(count = definitions.len(), "Collected definitions");
+                return Ok(definitions);
+            }
+        }
+        Err(crate::Error::internal(
+            "gateway closed the connection before the end of the definitions",
+        ))
+    }
+
+    /// Splits the client into a [`Sender`] for adding subscriptions and a [`Receiver`]
+    /// for reading records, which can be used concurrently from different tasks. This
+    /// method should generally be called after the session has been
+    /// [started](Self::start).
+    pub fn split(self) -> (Sender, Receiver) {
+        (
+            Sender {
+                dataset: self.dataset,
+                session_id: self.session_id,
+                subscriptions: self.subscriptions,
+                groups: self.groups.clone(),
+                end: self.progress.end.clone(),
+                protocol: self.protocol,
+                span: self.span.clone(),
+            },
+            Receiver {
+                decoder: self.decoder,
+                raise_gateway_errors: self.raise_gateway_errors,
+                rate_limiter: self.rate_limiter,
+                instrument_filter: self.instrument_filter,
+                groups: self.groups,
+                metadata: self.metadata,
+                dbn_version: self.dbn_version,
+                progress: self.progress,
+                latency: self.latency,
+                span: self.span,
+            },
+        )
     }
 }
 
-impl fmt::Debug for Client {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("LiveClient")
-            .field("key", &self.key)
-            .field("dataset", &self.dataset)
-            .field("send_ts_out", &self.send_ts_out)
-            .field("upgrade_policy", &self.upgrade_policy)
-            .field("heartbeat_interval", &self.heartbeat_interval)
-            .field("session_id", &self.session_id)
-            .finish_non_exhaustive()
+/// Removes the subscriptions in `group` from `subscriptions` and marks them as
+/// unsubscribed in `groups`.
+fn unsubscribe_group(
+    subscriptions: &mut Vec<Subscription>,
+    groups: &SubscriptionGroups,
+    group: &str,
+) -> crate::Result<()> {
+    let len = subscriptions.len();
+    subscriptions.retain(|sub| sub.group.as_deref() != Some(group));
+    if subscriptions.len() == len {
+        return Err(Error::bad_arg(
+            "group",
+            format!("no subscriptions in group {group}"),
+        ));
     }
+    groups.unsubscribe(group);
+    info!(group, "Unsubscribed group");
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use std::{ffi::c_char, fmt};
+/// The start of the system message sent by the gateway once it has finished replaying
+/// definitions.
+const DEFINITION_REPLAY_END_MSG: &str = "Finished definition replay";
 
-    use dbn::{
-        encode::AsyncDbnMetadataEncoder,
-        enums::rtype,
-        publishers::Dataset,
-        record::{HasRType, OhlcvMsg, RecordHeader, TradeMsg, WithTsOut},
-        FlagSet, Mbp10Msg, MetadataBuilder, Record, SType, Schema,
-    };
-    use time::Duration;
-    use tokio::{
-        io::{AsyncBufReadExt, BufReader},
-        join,
-        net::{TcpListener, TcpStream},
-        select,
-        sync::mpsc::UnboundedSender,
-        task::JoinHandle,
-    };
-    use tracing::level_filters::LevelFilter;
+/// The delay before the first retry of a refused connection, which doubles with each
+/// retry.
+const CONNECT_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(250);
 
-    use super::*;
+/// How the TCP connection to the gateway is established.
+struct ConnectOptions {
+    bind_addr: Option<IpAddr>,
+    ip_version: IpVersion,
+    timeout: Option<std::time::Duration>,
+    retries: u32,
+}
 
-    struct MockLsgServer {
-        dataset: String,
-        send_ts_out: bool,
-        listener: TcpListener,
-        stream: Option<BufReader<TcpStream>>,
+impl ConnectOptions {
+    /// Returns `true` if `addr` matches `ip_version` and the IP version of `bind_addr`.
+    fn allows(&self, addr: &SocketAddr) -> bool {
+        let is_allowed = match self.ip_version {
+            IpVersion::Any => true,
+            IpVersion::V4 => addr.is_ipv4(),
+            IpVersion::V6 => addr.is_ipv6(),
+        };
+        is_allowed
+            && self
+                .bind_addr
+                .is_none_or(|bind_addr| bind_addr.is_ipv4() == addr.is_ipv4())
     }
+}
 
-    impl MockLsgServer {
-        async fn new(dataset: String, send_ts_out: bool) -> Self {
-            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
-            Self {
-                dataset,
-                send_ts_out,
-                listener,
-                stream: None,
+/// Connects to the first address resolved from `addr` allowed by `options`, binding the
+/// local end of the connection to `bind_addr` if it's set. If every address refuses the
+/// connection, it's retried up to `retries` times with an exponential backoff.
+async fn connect_tcp(
+    addr: impl ToSocketAddrs,
+    options: &ConnectOptions,
+) -> crate::Result<TcpStream> {
+    let addrs: Vec<_> = lookup_host(addr)
+        .await?
+        .filter(|addr| options.allows(addr))
+        .collect();
+    if addrs.is_empty() {
+        return Err(Error::bad_arg(
+            "ip_version",
+            "no gateway address matches the IP version and bind address",
+        ));
+    }
+    let mut delay = CONNECT_RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+        match connect_any(&addrs, options).await {
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused && attempt < options.retries => {
+                attempt += 1;
+                warn!(attempt, ?delay, "Gateway refused connection, retrying");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
             }
+            res => return Ok(res?),
         }
+    }
+}
 
-        async fn accept(&mut self) {
-            let stream = self.listener.accept().await.unwrap().0;
-            stream.set_nodelay(true).unwrap();
-            self.stream = Some(BufReader::new(stream));
+/// Tries each of `addrs` in order, returning the first successful connection or the last
+/// error.
+async fn connect_any(addrs: &[SocketAddr], options: &ConnectOptions) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for &addr in addrs {
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        if let Some(bind_addr) = options.bind_addr {
+            socket.bind(SocketAddr::new(bind_addr, 0))?;
         }
-
-        async fn authenticate(&mut self, heartbeat_interval: Option<Duration>) {
-            self.accept().await;
-            self.send("lsg-test\n").await;
-            self.send("cram=t7kNhwj4xqR0QYjzFKtBEG2ec2pXJ4FK\n").await;
-            let auth_line = self.read_line().await;
-            let auth_start = auth_line.find("auth=").unwrap() + 5;
-            let auth_end = auth_line[auth_start..].find('|').unwrap();
-            let auth = &auth_line[auth_start..auth_start + auth_end];
-            let (auth, bucket) = auth.split_once('-').unwrap();
-            assert!(
-                auth.chars().all(|c| c.is_ascii_hexdigit()),
-                "Expected '{auth}' to be composed of only hex characters"
-            );
-            assert_eq!(bucket, "iller");
-            assert!(auth_line.contains(&format!("dataset={}", self.dataset)));
-            assert!(auth_line.contains("encoding=dbn"));
-            assert!(auth_line.contains(&format!("ts_out={}", if self.send_ts_out { 1 } else { 0 })));
-            assert!(auth_line.contains(&format!("client=Rust {}", env!("CARGO_PKG_VERSION"))));
-            if let Some(heartbeat_interval) = heartbeat_interval {
-                assert!(auth_line.contains(&format!(
-                    "heartbeat_interval_s={}",
-                    heartbeat_interval.whole_seconds()
-                )));
-            } else {
-                assert!(!auth_line.contains("heartbeat_interval_s="));
-            }
-            self.send("success=1|session_id=5\n").await;
+        let res = match options.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, socket.connect(addr))
+                .await
+                .unwrap_or_else(|_| {
+                    Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("timed out connecting to {addr}"),
+                    ))
+                }),
+            None => socket.connect(addr).await,
+        };
+        match res {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_err = Some(e),
         }
+    }
+    // `addrs` is never empty
+    Err(last_err.unwrap_or_else(|| io::ErrorKind::AddrNotAvailable.into()))
+}
 
-        async fn subscribe(&mut self, subscription: Subscription) {
-            let sub_line = self.read_line().await;
-            assert!(sub_line.contains(&format!("symbols={}", subscription.symbols.to_api_string())));
-            assert!(sub_line.contains(&format!("schema={}", subscription.schema)));
-            assert!(sub_line.contains(&format!("stype_in={}", subscription.stype_in)));
-            if let Some(start) = subscription.start {
-                assert!(sub_line.contains(&format!("start={}", start.unix_timestamp_nanos())))
-            }
+/// The sending half of a [`LiveClient`](Client), created with [`Client::split()`].
+/// Used for adding subscriptions to the session.
+pub struct Sender {
+    dataset: String,
+    session_id: String,
+    subscriptions: Vec<Subscription>,
+    groups: SubscriptionGroups,
+    end: SessionEnd,
+    protocol: Protocol<WriteHalf<TcpStream>>,
+    span: Span,
+}
 
-            if subscription.use_snapshot {
-                assert!(sub_line.contains("snapshot=1"));
-            } else {
-                assert!(sub_line.contains("snapshot=0"));
-            }
-        }
+impl Sender {
+    /// Returns the dataset the client is configured for.
+    pub fn dataset(&self) -> &str {
+        &self.dataset
+    }
 
-        async fn start(&mut self) {
-            let start_line = self.read_line().await;
-            assert_eq!(start_line, "start_session\n");
-            let dataset = self.dataset.clone();
-            let stream = self.stream();
-            let mut encoder = AsyncDbnMetadataEncoder::new(stream);
-            encoder
+    /// Returns an identifier for the current Live session.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Returns the subscriptions that have been sent to the gateway in this session.
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    /// Attempts to add a new subscription to the session. Note that
+    /// an `Ok(())` result from this function does not necessarily indicate that
+    /// the subscription succeeded, only that it was sent to the gateway.
+    ///
+    /// # Errors
+    /// This function returns an error if it's unable to communicate with the gateway.
+    ///
+    /// # Cancel safety
+    /// This method is not cancellation safe. If this method is used in a
+    /// [`tokio::select!`] statement and another branch completes first, the subscription
+    /// may have been partially sent, resulting in the gateway rejecting the
+    /// subscription, sending an error, and closing the connection.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn subscribe(&mut self, sub: &Subscription) -> crate::Result<()> {
+        self.protocol.subscribe(sub).await?;
+        self.end.add_subscription(sub);
+        self.groups.add(sub);
+        self.subscriptions.push(sub.clone());
+        Ok(())
+    }
+
+    /// Removes the subscriptions in `group` from the session. See
+    /// [`Client::unsubscribe_group()`].
+    ///
+    /// # Errors
+    /// This function returns an error if there are no subscriptions in `group`.
+    pub fn unsubscribe_group(&mut self, group: &str) -> crate::Result<()> {
+        unsubscribe_group(&mut self.subscriptions, &self.groups, group)
+    }
+
+    /// Shuts down the sending half of the connection, which causes the gateway to end
+    /// the session. The [`Receiver`] will return `Ok(None)` once the gateway closes the
+    /// connection. Consumes the sender.
+    ///
+    /// # Errors
+    /// This function returns an error if the shutdown of the TCP stream is unsuccessful.
+    pub async fn close(self) -> crate::Result<()> {
+        Ok(self.protocol.into_inner().shutdown().await?)
+    }
+}
+
+impl fmt::Debug for Sender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LiveSender")
+            .field("dataset", &self.dataset)
+            .field("session_id", &self.session_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The receiving half of a [`LiveClient`](Client), created with [`Client::split()`].
+/// Used for reading records from the session.
+pub struct Receiver {
+    decoder: AsyncRecordDecoder<BufReader<ReadHalf<TcpStream>>>,
+    raise_gateway_errors: bool,
+    rate_limiter: Option<RateLimiter>,
+    instrument_filter: Option<InstrumentFilter>,
+    groups: SubscriptionGroups,
+    metadata: Option<Metadata>,
+    dbn_version: Option<u8>,
+    progress: Progress,
+    latency: Option<LatencyTracker>,
+    span: Span,
+}
+
+impl Receiver {
+    /// Returns the DBN metadata of the session, or `None` if the session hadn't been
+    /// started when the client was split.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Changes the upgrade policy for decoding DBN from previous versions for all
+    /// records read afterward.
+    pub fn set_upgrade_policy(&mut self, upgrade_policy: VersionUpgradePolicy) {
+        self.decoder.set_upgrade_policy(upgrade_policy);
+    }
+
+    /// Returns the DBN version of the data sent by the gateway, or `None` if the
+    /// session hadn't been started when the client was split.
+    pub fn dbn_version(&self) -> Option<u8> {
+        self.dbn_version
+    }
+
+    /// Returns the `ts_event` of the most recent data record returned by
+    /// [`next_record()`](Self::next_record), if any.
+    pub fn last_ts_event(&self) -> Option<OffsetDateTime> {
+        self.progress.last_ts_event()
+    }
+
+    /// Returns the groups and priorities of the session's subscriptions, including
+    /// those added with the [`Sender`] after the client was split.
+    pub fn subscription_groups(&self) -> &SubscriptionGroups {
+        &self.groups
+    }
+
+    /// Returns a summary of the latencies measured if
+    /// [`Client::measure_latency()`] was called before the client was split.
+    pub fn latency(&self) -> Option<LatencySummary> {
+        self.latency.as_ref().and_then(LatencyTracker::summary)
+    }
+
+    /// Fetches the next record.
+    ///
+    /// Returns `Ok(None)` if the gateway closed the connection and no more records
+    /// can be read, or the [`end`](Subscription::end) of all subscriptions has been
+    /// reached.
+    ///
+    /// # Errors
+    /// This function returns an error when it's unable to decode the next record
+    /// or it's unable to read from the TCP stream. If
+    /// [`raise_gateway_errors`](ClientBuilder::raise_gateway_errors) is enabled, it
+    /// will also return an error when the gateway sends an error message.
+    ///
+    /// # Cancel safety
+    /// This method is cancel safe. It can be used within a [`tokio::select!`] statement
+    /// without the potential for corrupting the input stream.
+    #[instrument(parent = &self.span, skip_all)]
+    pub async fn next_record(&mut self) -> crate::Result<Option<RecordRef>> {
+        if self.progress.is_ended {
+            return Ok(None);
+        }
+        if let Some(rate_limiter) = self.rate_limiter.as_mut() {
+            rate_limiter.wait().await;
+        }
+        let rec = check_gateway_error(self.decoder.decode_ref().await?, self.raise_gateway_errors)?;
+        if let (Some(rate_limiter), Some(rec)) = (self.rate_limiter.as_mut(), rec.as_ref()) {
+            rate_limiter.consume(rec);
+        }
+        if let (Some(latency), Some(rec)) = (self.latency.as_mut(), rec.as_ref()) {
+            latency.observe(rec);
+        }
+        if let Some(rec) = rec.as_ref() {
+            self.groups.observe(rec);
+        }
+        Ok(rec.and_then(|rec| self.progress.update(rec)))
+    }
+
+    /// Fetches the next record and copies it into an owned [`RecordEnum`], which can be
+    /// sent across channels and threads. Records for instruments not in the
+    /// [`instrument_filter`](ClientBuilder::instrument_filter) are skipped without
+    /// being copied.
+    ///
+    /// Returns `Ok(None)` if the gateway closed the connection and no more records
+    /// can be read, or the [`end`](Subscription::end) of all subscriptions has been
+    /// reached.
+    ///
+    /// # Errors
+    /// This function returns an error when it's unable to decode the next record
+    /// or it's unable to read from the TCP stream. If
+    /// [`raise_gateway_errors`](ClientBuilder::raise_gateway_errors) is enabled, it
+    /// will also return an error when the gateway sends an error message.
+    ///
+    /// # Cancel safety
+    /// This method is cancel safe. It can be used within a [`tokio::select!`] statement
+    /// without the potential for corrupting the input stream.
+    pub async fn next_record_owned(&mut self) -> crate::Result<Option<RecordEnum>> {
+        Ok(self
+            .next_prioritized_record(false)
+            .await?
+            .map(|(_, rec)| rec))
+    }
+
+    /// Like [`next_record_owned()`](Self::next_record_owned), but also returns the
+    /// priority of the record if `with_priority` is `true`, otherwise 0.
+    pub(crate) async fn next_prioritized_record(
+        &mut self,
+        with_priority: bool,
+    ) -> crate::Result<Option<(u8, RecordEnum)>> {
+        let instrument_filter = self.instrument_filter.clone();
+        let groups = self.groups.clone();
+        loop {
+            let Some(rec) = self.next_record().await? else {
+                return Ok(None);
+            };
+            if instrument_filter
+                .as_ref()
+                .is_some_and(|filter| !filter.matches(&rec))
+                || groups.is_unsubscribed(&rec)
+            {
+                continue;
+            }
+            let priority = if with_priority {
+                groups.priority(&rec)
+            } else {
+                0
+            };
+            return Ok(Some((priority, RecordRefEnum::try_from(rec)?.to_owned())));
+        }
+    }
+}
+
+impl fmt::Debug for Receiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LiveReceiver").finish_non_exhaustive()
+    }
+}
+
+/// Returns the start for replaying the gap after `last_ts_event`, limited to `max_gap`
+/// before `now`.
+fn bounded_backfill_start(
+    last_ts_event: OffsetDateTime,
+    max_gap: Duration,
+    now: OffsetDateTime,
+) -> OffsetDateTime {
+    let start = last_ts_event + Duration::NANOSECOND;
+    match now.checked_sub(max_gap) {
+        Some(earliest) if earliest > start => {
+            warn!(
+                %last_ts_event, %earliest,
+                "Gap since last record exceeds the maximum, some records won't be replayed"
+            );
+            earliest
+        }
+        _ => start,
+    }
+}
+
+/// Converts a gateway error message into [`Error::Gateway`](crate::Error::Gateway)
+/// if `raise_gateway_errors` is enabled, otherwise passes all records through.
+fn check_gateway_error(
+    rec: Option<RecordRef>,
+    raise_gateway_errors: bool,
+) -> crate::Result<Option<RecordRef>> {
+    if raise_gateway_errors {
+        if let Some(error_msg) = rec.as_ref().and_then(get_upgraded::<ErrorMsg, ErrorMsgV1>) {
+            let gateway_error = GatewayError::try_from(&error_msg)?;
+            error!(%gateway_error, is_fatal = gateway_error.is_fatal, "Received error from gateway");
+            return Err(crate::Error::Gateway(gateway_error));
+        }
+    }
+    Ok(rec)
+}
+
+/// Returns `rec` as a `T`, converting it from `V1` if it's a smaller DBN version 1
+/// record, which isn't upgraded with [`VersionUpgradePolicy::AsIs`]. Unlike
+/// [`RecordRef::get()`], this doesn't panic on version 1 records.
+fn get_upgraded<T, V1>(rec: &RecordRef) -> Option<T>
+where
+    T: HasRType + Clone + for<'a> From<&'a V1>,
+    V1: HasRType,
+{
+    if rec.record_size() < mem::size_of::<T>() {
+        rec.get::<V1>().map(T::from)
+    } else {
+        rec.get::<T>().cloned()
+    }
+}
+
+/// Tracks the progress of the session through the data: the `ts_event` of the last
+/// data record and whether the end of all subscriptions has been reached.
+#[derive(Debug, Default)]
+struct Progress {
+    last_ts_event: Option<u64>,
+    end: SessionEnd,
+    is_ended: bool,
+}
+
+impl Progress {
+    /// Returns `rec` if it's before the end of the subscriptions, otherwise marks
+    /// the session as ended and returns `None`.
+    fn update<'a>(&mut self, rec: RecordRef<'a>) -> Option<RecordRef<'a>> {
+        // Gateway messages' timestamps aren't related to the data
+        let header = rec.header();
+        if matches!(
+            header.rtype,
+            rtype::ERROR | rtype::SYSTEM | rtype::SYMBOL_MAPPING
+        ) || header.ts_event == dbn::UNDEF_TIMESTAMP
+        {
+            return Some(rec);
+        }
+        if self.end.is_reached(header.ts_event) {
+            info!("Reached the end of all subscriptions");
+            self.is_ended = true;
+            return None;
+        }
+        self.last_ts_event = Some(header.ts_event);
+        Some(rec)
+    }
+
+    fn last_ts_event(&self) -> Option<OffsetDateTime> {
+        self.last_ts_event
+            .and_then(|ts| OffsetDateTime::from_unix_timestamp_nanos(i128::from(ts)).ok())
+    }
+}
+
+/// The latest [`end`](Subscription::end) of all subscriptions as UNIX nanoseconds,
+/// shared between a [`Sender`] and [`Receiver`]. `0` indicates there are no
+/// subscriptions and `u64::MAX` that at least one subscription has no end.
+#[derive(Debug, Clone, Default)]
+struct SessionEnd(Arc<AtomicU64>);
+
+impl SessionEnd {
+    fn add_subscription(&self, sub: &Subscription) {
+        let sub_end = sub.end.map_or(u64::MAX, |end| {
+            u64::try_from(end.unix_timestamp_nanos())
+                .unwrap_or(0)
+                .max(1)
+        });
+        // Closure always returns `Some`
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |end| {
+                Some(if end == 0 { sub_end } else { end.max(sub_end) })
+            });
+    }
+
+    fn is_reached(&self, ts_event: u64) -> bool {
+        let end = self.0.load(Ordering::Relaxed);
+        end != 0 && end != u64::MAX && ts_event >= end
+    }
+}
+
+/// A record read ahead while collecting a snapshot that still needs to be returned
+/// from `next_record()`.
+#[derive(Default)]
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LiveClient")
+            .field("key", &self.key)
+            .field("dataset", &self.dataset)
+            .field("gateway_addr", &self.gateway_addr)
+            .field("send_ts_out", &self.send_ts_out)
+            .field("upgrade_policy", &self.upgrade_policy)
+            .field("heartbeat_interval", &self.heartbeat_interval)
+            .field("raise_gateway_errors", &self.raise_gateway_errors)
+            .field("rate_limit", &self.rate_limit())
+            .field("instrument_filter", &self.instrument_filter)
+            .field("session_id", &self.session_id)
+            .field("gateway_version", &self.gateway_version())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::c_char, fmt, num::NonZeroUsize, ops::ControlFlow};
+
+    use dbn::{
+        encode::AsyncDbnMetadataEncoder,
+        enums::{flags, rtype},
+        publishers::Dataset,
+        record::{HasRType, OhlcvMsg, RecordHeader, TradeMsg, WithTsOut},
+        FlagSet, Mbp10Msg, MetadataBuilder, Record, SType, Schema,
+    };
+    use time::{macros::datetime, Duration};
+    use tokio::{
+        io::{AsyncBufReadExt, BufReader},
+        join,
+        net::{TcpListener, TcpStream},
+        select,
+        sync::mpsc::UnboundedSender,
+        task::JoinHandle,
+    };
+    use tracing::level_filters::LevelFilter;
+
+    use super::*;
+    use crate::{
+        live::{
+            shutdown::{run_until, ShutdownReason},
+            OverflowPolicy,
+        },
+        Symbols,
+    };
+
+    struct MockLsgServer {
+        dataset: String,
+        send_ts_out: bool,
+        listener: TcpListener,
+        stream: Option<BufReader<TcpStream>>,
+    }
+
+    impl MockLsgServer {
+        async fn new(dataset: String, send_ts_out: bool) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            Self {
+                dataset,
+                send_ts_out,
+                listener,
+                stream: None,
+            }
+        }
+
+        async fn accept(&mut self) {
+            let stream = self.listener.accept().await.unwrap().0;
+            stream.set_nodelay(true).unwrap();
+            self.stream = Some(BufReader::new(stream));
+        }
+
+        async fn authenticate(&mut self, heartbeat_interval: Option<Duration>) {
+            self.accept().await;
+            self.send("lsg-test\n").await;
+            self.send("cram=t7kNhwj4xqR0QYjzFKtBEG2ec2pXJ4FK\n").await;
+            let auth_line = self.read_line().await;
+            let auth_start = auth_line.find("auth=").unwrap() + 5;
+            let auth_end = auth_line[auth_start..].find('|').unwrap();
+            let auth = &auth_line[auth_start..auth_start + auth_end];
+            let (auth, bucket) = auth.split_once('-').unwrap();
+            assert!(
+                auth.chars().all(|c| c.is_ascii_hexdigit()),
+                "Expected '{auth}' to be composed of only hex characters"
+            );
+            assert_eq!(bucket, "iller");
+            assert!(auth_line.contains(&format!("dataset={}", self.dataset)));
+            assert!(auth_line.contains("encoding=dbn"));
+            assert!(auth_line.contains(&format!("ts_out={}", if self.send_ts_out { 1 } else { 0 })));
+            assert!(auth_line.contains(&format!("client=Rust {}", env!("CARGO_PKG_VERSION"))));
+            if let Some(heartbeat_interval) = heartbeat_interval {
+                assert!(auth_line.contains(&format!(
+                    "heartbeat_interval_s={}",
+                    heartbeat_interval.whole_seconds()
+                )));
+            } else {
+                assert!(!auth_line.contains("heartbeat_interval_s="));
+            }
+            self.send("success=1|session_id=5\n").await;
+        }
+
+        async fn subscribe(&mut self, subscription: Subscription, is_last: bool) {
+            let sub_line = self.read_line().await;
+            assert!(sub_line.contains(&format!("symbols={}", subscription.symbols.to_api_string())));
+            assert!(sub_line.contains(&format!("schema={}", subscription.schema)));
+            assert!(sub_line.contains(&format!("stype_in={}", subscription.stype_in)));
+            if let Some(start) = subscription.start {
+                assert!(sub_line.contains(&format!("start={}", start.unix_timestamp_nanos())))
+            } else {
+                assert!(!sub_line.contains("start="));
+            }
+
+            if subscription.use_snapshot {
+                assert!(sub_line.contains("snapshot=1"));
+            } else {
+                assert!(sub_line.contains("snapshot=0"));
+            }
+            assert!(sub_line.contains(&format!("is_last={}", is_last as u8)));
+        }
+
+        async fn start(&mut self) {
+            let start_line = self.read_line().await;
+            assert_eq!(start_line, "start_session\n");
+            let dataset = self.dataset.clone();
+            let stream = self.stream();
+            let mut encoder = AsyncDbnMetadataEncoder::new(stream);
+            encoder
                 .encode(
                     &MetadataBuilder::new()
                         .dataset(dataset)
@@ -393,7 +1432,7 @@ mod tests {
         Accept,
         Authenticate(Option<Duration>),
         Send(String),
-        Subscribe(Subscription),
+        Subscribe(Subscription, bool),
         Start,
         SendRecord(Box<dyn AsRef<[u8]> + Send>),
     }
@@ -405,7 +1444,7 @@ mod tests {
                 Event::Accept => write!(f, "Accept"),
                 Event::Authenticate(hb_int) => write!(f, "Authenticate({hb_int:?})"),
                 Event::Send(msg) => write!(f, "Send({msg:?})"),
-                Event::Subscribe(sub) => write!(f, "Subscribe({sub:?})"),
+                Event::Subscribe(sub, is_last) => write!(f, "Subscribe({sub:?}, {is_last})"),
                 Event::Start => write!(f, "Start"),
                 Event::SendRecord(_) => write!(f, "SendRecord"),
             }
@@ -423,7 +1462,7 @@ mod tests {
                         Some(Event::Authenticate(hb_int)) => mock.authenticate(hb_int).await,
                         Some(Event::Accept) => mock.accept().await,
                         Some(Event::Send(msg)) => mock.send(&msg).await,
-                        Some(Event::Subscribe(sub)) => mock.subscribe(sub).await,
+                        Some(Event::Subscribe(sub, is_last)) => mock.subscribe(sub, is_last).await,
                         Some(Event::Start) => mock.start().await,
                         Some(Event::SendRecord(rec)) => mock.send_record(rec).await,
                         Some(Event::Stop) | None => break,
@@ -446,7 +1485,13 @@ mod tests {
         }
 
         pub fn expect_subscribe(&mut self, subscription: Subscription) {
-            self.send.send(Event::Subscribe(subscription)).unwrap();
+            self.expect_subscribe_chunk(subscription, true);
+        }
+
+        pub fn expect_subscribe_chunk(&mut self, subscription: Subscription, is_last: bool) {
+            self.send
+                .send(Event::Subscribe(subscription, is_last))
+                .unwrap();
         }
 
         pub fn start(&mut self) {
@@ -555,21 +1600,105 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_subscription_chunking() {
-        const SYMBOL: &str = "TEST";
-        const SYMBOL_COUNT: usize = 1000;
-        let (mut fixture, mut client) = setup(Dataset::XnasItch, false, None).await;
-        let sub_base = Subscription::builder()
-            .schema(Schema::Ohlcv1M)
-            .stype_in(SType::RawSymbol);
-        let subscription = sub_base.clone().symbols(vec![SYMBOL; SYMBOL_COUNT]).build();
+    async fn test_subscribe_smart() {
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        let sub_base = Subscription::builder().schema(Schema::Trades);
+        let subscription = sub_base
+            .clone()
+            .symbols(Symbols::Smart(vec![
+                (SType::Parent, "ES.FUT".to_owned()),
+                (SType::RawSymbol, "CLZ4".to_owned()),
+                (SType::Parent, "NQ.FUT".to_owned()),
+            ]))
+            .build();
         client.subscribe(&subscription).await.unwrap();
-        let mut i = 0;
-        while i < SYMBOL_COUNT {
-            let chunk_size = 128.min(SYMBOL_COUNT - i);
-            fixture.expect_subscribe(sub_base.clone().symbols(vec![SYMBOL; chunk_size]).build());
-            i += chunk_size;
-        }
+        fixture.expect_subscribe_chunk(
+            sub_base
+                .clone()
+                .symbols(vec!["ES.FUT", "NQ.FUT"])
+                .stype_in(SType::Parent)
+                .build(),
+            false,
+        );
+        fixture.expect_subscribe(sub_base.symbols("CLZ4").stype_in(SType::RawSymbol).build());
+        fixture.stop().await;
+    }
+
+    #[cfg(feature = "historical")]
+    #[tokio::test]
+    async fn test_validate_subscription() {
+        use wiremock::{
+            matchers::{method, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(format!(
+                "/v{}/symbology.resolve",
+                crate::historical::API_VERSION
+            )))
+            .and(crate::body_contains("start_date", "2024-06-03"))
+            .and(crate::body_contains("end_date", "2024-06-10"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "result": {
+                    "ESM4": [{"d0": "2024-06-03", "d1": "2024-06-10", "s": "1"}]
+                },
+                "partial": [],
+                "not_found": ["ESM44", "NQ"]
+            })))
+            .mount(&mock_server)
+            .await;
+        let historical = crate::HistoricalClient::with_url(
+            mock_server.uri(),
+            "32-character-with-lots-of-filler".to_owned(),
+            crate::historical::HistoricalGateway::Bo1,
+        )
+        .unwrap();
+        let (fixture, client) = setup(Dataset::GlbxMdp3, false, None).await;
+        let sub_base = Subscription::builder()
+            .schema(Schema::Trades)
+            .start(datetime!(2024-06-10 13:30 UTC));
+        let res = client
+            .validate_subscription(
+                &sub_base
+                    .clone()
+                    .symbols(vec!["ESM4", "ESM44", "NQ"])
+                    .build(),
+                &historical,
+            )
+            .await;
+        let Err(Error::BadArgument { desc, .. }) = res else {
+            panic!("Expected bad argument error, got {res:?}");
+        };
+        assert!(desc.ends_with("GLBX.MDP3: ESM44, NQ"), "{desc}");
+        // No request needed
+        client
+            .validate_subscription(&sub_base.symbols(Symbols::All).build(), &historical)
+            .await
+            .unwrap();
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_subscription_chunking() {
+        const SYMBOL: &str = "TEST";
+        const SYMBOL_COUNT: usize = 1000;
+        let (mut fixture, mut client) = setup(Dataset::XnasItch, false, None).await;
+        let sub_base = Subscription::builder()
+            .schema(Schema::Ohlcv1M)
+            .stype_in(SType::RawSymbol);
+        let subscription = sub_base.clone().symbols(vec![SYMBOL; SYMBOL_COUNT]).build();
+        client.subscribe(&subscription).await.unwrap();
+        let mut i = 0;
+        while i < SYMBOL_COUNT {
+            let chunk_size = 128.min(SYMBOL_COUNT - i);
+            i += chunk_size;
+            fixture.expect_subscribe_chunk(
+                sub_base.clone().symbols(vec![SYMBOL; chunk_size]).build(),
+                i == SYMBOL_COUNT,
+            );
+        }
         fixture.stop().await;
     }
 
@@ -586,16 +1715,176 @@ mod tests {
         let (mut fixture, mut client) =
             setup(Dataset::GlbxMdp3, false, Some(Duration::minutes(5))).await;
         fixture.start();
+        assert!(client.metadata().is_none());
+        assert!(client.dbn_version().is_none());
+        assert_eq!(client.gateway_version(), "test");
         let metadata = client.start().await.unwrap();
         assert_eq!(metadata.version, dbn::DBN_VERSION);
+        assert_eq!(client.dbn_version(), Some(dbn::DBN_VERSION));
         assert!(metadata.schema.is_none());
         assert_eq!(metadata.dataset, Dataset::GlbxMdp3.as_str());
+        assert_eq!(client.metadata(), Some(&metadata));
         fixture.send_record(REC);
         let rec = client.next_record().await.unwrap().unwrap();
         assert_eq!(*rec.get::<OhlcvMsg>().unwrap(), REC);
         fixture.stop().await;
     }
 
+    #[tokio::test]
+    async fn test_next_record_owned() {
+        const REC: OhlcvMsg = OhlcvMsg {
+            hd: RecordHeader::new::<OhlcvMsg>(rtype::OHLCV_1M, 1, 2, 3),
+            open: 1,
+            high: 2,
+            low: 3,
+            close: 4,
+            volume: 5,
+        };
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        fixture.start();
+        client.start().await.unwrap();
+        fixture.send_record(REC);
+        let rec = client.next_record_owned().await.unwrap().unwrap();
+        let handle = tokio::spawn(async move {
+            match rec {
+                RecordEnum::Ohlcv(ohlcv) => ohlcv,
+                rec => panic!("Unexpected record {rec:?}"),
+            }
+        });
+        assert_eq!(handle.await.unwrap(), REC);
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_next_record_owned_with_instrument_filter() {
+        let mut fixture = Fixture::new(Dataset::XnasItch.to_string(), false).await;
+        fixture.authenticate(None);
+        let mut client = Client::builder()
+            .addr(format!("127.0.0.1:{}", fixture.port))
+            .await
+            .unwrap()
+            .key("32-character-with-lots-of-filler".to_owned())
+            .unwrap()
+            .dataset(Dataset::XnasItch.to_string())
+            .instrument_filter(InstrumentFilter::new([2]))
+            .build()
+            .await
+            .unwrap();
+        fixture.start();
+        client.start().await.unwrap();
+        for instrument_id in [1, 2, 3, 2] {
+            fixture.send_record(TradeMsg {
+                hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, instrument_id, 3),
+                price: instrument_id as i64,
+                ..Default::default()
+            });
+        }
+        fixture.stop().await;
+        for _ in 0..2 {
+            let rec = client.next_record_owned().await.unwrap().unwrap();
+            let RecordEnum::Trade(trade) = rec else {
+                panic!("unexpected record {rec:?}");
+            };
+            assert_eq!(trade.hd.instrument_id, 2);
+        }
+        assert!(client.next_record_owned().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_group() {
+        let (mut fixture, mut client) = setup(Dataset::XnasItch, false, None).await;
+        let subscriptions = [
+            Subscription::builder()
+                .symbols(vec![1, 2])
+                .schema(Schema::Trades)
+                .stype_in(SType::InstrumentId)
+                .build(),
+            Subscription::builder()
+                .symbols(vec![2, 3])
+                .schema(Schema::Trades)
+                .stype_in(SType::InstrumentId)
+                .group("analytics")
+                .build(),
+        ];
+        for subscription in subscriptions.iter() {
+            fixture.expect_subscribe(subscription.clone());
+            client.subscribe(subscription).await.unwrap();
+        }
+        assert_eq!(client.subscription_groups().groups(), ["analytics"]);
+        assert!(client.unsubscribe_group("other").is_err());
+        client.unsubscribe_group("analytics").unwrap();
+        assert_eq!(client.subscriptions(), &subscriptions[..1]);
+        fixture.start();
+        client.start().await.unwrap();
+        for instrument_id in [1, 2, 3, 2] {
+            fixture.send_record(TradeMsg {
+                hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, instrument_id, 3),
+                ..Default::default()
+            });
+        }
+        fixture.stop().await;
+        for instrument_id in [1, 2, 2] {
+            let rec = client.next_record_owned().await.unwrap().unwrap();
+            let RecordEnum::Trade(trade) = rec else {
+                panic!("unexpected record {rec:?}");
+            };
+            assert_eq!(trade.hd.instrument_id, instrument_id);
+        }
+        assert!(client.next_record_owned().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bind_addr() {
+        let mut fixture = Fixture::new(Dataset::XnasItch.to_string(), false).await;
+        fixture.authenticate(None);
+        let builder = Client::builder()
+            .addr(format!("127.0.0.1:{}", fixture.port))
+            .await
+            .unwrap()
+            .key("32-character-with-lots-of-filler".to_owned())
+            .unwrap()
+            .dataset(Dataset::XnasItch.to_string());
+        // No IPv6 gateway address
+        assert!(matches!(
+            builder.clone().ip_version(IpVersion::V6).build().await,
+            Err(Error::BadArgument { .. })
+        ));
+        let client = builder
+            .bind_addr(IpAddr::from([127, 0, 0, 1]))
+            .ip_version(IpVersion::V4)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(
+            client.gateway_addr(),
+            SocketAddr::from(([127, 0, 0, 1], fixture.port))
+        );
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_connect_retries() {
+        // Find a port with nothing listening on it
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let start = std::time::Instant::now();
+        let res = Client::builder()
+            .addr(addr)
+            .await
+            .unwrap()
+            .key("32-character-with-lots-of-filler".to_owned())
+            .unwrap()
+            .dataset(Dataset::XnasItch.to_string())
+            .connect_timeout(std::time::Duration::from_secs(5))
+            .connect_retries(2)
+            .build()
+            .await;
+        assert!(matches!(res, Err(Error::Io(e)) if e.kind() == io::ErrorKind::ConnectionRefused));
+        // Retried after 250ms and then 500ms
+        assert!(start.elapsed() >= std::time::Duration::from_millis(750));
+    }
+
     #[tokio::test]
     async fn test_next_record_with_ts_out() {
         let expected = WithTsOut::new(
@@ -625,6 +1914,386 @@ mod tests {
         fixture.stop().await;
     }
 
+    #[tokio::test]
+    async fn test_collect_snapshot() {
+        fn mbo(order_id: u64, price: i64, flags: FlagSet) -> MboMsg {
+            MboMsg {
+                hd: RecordHeader::new::<MboMsg>(rtype::MBO, 1, 10, 0),
+                order_id,
+                price,
+                size: 1,
+                flags,
+                action: b'A' as c_char,
+                side: b'B' as c_char,
+                ..Default::default()
+            }
+        }
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        let subscription = Subscription::builder()
+            .symbols(vec!["ESM5"])
+            .schema(Schema::Mbo)
+            .use_snapshot()
+            .build();
+        fixture.expect_subscribe(subscription.clone());
+        client.subscribe(&subscription).await.unwrap();
+        fixture.start();
+        client.start().await.unwrap();
+        fixture.send_record(mbo(1, 100, FlagSet::from(flags::SNAPSHOT)));
+        fixture.send_record(mbo(2, 101, FlagSet::from(flags::SNAPSHOT | flags::LAST)));
+        // Returns at the end of the snapshot without waiting for an incremental record
+        let snapshot = client.collect_snapshot().await.unwrap();
+        let book = snapshot.book(10).unwrap();
+        assert_eq!(book.orders().len(), 2);
+        assert_eq!(book.bids()[0].price, 101);
+        assert!(book.asks().is_empty());
+        let incremental = mbo(3, 102, FlagSet::default());
+        fixture.send_record(incremental.clone());
+        let rec = client.next_record().await.unwrap().unwrap();
+        assert_eq!(*rec.get::<MboMsg>().unwrap(), incremental);
+        fixture.send_record(OhlcvMsg::default());
+        let rec = client.next_record().await.unwrap().unwrap();
+        assert!(rec.get::<OhlcvMsg>().is_some());
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_collect_snapshot_invalid_subscriptions() {
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        assert!(matches!(
+            client.collect_snapshot().await,
+            Err(Error::BadArgument { .. })
+        ));
+        let subscription = Subscription::builder()
+            .symbols(vec!["ESM5"])
+            .schema(Schema::Mbo)
+            .build();
+        fixture.expect_subscribe(subscription.clone());
+        client.subscribe(&subscription).await.unwrap();
+        assert!(matches!(
+            client.collect_snapshot().await,
+            Err(Error::BadArgument { .. })
+        ));
+        fixture.stop().await;
+
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        let subscription = Subscription::builder()
+            .symbols(vec!["ESM5"])
+            .schema(Schema::Trades)
+            .use_snapshot()
+            .build();
+        fixture.expect_subscribe(subscription.clone());
+        client.subscribe(&subscription).await.unwrap();
+        assert!(matches!(
+            client.collect_snapshot().await,
+            Err(Error::BadArgument { .. })
+        ));
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_request_definitions() {
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        let subscription = Subscription::builder()
+            .symbols("ES.FUT")
+            .schema(Schema::Definition)
+            .stype_in(SType::Parent)
+            .use_snapshot()
+            .build();
+        fixture.expect_subscribe(subscription);
+        fixture.start();
+        for instrument_id in [1, 2, 1] {
+            fixture.send_record(InstrumentDefMsg {
+                hd: RecordHeader::new::<InstrumentDefMsg>(
+                    rtype::INSTRUMENT_DEF,
+                    1,
+                    instrument_id,
+                    0,
+                ),
+                ..Default::default()
+            });
+        }
+        fixture.send_record(SystemMsg::new(0, "Finished definition replay").unwrap());
+        let definitions = client
+            .request_definitions("ES.FUT", SType::Parent)
+            .await
+            .unwrap();
+        assert_eq!(definitions.len(), 2);
+        assert!(definitions.contains_key(&1));
+        assert!(definitions.contains_key(&2));
+        assert!(matches!(
+            client.request_definitions("ES.FUT", SType::Parent).await,
+            Err(Error::BadArgument { .. })
+        ));
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_request_definitions_v1() {
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        client.set_upgrade_policy(VersionUpgradePolicy::AsIs);
+        fixture.expect_subscribe(
+            Subscription::builder()
+                .symbols("ES.FUT")
+                .schema(Schema::Definition)
+                .stype_in(SType::Parent)
+                .use_snapshot()
+                .build(),
+        );
+        fixture.start();
+        fixture.send_record(InstrumentDefMsgV1 {
+            hd: RecordHeader::new::<InstrumentDefMsgV1>(rtype::INSTRUMENT_DEF, 1, 1, 0),
+            ..Default::default()
+        });
+        fixture.send_record(SystemMsgV1::new(0, "Finished definition replay").unwrap());
+        let definitions = client
+            .request_definitions("ES.FUT", SType::Parent)
+            .await
+            .unwrap();
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[&1].hd.instrument_id, 1);
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_split() {
+        const REC: OhlcvMsg = OhlcvMsg {
+            hd: RecordHeader::new::<OhlcvMsg>(rtype::OHLCV_1M, 1, 2, 3),
+            open: 1,
+            high: 2,
+            low: 3,
+            close: 4,
+            volume: 5,
+        };
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        fixture.start();
+        client.start().await.unwrap();
+        let (mut sender, mut receiver) = client.split();
+        let recv_task = tokio::spawn(async move {
+            let rec = receiver.next_record().await.unwrap().unwrap();
+            rec.get::<OhlcvMsg>().cloned()
+        });
+        let subscription = Subscription::builder()
+            .symbols(vec!["ESM5"])
+            .schema(Schema::Ohlcv1M)
+            .build();
+        fixture.expect_subscribe(subscription.clone());
+        sender.subscribe(&subscription).await.unwrap();
+        fixture.send_record(REC);
+        assert_eq!(recv_task.await.unwrap(), Some(REC));
+        sender.close().await.unwrap();
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_raise_gateway_errors() {
+        const REC: OhlcvMsg = OhlcvMsg {
+            hd: RecordHeader::new::<OhlcvMsg>(rtype::OHLCV_1M, 1, 2, 3),
+            open: 1,
+            high: 2,
+            low: 3,
+            close: 4,
+            volume: 5,
+        };
+        const ERR: &str = "Unknown symbol: ESZ0";
+        let mut fixture = Fixture::new(Dataset::GlbxMdp3.to_string(), false).await;
+        fixture.authenticate(None);
+        let mut target = Client::builder()
+            .addr(format!("127.0.0.1:{}", fixture.port))
+            .await
+            .unwrap()
+            .key("32-character-with-lots-of-filler".to_owned())
+            .unwrap()
+            .dataset(Dataset::GlbxMdp3.to_string())
+            .raise_gateway_errors(true)
+            .build()
+            .await
+            .unwrap();
+        assert!(target.raise_gateway_errors());
+        fixture.start();
+        target.start().await.unwrap();
+        fixture.send_record(REC);
+        fixture.send_record(ErrorMsg::new(0, ERR, true));
+        let rec = target.next_record().await.unwrap().unwrap();
+        assert_eq!(*rec.get::<OhlcvMsg>().unwrap(), REC);
+        let err = target.next_record().await.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::Error::Gateway(GatewayError { message, is_fatal: false, .. }) if message == ERR
+        ));
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_raise_gateway_errors_v1() {
+        const ERR: &str = "Unknown symbol: ESZ0";
+        let mut fixture = Fixture::new(Dataset::GlbxMdp3.to_string(), false).await;
+        fixture.authenticate(None);
+        let mut target = Client::builder()
+            .addr(format!("127.0.0.1:{}", fixture.port))
+            .await
+            .unwrap()
+            .key("32-character-with-lots-of-filler".to_owned())
+            .unwrap()
+            .dataset(Dataset::GlbxMdp3.to_string())
+            .raise_gateway_errors(true)
+            .upgrade_policy(VersionUpgradePolicy::AsIs)
+            .build()
+            .await
+            .unwrap();
+        fixture.start();
+        target.start().await.unwrap();
+        fixture.send_record(ErrorMsgV1::new(0, ERR));
+        let err = target.next_record().await.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::Error::Gateway(GatewayError { message, .. }) if message == ERR
+        ));
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_subscription_end() {
+        fn ohlcv(ts_event: u64) -> OhlcvMsg {
+            OhlcvMsg {
+                hd: RecordHeader::new::<OhlcvMsg>(rtype::OHLCV_1M, 1, 2, ts_event),
+                ..Default::default()
+            }
+        }
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        let subscription = Subscription::builder()
+            .symbols(vec!["ESM5"])
+            .schema(Schema::Ohlcv1M)
+            .start(OffsetDateTime::from_unix_timestamp_nanos(1).unwrap())
+            .end(OffsetDateTime::from_unix_timestamp_nanos(2_000).unwrap())
+            .build();
+        fixture.expect_subscribe(subscription.clone());
+        client.subscribe(&subscription).await.unwrap();
+        fixture.start();
+        client.start().await.unwrap();
+        fixture.send_record(ohlcv(1_000));
+        fixture.send_record(ohlcv(2_000));
+        fixture.send_record(ohlcv(3_000));
+        let rec = client.next_record().await.unwrap().unwrap();
+        assert_eq!(rec.header().ts_event, 1_000);
+        assert!(client.next_record().await.unwrap().is_none());
+        assert!(client.next_record().await.unwrap().is_none());
+        assert_eq!(
+            client.last_ts_event().unwrap().unix_timestamp_nanos(),
+            1_000
+        );
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_session_snapshot_restore() {
+        const REC: OhlcvMsg = OhlcvMsg {
+            hd: RecordHeader::new::<OhlcvMsg>(rtype::OHLCV_1M, 1, 2, 1_000),
+            open: 1,
+            high: 2,
+            low: 3,
+            close: 4,
+            volume: 5,
+        };
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        let subscription = Subscription::builder()
+            .symbols(vec!["ESM5"])
+            .schema(Schema::Ohlcv1M)
+            .build();
+        fixture.expect_subscribe(subscription.clone());
+        client.subscribe(&subscription).await.unwrap();
+        fixture.start();
+        client.start().await.unwrap();
+        assert!(client.last_ts_event().is_none());
+        fixture.send_record(REC);
+        client.next_record().await.unwrap().unwrap();
+        let snapshot = client.session_snapshot();
+        assert_eq!(snapshot.subscriptions, vec![subscription.clone()]);
+        assert_eq!(
+            snapshot.last_ts_event.unwrap().unix_timestamp_nanos(),
+            1_000
+        );
+        client.close().await.unwrap();
+        fixture.stop().await;
+
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        let mut expected = subscription;
+        expected.start = Some(OffsetDateTime::from_unix_timestamp_nanos(1_001).unwrap());
+        fixture.expect_subscribe(expected.clone());
+        client.restore(&snapshot, true).await.unwrap();
+        assert_eq!(client.subscriptions(), &[expected]);
+        fixture.stop().await;
+
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        fixture.expect_subscribe(expected.clone());
+        client
+            .restore_with_max_gap(&snapshot, Duration::MAX)
+            .await
+            .unwrap();
+        assert_eq!(client.subscriptions(), &[expected]);
+        fixture.stop().await;
+
+        let (_fixture, mut client) = setup(Dataset::XnasItch, false, None).await;
+        assert!(client.restore(&snapshot, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restore_without_backfill() {
+        let replay = Subscription::builder()
+            .symbols(vec!["ESM5"])
+            .schema(Schema::Ohlcv1M)
+            .start(datetime!(2025-01-02 12:00 UTC))
+            .build();
+        let snapshot_sub = Subscription::builder()
+            .symbols(vec!["NQM5"])
+            .schema(Schema::Mbo)
+            .use_snapshot()
+            .build();
+        let mut snapshot = SessionSnapshot {
+            dataset: Dataset::GlbxMdp3.to_string(),
+            subscriptions: vec![replay.clone(), snapshot_sub.clone()],
+            last_ts_event: Some(datetime!(2025-01-02 13:00 UTC)),
+        };
+        let live = |sub: &Subscription| Subscription {
+            start: None,
+            use_snapshot: false,
+            ..sub.clone()
+        };
+        let expected = vec![live(&replay), live(&snapshot_sub)];
+
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        for sub in expected.iter() {
+            fixture.expect_subscribe(sub.clone());
+        }
+        client.restore(&snapshot, false).await.unwrap();
+        assert_eq!(client.subscriptions(), expected.as_slice());
+        fixture.stop().await;
+
+        snapshot.last_ts_event = None;
+        let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, false, None).await;
+        for sub in expected.iter() {
+            fixture.expect_subscribe(sub.clone());
+        }
+        client
+            .restore_with_max_gap(&snapshot, Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(client.subscriptions(), expected.as_slice());
+        fixture.stop().await;
+    }
+
+    #[test]
+    fn test_bounded_backfill_start() {
+        let now = datetime!(2025-01-02 12:00 UTC);
+        assert_eq!(
+            bounded_backfill_start(datetime!(2025-01-02 11:00 UTC), Duration::hours(2), now),
+            datetime!(2025-01-02 11:00 UTC) + Duration::NANOSECOND
+        );
+        assert_eq!(
+            bounded_backfill_start(datetime!(2025-01-01 12:00 UTC), Duration::hours(2), now),
+            datetime!(2025-01-02 10:00 UTC)
+        );
+    }
+
     #[tokio::test]
     async fn test_close() {
         let (mut fixture, mut client) =
@@ -635,6 +2304,53 @@ mod tests {
         fixture.stop().await;
     }
 
+    #[tokio::test]
+    async fn test_run_until_handler_breaks() {
+        let (mut fixture, mut client) = setup(Dataset::XnasItch, false, None).await;
+        fixture.start();
+        client.start().await.unwrap();
+        for instrument_id in [1, 2, 3] {
+            fixture.send_record(TradeMsg {
+                hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, instrument_id, 3),
+                ..Default::default()
+            });
+        }
+        let mut instrument_ids = Vec::new();
+        let mut handler = |rec: RecordRef| -> crate::Result<ControlFlow<()>> {
+            instrument_ids.push(rec.header().instrument_id);
+            Ok(if instrument_ids.len() == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            })
+        };
+        let summary = run_until(client, &mut handler, std::future::pending())
+            .await
+            .unwrap();
+        assert_eq!(summary.reason, ShutdownReason::Handler);
+        assert_eq!(summary.record_count, 2);
+        assert_eq!(summary.session.dataset, Dataset::XnasItch.as_str());
+        assert_eq!(instrument_ids, [1, 2]);
+        fixture.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_until_shutdown() {
+        let (mut fixture, mut client) = setup(Dataset::XnasItch, false, None).await;
+        fixture.start();
+        client.start().await.unwrap();
+        let summary = run_until(
+            client,
+            &mut |_: RecordRef| -> crate::Result<ControlFlow<()>> { panic!("unexpected record") },
+            std::future::ready(Ok(())),
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.reason, ShutdownReason::Signal);
+        assert_eq!(summary.record_count, 0);
+        fixture.stop().await;
+    }
+
     #[tokio::test]
     async fn test_error_without_success() {
         const DATASET: Dataset = Dataset::OpraPillar;
@@ -666,6 +2382,37 @@ mod tests {
         r2.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_incompatible_gateway_version() {
+        const DATASET: Dataset = Dataset::XnasItch;
+        for require_compatible_gateway in [false, true] {
+            let mut fixture = Fixture::new(DATASET.to_string(), false).await;
+            fixture.accept();
+            fixture.send("lsg_version=99.0.0\n".to_owned());
+            fixture.send("cram=t7kNhwj4xqR0QYjzFKtBEG2ec2pXJ4FK\n".to_owned());
+            fixture.send("success=1|session_id=5\n".to_owned());
+            let res = Client::builder()
+                .addr(format!("127.0.0.1:{}", fixture.port))
+                .await
+                .unwrap()
+                .key("32-character-with-lots-of-filler".to_owned())
+                .unwrap()
+                .dataset(DATASET.to_string())
+                .require_compatible_gateway(require_compatible_gateway)
+                .build()
+                .await;
+            if require_compatible_gateway {
+                let Err(Error::IncompatibleGateway { version, .. }) = res else {
+                    panic!("Expected incompatible gateway error, got {res:?}");
+                };
+                assert_eq!(version, "99.0.0");
+            } else {
+                assert_eq!(res.unwrap().gateway_version(), "99.0.0");
+            }
+            fixture.stop().await;
+        }
+    }
+
     #[tokio::test]
     async fn test_cancellation_safety() {
         let (mut fixture, mut client) = setup(Dataset::GlbxMdp3, true, None).await;
@@ -711,4 +2458,84 @@ mod tests {
         }
         fixture.stop().await;
     }
+
+    #[tokio::test]
+    async fn test_buffered_receiver() {
+        let (mut fixture, mut client) = setup(Dataset::XnasItch, false, None).await;
+        fixture.start();
+        client.start().await.unwrap();
+        let (_sender, receiver) = client.split();
+        let mut target =
+            receiver.buffered(NonZeroUsize::new(2).unwrap(), OverflowPolicy::DropOldest);
+        for price in 0..3 {
+            fixture.send_record(TradeMsg {
+                hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, 2, 3),
+                price,
+                ..Default::default()
+            });
+        }
+        fixture.stop().await;
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while target.dropped() == 0 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(target.dropped(), 1);
+        for price in 1..3 {
+            let rec = target.next_record().await.unwrap().unwrap();
+            let RecordEnum::Trade(trade) = rec else {
+                panic!("unexpected record {rec:?}");
+            };
+            assert_eq!(trade.price, price);
+        }
+        assert!(target.next_record().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_buffered_receiver_drop_lowest_priority() {
+        let (mut fixture, mut client) = setup(Dataset::XnasItch, false, None).await;
+        for (instrument_id, priority) in [(1, 0), (2, 5)] {
+            let subscription = Subscription::builder()
+                .symbols(vec![instrument_id])
+                .schema(Schema::Trades)
+                .stype_in(SType::InstrumentId)
+                .priority(priority)
+                .build();
+            fixture.expect_subscribe(subscription.clone());
+            client.subscribe(&subscription).await.unwrap();
+        }
+        fixture.start();
+        client.start().await.unwrap();
+        let (_sender, receiver) = client.split();
+        let mut target = receiver.buffered(
+            NonZeroUsize::new(2).unwrap(),
+            OverflowPolicy::DropLowestPriority,
+        );
+        for (instrument_id, price) in [(2, 0), (1, 1), (2, 2), (1, 3)] {
+            fixture.send_record(TradeMsg {
+                hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, instrument_id, 3),
+                price,
+                ..Default::default()
+            });
+        }
+        fixture.stop().await;
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while target.dropped() < 2 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(target.dropped(), 2);
+        for price in [0, 2] {
+            let rec = target.next_record().await.unwrap().unwrap();
+            let RecordEnum::Trade(trade) = rec else {
+                panic!("unexpected record {rec:?}");
+            };
+            assert_eq!(trade.price, price);
+        }
+        assert!(target.next_record().await.unwrap().is_none());
+    }
 }
@@ -0,0 +1,164 @@
+//! Routing records to typed channels by schema.
+
+use std::{future::Future, pin::Pin};
+
+use dbn::{record::HasRType, RecordRef};
+use tokio::sync::mpsc;
+
+use super::Client;
+
+/// Routes records from a live session or other stream to strongly-typed channels,
+/// one per record type, removing the need to match on `rtype` in applications.
+///
+/// Records are sent to every channel of a matching type and dropped if there's none.
+/// Sending waits for space in the channel, so a slow consumer applies backpressure
+/// to all channels. Channels whose receiver was dropped are removed.
+///
+/// # Example
+/// ```no_run
+/// # async fn example(client: &mut databento::LiveClient) -> databento::Result<()> {
+/// use databento::{dbn::{Mbp10Msg, TradeMsg}, live::demux::Demux};
+///
+/// let mut demux = Demux::new();
+/// let mut trades = demux.channel::<TradeMsg>(1024);
+/// let mut books = demux.channel::<Mbp10Msg>(1024);
+/// tokio::spawn(async move {
+///     while let Some(trade) = trades.recv().await {
+///         println!("{trade:?}");
+///     }
+/// });
+/// tokio::spawn(async move {
+///     while let Some(book) = books.recv().await {
+///         println!("{book:?}");
+///     }
+/// });
+/// demux.run(client).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Demux {
+    routes: Vec<Box<dyn Route>>,
+}
+
+impl Demux {
+    /// Creates a new demultiplexer without any channels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a channel with a buffer of `capacity` records that receives all
+    /// records of type `T`.
+    ///
+    /// # Panics
+    /// This function panics if `capacity` is 0.
+    pub fn channel<T>(&mut self, capacity: usize) -> mpsc::Receiver<T>
+    where
+        T: HasRType + Clone + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(capacity);
+        self.routes.push(Box::new(TypedRoute(sender)));
+        receiver
+    }
+
+    /// Returns `true` if all channels have been closed, in which case no further
+    /// records will be routed.
+    pub fn is_closed(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Sends a copy of `rec` to each channel of its type, returning the number of
+    /// channels it was sent to.
+    pub async fn dispatch(&mut self, rec: RecordRef<'_>) -> usize {
+        let mut sent_count = 0;
+        for route in self.routes.iter() {
+            if let Some(send) = route.send(rec) {
+                if send.await {
+                    sent_count += 1;
+                }
+            }
+        }
+        self.routes.retain(|route| !route.is_closed());
+        sent_count
+    }
+
+    /// Reads records from `client` and routes them until the session ends or all
+    /// channels are closed. The session should already be
+    /// [started](Client::start).
+    ///
+    /// # Errors
+    /// This function returns an error when [`Client::next_record()`] does.
+    pub async fn run(mut self, client: &mut Client) -> crate::Result<()> {
+        while let Some(rec) = client.next_record().await? {
+            self.dispatch(rec).await;
+            if self.is_closed() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+type SendFuture<'a> = Pin<Box<dyn Future<Output = bool> + Send + 'a>>;
+
+trait Route: Send {
+    /// Returns a future that sends a copy of `rec` and resolves to `false` if the
+    /// channel is closed, or `None` if `rec` isn't of the route's type.
+    fn send(&self, rec: RecordRef<'_>) -> Option<SendFuture<'_>>;
+
+    fn is_closed(&self) -> bool;
+}
+
+struct TypedRoute<T>(mpsc::Sender<T>);
+
+impl<T> Route for TypedRoute<T>
+where
+    T: HasRType + Clone + Send + 'static,
+{
+    fn send(&self, rec: RecordRef<'_>) -> Option<SendFuture<'_>> {
+        let rec = rec.get::<T>()?.clone();
+        Some(Box::pin(async move { self.0.send(rec).await.is_ok() }))
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{enums::rtype, record::RecordHeader, Mbp10Msg, OhlcvMsg, TradeMsg};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let trade = TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, 2, 3),
+            ..Default::default()
+        };
+        let bar = OhlcvMsg {
+            hd: RecordHeader::new::<OhlcvMsg>(rtype::OHLCV_1M, 1, 2, 3),
+            open: 1,
+            high: 2,
+            low: 3,
+            close: 4,
+            volume: 5,
+        };
+        let mut target = Demux::new();
+        let mut trades = target.channel::<TradeMsg>(2);
+        let mut trades2 = target.channel::<TradeMsg>(2);
+        let books = target.channel::<Mbp10Msg>(2);
+        assert_eq!(target.dispatch(RecordRef::from(&trade)).await, 2);
+        assert_eq!(target.dispatch(RecordRef::from(&bar)).await, 0);
+        assert_eq!(trades.recv().await.unwrap(), trade);
+        assert_eq!(trades2.recv().await.unwrap(), trade);
+        drop(books);
+        drop(trades2);
+        assert_eq!(target.dispatch(RecordRef::from(&trade)).await, 1);
+        assert_eq!(target.routes.len(), 1);
+        drop(trades);
+        assert_eq!(target.dispatch(RecordRef::from(&trade)).await, 0);
+        assert!(target.is_closed());
+    }
+}
@@ -0,0 +1,288 @@
+//! Grouping subscriptions by name and priority, so related symbols can be shed together
+//! under load or unsubscribed in one call.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use dbn::{enums::rtype, RecordRef, SymbolMappingMsg};
+
+use crate::Symbols;
+
+use super::Subscription;
+
+/// The [`group`](Subscription::group) and [`priority`](Subscription::priority) of the
+/// subscriptions of a session, used to look up the priority of records and whether
+/// they're only part of unsubscribed groups. Instruments are matched to subscriptions
+/// through the symbol mappings sent by the gateway.
+///
+/// Shared between a [`LiveClient`](super::Client) and the [`Sender`](super::Sender)
+/// and [`Receiver`](super::Receiver) it's split into, so cloning is cheap and clones
+/// reflect later subscriptions.
+#[derive(Debug, Clone, Default)]
+pub struct SubscriptionGroups(Arc<Shared>);
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    has_unsubscribed: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    entries: Vec<Entry>,
+    /// The indices of the entries each instrument is part of.
+    instruments: HashMap<u32, Vec<usize>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    group: Option<String>,
+    priority: u8,
+    symbols: Symbols,
+    is_unsubscribed: bool,
+}
+
+impl SubscriptionGroups {
+    /// Returns the priority of `rec`: the highest priority of the subscriptions its
+    /// instrument is part of that haven't been unsubscribed. Gateway messages such as
+    /// errors and symbol mappings always have the highest priority, and records of
+    /// instruments that aren't part of any known subscription have the lowest.
+    pub fn priority(&self, rec: &RecordRef) -> u8 {
+        let header = rec.header();
+        if is_gateway_msg(header.rtype) {
+            return u8::MAX;
+        }
+        let mut state = self.lock();
+        let State {
+            entries,
+            instruments,
+        } = &mut *state;
+        memberships(entries, instruments, header.instrument_id)
+            .iter()
+            .map(|&idx| &entries[idx])
+            .filter(|entry| !entry.is_unsubscribed)
+            .map(|entry| entry.priority)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if every subscription the instrument of `rec` is part of has been
+    /// unsubscribed with [`LiveClient::unsubscribe_group()`](super::Client::unsubscribe_group).
+    pub fn is_unsubscribed(&self, rec: &RecordRef) -> bool {
+        if !self.0.has_unsubscribed.load(Ordering::Relaxed) {
+            return false;
+        }
+        let header = rec.header();
+        if is_gateway_msg(header.rtype) {
+            return false;
+        }
+        let mut state = self.lock();
+        let State {
+            entries,
+            instruments,
+        } = &mut *state;
+        let memberships = memberships(entries, instruments, header.instrument_id);
+        !memberships.is_empty() && memberships.iter().all(|&idx| entries[idx].is_unsubscribed)
+    }
+
+    /// Returns the names of the groups with at least one subscription that hasn't been
+    /// unsubscribed, in the order they were first subscribed.
+    pub fn groups(&self) -> Vec<String> {
+        let mut groups: Vec<String> = Vec::new();
+        for entry in self.lock().entries.iter().filter(|e| !e.is_unsubscribed) {
+            if let Some(group) = &entry.group {
+                if !groups.contains(group) {
+                    groups.push(group.clone());
+                }
+            }
+        }
+        groups
+    }
+
+    pub(crate) fn add(&self, sub: &Subscription) {
+        let mut state = self.lock();
+        let idx = state.entries.len();
+        state.entries.push(Entry {
+            group: sub.group.clone(),
+            priority: sub.priority,
+            symbols: sub.symbols.clone(),
+            is_unsubscribed: false,
+        });
+        // Subscriptions by ID don't receive symbol mappings
+        for (&instrument_id, memberships) in state.instruments.iter_mut() {
+            if matches_id(&sub.symbols, instrument_id) {
+                memberships.push(idx);
+            }
+        }
+    }
+
+    /// Marks the subscriptions in `group` as unsubscribed and returns the number of
+    /// subscriptions affected.
+    pub(crate) fn unsubscribe(&self, group: &str) -> usize {
+        let mut count = 0;
+        for entry in self
+            .lock()
+            .entries
+            .iter_mut()
+            .filter(|entry| !entry.is_unsubscribed && entry.group.as_deref() == Some(group))
+        {
+            entry.is_unsubscribed = true;
+            count += 1;
+        }
+        if count > 0 {
+            self.0.has_unsubscribed.store(true, Ordering::Relaxed);
+        }
+        count
+    }
+
+    /// Records the instrument of `rec` if it's a symbol mapping.
+    pub(crate) fn observe(&self, rec: &RecordRef) {
+        if rec.header().rtype != rtype::SYMBOL_MAPPING {
+            return;
+        }
+        let Some(symbol) = rec
+            .get::<SymbolMappingMsg>()
+            .and_then(|mapping| mapping.stype_in_symbol().ok())
+        else {
+            return;
+        };
+        let mut state = self.lock();
+        let State {
+            entries,
+            instruments,
+        } = &mut *state;
+        let memberships = memberships(entries, instruments, rec.header().instrument_id);
+        for (idx, entry) in entries.iter().enumerate() {
+            if !memberships.contains(&idx) && matches_symbol(&entry.symbols, symbol) {
+                memberships.push(idx);
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The lock is never held across a panic
+        self.0.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns the indices of the entries the instrument with `instrument_id` is part of,
+/// matching it against subscriptions by ID the first time it's seen.
+fn memberships<'a>(
+    entries: &[Entry],
+    instruments: &'a mut HashMap<u32, Vec<usize>>,
+    instrument_id: u32,
+) -> &'a mut Vec<usize> {
+    instruments.entry(instrument_id).or_insert_with(|| {
+        entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| matches_id(&entry.symbols, instrument_id))
+            .map(|(idx, _)| idx)
+            .collect()
+    })
+}
+
+fn is_gateway_msg(rtype: u8) -> bool {
+    matches!(rtype, rtype::ERROR | rtype::SYSTEM | rtype::SYMBOL_MAPPING)
+}
+
+fn matches_id(symbols: &Symbols, instrument_id: u32) -> bool {
+    match symbols {
+        Symbols::All => true,
+        Symbols::Ids(ids) => ids.contains(&instrument_id),
+        Symbols::Symbols(_) | Symbols::Smart(_) => false,
+    }
+}
+
+fn matches_symbol(symbols: &Symbols, symbol: &str) -> bool {
+    match symbols {
+        Symbols::All | Symbols::Ids(_) => false,
+        Symbols::Symbols(symbols) => symbols.iter().any(|s| s == symbol),
+        Symbols::Smart(symbols) => symbols.iter().any(|(_, s)| s == symbol),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{record::RecordHeader, SType, Schema, TradeMsg};
+
+    use super::*;
+
+    fn trade(instrument_id: u32) -> TradeMsg {
+        TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, instrument_id, 0),
+            ..Default::default()
+        }
+    }
+
+    fn mapping(instrument_id: u32, symbol: &str) -> SymbolMappingMsg {
+        SymbolMappingMsg::new(
+            instrument_id,
+            0,
+            SType::RawSymbol,
+            symbol,
+            SType::RawSymbol,
+            symbol,
+            0,
+            u64::MAX,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_groups() {
+        let target = SubscriptionGroups::default();
+        target.add(
+            &Subscription::builder()
+                .symbols(vec!["AAPL", "MSFT"])
+                .schema(Schema::Trades)
+                .priority(10)
+                .build(),
+        );
+        target.add(
+            &Subscription::builder()
+                .symbols(vec!["MSFT", "QQQ"])
+                .schema(Schema::Trades)
+                .group("analytics")
+                .build(),
+        );
+        target.add(
+            &Subscription::builder()
+                .symbols(vec![4])
+                .schema(Schema::Trades)
+                .stype_in(SType::InstrumentId)
+                .group("analytics")
+                .priority(1)
+                .build(),
+        );
+        for (instrument_id, symbol) in [(1, "AAPL"), (2, "MSFT"), (3, "QQQ")] {
+            target.observe(&RecordRef::from(&mapping(instrument_id, symbol)));
+        }
+        let priority = |instrument_id| target.priority(&RecordRef::from(&trade(instrument_id)));
+        assert_eq!(priority(1), 10);
+        assert_eq!(priority(2), 10);
+        assert_eq!(priority(3), 0);
+        assert_eq!(priority(4), 1);
+        assert_eq!(target.groups(), ["analytics"]);
+        assert!(!target.is_unsubscribed(&RecordRef::from(&trade(3))));
+
+        assert_eq!(target.unsubscribe("analytics"), 2);
+        assert_eq!(target.unsubscribe("analytics"), 0);
+        assert!(target.groups().is_empty());
+        let is_unsubscribed =
+            |instrument_id| target.is_unsubscribed(&RecordRef::from(&trade(instrument_id)));
+        // MSFT is also part of the first subscription
+        assert!(!is_unsubscribed(1));
+        assert!(!is_unsubscribed(2));
+        assert!(is_unsubscribed(3));
+        assert!(is_unsubscribed(4));
+        assert!(!is_unsubscribed(5));
+        assert_eq!(priority(4), 0);
+        assert!(!target.is_unsubscribed(&RecordRef::from(&mapping(3, "QQQ"))));
+    }
+}
@@ -0,0 +1,123 @@
+//! Writing live data as JSON lines for consumers without a DBN decoder.
+
+use std::path::Path;
+
+use dbn::{
+    encode::{json::Encoder, EncodeRecordRef},
+    RecordRef,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncWrite, AsyncWriteExt, BufWriter},
+};
+use typed_builder::TypedBuilder;
+
+/// The parameters for [`JsonLinesSink::new()`]. Use [`JsonLinesParams::builder()`] to
+/// get a builder type with all the preset defaults.
+#[derive(Debug, Clone, Copy, Default, TypedBuilder, PartialEq, Eq)]
+pub struct JsonLinesParams {
+    /// If `true`, prices will be formatted to the correct scale (using the fixed-
+    /// precision scalar 1e-9). Defaults to `false`.
+    #[builder(default)]
+    pub pretty_px: bool,
+    /// If `true`, timestamps will be formatted as ISO 8601 strings. Defaults to
+    /// `false`.
+    #[builder(default)]
+    pub pretty_ts: bool,
+}
+
+/// Writes live records as JSON lines, one JSON object per record, using DBN's JSON
+/// encoding. The text counterpart to a [`Recorder`](super::recorder::Recorder).
+/// [`finish()`](Self::finish) must be called to flush the remaining output.
+pub struct JsonLinesSink<W> {
+    writer: W,
+    params: JsonLinesParams,
+    buffer: Vec<u8>,
+}
+
+impl JsonLinesSink<BufWriter<File>> {
+    /// Creates a sink that writes to a new file at `path`, truncating it if it already
+    /// exists.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to create the file.
+    pub async fn create(path: impl AsRef<Path>, params: JsonLinesParams) -> crate::Result<Self> {
+        let file = File::create(path).await?;
+        Ok(Self::new(BufWriter::new(file), params))
+    }
+}
+
+impl<W> JsonLinesSink<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Creates a sink that writes to `writer`.
+    pub fn new(writer: W, params: JsonLinesParams) -> Self {
+        Self {
+            writer,
+            params,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Writes `rec` as a single line of JSON.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to encode `rec` or write to the
+    /// underlying writer.
+    pub async fn record(&mut self, rec: RecordRef<'_>) -> crate::Result<()> {
+        self.buffer.clear();
+        Encoder::new(
+            &mut self.buffer,
+            false,
+            self.params.pretty_px,
+            self.params.pretty_ts,
+        )
+        .encode_record_ref(rec)?;
+        self.writer.write_all(&self.buffer).await?;
+        Ok(())
+    }
+
+    /// Flushes and shuts down the underlying writer, returning it.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to flush or shut down the writer.
+    pub async fn finish(mut self) -> crate::Result<W> {
+        self.writer.shutdown().await?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{enums::rtype, record::RecordHeader, TradeMsg, FIXED_PRICE_SCALE};
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_json_lines_sink() {
+        let ts = datetime!(2024-07-01 13:30 UTC).unix_timestamp_nanos() as u64;
+        let trade = TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, 1, ts),
+            price: 10 * FIXED_PRICE_SCALE + FIXED_PRICE_SCALE / 4,
+            ts_recv: ts,
+            ..Default::default()
+        };
+        let mut target = JsonLinesSink::new(
+            Vec::new(),
+            JsonLinesParams::builder()
+                .pretty_px(true)
+                .pretty_ts(true)
+                .build(),
+        );
+        target.record(RecordRef::from(&trade)).await.unwrap();
+        target.record(RecordRef::from(&trade)).await.unwrap();
+        let output = String::from_utf8(target.finish().await.unwrap()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], lines[1]);
+        assert!(lines[0].contains(r#""ts_recv":"2024-07-01T13:30:00.000000000Z""#));
+        assert!(lines[0].contains(r#""price":"10.250000000""#));
+    }
+}
@@ -0,0 +1,156 @@
+//! Measuring the latency between the live gateway and the client.
+
+use std::{collections::VecDeque, num::NonZeroUsize};
+
+use dbn::{Record, RecordRef, UNDEF_TIMESTAMP};
+use time::{Duration, OffsetDateTime};
+
+/// A summary of the latencies of the most recent records measured by
+/// [`LiveClient::measure_latency()`](super::Client::measure_latency).
+///
+/// Latency is measured as the difference between the local time a record was read
+/// and its `ts_out`, the time the gateway sent it. Because the two timestamps come
+/// from different clocks, each latency includes the skew between them. One-way
+/// latency can't be separated from skew, so [`min`](Self::min) is the best estimate
+/// of the skew: a negative value means the local clock is behind the gateway's by at
+/// least that much.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    /// The number of records the summary is based on.
+    pub sample_count: usize,
+    /// The lowest latency, which approximates clock skew plus the minimum network
+    /// latency.
+    pub min: Duration,
+    /// The median latency.
+    pub p50: Duration,
+    /// The 90th percentile latency.
+    pub p90: Duration,
+    /// The 99th percentile latency.
+    pub p99: Duration,
+    /// The highest latency.
+    pub max: Duration,
+    /// The median time between a record's primary timestamp, `ts_recv` for most
+    /// schemas, and the gateway sending it. Both are from Databento's clocks so this
+    /// isn't affected by skew.
+    pub gateway_p50: Duration,
+}
+
+/// Tracks the latencies of a rolling window of records with `ts_out`.
+#[derive(Debug, Clone)]
+pub(crate) struct LatencyTracker {
+    window: NonZeroUsize,
+    latencies: VecDeque<i64>,
+    gateway_latencies: VecDeque<i64>,
+}
+
+impl LatencyTracker {
+    pub fn new(window: NonZeroUsize) -> Self {
+        Self {
+            window,
+            latencies: VecDeque::with_capacity(window.get()),
+            gateway_latencies: VecDeque::with_capacity(window.get()),
+        }
+    }
+
+    /// Records the latency of `rec`, which must have been decoded with `ts_out`.
+    pub fn observe(&mut self, rec: &RecordRef) {
+        let now = OffsetDateTime::now_utc().unix_timestamp_nanos() as u64;
+        let Some(ts_out) = ts_out(rec) else {
+            return;
+        };
+        self.observe_at(rec.raw_index_ts(), ts_out, now);
+    }
+
+    fn observe_at(&mut self, index_ts: u64, ts_out: u64, now: u64) {
+        if ts_out == UNDEF_TIMESTAMP || ts_out == 0 {
+            return;
+        }
+        push_bounded(&mut self.latencies, self.window, now as i64 - ts_out as i64);
+        if index_ts != UNDEF_TIMESTAMP && index_ts <= ts_out {
+            push_bounded(
+                &mut self.gateway_latencies,
+                self.window,
+                (ts_out - index_ts) as i64,
+            );
+        }
+    }
+
+    pub fn summary(&self) -> Option<LatencySummary> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let mut latencies = Vec::from(self.latencies.clone());
+        latencies.sort_unstable();
+        let mut gateway_latencies = Vec::from(self.gateway_latencies.clone());
+        gateway_latencies.sort_unstable();
+        Some(LatencySummary {
+            sample_count: latencies.len(),
+            min: Duration::nanoseconds(latencies[0]),
+            p50: percentile(&latencies, 50),
+            p90: percentile(&latencies, 90),
+            p99: percentile(&latencies, 99),
+            max: Duration::nanoseconds(latencies[latencies.len() - 1]),
+            gateway_p50: percentile(&gateway_latencies, 50),
+        })
+    }
+}
+
+/// Returns the `ts_out` of `rec`, which must have been decoded with `ts_out`.
+pub(crate) fn ts_out(rec: &RecordRef) -> Option<u64> {
+    let bytes: &[u8] = rec.as_ref();
+    // `ts_out` is appended after the record body
+    bytes
+        .len()
+        .checked_sub(8)
+        .and_then(|start| bytes[start..].try_into().ok())
+        .map(u64::from_le_bytes)
+}
+
+fn push_bounded(samples: &mut VecDeque<i64>, window: NonZeroUsize, sample: i64) {
+    if samples.len() == window.get() {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+/// Returns the nearest-rank percentile of `sorted`, or zero if it's empty.
+fn percentile(sorted: &[i64], pct: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    Duration::nanoseconds(sorted[rank - 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_tracker_window() {
+        let mut target = LatencyTracker::new(NonZeroUsize::new(100).unwrap());
+        assert!(target.summary().is_none());
+        // Pushed out of the window
+        target.observe_at(UNDEF_TIMESTAMP, 1_000, 1_000_000);
+        for latency in 1..=100 {
+            target.observe_at(900, 1_000, 1_000 + latency);
+        }
+        let summary = target.summary().unwrap();
+        assert_eq!(summary.sample_count, 100);
+        assert_eq!(summary.min, Duration::nanoseconds(1));
+        assert_eq!(summary.p50, Duration::nanoseconds(50));
+        assert_eq!(summary.p90, Duration::nanoseconds(90));
+        assert_eq!(summary.p99, Duration::nanoseconds(99));
+        assert_eq!(summary.max, Duration::nanoseconds(100));
+        assert_eq!(summary.gateway_p50, Duration::nanoseconds(100));
+    }
+
+    #[test]
+    fn test_latency_tracker_negative_skew() {
+        let mut target = LatencyTracker::new(NonZeroUsize::new(10).unwrap());
+        target.observe_at(UNDEF_TIMESTAMP, 2_000, 1_500);
+        let summary = target.summary().unwrap();
+        assert_eq!(summary.min, Duration::nanoseconds(-500));
+        assert_eq!(summary.gateway_p50, Duration::ZERO);
+    }
+}
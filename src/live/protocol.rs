@@ -44,11 +44,12 @@ where
         Self { sender }
     }
 
-    /// Conducts CRAM authentication with the live gateway. Returns the session ID.
+    /// Conducts CRAM authentication with the live gateway. Returns the session ID and
+    /// the greeting sent by the gateway.
     ///
     /// # Errors
-    /// This function returns an error if the gateway fails to respond or the authentication
-    /// request is rejected.
+    /// This function returns an error if the gateway fails to respond, its version isn't
+    /// compatible with this client, or the authentication request is rejected.
     ///
     /// # Cancel safety
     /// This method is not cancellation safe. If this method is used in a
@@ -63,17 +64,19 @@ where
         dataset: &str,
         send_ts_out: bool,
         heartbeat_interval_s: Option<i64>,
-    ) -> crate::Result<String>
+        user_agent_extension: Option<&str>,
+    ) -> crate::Result<(String, Greeting)>
     where
         R: AsyncBufReadExt + Unpin,
     {
-        let mut greeting = String::new();
+        let mut response = String::new();
         // Greeting
-        recver.read_line(&mut greeting).await?;
-        greeting.pop(); // remove newline
+        recver.read_line(&mut response).await?;
+        response.pop(); // remove newline
 
-        debug!(greeting);
-        let mut response = String::new();
+        debug!(greeting = response);
+        let greeting = Greeting::parse(&response);
+        response.clear();
         // Challenge
         recver.read_line(&mut response).await?;
         response.pop(); // remove newline
@@ -85,8 +88,14 @@ where
         debug!(%challenge, "Received CRAM challenge");
 
         // Send CRAM reply/auth request
-        let auth_req =
-            AuthRequest::new(key, dataset, send_ts_out, heartbeat_interval_s, &challenge);
+        let auth_req = AuthRequest::new(
+            key,
+            dataset,
+            send_ts_out,
+            heartbeat_interval_s,
+            user_agent_extension,
+            &challenge,
+        );
         debug!(?auth_req, "Sending CRAM reply");
         self.sender.write_all(auth_req.as_bytes()).await.unwrap();
 
@@ -99,14 +108,16 @@ where
         response.pop(); // remove newline
 
         let auth_resp = AuthResponse::parse(&response)?;
-        Ok(auth_resp
+        let session_id = auth_resp
             .0
             .get("session_id")
             .map(|sid| (*sid).to_owned())
-            .unwrap_or_default())
+            .unwrap_or_default();
+        Ok((session_id, greeting))
     }
 
-    /// Sends one or more subscription messages for `sub` depending on the number of symbols.
+    /// Sends one or more subscription messages for `sub` depending on the number of symbols
+    /// and their `SType`s.
     ///
     /// # Errors
     /// This function returns an error if it's unable to communicate with the gateway.
@@ -121,6 +132,7 @@ where
             schema,
             stype_in,
             start,
+            end,
             use_snapshot,
             ..
         } = &sub;
@@ -131,12 +143,44 @@ where
                 desc: "cannot request snapshot with start time".to_string(),
             });
         }
+        if let (Some(start), Some(end)) = (start, end) {
+            if end <= start {
+                return Err(Error::bad_arg("end", "must be after start"));
+            }
+        }
         let start_nanos = sub.start.as_ref().map(|start| start.unix_timestamp_nanos());
 
-        for sym_str in sub.symbols.to_chunked_api_string() {
-            let sub_req = SubRequest::new(*schema, *stype_in, start_nanos, *use_snapshot, &sym_str);
+        let chunks: Vec<_> = sub
+            .symbols
+            .group_by_stype(*stype_in)
+            .into_iter()
+            .flat_map(|(stype_in, symbols)| {
+                symbols
+                    .to_chunked_api_string()
+                    .into_iter()
+                    .map(move |sym_str| (stype_in, sym_str))
+            })
+            .collect();
+        let chunk_count = chunks.len();
+        for (i, (stype_in, sym_str)) in chunks.into_iter().enumerate() {
+            let is_last = i + 1 == chunk_count;
+            let sub_req = SubRequest::with_is_last(
+                *schema,
+                stype_in,
+                start_nanos,
+                *use_snapshot,
+                &sym_str,
+                is_last,
+            );
             debug!(?sub_req, "Sending subscription request");
-            self.sender.write_all(sub_req.as_bytes()).await?;
+            if let Err(e) = self.sender.write_all(sub_req.as_bytes()).await {
+                error!(
+                    sent = i,
+                    total = chunk_count,
+                    "Failed to send subscription request, subscription was partially sent"
+                );
+                return Err(Error::from(e));
+            }
         }
         Ok(())
     }
@@ -192,6 +236,62 @@ impl Display for Challenge<'_> {
     }
 }
 
+/// The greeting sent by the live gateway when a connection is opened, which contains
+/// the gateway's version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Greeting {
+    lsg_version: String,
+}
+
+impl Greeting {
+    /// The latest major version of the live gateway whose protocol is supported by
+    /// this client.
+    pub const MAX_SUPPORTED_MAJOR_VERSION: u32 = 0;
+
+    /// Parses the greeting from the given raw response, either of the form
+    /// `lsg_version=VERSION` or `lsg-VERSION`. Other responses are kept as is.
+    pub fn parse(response: &str) -> Self {
+        let lsg_version = response
+            .strip_prefix("lsg_version=")
+            .or_else(|| response.strip_prefix("lsg-"))
+            .unwrap_or(response);
+        Self {
+            lsg_version: lsg_version.to_owned(),
+        }
+    }
+
+    /// Returns the version of the live gateway.
+    pub fn lsg_version(&self) -> &str {
+        &self.lsg_version
+    }
+
+    /// Returns the major version of the live gateway, or `None` if the version isn't
+    /// numeric.
+    pub fn major_version(&self) -> Option<u32> {
+        self.lsg_version.split('.').next()?.parse().ok()
+    }
+
+    /// Checks whether the protocol of the live gateway is supported by this client.
+    /// Gateways whose version isn't numeric are assumed to be compatible.
+    ///
+    /// # Errors
+    /// This function returns an [`Error::IncompatibleGateway`] if the major version of
+    /// the gateway is later than
+    /// [`MAX_SUPPORTED_MAJOR_VERSION`](Self::MAX_SUPPORTED_MAJOR_VERSION), indicating a
+    /// possible breaking change to the protocol.
+    pub fn check_compatible(&self) -> crate::Result<()> {
+        match self.major_version() {
+            Some(major) if major > Self::MAX_SUPPORTED_MAJOR_VERSION => {
+                Err(Error::IncompatibleGateway {
+                    version: self.lsg_version.clone(),
+                    max_supported_major_version: Self::MAX_SUPPORTED_MAJOR_VERSION,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 /// An authentication request to be sent to the live gateway.
 ///
 /// See the [raw API documentation](https://databento.com/docs/api-reference-live/client-control-messages/authentication-request?live=raw)
@@ -205,6 +305,7 @@ impl AuthRequest {
         dataset: &str,
         send_ts_out: bool,
         heartbeat_interval_s: Option<i64>,
+        user_agent_extension: Option<&str>,
         challenge: &Challenge,
     ) -> Self {
         let challenge_key = format!("{challenge}|{}", key.0);
@@ -216,6 +317,9 @@ impl AuthRequest {
         let send_ts_out = send_ts_out as u8;
         let mut req =
                 format!("auth={encoded_response}-{bucket_id}|dataset={dataset}|encoding=dbn|ts_out={send_ts_out}|client=Rust {}", env!("CARGO_PKG_VERSION"));
+        if let Some(user_agent_extension) = user_agent_extension {
+            req = format!("{req} {user_agent_extension}");
+        }
         if let Some(heartbeat_interval_s) = heartbeat_interval_s {
             req = format!("{req}|heartbeat_interval_s={heartbeat_interval_s}");
         }
@@ -286,16 +390,33 @@ impl SubRequest {
     /// Creates the raw API authentication request message from the given parameters.
     /// `symbols` is expected to already be a valid length, such as from
     /// [`Symbols::to_chunked_api_string()`](crate::Symbols::to_chunked_api_string).
+    /// The request is marked as the last one of its subscription.
     pub fn new(
         schema: Schema,
         stype_in: SType,
         start_nanos: Option<i128>,
         use_snapshot: bool,
         symbols: &str,
+    ) -> Self {
+        Self::with_is_last(schema, stype_in, start_nanos, use_snapshot, symbols, true)
+    }
+
+    /// Like [`new()`](Self::new), but `is_last` should only be `true` for the final
+    /// request of a subscription split into several requests, which lets the gateway
+    /// know the subscription is complete.
+    pub(crate) fn with_is_last(
+        schema: Schema,
+        stype_in: SType,
+        start_nanos: Option<i128>,
+        use_snapshot: bool,
+        symbols: &str,
+        is_last: bool,
     ) -> Self {
         let use_snapshot = use_snapshot as u8;
+        let is_last = is_last as u8;
         let args = format!(
-            "schema={schema}|stype_in={stype_in}|symbols={symbols}|snapshot={use_snapshot}"
+            "schema={schema}|stype_in={stype_in}|symbols={symbols}|snapshot={use_snapshot}\
+             |is_last={is_last}"
         );
 
         let sub_str = if let Some(start) = start_nanos {
@@ -341,3 +462,44 @@ impl StartRequest {
         self.as_str().as_bytes()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_greeting() {
+        let target = Greeting::parse("lsg_version=0.7.2");
+        assert_eq!(target.lsg_version(), "0.7.2");
+        assert_eq!(target.major_version(), Some(0));
+        assert!(target.check_compatible().is_ok());
+        let target = Greeting::parse("lsg-test");
+        assert_eq!(target.lsg_version(), "test");
+        assert!(target.major_version().is_none());
+        assert!(target.check_compatible().is_ok());
+        let target = Greeting::parse("lsg_version=1.0.0");
+        assert!(matches!(
+            target.check_compatible(),
+            Err(Error::IncompatibleGateway { version, max_supported_major_version: 0 })
+                if version == "1.0.0"
+        ));
+    }
+
+    #[test]
+    fn test_auth_request_user_agent_extension() {
+        let key = ApiKey::new("32-character-with-lots-of-filler".to_owned()).unwrap();
+        let challenge = Challenge::parse("cram=t7kNhwj4xqR0QYjzFKtBEG2ec2pXJ4FK").unwrap();
+        let target = AuthRequest::new(
+            &key,
+            "GLBX.MDP3",
+            false,
+            None,
+            Some("my-lib/1.0"),
+            &challenge,
+        );
+        assert!(target.as_str().contains(&format!(
+            "|client=Rust {} my-lib/1.0\n",
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+}
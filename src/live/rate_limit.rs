@@ -0,0 +1,159 @@
+//! Limiting the rate at which records are read from the live gateway.
+
+use std::num::NonZeroU64;
+
+use dbn::RecordRef;
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// The maximum rate at which a [`LiveClient`](super::Client) reads records, such as
+/// for development and simulation environments that shouldn't consume a full feed.
+/// Set with [`ClientBuilder::rate_limit()`](super::ClientBuilder::rate_limit).
+///
+/// The limit is enforced with a token bucket: up to [`burst()`](Self::burst) records
+/// or bytes can be read at once, after which reading is paced to the rate so the
+/// long-run average doesn't exceed it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    unit: RateUnit,
+    per_second: NonZeroU64,
+    burst: NonZeroU64,
+}
+
+/// The unit of a [`RateLimit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateUnit {
+    /// Limit the number of records.
+    Records,
+    /// Limit the number of bytes of records.
+    Bytes,
+}
+
+impl RateLimit {
+    /// Creates a new rate limit of `per_second` records per second, with a burst of
+    /// one second's worth of records.
+    pub fn records_per_second(per_second: NonZeroU64) -> Self {
+        Self {
+            unit: RateUnit::Records,
+            per_second,
+            burst: per_second,
+        }
+    }
+
+    /// Creates a new rate limit of `per_second` bytes of records per second, with a
+    /// burst of one second's worth of bytes.
+    pub fn bytes_per_second(per_second: NonZeroU64) -> Self {
+        Self {
+            unit: RateUnit::Bytes,
+            per_second,
+            burst: per_second,
+        }
+    }
+
+    /// Sets the maximum number of records or bytes that can be read at once before
+    /// reading is paced.
+    pub fn with_burst(mut self, burst: NonZeroU64) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    /// Returns the unit of the limit.
+    pub fn unit(&self) -> RateUnit {
+        self.unit
+    }
+
+    /// Returns the number of records or bytes per second.
+    pub fn per_second(&self) -> NonZeroU64 {
+        self.per_second
+    }
+
+    /// Returns the maximum number of records or bytes that can be read at once.
+    pub fn burst(&self) -> NonZeroU64 {
+        self.burst
+    }
+}
+
+/// A token bucket enforcing a [`RateLimit`]. Tokens are consumed after a record is
+/// read and the bucket is allowed to go into debt, which is repaid before the next
+/// record is read. This keeps waiting separate from reading so a cancelled wait never
+/// loses a record.
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst.get() as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Waits until any debt from previously-read records has been repaid.
+    ///
+    /// # Cancel safety
+    /// This method is cancel safe.
+    pub async fn wait(&mut self) {
+        self.refill();
+        if self.tokens < 0.0 {
+            let debt = Duration::from_secs_f64(-self.tokens / self.limit.per_second.get() as f64);
+            sleep_until(self.last_refill + debt).await;
+            self.refill();
+        }
+    }
+
+    /// Consumes the tokens for `rec`.
+    pub fn consume(&mut self, rec: &RecordRef) {
+        self.tokens -= match self.limit.unit {
+            RateUnit::Records => 1.0,
+            RateUnit::Bytes => rec.record_size() as f64,
+        };
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second.get() as f64)
+            .min(self.limit.burst.get() as f64);
+        self.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::TradeMsg;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter() {
+        let rec = TradeMsg::default();
+        let rec = RecordRef::from(&rec);
+        let mut target = RateLimiter::new(
+            RateLimit::records_per_second(NonZeroU64::new(10).unwrap())
+                .with_burst(NonZeroU64::new(2).unwrap()),
+        );
+        let start = Instant::now();
+        // Burst
+        for _ in 0..2 {
+            target.wait().await;
+            target.consume(&rec);
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        for _ in 0..10 {
+            target.wait().await;
+            target.consume(&rec);
+        }
+        // The last record is read after 900ms
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(899), "{elapsed:?}");
+        assert!(elapsed <= Duration::from_millis(1_001), "{elapsed:?}");
+    }
+}
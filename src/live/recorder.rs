@@ -0,0 +1,546 @@
+//! Recording live data to DBN files with optional compression and rotation.
+
+use std::{
+    io,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+};
+
+use async_compression::{tokio::write::ZstdEncoder, Level};
+use dbn::{encode::AsyncDbnEncoder, Metadata, Record, RecordRef, UNDEF_TIMESTAMP};
+use time::{format_description::FormatItem, macros::format_description, OffsetDateTime, Time};
+use tokio::{
+    fs::File,
+    io::{AsyncWrite, AsyncWriteExt, BufWriter},
+};
+use tracing::{info, warn};
+use typed_builder::TypedBuilder;
+
+use crate::Error;
+
+use super::{latency::ts_out, shutdown::RecordHandler};
+
+/// The default template for the names of recorded files.
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{dataset}_{date}_{schema}";
+/// The default zstd compression level.
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+const DAILY_FORMAT: &[FormatItem<'static>] = format_description!("[year][month][day]");
+const HOURLY_FORMAT: &[FormatItem<'static>] = format_description!("[year][month][day]T[hour]");
+
+/// How often a [`Recorder`] starts a new file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rotation {
+    /// Write all records to a single file.
+    #[default]
+    Never,
+    /// Start a new file at the start of each UTC hour.
+    Hourly,
+    /// Start a new file at the start of each UTC day.
+    Daily,
+}
+
+impl Rotation {
+    /// Returns the start of the period containing `dt`, or `None` if files are never
+    /// rotated.
+    fn period_start(&self, dt: OffsetDateTime) -> Option<OffsetDateTime> {
+        match self {
+            Self::Never => None,
+            Self::Hourly => Some(dt.replace_time(Time::from_hms(dt.hour(), 0, 0).unwrap())),
+            Self::Daily => Some(dt.replace_time(Time::MIDNIGHT)),
+        }
+    }
+}
+
+/// The parameters for [`Recorder::new()`]. Use [`RecorderParams::builder()`] to get a
+/// builder type with all the preset defaults.
+#[derive(Debug, Clone, TypedBuilder, PartialEq, Eq)]
+pub struct RecorderParams {
+    /// The directory to write files to. It must already exist.
+    #[builder(setter(into))]
+    pub dir: PathBuf,
+    /// The template for file names, excluding the extension. `{dataset}`, `{date}`,
+    /// and `{schema}` are replaced with the dataset, the UTC date of the first record
+    /// in the file (including the hour with [`Rotation::Hourly`]), and the schema or
+    /// `mixed` if the session has several schemas. Defaults to
+    /// [`DEFAULT_FILENAME_TEMPLATE`].
+    #[builder(default = DEFAULT_FILENAME_TEMPLATE.to_owned(), setter(into))]
+    pub filename_template: String,
+    /// How often to start a new file. Defaults to [`Never`](Rotation::Never).
+    #[builder(default)]
+    pub rotation: Rotation,
+    /// The zstd compression level or `None` to write uncompressed DBN. Defaults to
+    /// [`DEFAULT_ZSTD_LEVEL`].
+    #[builder(default = Some(DEFAULT_ZSTD_LEVEL))]
+    pub zstd_level: Option<i32>,
+    /// Whether to write an [`ArrivalIndex`] sidecar with the `ts_out` of each record
+    /// next to each file, so the file can later be replayed with the spacing the
+    /// records arrived with. Requires a session with
+    /// [`send_ts_out`](super::Client::send_ts_out) and is otherwise ignored. Defaults
+    /// to `false`.
+    #[builder(default)]
+    pub arrival_index: bool,
+}
+
+/// The contents of the index sidecar written next to each recorded file, which
+/// allows selecting files for replay without scanning them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileIndex {
+    /// The earliest primary timestamp of the records in the file as the number of
+    /// nanoseconds since the UNIX epoch, or [`UNDEF_TIMESTAMP`] if none had one.
+    pub start_ts: u64,
+    /// The latest primary timestamp of the records in the file as the number of
+    /// nanoseconds since the UNIX epoch, or [`UNDEF_TIMESTAMP`] if none had one.
+    pub end_ts: u64,
+    /// The number of records in the file.
+    pub record_count: u64,
+}
+
+impl FileIndex {
+    /// Returns the path of the index sidecar for the recorded file at `path`.
+    pub fn sidecar_path(path: impl AsRef<Path>) -> PathBuf {
+        let mut sidecar = path.as_ref().as_os_str().to_owned();
+        sidecar.push(".idx");
+        PathBuf::from(sidecar)
+    }
+
+    /// Reads the index sidecar for the recorded file at `path`.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to read the sidecar or it's
+    /// malformed.
+    pub async fn read(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let contents = tokio::fs::read_to_string(Self::sidecar_path(path)).await?;
+        let mut index = Self::default();
+        for line in contents.lines() {
+            let parsed = line.split_once('=').and_then(|(key, value)| {
+                let value = value.parse().ok()?;
+                match key {
+                    "start_ts" => index.start_ts = value,
+                    "end_ts" => index.end_ts = value,
+                    "record_count" => index.record_count = value,
+                    _ => return None,
+                }
+                Some(())
+            });
+            if parsed.is_none() {
+                return Err(Error::internal(format!(
+                    "malformed line in file index: {line:?}"
+                )));
+            }
+        }
+        Ok(index)
+    }
+
+    /// Returns `true` if the file contains records with a primary timestamp in
+    /// `[start, end)`.
+    pub fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.record_count > 0
+            && self.start_ts != UNDEF_TIMESTAMP
+            && self.start_ts < end
+            && self.end_ts >= start
+    }
+
+    fn update(&mut self, ts: u64) {
+        self.record_count += 1;
+        if ts != UNDEF_TIMESTAMP {
+            if self.start_ts == UNDEF_TIMESTAMP || ts < self.start_ts {
+                self.start_ts = ts;
+            }
+            if self.end_ts == UNDEF_TIMESTAMP || ts > self.end_ts {
+                self.end_ts = ts;
+            }
+        }
+    }
+}
+
+impl Default for FileIndex {
+    fn default() -> Self {
+        Self {
+            start_ts: UNDEF_TIMESTAMP,
+            end_ts: UNDEF_TIMESTAMP,
+            record_count: 0,
+        }
+    }
+}
+
+/// The arrival index sidecar written next to each recorded file when
+/// [`RecorderParams::arrival_index`] is enabled: the `ts_out` of each record in the
+/// file, the time the gateway sent it, in the order the records were written. Because
+/// records are recorded as they arrive, the index is sorted by arrival time, except
+/// for records without a `ts_out`, which have [`UNDEF_TIMESTAMP`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArrivalIndex {
+    ts_outs: Vec<u64>,
+}
+
+impl ArrivalIndex {
+    /// Returns the path of the arrival index sidecar for the recorded file at `path`.
+    pub fn sidecar_path(path: impl AsRef<Path>) -> PathBuf {
+        let mut sidecar = path.as_ref().as_os_str().to_owned();
+        sidecar.push(".arrivals");
+        PathBuf::from(sidecar)
+    }
+
+    /// Reads the arrival index sidecar for the recorded file at `path`.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to read the sidecar or it's
+    /// truncated.
+    pub async fn read(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let contents = tokio::fs::read(Self::sidecar_path(path)).await?;
+        let chunks = contents.chunks_exact(8);
+        if !chunks.remainder().is_empty() {
+            return Err(Error::internal("truncated arrival index"));
+        }
+        Ok(Self {
+            ts_outs: chunks
+                .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+                .collect(),
+        })
+    }
+
+    /// Returns the `ts_out` of each record in the file, in file order.
+    pub fn ts_outs(&self) -> &[u64] {
+        &self.ts_outs
+    }
+
+    /// Returns the number of records in the index.
+    pub fn len(&self) -> usize {
+        self.ts_outs.len()
+    }
+
+    /// Returns `true` if the index has no records.
+    pub fn is_empty(&self) -> bool {
+        self.ts_outs.is_empty()
+    }
+
+    /// Returns the position of the first record that arrived at or after `ts`, such as
+    /// for starting a replay at a given time, or [`len()`](Self::len) if there's none.
+    pub fn position(&self, ts: u64) -> usize {
+        self.ts_outs
+            .iter()
+            .position(|&ts_out| ts_out != UNDEF_TIMESTAMP && ts_out >= ts)
+            .unwrap_or(self.ts_outs.len())
+    }
+
+    /// Returns how long after the record at `position - 1` the record at `position`
+    /// arrived, for pacing a replay. Returns `None` for the first record or if either
+    /// record has no `ts_out`.
+    pub fn delay(&self, position: usize) -> Option<std::time::Duration> {
+        let prev = *self.ts_outs.get(position.checked_sub(1)?)?;
+        let ts_out = *self.ts_outs.get(position)?;
+        if prev == UNDEF_TIMESTAMP || ts_out == UNDEF_TIMESTAMP {
+            return None;
+        }
+        Some(std::time::Duration::from_nanos(ts_out.saturating_sub(prev)))
+    }
+}
+
+/// Writes live records to DBN files, starting a new file as configured by
+/// [`RecorderParams::rotation`]. Each file is followed by an index sidecar once it's
+/// complete. [`finish()`](Self::finish) must be called to complete the last file.
+pub struct Recorder {
+    params: RecorderParams,
+    /// Whether to write arrival index sidecars.
+    index_arrivals: bool,
+    metadata: Metadata,
+    file: Option<RecordingFile>,
+}
+
+struct RecordingFile {
+    period_start: Option<OffsetDateTime>,
+    path: PathBuf,
+    encoder: AsyncDbnEncoder<Box<dyn AsyncWrite + Send + Unpin>>,
+    index: FileIndex,
+    arrivals: Option<BufWriter<File>>,
+}
+
+impl Recorder {
+    /// Creates a new recorder that writes files with the `metadata` of the session,
+    /// such as that returned by [`LiveClient::metadata()`](super::Client::metadata).
+    pub fn new(params: RecorderParams, metadata: Metadata) -> Self {
+        if params.arrival_index && !metadata.ts_out {
+            warn!("Not writing arrival index because the session doesn't have `ts_out`");
+        }
+        Self {
+            index_arrivals: params.arrival_index && metadata.ts_out,
+            params,
+            metadata,
+            file: None,
+        }
+    }
+
+    /// Returns the path of the file currently being written, if any.
+    pub fn current_path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|file| file.path.as_path())
+    }
+
+    /// Writes `rec` to the file for the period of its primary timestamp, completing
+    /// the current file first if it's for a different period. Records without a
+    /// timestamp are written to the current file.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to create or write to a file.
+    pub async fn record(&mut self, rec: RecordRef<'_>) -> crate::Result<()> {
+        let ts = rec.raw_index_ts();
+        let period_start = match ts_to_dt(ts) {
+            Some(dt) => self.params.rotation.period_start(dt),
+            None => match &self.file {
+                Some(file) => file.period_start,
+                None => self.params.rotation.period_start(OffsetDateTime::now_utc()),
+            },
+        };
+        let file = match self.file.take() {
+            Some(file) if file.period_start == period_start => file,
+            Some(file) => {
+                file.finish().await?;
+                self.create_file(period_start, ts).await?
+            }
+            None => self.create_file(period_start, ts).await?,
+        };
+        let file = self.file.insert(file);
+        file.encoder.encode_record_ref(rec).await?;
+        file.index.update(ts);
+        if let Some(arrivals) = file.arrivals.as_mut() {
+            arrivals
+                .write_u64_le(ts_out(&rec).unwrap_or(UNDEF_TIMESTAMP))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Completes the current file and writes its index sidecar.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to write to the file or sidecar.
+    pub async fn finish(mut self) -> crate::Result<()> {
+        RecordHandler::flush(&mut self).await
+    }
+
+    async fn create_file(
+        &self,
+        period_start: Option<OffsetDateTime>,
+        ts: u64,
+    ) -> crate::Result<RecordingFile> {
+        let dt = period_start
+            .or_else(|| ts_to_dt(ts))
+            .unwrap_or_else(OffsetDateTime::now_utc);
+        let date = dt
+            .format(if self.params.rotation == Rotation::Hourly {
+                HOURLY_FORMAT
+            } else {
+                DAILY_FORMAT
+            })
+            .map_err(|e| Error::internal(format!("failed to format date: {e}")))?;
+        let stem = self
+            .params
+            .filename_template
+            .replace("{dataset}", &self.metadata.dataset)
+            .replace("{date}", &date)
+            .replace(
+                "{schema}",
+                self.metadata
+                    .schema
+                    .map_or("mixed", |schema| schema.as_str()),
+            );
+        let extension = if self.params.zstd_level.is_some() {
+            "dbn.zst"
+        } else {
+            "dbn"
+        };
+        let (path, file) = self.create_new(&stem, extension).await?;
+        info!(path = %path.display(), "Recording to new file");
+        let writer: Box<dyn AsyncWrite + Send + Unpin> = match self.params.zstd_level {
+            Some(level) => Box::new(ZstdEncoder::with_quality(
+                BufWriter::new(file),
+                Level::Precise(level),
+            )),
+            None => Box::new(BufWriter::new(file)),
+        };
+        let encoder = AsyncDbnEncoder::new(writer, &self.metadata).await?;
+        let arrivals = if self.index_arrivals {
+            Some(BufWriter::new(
+                File::create(ArrivalIndex::sidecar_path(&path)).await?,
+            ))
+        } else {
+            None
+        };
+        Ok(RecordingFile {
+            period_start,
+            path,
+            encoder,
+            index: FileIndex::default(),
+            arrivals,
+        })
+    }
+
+    /// Creates a new file, adding a numbered suffix to the name rather than
+    /// overwriting a file from an earlier session.
+    async fn create_new(&self, stem: &str, extension: &str) -> crate::Result<(PathBuf, File)> {
+        for suffix in 0.. {
+            let name = if suffix == 0 {
+                format!("{stem}.{extension}")
+            } else {
+                format!("{stem}_{suffix}.{extension}")
+            };
+            let path = self.params.dir.join(name);
+            match File::create_new(&path).await {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        unreachable!("exhausted file suffixes")
+    }
+}
+
+impl RecordHandler for Recorder {
+    async fn handle(&mut self, rec: RecordRef<'_>) -> crate::Result<ControlFlow<()>> {
+        self.record(rec).await?;
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Completes the current file and writes its index sidecar. The next record starts
+    /// a new file.
+    async fn flush(&mut self) -> crate::Result<()> {
+        if let Some(file) = self.file.take() {
+            file.finish().await?;
+        }
+        Ok(())
+    }
+}
+
+impl RecordingFile {
+    async fn finish(self) -> crate::Result<()> {
+        self.encoder.shutdown().await?;
+        if let Some(mut arrivals) = self.arrivals {
+            arrivals.flush().await?;
+        }
+        let index = self.index;
+        tokio::fs::write(
+            FileIndex::sidecar_path(&self.path),
+            format!(
+                "start_ts={}\nend_ts={}\nrecord_count={}\n",
+                index.start_ts, index.end_ts, index.record_count
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+fn ts_to_dt(ts: u64) -> Option<OffsetDateTime> {
+    if ts == UNDEF_TIMESTAMP {
+        return None;
+    }
+    OffsetDateTime::from_unix_timestamp_nanos(i128::from(ts)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{
+        decode::AsyncDbnDecoder, enums::rtype, record::RecordHeader, MetadataBuilder, SType,
+        Schema, TradeMsg, WithTsOut,
+    };
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn trade(ts: OffsetDateTime) -> TradeMsg {
+        TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, 1, ts.unix_timestamp_nanos() as u64),
+            ts_recv: ts.unix_timestamp_nanos() as u64,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recorder_rotates_hourly() {
+        let dir = tempfile::tempdir().unwrap();
+        let metadata = MetadataBuilder::new()
+            .dataset("XNAS.ITCH".to_owned())
+            .start(0)
+            .schema(Some(Schema::Trades))
+            .stype_in(Some(SType::RawSymbol))
+            .stype_out(SType::InstrumentId)
+            .build();
+        let mut target = Recorder::new(
+            RecorderParams::builder()
+                .dir(dir.path())
+                .rotation(Rotation::Hourly)
+                .build(),
+            metadata,
+        );
+        for ts in [
+            datetime!(2024-07-01 13:30 UTC),
+            datetime!(2024-07-01 13:59 UTC),
+            datetime!(2024-07-01 14:00 UTC),
+        ] {
+            target.record(RecordRef::from(&trade(ts))).await.unwrap();
+        }
+        target.finish().await.unwrap();
+
+        let first = dir.path().join("XNAS.ITCH_20240701T13_trades.dbn.zst");
+        let second = dir.path().join("XNAS.ITCH_20240701T14_trades.dbn.zst");
+        let index = FileIndex::read(&first).await.unwrap();
+        assert_eq!(index.record_count, 2);
+        assert_eq!(
+            index.start_ts,
+            datetime!(2024-07-01 13:30 UTC).unix_timestamp_nanos() as u64
+        );
+        assert_eq!(
+            index.end_ts,
+            datetime!(2024-07-01 13:59 UTC).unix_timestamp_nanos() as u64
+        );
+        assert!(index.overlaps(index.end_ts, u64::MAX));
+        assert!(!index.overlaps(index.end_ts + 1, u64::MAX));
+        assert_eq!(FileIndex::read(&second).await.unwrap().record_count, 1);
+
+        let mut decoder = AsyncDbnDecoder::from_zstd_file(&first).await.unwrap();
+        let mut count = 0;
+        while decoder.decode_record::<TradeMsg>().await.unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_recorder_arrival_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let metadata = MetadataBuilder::new()
+            .dataset("XNAS.ITCH".to_owned())
+            .start(0)
+            .schema(Some(Schema::Trades))
+            .stype_in(Some(SType::RawSymbol))
+            .stype_out(SType::InstrumentId)
+            .ts_out(true)
+            .build();
+        let mut target = Recorder::new(
+            RecorderParams::builder()
+                .dir(dir.path())
+                .zstd_level(None)
+                .arrival_index(true)
+                .build(),
+            metadata,
+        );
+        let ts = datetime!(2024-07-01 13:30 UTC);
+        let ts_out = ts.unix_timestamp_nanos() as u64 + 1_000;
+        for (offset, rec_ts_out) in [(0, ts_out), (5, ts_out + 200), (3, UNDEF_TIMESTAMP)] {
+            let rec = WithTsOut::new(trade(ts + time::Duration::nanoseconds(offset)), rec_ts_out);
+            target.record(RecordRef::from(&rec)).await.unwrap();
+        }
+        target.finish().await.unwrap();
+
+        let path = dir.path().join("XNAS.ITCH_20240701_trades.dbn");
+        let index = ArrivalIndex::read(&path).await.unwrap();
+        assert_eq!(index.ts_outs(), [ts_out, ts_out + 200, UNDEF_TIMESTAMP]);
+        assert_eq!(index.delay(0), None);
+        assert_eq!(index.delay(1), Some(std::time::Duration::from_nanos(200)));
+        assert_eq!(index.delay(2), None);
+        assert_eq!(index.position(0), 0);
+        assert_eq!(index.position(ts_out + 1), 1);
+        assert_eq!(index.position(u64::MAX - 1), 3);
+        assert_eq!(FileIndex::read(&path).await.unwrap().record_count, 3);
+    }
+}
@@ -0,0 +1,176 @@
+//! Running a live session until the process is asked to shut down, such as with Ctrl-C
+//! or by a service manager, then closing the session and flushing any output.
+
+use std::{
+    future::Future,
+    ops::ControlFlow,
+    time::{Duration, Instant},
+};
+
+use dbn::RecordRef;
+use tracing::info;
+
+use super::{latency::LatencySummary, Client, SessionSnapshot};
+
+/// Handles the records of a session run with [`run_until_shutdown()`].
+///
+/// This trait is implemented for closures that handle records synchronously and for
+/// [`Recorder`](super::recorder::Recorder).
+pub trait RecordHandler {
+    /// Handles `rec`. Returning [`ControlFlow::Break`] ends the session.
+    ///
+    /// # Errors
+    /// An error ends the session and is returned by [`run_until_shutdown()`].
+    fn handle(
+        &mut self,
+        rec: RecordRef<'_>,
+    ) -> impl Future<Output = crate::Result<ControlFlow<()>>>;
+
+    /// Writes out any buffered output, such as to a file. Called once after the
+    /// session ends, including when it ends with an error.
+    ///
+    /// # Errors
+    /// This function returns an error if it fails to write the output.
+    fn flush(&mut self) -> impl Future<Output = crate::Result<()>> {
+        std::future::ready(Ok(()))
+    }
+}
+
+impl<F> RecordHandler for F
+where
+    F: FnMut(RecordRef<'_>) -> crate::Result<ControlFlow<()>>,
+{
+    async fn handle(&mut self, rec: RecordRef<'_>) -> crate::Result<ControlFlow<()>> {
+        self(rec)
+    }
+}
+
+/// Why a session run with [`run_until_shutdown()`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// The process received a shutdown signal, or the `shutdown` future passed to
+    /// [`run_until()`] completed.
+    Signal,
+    /// The handler returned [`ControlFlow::Break`].
+    Handler,
+    /// The gateway closed the connection or the end of all subscriptions was reached.
+    Ended,
+}
+
+/// A summary of a session run with [`run_until_shutdown()`], which is also logged at
+/// the info level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownSummary {
+    /// Why the session ended.
+    pub reason: ShutdownReason,
+    /// The number of records passed to the handler.
+    pub record_count: u64,
+    /// How long the session ran.
+    pub elapsed: Duration,
+    /// The dataset, subscriptions, and last `ts_event` of the session, which can be
+    /// persisted to resume the session later.
+    pub session: SessionSnapshot,
+    /// The latency of the session, if it was
+    /// [measured](Client::measure_latency).
+    pub latency: Option<LatencySummary>,
+}
+
+/// Waits until the process receives a request to shut down: Ctrl-C on all platforms,
+/// `SIGTERM` on Unix, and the console close and system shutdown events on Windows.
+///
+/// # Errors
+/// This function returns an error if it fails to listen for signals.
+pub async fn shutdown_signal() -> crate::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(windows)]
+    {
+        use tokio::signal::windows::{ctrl_close, ctrl_shutdown};
+
+        let mut close = ctrl_close()?;
+        let mut shutdown = ctrl_shutdown()?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res?,
+            _ = close.recv() => {}
+            _ = shutdown.recv() => {}
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// Passes the records of the started session of `client` to `handler` until the
+/// process receives a [shutdown signal](shutdown_signal), the handler ends the session,
+/// or no more records can be read. Then flushes the handler, closes the session, and
+/// returns a summary.
+///
+/// # Errors
+/// This function returns an error if it fails to listen for signals, read a record,
+/// flush the handler, or close the session, or if the handler returns an error. The
+/// handler is flushed and the session is closed even after an error.
+pub async fn run_until_shutdown(
+    client: Client,
+    handler: &mut impl RecordHandler,
+) -> crate::Result<ShutdownSummary> {
+    run_until(client, handler, shutdown_signal()).await
+}
+
+/// Like [`run_until_shutdown()`], but ends the session when `shutdown` completes rather
+/// than on a shutdown signal, such as for a shutdown triggered by another task.
+///
+/// # Errors
+/// This function returns an error if `shutdown` returns an error, it fails to read a
+/// record, flush the handler, or close the session, or if the handler returns an
+/// error. The handler is flushed and the session is closed even after an error.
+pub async fn run_until(
+    mut client: Client,
+    handler: &mut impl RecordHandler,
+    shutdown: impl Future<Output = crate::Result<()>>,
+) -> crate::Result<ShutdownSummary> {
+    let start = Instant::now();
+    let mut record_count = 0;
+    tokio::pin!(shutdown);
+    let res = loop {
+        let rec = tokio::select! {
+            biased;
+            res = &mut shutdown => break res.map(|()| ShutdownReason::Signal),
+            rec = client.next_record() => rec,
+        };
+        let rec = match rec {
+            Ok(Some(rec)) => rec,
+            Ok(None) => break Ok(ShutdownReason::Ended),
+            Err(e) => break Err(e),
+        };
+        record_count += 1;
+        match handler.handle(rec).await {
+            Ok(ControlFlow::Continue(())) => {}
+            Ok(ControlFlow::Break(())) => break Ok(ShutdownReason::Handler),
+            Err(e) => break Err(e),
+        }
+    };
+    let session = client.session_snapshot();
+    let latency = client.latency();
+    let flush_res = handler.flush().await;
+    let close_res = client.close().await;
+    let reason = res?;
+    flush_res?;
+    close_res?;
+    let summary = ShutdownSummary {
+        reason,
+        record_count,
+        elapsed: start.elapsed(),
+        session,
+        latency,
+    };
+    info!(?summary, "Shut down live session");
+    Ok(summary)
+}
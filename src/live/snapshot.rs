@@ -0,0 +1,204 @@
+//! Types for materializing the book state sent by the gateway when subscribing with
+//! snapshot.
+
+use std::collections::HashMap;
+
+use dbn::{enums::Side, MboMsg, RecordEnum};
+
+use crate::book::PriceLevels;
+
+/// The state of the books received from the gateway at the start of a subscription
+/// with [`use_snapshot`](super::Subscription::use_snapshot), returned by
+/// [`LiveClient::collect_snapshot()`](super::Client::collect_snapshot).
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    books: HashMap<u32, Book>,
+    other_records: Vec<RecordEnum>,
+}
+
+impl Snapshot {
+    /// Returns the book for the instrument with `instrument_id` if one was included
+    /// in the snapshot.
+    pub fn book(&self, instrument_id: u32) -> Option<&Book> {
+        self.books.get(&instrument_id)
+    }
+
+    /// Returns the books for all instruments in the snapshot keyed by instrument ID.
+    pub fn books(&self) -> &HashMap<u32, Book> {
+        &self.books
+    }
+
+    /// Returns the non-MBO records received while collecting the snapshot, such as
+    /// symbol mappings and system messages, in the order they were received.
+    pub fn other_records(&self) -> &[RecordEnum] {
+        &self.other_records
+    }
+
+    pub(crate) fn apply(&mut self, mbo: &MboMsg) {
+        self.books
+            .entry(mbo.hd.instrument_id)
+            .or_default()
+            .apply(mbo);
+    }
+
+    pub(crate) fn push_other(&mut self, rec: RecordEnum) {
+        self.other_records.push(rec);
+    }
+}
+
+/// A market-by-order book for a single instrument.
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    orders: HashMap<u64, MboMsg>,
+    levels: PriceLevels,
+}
+
+/// An aggregated price level of a [`Book`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceLevel {
+    /// The price where every 1 unit corresponds to 1e-9, i.e. 1/1,000,000,000 or
+    /// 0.000000001.
+    pub price: i64,
+    /// The total size of all resting orders at the level.
+    pub size: u64,
+    /// The number of resting orders at the level.
+    pub count: u32,
+}
+
+impl Book {
+    /// Applies the action of `mbo` to the book. Trades and fills don't affect the book
+    /// and are ignored.
+    pub fn apply(&mut self, mbo: &MboMsg) {
+        match mbo.action as u8 {
+            b'R' => {
+                self.orders.clear();
+                self.levels = PriceLevels::default();
+            }
+            b'A' | b'M' => {
+                if let Some(prev) = self.orders.insert(mbo.order_id, mbo.clone()) {
+                    self.remove_level(&prev);
+                }
+                self.add_level(mbo);
+            }
+            b'C' => {
+                if let Some(order) = self.orders.get_mut(&mbo.order_id) {
+                    let prev = order.clone();
+                    order.size = order.size.saturating_sub(mbo.size);
+                    let order = order.clone();
+                    self.remove_level(&prev);
+                    if order.size == 0 {
+                        self.orders.remove(&mbo.order_id);
+                    } else {
+                        self.add_level(&order);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn add_level(&mut self, order: &MboMsg) {
+        self.levels
+            .add(order.side().unwrap_or(Side::None), order.price, order.size);
+    }
+
+    fn remove_level(&mut self, order: &MboMsg) {
+        self.levels
+            .remove(order.side().unwrap_or(Side::None), order.price, order.size);
+    }
+
+    /// Returns the resting orders in the book keyed by order ID.
+    pub fn orders(&self) -> &HashMap<u64, MboMsg> {
+        &self.orders
+    }
+
+    /// Returns the aggregated bid levels from best (highest) to worst price.
+    pub fn bids(&self) -> Vec<PriceLevel> {
+        self.levels
+            .bids()
+            .map(|(price, level)| PriceLevel {
+                price,
+                size: level.size,
+                count: level.count,
+            })
+            .collect()
+    }
+
+    /// Returns the aggregated ask levels from best (lowest) to worst price.
+    pub fn asks(&self) -> Vec<PriceLevel> {
+        self.levels
+            .asks()
+            .map(|(price, level)| PriceLevel {
+                price,
+                size: level.size,
+                count: level.count,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::c_char;
+
+    use dbn::{enums::rtype, record::RecordHeader};
+
+    use super::*;
+
+    fn mbo(order_id: u64, action: u8, side: u8, price: i64, size: u32) -> MboMsg {
+        MboMsg {
+            hd: RecordHeader::new::<MboMsg>(rtype::MBO, 1, 10, 0),
+            order_id,
+            price,
+            size,
+            action: action as c_char,
+            side: side as c_char,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_book_levels() {
+        let mut target = Book::default();
+        target.apply(&mbo(1, b'A', b'B', 100, 5));
+        target.apply(&mbo(2, b'A', b'B', 100, 3));
+        target.apply(&mbo(3, b'A', b'B', 99, 1));
+        target.apply(&mbo(4, b'A', b'A', 101, 2));
+        target.apply(&mbo(5, b'A', b'A', 102, 7));
+        target.apply(&mbo(2, b'C', b'B', 100, 3));
+        target.apply(&mbo(5, b'M', b'A', 103, 6));
+        target.apply(&mbo(4, b'T', b'A', 101, 1));
+        assert_eq!(
+            target.bids(),
+            vec![
+                PriceLevel {
+                    price: 100,
+                    size: 5,
+                    count: 1
+                },
+                PriceLevel {
+                    price: 99,
+                    size: 1,
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(
+            target.asks(),
+            vec![
+                PriceLevel {
+                    price: 101,
+                    size: 2,
+                    count: 1
+                },
+                PriceLevel {
+                    price: 103,
+                    size: 6,
+                    count: 1
+                },
+            ]
+        );
+        target.apply(&mbo(0, b'R', b'N', 0, 0));
+        assert!(target.orders().is_empty());
+    }
+}
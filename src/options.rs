@@ -0,0 +1,319 @@
+//! Building option chains from instrument definitions, such as those of OPRA, to
+//! select the contracts near the money or expiring soon instead of requesting or
+//! subscribing to all symbols.
+//!
+//! ```no_run
+//! # async fn example(client: &databento::HistoricalClient) -> databento::Result<()> {
+//! use databento::{
+//!     dbn::SType,
+//!     historical::timeseries::GetDefinitionsParams,
+//!     options::{ChainFilter, MoneynessWindow, OptionChains},
+//! };
+//! use time::macros::{date, datetime};
+//!
+//! let definitions = client
+//!     .timeseries()
+//!     .get_definitions(
+//!         &GetDefinitionsParams::builder()
+//!             .dataset("OPRA.PILLAR")
+//!             .symbols("SPY.OPT")
+//!             .stype_in(SType::Parent)
+//!             .date_time_range((datetime!(2024-07-01 00:00 UTC), time::Duration::DAY))
+//!             .build(),
+//!     )
+//!     .await?;
+//! let chains = OptionChains::from_definitions(definitions.values());
+//! let filter = ChainFilter::builder()
+//!     .moneyness(MoneynessWindow::new(545.0, 0.95, 1.05))
+//!     .expirations(date!(2024 - 07 - 01)..date!(2024 - 07 - 08))
+//!     .build();
+//! let instrument_ids = chains.get("SPY").unwrap().instrument_ids(&filter);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::{btree_map, BTreeMap},
+    ops::Range,
+};
+
+use dbn::{enums::InstrumentClass, InstrumentDefMsg, FIXED_PRICE_SCALE, UNDEF_TIMESTAMP};
+use time::{Date, OffsetDateTime};
+use typed_builder::TypedBuilder;
+
+/// The option chains of several underlyings, keyed by underlying symbol.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptionChains {
+    chains: BTreeMap<String, OptionChain>,
+}
+
+/// The option contracts of a single underlying, keyed by expiration and then by strike
+/// price.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptionChain {
+    expirations: BTreeMap<OffsetDateTime, BTreeMap<i64, Strike>>,
+}
+
+/// The call and put at a single strike price and expiration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Strike {
+    /// The call option, if one is listed.
+    pub call: Option<OptionContract>,
+    /// The put option, if one is listed.
+    pub put: Option<OptionContract>,
+}
+
+/// A single listed option contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionContract {
+    /// The instrument ID assigned by the publisher.
+    pub instrument_id: u32,
+    /// The instrument name (symbol) provided by the publisher.
+    pub raw_symbol: String,
+}
+
+/// The criteria for selecting contracts from an [`OptionChain`]. Use
+/// [`ChainFilter::builder()`] to get a builder type with all the preset defaults, which
+/// select every contract.
+#[derive(Debug, Clone, Default, TypedBuilder, PartialEq)]
+pub struct ChainFilter {
+    /// The range of moneyness of the strikes to select. Defaults to all strikes.
+    #[builder(default, setter(strip_option))]
+    pub moneyness: Option<MoneynessWindow>,
+    /// The UTC dates of the expirations to select. Defaults to all expirations.
+    #[builder(default, setter(strip_option))]
+    pub expirations: Option<Range<Date>>,
+}
+
+/// A range of strike prices relative to the price of the underlying, where a strike's
+/// moneyness is its price divided by the underlying price.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoneynessWindow {
+    /// The price of the underlying.
+    pub underlying_price: f64,
+    /// The lowest moneyness to select (inclusive).
+    pub min: f64,
+    /// The highest moneyness to select (inclusive).
+    pub max: f64,
+}
+
+impl MoneynessWindow {
+    /// Creates a new window selecting strikes with a moneyness between `min` and `max`,
+    /// e.g. `0.9` and `1.1` for strikes within 10% of `underlying_price`.
+    pub fn new(underlying_price: f64, min: f64, max: f64) -> Self {
+        Self {
+            underlying_price,
+            min,
+            max,
+        }
+    }
+
+    /// Returns `true` if `strike_price`, a fixed-precision price, is within the window.
+    pub fn contains(&self, strike_price: i64) -> bool {
+        let moneyness = strike_price as f64 / FIXED_PRICE_SCALE as f64 / self.underlying_price;
+        (self.min..=self.max).contains(&moneyness)
+    }
+}
+
+impl OptionChains {
+    /// Builds the option chains from `definitions`, such as those returned by
+    /// [`TimeseriesClient::get_definitions()`](crate::historical::timeseries::TimeseriesClient::get_definitions).
+    /// Definitions of instruments other than calls and puts, and those with an
+    /// undefined expiration, are skipped.
+    pub fn from_definitions<'a>(
+        definitions: impl IntoIterator<Item = &'a InstrumentDefMsg>,
+    ) -> Self {
+        let mut chains = Self::default();
+        for definition in definitions {
+            chains.insert(definition);
+        }
+        chains
+    }
+
+    /// Adds the contract of `definition` to the chain of its underlying, replacing any
+    /// previous contract with the same expiration, strike price, and class. Returns
+    /// `false` if `definition` isn't for a call or put or it has an undefined
+    /// expiration.
+    pub fn insert(&mut self, definition: &InstrumentDefMsg) -> bool {
+        let is_call = match definition.instrument_class() {
+            Ok(InstrumentClass::Call) => true,
+            Ok(InstrumentClass::Put) => false,
+            _ => return false,
+        };
+        if definition.expiration == UNDEF_TIMESTAMP {
+            return false;
+        }
+        let (Ok(expiration), Ok(underlying), Ok(raw_symbol)) = (
+            OffsetDateTime::from_unix_timestamp_nanos(i128::from(definition.expiration)),
+            definition.underlying(),
+            definition.raw_symbol(),
+        ) else {
+            return false;
+        };
+        let contract = OptionContract {
+            instrument_id: definition.hd.instrument_id,
+            raw_symbol: raw_symbol.to_owned(),
+        };
+        let strike = self
+            .chains
+            .entry(underlying.to_owned())
+            .or_default()
+            .expirations
+            .entry(expiration)
+            .or_default()
+            .entry(definition.strike_price)
+            .or_default();
+        if is_call {
+            strike.call = Some(contract);
+        } else {
+            strike.put = Some(contract);
+        }
+        true
+    }
+
+    /// Returns the chain of `underlying`.
+    pub fn get(&self, underlying: &str) -> Option<&OptionChain> {
+        self.chains.get(underlying)
+    }
+
+    /// Returns an iterator over the underlying symbols and their chains in order.
+    pub fn iter(&self) -> btree_map::Iter<'_, String, OptionChain> {
+        self.chains.iter()
+    }
+
+    /// Returns the instrument IDs of the contracts selected by `filter` across all
+    /// underlyings.
+    pub fn instrument_ids(&self, filter: &ChainFilter) -> Vec<u32> {
+        self.chains
+            .values()
+            .flat_map(|chain| chain.instrument_ids(filter))
+            .collect()
+    }
+}
+
+impl OptionChain {
+    /// Returns the expirations in chronological order.
+    pub fn expirations(&self) -> impl Iterator<Item = OffsetDateTime> + '_ {
+        self.expirations.keys().copied()
+    }
+
+    /// Returns the strikes of `expiration` keyed by their fixed-precision strike price.
+    pub fn strikes(&self, expiration: OffsetDateTime) -> Option<&BTreeMap<i64, Strike>> {
+        self.expirations.get(&expiration)
+    }
+
+    /// Returns the contracts selected by `filter`, ordered by expiration, strike price,
+    /// and then with calls before puts.
+    pub fn contracts<'a>(
+        &'a self,
+        filter: &'a ChainFilter,
+    ) -> impl Iterator<Item = &'a OptionContract> + 'a {
+        self.expirations
+            .iter()
+            .filter(|(expiration, _)| {
+                filter
+                    .expirations
+                    .as_ref()
+                    .is_none_or(|dates| dates.contains(&expiration.date()))
+            })
+            .flat_map(|(_, strikes)| strikes.iter())
+            .filter(|(strike_price, _)| {
+                filter
+                    .moneyness
+                    .is_none_or(|window| window.contains(**strike_price))
+            })
+            .flat_map(|(_, strike)| strike.call.iter().chain(strike.put.iter()))
+    }
+
+    /// Returns the instrument IDs of the contracts selected by `filter`, such as for
+    /// subscribing with [`SType::InstrumentId`](dbn::SType::InstrumentId).
+    pub fn instrument_ids(&self, filter: &ChainFilter) -> Vec<u32> {
+        self.contracts(filter)
+            .map(|contract| contract.instrument_id)
+            .collect()
+    }
+
+    /// Returns the raw symbols of the contracts selected by `filter`, such as for
+    /// requests with [`SType::RawSymbol`](dbn::SType::RawSymbol).
+    pub fn raw_symbols(&self, filter: &ChainFilter) -> Vec<String> {
+        self.contracts(filter)
+            .map(|contract| contract.raw_symbol.clone())
+            .collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a OptionChains {
+    type Item = (&'a String, &'a OptionChain);
+    type IntoIter = btree_map::Iter<'a, String, OptionChain>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::c_char;
+
+    use dbn::{enums::rtype, record::RecordHeader};
+    use time::macros::{date, datetime};
+
+    use super::*;
+
+    fn c_str<const N: usize>(s: &str) -> [c_char; N] {
+        let mut res = [0; N];
+        for (i, b) in s.bytes().enumerate() {
+            res[i] = b as c_char;
+        }
+        res
+    }
+
+    fn definition(
+        instrument_id: u32,
+        class: InstrumentClass,
+        expiration: OffsetDateTime,
+        strike: i64,
+    ) -> InstrumentDefMsg {
+        InstrumentDefMsg {
+            hd: RecordHeader::new::<InstrumentDefMsg>(rtype::INSTRUMENT_DEF, 1, instrument_id, 0),
+            instrument_class: class as c_char,
+            expiration: expiration.unix_timestamp_nanos() as u64,
+            strike_price: strike * FIXED_PRICE_SCALE,
+            underlying: c_str("SPY"),
+            raw_symbol: c_str(&format!("SPY{instrument_id}")),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_option_chains() {
+        let week1 = datetime!(2024-07-05 20:00 UTC);
+        let week2 = datetime!(2024-07-12 20:00 UTC);
+        let definitions = [
+            definition(1, InstrumentClass::Call, week1, 500),
+            definition(2, InstrumentClass::Put, week1, 500),
+            definition(3, InstrumentClass::Call, week1, 545),
+            definition(4, InstrumentClass::Put, week1, 600),
+            definition(5, InstrumentClass::Call, week2, 545),
+            definition(6, InstrumentClass::Future, week2, 0),
+        ];
+        let mut target = OptionChains::from_definitions(&definitions);
+        assert!(!target.insert(&definitions[5]));
+        let chain = target.get("SPY").unwrap();
+        assert_eq!(chain.expirations().collect::<Vec<_>>(), [week1, week2]);
+        assert_eq!(chain.strikes(week1).unwrap().len(), 3);
+        assert_eq!(
+            chain.instrument_ids(&ChainFilter::default()),
+            [1, 2, 3, 4, 5]
+        );
+        let filter = ChainFilter::builder()
+            .moneyness(MoneynessWindow::new(545.0, 0.9, 1.05))
+            .build();
+        assert_eq!(target.instrument_ids(&filter), [1, 2, 3, 5]);
+        let filter = ChainFilter::builder()
+            .moneyness(MoneynessWindow::new(545.0, 0.95, 1.05))
+            .expirations(date!(2024 - 07 - 01)..date!(2024 - 07 - 08))
+            .build();
+        assert_eq!(chain.raw_symbols(&filter), ["SPY3"]);
+    }
+}
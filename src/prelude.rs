@@ -0,0 +1,61 @@
+//! A curated set of the most commonly used types, for glob importing.
+//!
+//! ```no_run
+//! use databento::prelude::*;
+//! use time::macros::datetime;
+//!
+//! # async fn example() -> databento::Result<()> {
+//! let client = HistoricalClient::builder().key_from_env()?.build()?;
+//! let mut decoder = client
+//!     .timeseries()
+//!     .get_range(
+//!         &GetRangeParams::builder()
+//!             .dataset(Dataset::GlbxMdp3)
+//!             .date_time_range((
+//!                 datetime!(2022-06-10 14:30 UTC),
+//!                 datetime!(2022-06-10 14:40 UTC),
+//!             ))
+//!             .symbols("ES.FUT")
+//!             .stype_in(SType::Parent)
+//!             .schema(Schema::Trades)
+//!             .build(),
+//!     )
+//!     .await?;
+//! while let Some(trade) = decoder.decode_record::<TradeMsg>().await? {
+//!     println!("{trade:?}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The prelude is semi-stable: items may be added in any release, but they're only
+//! removed or renamed as part of a breaking release. Less common types, such as those
+//! for the metadata endpoints, aren't included and should be imported from their
+//! modules. [`Result`](crate::Result) isn't included so it doesn't shadow the
+//! standard library's.
+
+pub use dbn::{
+    record::HasRType, BboMsg, Compression, Dataset, Encoding, ErrorMsg, ImbalanceMsg,
+    InstrumentDefMsg, MboMsg, Mbp10Msg, Mbp1Msg, Metadata, OhlcvMsg, PitSymbolMap, Publisher,
+    Record, RecordEnum, RecordRef, SType, Schema, StatMsg, StatusMsg, SymbolMappingMsg, TradeMsg,
+    TsSymbolMap, VersionUpgradePolicy,
+};
+
+pub use crate::{ApiKey, Error, Symbols};
+
+#[cfg(feature = "historical")]
+pub use crate::{
+    historical::{
+        batch::{DownloadParams, SubmitJobParams},
+        symbology::ResolveParams,
+        timeseries::{GetRangeParams, GetRangeToFileParams},
+        DateRange, DateTimeRange,
+    },
+    HistoricalClient,
+};
+
+#[cfg(feature = "live")]
+pub use crate::{
+    live::{SessionSnapshot, Subscription},
+    LiveClient,
+};
@@ -0,0 +1,308 @@
+//! Detecting common data quality issues, such as crossed books and stale instruments,
+//! in top-of-book and market-by-price records received from either client.
+
+use std::collections::HashMap;
+
+use dbn::{enums::Side, BboMsg, BidAskPair, Mbp10Msg, Mbp1Msg, RecordRef, UNDEF_PRICE};
+use typed_builder::TypedBuilder;
+
+/// The checks performed by a [`QualityMonitor`].
+#[derive(Debug, Clone, PartialEq, Eq, TypedBuilder)]
+pub struct QualityConfig {
+    /// Whether to report books where the best bid is above the best ask. Defaults to
+    /// `true`.
+    #[builder(default = true)]
+    pub check_crossed: bool,
+    /// Whether to report books where the best bid is equal to the best ask. Defaults to
+    /// `true`.
+    #[builder(default = true)]
+    pub check_locked: bool,
+    /// Whether to report levels with a price but no size. Defaults to `true`.
+    #[builder(default = true)]
+    pub check_zero_size: bool,
+    /// The amount of time without an update after which an instrument is reported as
+    /// stale, based on the `ts_recv` of the records being checked. Defaults to `None`,
+    /// which disables the check.
+    #[builder(default, setter(strip_option))]
+    pub stale_after: Option<time::Duration>,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// A data quality issue detected by a [`QualityMonitor`]. Prices are in fixed-precision
+/// and timestamps are the number of nanoseconds since the UNIX epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityEvent {
+    /// The best bid is above the best ask.
+    Crossed {
+        /// The instrument ID of the record.
+        instrument_id: u32,
+        /// The capture-server-received timestamp of the record.
+        ts_recv: u64,
+        /// The best bid price.
+        bid_px: i64,
+        /// The best ask price.
+        ask_px: i64,
+    },
+    /// The best bid is equal to the best ask.
+    Locked {
+        /// The instrument ID of the record.
+        instrument_id: u32,
+        /// The capture-server-received timestamp of the record.
+        ts_recv: u64,
+        /// The best bid and ask price.
+        price: i64,
+    },
+    /// A level has a price but no size.
+    ZeroSizeLevel {
+        /// The instrument ID of the record.
+        instrument_id: u32,
+        /// The capture-server-received timestamp of the record.
+        ts_recv: u64,
+        /// The index of the level, where 0 is the top of the book.
+        level: usize,
+        /// The side of the level.
+        side: Side,
+        /// The price of the level.
+        price: i64,
+    },
+    /// An instrument hasn't been updated for longer than
+    /// [`stale_after`](QualityConfig::stale_after). Each instrument is reported at most
+    /// once until it's updated again.
+    Stale {
+        /// The instrument ID.
+        instrument_id: u32,
+        /// The capture-server-received timestamp of the record that triggered the
+        /// check.
+        ts_recv: u64,
+        /// The capture-server-received timestamp of the last update for the
+        /// instrument.
+        last_ts_recv: u64,
+    },
+}
+
+/// Inspects MBP-1, MBP-10, and BBO records passed to [`check()`](Self::check) in the
+/// order they're received and reports any [`QualityEvent`]s. Other records are
+/// ignored.
+#[derive(Debug, Clone)]
+pub struct QualityMonitor {
+    config: QualityConfig,
+    instruments: HashMap<u32, InstrumentState>,
+    last_stale_check: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct InstrumentState {
+    last_ts_recv: u64,
+    is_stale: bool,
+}
+
+impl QualityMonitor {
+    /// Creates a new monitor performing the checks in `config`.
+    pub fn new(config: QualityConfig) -> Self {
+        Self {
+            config,
+            instruments: HashMap::new(),
+            last_stale_check: 0,
+        }
+    }
+
+    /// Returns the checks performed by the monitor.
+    pub fn config(&self) -> &QualityConfig {
+        &self.config
+    }
+
+    /// Checks `rec` and returns any quality issues found. Stale instruments are
+    /// checked at most once per [`stale_after`](QualityConfig::stale_after) interval.
+    pub fn check(&mut self, rec: RecordRef) -> Vec<QualityEvent> {
+        let mut events = Vec::new();
+        let Some((instrument_id, ts_recv, levels)) = book_levels(&rec) else {
+            return events;
+        };
+        self.check_levels(instrument_id, ts_recv, levels, &mut events);
+        self.instruments.insert(
+            instrument_id,
+            InstrumentState {
+                last_ts_recv: ts_recv,
+                is_stale: false,
+            },
+        );
+        self.check_stale(ts_recv, &mut events);
+        events
+    }
+
+    fn check_levels(
+        &self,
+        instrument_id: u32,
+        ts_recv: u64,
+        levels: &[BidAskPair],
+        events: &mut Vec<QualityEvent>,
+    ) {
+        if let Some(top) = levels.first() {
+            if top.bid_px != UNDEF_PRICE && top.ask_px != UNDEF_PRICE {
+                if self.config.check_crossed && top.bid_px > top.ask_px {
+                    events.push(QualityEvent::Crossed {
+                        instrument_id,
+                        ts_recv,
+                        bid_px: top.bid_px,
+                        ask_px: top.ask_px,
+                    });
+                } else if self.config.check_locked && top.bid_px == top.ask_px {
+                    events.push(QualityEvent::Locked {
+                        instrument_id,
+                        ts_recv,
+                        price: top.bid_px,
+                    });
+                }
+            }
+        }
+        if !self.config.check_zero_size {
+            return;
+        }
+        for (level, pair) in levels.iter().enumerate() {
+            for (side, price, size) in [
+                (Side::Bid, pair.bid_px, pair.bid_sz),
+                (Side::Ask, pair.ask_px, pair.ask_sz),
+            ] {
+                if price != UNDEF_PRICE && size == 0 {
+                    events.push(QualityEvent::ZeroSizeLevel {
+                        instrument_id,
+                        ts_recv,
+                        level,
+                        side,
+                        price,
+                    });
+                }
+            }
+        }
+    }
+
+    fn check_stale(&mut self, ts_recv: u64, events: &mut Vec<QualityEvent>) {
+        let Some(stale_after) = self.config.stale_after else {
+            return;
+        };
+        let stale_after = u64::try_from(stale_after.whole_nanoseconds()).unwrap_or(0);
+        if ts_recv.saturating_sub(self.last_stale_check) < stale_after {
+            return;
+        }
+        self.last_stale_check = ts_recv;
+        for (instrument_id, state) in self.instruments.iter_mut() {
+            if !state.is_stale && ts_recv.saturating_sub(state.last_ts_recv) > stale_after {
+                state.is_stale = true;
+                events.push(QualityEvent::Stale {
+                    instrument_id: *instrument_id,
+                    ts_recv,
+                    last_ts_recv: state.last_ts_recv,
+                });
+            }
+        }
+    }
+}
+
+impl Default for QualityMonitor {
+    fn default() -> Self {
+        Self::new(QualityConfig::default())
+    }
+}
+
+fn book_levels<'a>(rec: &'a RecordRef) -> Option<(u32, u64, &'a [BidAskPair])> {
+    if let Some(mbp1) = rec.get::<Mbp1Msg>() {
+        Some((mbp1.hd.instrument_id, mbp1.ts_recv, mbp1.levels.as_slice()))
+    } else if let Some(mbp10) = rec.get::<Mbp10Msg>() {
+        Some((
+            mbp10.hd.instrument_id,
+            mbp10.ts_recv,
+            mbp10.levels.as_slice(),
+        ))
+    } else {
+        rec.get::<BboMsg>()
+            .map(|bbo| (bbo.hd.instrument_id, bbo.ts_recv, bbo.levels.as_slice()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{enums::rtype, record::RecordHeader, TradeMsg};
+
+    use super::*;
+
+    fn mbp1(instrument_id: u32, ts_recv: u64, bid_px: i64, ask_px: i64, sz: u32) -> Mbp1Msg {
+        Mbp1Msg {
+            hd: RecordHeader::new::<Mbp1Msg>(rtype::MBP_1, 1, instrument_id, ts_recv),
+            ts_recv,
+            levels: [BidAskPair {
+                bid_px,
+                ask_px,
+                bid_sz: sz,
+                ask_sz: sz,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let mut target = QualityMonitor::new(
+            QualityConfig::builder()
+                .stale_after(time::Duration::seconds(1))
+                .build(),
+        );
+        assert!(target
+            .check(RecordRef::from(&mbp1(1, 1, 100, 101, 5)))
+            .is_empty());
+        assert!(target
+            .check(RecordRef::from(&TradeMsg::default()))
+            .is_empty());
+        assert_eq!(
+            target.check(RecordRef::from(&mbp1(2, 2, 101, 100, 5))),
+            vec![QualityEvent::Crossed {
+                instrument_id: 2,
+                ts_recv: 2,
+                bid_px: 101,
+                ask_px: 100
+            }]
+        );
+        assert_eq!(
+            target.check(RecordRef::from(&mbp1(2, 3, 100, 100, 0))),
+            vec![
+                QualityEvent::Locked {
+                    instrument_id: 2,
+                    ts_recv: 3,
+                    price: 100
+                },
+                QualityEvent::ZeroSizeLevel {
+                    instrument_id: 2,
+                    ts_recv: 3,
+                    level: 0,
+                    side: Side::Bid,
+                    price: 100
+                },
+                QualityEvent::ZeroSizeLevel {
+                    instrument_id: 2,
+                    ts_recv: 3,
+                    level: 0,
+                    side: Side::Ask,
+                    price: 100
+                },
+            ]
+        );
+        let ts_recv = 2_000_000_000;
+        assert_eq!(
+            target.check(RecordRef::from(&mbp1(2, ts_recv, 100, 101, 5))),
+            vec![QualityEvent::Stale {
+                instrument_id: 1,
+                ts_recv,
+                last_ts_recv: 1
+            }]
+        );
+        // Only reported once
+        assert!(target
+            .check(RecordRef::from(&mbp1(2, ts_recv * 2, 100, 101, 5)))
+            .is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+//! Sampling and counting records, such as for dashboards and UI applications that don't
+//! need every tick.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    num::NonZeroU64,
+    time::Duration,
+};
+
+use dbn::{enums::rtype, Record, RecordRef, UNDEF_TIMESTAMP};
+
+/// How a [`Sampler`] thins out records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleRate {
+    /// Keep every nth record, starting with the first.
+    EveryNth(NonZeroU64),
+    /// Keep every nth record of each instrument, starting with the first.
+    EveryNthPerInstrument(NonZeroU64),
+    /// Keep at most one record of each instrument per interval, based on the primary
+    /// timestamp of the records.
+    Interval(Duration),
+}
+
+/// Selects a subset of the records passed to [`sample()`](Self::sample) according to
+/// a [`SampleRate`].
+///
+/// Error and system records apply to the whole session and are always kept, as are
+/// records without a timestamp when sampling by interval.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    rate: SampleRate,
+    count: u64,
+    instruments: HashMap<u32, u64>,
+}
+
+impl Sampler {
+    /// Creates a new sampler that keeps records at `rate`.
+    pub fn new(rate: SampleRate) -> Self {
+        Self {
+            rate,
+            count: 0,
+            instruments: HashMap::new(),
+        }
+    }
+
+    /// Returns the rate at which records are kept.
+    pub fn rate(&self) -> SampleRate {
+        self.rate
+    }
+
+    /// Returns `true` if `rec` should be kept.
+    pub fn sample(&mut self, rec: &RecordRef) -> bool {
+        let header = rec.header();
+        if matches!(header.rtype, rtype::ERROR | rtype::SYSTEM) {
+            return true;
+        }
+        match self.rate {
+            SampleRate::EveryNth(n) => {
+                let count = self.count;
+                self.count += 1;
+                count % n == 0
+            }
+            SampleRate::EveryNthPerInstrument(n) => {
+                let count = self.instruments.entry(header.instrument_id).or_default();
+                let is_kept = *count % n == 0;
+                *count += 1;
+                is_kept
+            }
+            SampleRate::Interval(interval) => {
+                let ts = rec.raw_index_ts();
+                if ts == UNDEF_TIMESTAMP {
+                    return true;
+                }
+                let interval = u64::try_from(interval.as_nanos()).unwrap_or(u64::MAX);
+                match self.instruments.get_mut(&header.instrument_id) {
+                    Some(last_kept) if ts.saturating_sub(*last_kept) < interval => false,
+                    Some(last_kept) => {
+                        *last_kept = ts;
+                        true
+                    }
+                    None => {
+                        self.instruments.insert(header.instrument_id, ts);
+                        true
+                    }
+                }
+            }
+        }
+    }
+
+    /// Clears the state of the sampler so the next record of each instrument is kept,
+    /// such as after reconnecting.
+    pub fn reset(&mut self) {
+        self.count = 0;
+        self.instruments.clear();
+    }
+}
+
+/// Counts of the records passed to [`update()`](Self::update), broken down by record
+/// type and publisher.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordCounts {
+    total: u64,
+    bytes: u64,
+    rtypes: BTreeMap<u8, u64>,
+    publishers: BTreeMap<u16, u64>,
+}
+
+impl RecordCounts {
+    /// Creates a new set of counts with no records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts `rec`.
+    pub fn update(&mut self, rec: &RecordRef) {
+        let header = rec.header();
+        self.total += 1;
+        self.bytes += rec.record_size() as u64;
+        *self.rtypes.entry(header.rtype).or_default() += 1;
+        *self.publishers.entry(header.publisher_id).or_default() += 1;
+    }
+
+    /// Returns the total number of records.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the total size of the records in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns the number of records with `rtype`, a value from [`rtype`].
+    pub fn rtype(&self, rtype: u8) -> u64 {
+        self.rtypes.get(&rtype).copied().unwrap_or_default()
+    }
+
+    /// Returns the number of records of each record type, keyed by `rtype`.
+    pub fn rtypes(&self) -> &BTreeMap<u8, u64> {
+        &self.rtypes
+    }
+
+    /// Returns the number of records from the publisher with `publisher_id`. See
+    /// [`Publisher`](dbn::Publisher) for the meaning of each ID.
+    pub fn publisher(&self, publisher_id: u16) -> u64 {
+        self.publishers
+            .get(&publisher_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of records from each publisher, keyed by publisher ID.
+    pub fn publishers(&self) -> &BTreeMap<u16, u64> {
+        &self.publishers
+    }
+
+    /// Resets all counts to zero, such as at the start of each reporting period.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{record::RecordHeader, ErrorMsg, MboMsg, TradeMsg};
+
+    use super::*;
+
+    const SEC: u64 = 1_000_000_000;
+
+    fn trade(instrument_id: u32, ts_recv: u64) -> TradeMsg {
+        TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, instrument_id, ts_recv),
+            ts_recv,
+            ..Default::default()
+        }
+    }
+
+    fn kept(target: &mut Sampler, recs: &[TradeMsg]) -> Vec<u64> {
+        recs.iter()
+            .filter(|rec| target.sample(&RecordRef::from(*rec)))
+            .map(|rec| rec.ts_recv)
+            .collect()
+    }
+
+    #[test]
+    fn test_every_nth() {
+        let recs = (0..7)
+            .map(|i| trade(1 + i as u32 % 2, i))
+            .collect::<Vec<_>>();
+        let mut target = Sampler::new(SampleRate::EveryNth(NonZeroU64::new(3).unwrap()));
+        assert_eq!(kept(&mut target, &recs), [0, 3, 6]);
+        let mut target = Sampler::new(SampleRate::EveryNthPerInstrument(
+            NonZeroU64::new(2).unwrap(),
+        ));
+        assert_eq!(kept(&mut target, &recs), [0, 1, 4, 5]);
+        assert!(target.sample(&RecordRef::from(&ErrorMsg::new(0, "error", true))));
+        target.reset();
+        assert!(target.sample(&RecordRef::from(&trade(1, 8))));
+    }
+
+    #[test]
+    fn test_interval() {
+        let recs = [
+            trade(1, 0),
+            trade(2, SEC / 2),
+            trade(1, SEC / 2),
+            trade(1, SEC),
+            trade(2, SEC),
+            trade(1, 3 * SEC / 2),
+            trade(2, 3 * SEC / 2),
+            trade(1, UNDEF_TIMESTAMP),
+        ];
+        let mut target = Sampler::new(SampleRate::Interval(Duration::from_secs(1)));
+        assert_eq!(
+            kept(&mut target, &recs),
+            [0, SEC / 2, SEC, 3 * SEC / 2, UNDEF_TIMESTAMP]
+        );
+    }
+
+    #[test]
+    fn test_record_counts() {
+        let mut target = RecordCounts::new();
+        target.update(&RecordRef::from(&trade(1, 0)));
+        target.update(&RecordRef::from(&trade(2, 0)));
+        let mbo = MboMsg {
+            hd: RecordHeader::new::<MboMsg>(rtype::MBO, 2, 1, 0),
+            ..Default::default()
+        };
+        target.update(&RecordRef::from(&mbo));
+        assert_eq!(target.total(), 3);
+        assert_eq!(
+            target.bytes(),
+            (2 * std::mem::size_of::<TradeMsg>() + std::mem::size_of::<MboMsg>()) as u64
+        );
+        assert_eq!(target.rtype(rtype::MBP_0), 2);
+        assert_eq!(target.rtype(rtype::MBP_1), 0);
+        assert_eq!(target.publisher(1), 2);
+        assert_eq!(target.publisher(2), 1);
+        assert_eq!(target.rtypes().len(), 2);
+        target.reset();
+        assert_eq!(target, RecordCounts::new());
+    }
+}
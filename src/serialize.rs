@@ -0,0 +1,80 @@
+//! Serde helpers for request parameter fields whose types come from other crates.
+
+#[cfg(any(feature = "historical", feature = "live"))]
+pub(crate) fn default_stype_in() -> dbn::SType {
+    dbn::SType::RawSymbol
+}
+
+#[cfg(feature = "historical")]
+pub(crate) fn default_stype_out() -> dbn::SType {
+    dbn::SType::InstrumentId
+}
+
+#[cfg(feature = "historical")]
+pub(crate) fn default_upgrade_policy() -> dbn::VersionUpgradePolicy {
+    dbn::VersionUpgradePolicy::UpgradeToV2
+}
+
+#[cfg(feature = "historical")]
+pub(crate) fn default_encoding() -> dbn::Encoding {
+    dbn::Encoding::Dbn
+}
+
+#[cfg(feature = "historical")]
+pub(crate) fn default_compression() -> dbn::Compression {
+    dbn::Compression::ZStd
+}
+
+/// (De)serializes a type through its `Display` and `FromStr` implementations.
+pub(crate) mod display_from_str {
+    use std::{fmt::Display, str::FromStr};
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T: Display, S: Serializer>(val: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(val)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let str = String::deserialize(deserializer)?;
+        T::from_str(&str).map_err(de::Error::custom)
+    }
+}
+
+/// (De)serializes a [`VersionUpgradePolicy`](dbn::VersionUpgradePolicy) as `"as_is"` or
+/// `"upgrade_to_v2"`.
+#[cfg(feature = "historical")]
+pub(crate) mod upgrade_policy {
+    use dbn::VersionUpgradePolicy;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    const AS_IS: &str = "as_is";
+    const UPGRADE_TO_V2: &str = "upgrade_to_v2";
+
+    pub fn serialize<S: Serializer>(
+        upgrade_policy: &VersionUpgradePolicy,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(if *upgrade_policy == VersionUpgradePolicy::AsIs {
+            AS_IS
+        } else {
+            UPGRADE_TO_V2
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<VersionUpgradePolicy, D::Error> {
+        let str = String::deserialize(deserializer)?;
+        match str.as_str() {
+            AS_IS => Ok(VersionUpgradePolicy::AsIs),
+            UPGRADE_TO_V2 => Ok(VersionUpgradePolicy::UpgradeToV2),
+            _ => Err(de::Error::unknown_variant(&str, &[AS_IS, UPGRADE_TO_V2])),
+        }
+    }
+}
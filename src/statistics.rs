@@ -0,0 +1,206 @@
+//! Helpers for summarizing the statistics schema, such as settlement prices and open
+//! interest, from [`StatMsg`] records received from either client.
+
+use std::collections::HashMap;
+
+use dbn::{
+    enums::{StatType, StatUpdateAction},
+    StatMsg, FIXED_PRICE_SCALE, UNDEF_PRICE, UNDEF_STAT_QUANTITY,
+};
+
+/// The latest statistics for a set of instruments, updated by passing [`StatMsg`]
+/// records to [`update()`](Self::update) in the order they're received.
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    instruments: HashMap<u32, InstrumentStatistics>,
+}
+
+/// The latest statistics of a single instrument. Fields are `None` when no
+/// statistic of that type has been received or the last one was deleted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InstrumentStatistics {
+    /// The price of the first trade of the session.
+    pub opening_price: Option<PriceStat>,
+    /// The settlement price. Depending on the venue, this may be preliminary until
+    /// it's republished.
+    pub settlement_price: Option<PriceStat>,
+    /// The lowest trade price of the session.
+    pub session_low_price: Option<PriceStat>,
+    /// The highest trade price of the session.
+    pub session_high_price: Option<PriceStat>,
+    /// The closing price of the session.
+    pub close_price: Option<PriceStat>,
+    /// The number of contracts cleared for the session.
+    pub cleared_volume: Option<QuantityStat>,
+    /// The number of open contracts.
+    pub open_interest: Option<QuantityStat>,
+}
+
+/// A price statistic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceStat {
+    /// The price converted from fixed-precision.
+    pub price: f64,
+    /// The reference timestamp of the statistic, such as the date it applies to, as
+    /// the number of nanoseconds since the UNIX epoch.
+    pub ts_ref: u64,
+    /// The capture-server-received timestamp expressed as the number of nanoseconds
+    /// since the UNIX epoch.
+    pub ts_recv: u64,
+}
+
+/// A quantity statistic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuantityStat {
+    /// The quantity.
+    pub quantity: i64,
+    /// The reference timestamp of the statistic, such as the date it applies to, as
+    /// the number of nanoseconds since the UNIX epoch.
+    pub ts_ref: u64,
+    /// The capture-server-received timestamp expressed as the number of nanoseconds
+    /// since the UNIX epoch.
+    pub ts_recv: u64,
+}
+
+impl Statistics {
+    /// Creates a new empty set of statistics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the statistics of the instrument of `stat`. Statistic types that aren't
+    /// tracked in [`InstrumentStatistics`] are ignored.
+    ///
+    /// # Errors
+    /// This function returns an error if `stat` contains an invalid `stat_type` or
+    /// `update_action`.
+    pub fn update(&mut self, stat: &StatMsg) -> crate::Result<()> {
+        let stat_type = stat.stat_type()?;
+        let is_delete = stat.update_action()? == StatUpdateAction::Delete;
+        let instrument = self.instruments.entry(stat.hd.instrument_id).or_default();
+        let field = match stat_type {
+            StatType::OpeningPrice => &mut instrument.opening_price,
+            StatType::SettlementPrice => &mut instrument.settlement_price,
+            StatType::TradingSessionLowPrice => &mut instrument.session_low_price,
+            StatType::TradingSessionHighPrice => &mut instrument.session_high_price,
+            StatType::ClosePrice => &mut instrument.close_price,
+            StatType::ClearedVolume => {
+                instrument.cleared_volume = QuantityStat::new(stat, is_delete);
+                return Ok(());
+            }
+            StatType::OpenInterest => {
+                instrument.open_interest = QuantityStat::new(stat, is_delete);
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+        *field = PriceStat::new(stat, is_delete);
+        Ok(())
+    }
+
+    /// Returns the statistics for the instrument with `instrument_id` if any have been
+    /// received.
+    pub fn get(&self, instrument_id: u32) -> Option<&InstrumentStatistics> {
+        self.instruments.get(&instrument_id)
+    }
+
+    /// Returns the statistics for all instruments keyed by instrument ID.
+    pub fn instruments(&self) -> &HashMap<u32, InstrumentStatistics> {
+        &self.instruments
+    }
+}
+
+impl PriceStat {
+    fn new(stat: &StatMsg, is_delete: bool) -> Option<Self> {
+        if is_delete || stat.price == UNDEF_PRICE {
+            return None;
+        }
+        Some(Self {
+            price: stat.price as f64 / FIXED_PRICE_SCALE as f64,
+            ts_ref: stat.ts_ref,
+            ts_recv: stat.ts_recv,
+        })
+    }
+}
+
+impl QuantityStat {
+    fn new(stat: &StatMsg, is_delete: bool) -> Option<Self> {
+        if is_delete || stat.quantity == UNDEF_STAT_QUANTITY {
+            return None;
+        }
+        Some(Self {
+            quantity: i64::from(stat.quantity),
+            ts_ref: stat.ts_ref,
+            ts_recv: stat.ts_recv,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{enums::rtype, record::RecordHeader};
+
+    use super::*;
+
+    fn stat(stat_type: StatType, action: StatUpdateAction, price: i64, quantity: i32) -> StatMsg {
+        StatMsg {
+            hd: RecordHeader::new::<StatMsg>(rtype::STATISTICS, 1, 10, 0),
+            ts_recv: 2,
+            ts_ref: 1,
+            price,
+            quantity,
+            stat_type: stat_type as u16,
+            update_action: action as u8,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let mut target = Statistics::new();
+        target
+            .update(&stat(
+                StatType::SettlementPrice,
+                StatUpdateAction::New,
+                4_500_250_000_000,
+                UNDEF_STAT_QUANTITY,
+            ))
+            .unwrap();
+        target
+            .update(&stat(
+                StatType::OpenInterest,
+                StatUpdateAction::New,
+                UNDEF_PRICE,
+                1_234,
+            ))
+            .unwrap();
+        target
+            .update(&stat(
+                StatType::Vwap,
+                StatUpdateAction::New,
+                1_000_000_000,
+                0,
+            ))
+            .unwrap();
+        let instrument = target.get(10).unwrap();
+        assert_eq!(
+            instrument.settlement_price,
+            Some(PriceStat {
+                price: 4500.25,
+                ts_ref: 1,
+                ts_recv: 2,
+            })
+        );
+        assert_eq!(instrument.open_interest.unwrap().quantity, 1_234);
+        assert!(instrument.opening_price.is_none());
+        target
+            .update(&stat(
+                StatType::OpenInterest,
+                StatUpdateAction::Delete,
+                UNDEF_PRICE,
+                1_234,
+            ))
+            .unwrap();
+        assert!(target.get(10).unwrap().open_interest.is_none());
+    }
+}
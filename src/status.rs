@@ -0,0 +1,300 @@
+//! Tracking the trading state of instruments from the status schema, such as for
+//! gating orders, from [`StatusMsg`] records received from either client.
+
+use std::{collections::HashMap, fmt};
+
+use dbn::{
+    enums::{StatusAction, StatusReason},
+    StatusMsg,
+};
+
+/// The trading state of an instrument, simplified from the [`StatusAction`] of status
+/// records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TradingState {
+    /// Before the open, including quoting, pre-cross, and opening auction periods.
+    PreOpen,
+    /// Open for trading.
+    Open,
+    /// In the closing period, such as a closing auction.
+    PreClose,
+    /// Trading was halted.
+    Halted,
+    /// Trading was paused.
+    Paused,
+    /// Trading was suspended.
+    Suspended,
+    /// Closed for trading or not available for trading.
+    Closed,
+}
+
+/// The latest status of a single instrument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstrumentStatus {
+    /// The trading state.
+    pub state: TradingState,
+    /// The reason for the latest state change, if the venue provided one.
+    pub reason: Option<StatusReason>,
+    /// Whether the instrument is trading, if the venue provided it.
+    pub is_trading: Option<bool>,
+    /// Whether the instrument is quoting, if the venue provided it.
+    pub is_quoting: Option<bool>,
+    /// Whether short selling is restricted, if the venue provided it.
+    pub is_short_sell_restricted: Option<bool>,
+    /// The capture-server-received timestamp of the latest status record expressed as
+    /// the number of nanoseconds since the UNIX epoch.
+    pub ts_recv: u64,
+}
+
+/// A change in the trading state of an instrument. Returned from
+/// [`StatusTracker::update()`] and passed to the callback set with
+/// [`StatusTracker::on_transition()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateTransition {
+    /// The instrument ID.
+    pub instrument_id: u32,
+    /// The state before the transition, or `None` if it's the first status of the
+    /// instrument.
+    pub from: Option<TradingState>,
+    /// The state after the transition.
+    pub to: TradingState,
+    /// The reason for the transition, if the venue provided one.
+    pub reason: Option<StatusReason>,
+    /// The capture-server-received timestamp of the status record expressed as the
+    /// number of nanoseconds since the UNIX epoch.
+    pub ts_recv: u64,
+}
+
+/// Tracks the trading state of a set of instruments, updated by passing
+/// [`StatusMsg`] records to [`update()`](Self::update) in the order they're
+/// received. It keeps the history of each instrument's transitions to answer which
+/// state it was in at a given time.
+#[derive(Default)]
+pub struct StatusTracker {
+    instruments: HashMap<u32, TrackedInstrument>,
+    on_transition: Option<Box<dyn FnMut(&StateTransition) + Send>>,
+}
+
+#[derive(Debug, Clone)]
+struct TrackedInstrument {
+    status: InstrumentStatus,
+    /// The `ts_recv` and state after each transition in order.
+    transitions: Vec<(u64, TradingState)>,
+}
+
+impl TradingState {
+    /// Returns the state corresponding to `action`, or `None` if `action` doesn't
+    /// change the trading state, such as a short sell restriction change.
+    pub fn from_action(action: StatusAction) -> Option<Self> {
+        Some(match action {
+            StatusAction::PreOpen
+            | StatusAction::PreCross
+            | StatusAction::Quoting
+            | StatusAction::Cross
+            | StatusAction::Rotation
+            | StatusAction::NewPriceIndication => Self::PreOpen,
+            StatusAction::Trading => Self::Open,
+            StatusAction::PreClose => Self::PreClose,
+            StatusAction::Halt => Self::Halted,
+            StatusAction::Pause => Self::Paused,
+            StatusAction::Suspend => Self::Suspended,
+            StatusAction::Close
+            | StatusAction::PostClose
+            | StatusAction::NotAvailableForTrading => Self::Closed,
+            StatusAction::None | StatusAction::SsrChange => return None,
+        })
+    }
+
+    /// Returns `true` if orders can be matched in this state.
+    pub fn is_open(&self) -> bool {
+        matches!(self, Self::Open | Self::PreClose)
+    }
+
+    /// Returns `true` if trading was halted, paused, or suspended.
+    pub fn is_halted(&self) -> bool {
+        matches!(self, Self::Halted | Self::Paused | Self::Suspended)
+    }
+}
+
+impl StatusTracker {
+    /// Creates a new empty tracker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a callback that's called with every state transition.
+    pub fn on_transition(
+        mut self,
+        callback: impl FnMut(&StateTransition) + Send + 'static,
+    ) -> Self {
+        self.on_transition = Some(Box::new(callback));
+        self
+    }
+
+    /// Updates the status of the instrument of `status`, returning the state transition
+    /// if its trading state changed. Records that only update the flags, such as short
+    /// sell restriction changes, never cause a transition, and are ignored until the
+    /// first record with a trading state.
+    ///
+    /// # Errors
+    /// This function returns an error if `status` contains an invalid `action` or
+    /// `reason`.
+    pub fn update(&mut self, status: &StatusMsg) -> crate::Result<Option<StateTransition>> {
+        let action = status.action()?;
+        let reason = match status.reason()? {
+            StatusReason::None => None,
+            reason => Some(reason),
+        };
+        let instrument_id = status.hd.instrument_id;
+        let new_state = TradingState::from_action(action);
+        let tracked = match (self.instruments.get_mut(&instrument_id), new_state) {
+            (Some(tracked), _) => tracked,
+            (None, Some(state)) => {
+                self.instruments
+                    .entry(instrument_id)
+                    .or_insert(TrackedInstrument {
+                        status: InstrumentStatus {
+                            state,
+                            reason,
+                            is_trading: None,
+                            is_quoting: None,
+                            is_short_sell_restricted: None,
+                            ts_recv: status.ts_recv,
+                        },
+                        transitions: Vec::new(),
+                    })
+            }
+            (None, None) => return Ok(None),
+        };
+        let from = tracked.transitions.last().map(|(_, state)| *state);
+        tracked.status.is_trading = status.is_trading().or(tracked.status.is_trading);
+        tracked.status.is_quoting = status.is_quoting().or(tracked.status.is_quoting);
+        tracked.status.is_short_sell_restricted = status
+            .is_short_sell_restricted()
+            .or(tracked.status.is_short_sell_restricted);
+        tracked.status.ts_recv = status.ts_recv;
+        let Some(to) = new_state.filter(|state| from != Some(*state)) else {
+            return Ok(None);
+        };
+        tracked.status.state = to;
+        tracked.status.reason = reason;
+        tracked.transitions.push((status.ts_recv, to));
+        let transition = StateTransition {
+            instrument_id,
+            from,
+            to,
+            reason,
+            ts_recv: status.ts_recv,
+        };
+        if let Some(callback) = self.on_transition.as_mut() {
+            callback(&transition);
+        }
+        Ok(Some(transition))
+    }
+
+    /// Returns the latest status of the instrument with `instrument_id`, if any status
+    /// with a trading state has been received.
+    pub fn get(&self, instrument_id: u32) -> Option<&InstrumentStatus> {
+        self.instruments
+            .get(&instrument_id)
+            .map(|tracked| &tracked.status)
+    }
+
+    /// Returns the current trading state of the instrument with `instrument_id`.
+    pub fn state(&self, instrument_id: u32) -> Option<TradingState> {
+        self.get(instrument_id).map(|status| status.state)
+    }
+
+    /// Returns the trading state of the instrument with `instrument_id` at `ts`, a
+    /// number of nanoseconds since the UNIX epoch, or `None` if it's before the first
+    /// status of the instrument.
+    pub fn state_at(&self, instrument_id: u32, ts: u64) -> Option<TradingState> {
+        let transitions = &self.instruments.get(&instrument_id)?.transitions;
+        let idx = transitions.partition_point(|(transition_ts, _)| *transition_ts <= ts);
+        idx.checked_sub(1).map(|idx| transitions[idx].1)
+    }
+
+    /// Returns `true` if the instrument with `instrument_id` is currently open for
+    /// trading.
+    pub fn is_open(&self, instrument_id: u32) -> bool {
+        self.state(instrument_id)
+            .is_some_and(|state| state.is_open())
+    }
+
+    /// Returns `true` if trading of the instrument with `instrument_id` is currently
+    /// halted, paused, or suspended.
+    pub fn is_halted(&self, instrument_id: u32) -> bool {
+        self.state(instrument_id)
+            .is_some_and(|state| state.is_halted())
+    }
+}
+
+impl fmt::Debug for StatusTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StatusTracker")
+            .field("instruments", &self.instruments)
+            .field("on_transition", &self.on_transition.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use dbn::{enums::rtype, record::RecordHeader};
+
+    use super::*;
+
+    fn status(ts_recv: u64, action: StatusAction, reason: StatusReason) -> StatusMsg {
+        StatusMsg {
+            hd: RecordHeader::new::<StatusMsg>(rtype::STATUS, 1, 10, ts_recv),
+            ts_recv,
+            action: action as u16,
+            reason: reason as u16,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let transitions = Arc::new(Mutex::new(Vec::new()));
+        let mut target = StatusTracker::new().on_transition({
+            let transitions = transitions.clone();
+            move |transition| transitions.lock().unwrap().push(*transition)
+        });
+        let ssr = status(1, StatusAction::SsrChange, StatusReason::None);
+        assert_eq!(target.update(&ssr).unwrap(), None);
+        assert!(target.get(10).is_none());
+        target
+            .update(&status(10, StatusAction::PreOpen, StatusReason::None))
+            .unwrap();
+        target
+            .update(&status(20, StatusAction::Trading, StatusReason::None))
+            .unwrap();
+        // No transition when the state doesn't change
+        assert_eq!(
+            target
+                .update(&status(25, StatusAction::Trading, StatusReason::None))
+                .unwrap(),
+            None
+        );
+        let halt = target
+            .update(&status(30, StatusAction::Halt, StatusReason::NewsPending))
+            .unwrap()
+            .unwrap();
+        assert_eq!(halt.from, Some(TradingState::Open));
+        assert_eq!(halt.reason, Some(StatusReason::NewsPending));
+        assert!(target.is_halted(10));
+        assert!(!target.is_open(10));
+        assert_eq!(target.get(10).unwrap().ts_recv, 30);
+        assert_eq!(target.state_at(10, 5), None);
+        assert_eq!(target.state_at(10, 10), Some(TradingState::PreOpen));
+        assert_eq!(target.state_at(10, 29), Some(TradingState::Open));
+        assert_eq!(target.state_at(10, 100), Some(TradingState::Halted));
+        let transitions = transitions.lock().unwrap();
+        assert_eq!(transitions.len(), 3);
+        assert_eq!(transitions[0].from, None);
+        assert_eq!(*transitions.last().unwrap(), halt);
+    }
+}
@@ -0,0 +1,266 @@
+//! Converting record timestamps to the local time of an exchange.
+
+use dbn::{Record, UNDEF_TIMESTAMP};
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset, Weekday};
+
+/// A time zone of the venues whose data is available from Databento.
+///
+/// Offsets are computed with the daylight saving time rules in effect since 2007 in
+/// the United States and since 1996 in the European Union and United Kingdom, so
+/// conversions of earlier timestamps may be off by an hour for a few weeks each year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeZone {
+    /// Coordinated Universal Time.
+    Utc,
+    /// US Eastern time.
+    NewYork,
+    /// US Central time.
+    Chicago,
+    /// UK time.
+    London,
+    /// Central European time.
+    CentralEurope,
+}
+
+impl TimeZone {
+    /// Returns the time zone of the venue of `dataset`, such as
+    /// [`Chicago`](Self::Chicago) for `GLBX.MDP3`, or `None` if the venue is unknown.
+    pub fn for_dataset(dataset: &str) -> Option<Self> {
+        let venue = dataset.split_once('.').map_or(dataset, |(venue, _)| venue);
+        Some(match venue {
+            "GLBX" => Self::Chicago,
+            "ARCX" | "BATS" | "BATY" | "DBEQ" | "EDGA" | "EDGX" | "EPRL" | "EQUS" | "FINN"
+            | "FINY" | "IEXG" | "IFUS" | "MEMX" | "OPRA" | "XASE" | "XBOS" | "XCHI" | "XCIS"
+            | "XNAS" | "XNYS" | "XPSX" => Self::NewYork,
+            "IFEU" | "NDEX" => Self::London,
+            "XEEE" | "XEUR" => Self::CentralEurope,
+            _ => return None,
+        })
+    }
+
+    /// Converts the enum to the name of the corresponding IANA time zone.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Utc => "UTC",
+            Self::NewYork => "America/New_York",
+            Self::Chicago => "America/Chicago",
+            Self::London => "Europe/London",
+            Self::CentralEurope => "Europe/Berlin",
+        }
+    }
+
+    /// Returns the offset from UTC in effect at `dt`.
+    pub fn offset_at(&self, dt: OffsetDateTime) -> UtcOffset {
+        let utc = dt.to_offset(UtcOffset::UTC);
+        let (standard_hours, is_dst) = match self {
+            Self::Utc => return UtcOffset::UTC,
+            Self::NewYork => (-5, is_us_dst(utc, 5)),
+            Self::Chicago => (-6, is_us_dst(utc, 6)),
+            Self::London => (0, is_eu_dst(utc)),
+            Self::CentralEurope => (1, is_eu_dst(utc)),
+        };
+        // Always a valid offset
+        UtcOffset::from_hms(standard_hours + i8::from(is_dst), 0, 0).unwrap()
+    }
+
+    /// Converts `dt` to the local time of the time zone.
+    pub fn to_local(&self, dt: OffsetDateTime) -> OffsetDateTime {
+        dt.to_offset(self.offset_at(dt))
+    }
+
+    /// Assigns the offset in effect at the local date and time `dt`. Local times that
+    /// are skipped or repeated when clocks change may be off by an hour.
+    pub fn localize(&self, dt: PrimitiveDateTime) -> OffsetDateTime {
+        let guess = dt.assume_offset(self.offset_at(dt.assume_utc()));
+        dt.assume_offset(self.offset_at(guess))
+    }
+}
+
+/// Extension methods for converting the timestamps of any record to the local time of
+/// a [`TimeZone`], including daylight saving time.
+pub trait TimestampExt {
+    /// Returns the `ts_event` of the record in `tz`, or `None` if it's undefined.
+    fn ts_event_in(&self, tz: TimeZone) -> Option<OffsetDateTime>;
+
+    /// Returns the primary timestamp of the record, the one records are sorted by, in
+    /// `tz`, or `None` if it's undefined.
+    fn index_ts_in(&self, tz: TimeZone) -> Option<OffsetDateTime>;
+}
+
+impl<R: Record + ?Sized> TimestampExt for R {
+    fn ts_event_in(&self, tz: TimeZone) -> Option<OffsetDateTime> {
+        ts_in(self.header().ts_event, tz)
+    }
+
+    fn index_ts_in(&self, tz: TimeZone) -> Option<OffsetDateTime> {
+        ts_in(self.raw_index_ts(), tz)
+    }
+}
+
+/// Converts `ts`, a number of nanoseconds since the UNIX epoch, to the local time of
+/// `tz`, or returns `None` if it's undefined. Useful for timestamp fields other than
+/// those of [`TimestampExt`], such as `ts_recv` and `expiration`.
+pub fn ts_in(ts: u64, tz: TimeZone) -> Option<OffsetDateTime> {
+    if ts == UNDEF_TIMESTAMP {
+        return None;
+    }
+    let utc = OffsetDateTime::from_unix_timestamp_nanos(i128::from(ts)).ok()?;
+    Some(tz.to_local(utc))
+}
+
+/// Daylight saving time starts on the second Sunday of March and ends on the first
+/// Sunday of November at 2:00 local time.
+fn is_us_dst(utc: OffsetDateTime, standard_hours_behind: u8) -> bool {
+    let year = utc.year();
+    let start = at_utc(
+        first_of_month(year, Month::March)
+            .previous_day()
+            .unwrap()
+            .nth_next_occurrence(Weekday::Sunday, 2),
+        2 + standard_hours_behind,
+    );
+    let end = at_utc(
+        first_of_month(year, Month::November)
+            .previous_day()
+            .unwrap()
+            .next_occurrence(Weekday::Sunday),
+        2 + standard_hours_behind - 1,
+    );
+    (start..end).contains(&utc)
+}
+
+/// Summer time starts on the last Sunday of March and ends on the last Sunday of
+/// October at 1:00 UTC.
+fn is_eu_dst(utc: OffsetDateTime) -> bool {
+    let year = utc.year();
+    let start = at_utc(
+        first_of_month(year, Month::April).prev_occurrence(Weekday::Sunday),
+        1,
+    );
+    let end = at_utc(
+        first_of_month(year, Month::November).prev_occurrence(Weekday::Sunday),
+        1,
+    );
+    (start..end).contains(&utc)
+}
+
+fn first_of_month(year: i32, month: Month) -> Date {
+    // Only called with years of valid `OffsetDateTime`s
+    Date::from_calendar_date(year, month, 1).unwrap()
+}
+
+fn at_utc(date: Date, hour: u8) -> OffsetDateTime {
+    PrimitiveDateTime::new(date, Time::from_hms(hour, 0, 0).unwrap()).assume_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{enums::rtype, record::RecordHeader, TradeMsg};
+    use time::macros::{datetime, offset};
+
+    use super::*;
+
+    #[test]
+    fn test_offset_at() {
+        for (tz, dt, exp) in [
+            (
+                TimeZone::NewYork,
+                datetime!(2024-03-10 06:59 UTC),
+                offset!(-5),
+            ),
+            (
+                TimeZone::NewYork,
+                datetime!(2024-03-10 07:00 UTC),
+                offset!(-4),
+            ),
+            (
+                TimeZone::NewYork,
+                datetime!(2024-11-03 05:59 UTC),
+                offset!(-4),
+            ),
+            (
+                TimeZone::NewYork,
+                datetime!(2024-11-03 06:00 UTC),
+                offset!(-5),
+            ),
+            (
+                TimeZone::Chicago,
+                datetime!(2024-03-10 07:59 UTC),
+                offset!(-6),
+            ),
+            (
+                TimeZone::Chicago,
+                datetime!(2024-03-10 08:00 UTC),
+                offset!(-5),
+            ),
+            (
+                TimeZone::Chicago,
+                datetime!(2024-11-03 06:59 UTC),
+                offset!(-5),
+            ),
+            (
+                TimeZone::Chicago,
+                datetime!(2024-11-03 07:00 UTC),
+                offset!(-6),
+            ),
+            (
+                TimeZone::London,
+                datetime!(2024-03-31 00:59 UTC),
+                offset!(UTC),
+            ),
+            (
+                TimeZone::London,
+                datetime!(2024-03-31 01:00 UTC),
+                offset!(+1),
+            ),
+            (
+                TimeZone::London,
+                datetime!(2024-10-27 01:00 UTC),
+                offset!(UTC),
+            ),
+            (
+                TimeZone::CentralEurope,
+                datetime!(2024-07-01 12:00 UTC),
+                offset!(+2),
+            ),
+            (
+                TimeZone::CentralEurope,
+                datetime!(2024-12-01 12:00 UTC),
+                offset!(+1),
+            ),
+            (TimeZone::Utc, datetime!(2024-07-01 12:00 UTC), offset!(UTC)),
+        ] {
+            assert_eq!(tz.offset_at(dt), exp, "{tz:?} at {dt}");
+        }
+    }
+
+    #[test]
+    fn test_for_dataset() {
+        assert_eq!(TimeZone::for_dataset("GLBX.MDP3"), Some(TimeZone::Chicago));
+        assert_eq!(TimeZone::for_dataset("XNAS.ITCH"), Some(TimeZone::NewYork));
+        assert_eq!(TimeZone::for_dataset("IFEU.IMPACT"), Some(TimeZone::London));
+        assert_eq!(
+            TimeZone::for_dataset("XEUR.EOBI"),
+            Some(TimeZone::CentralEurope)
+        );
+        assert_eq!(TimeZone::for_dataset("UNKNOWN"), None);
+    }
+
+    #[test]
+    fn test_ts_event_in() {
+        let rec = TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(
+                rtype::MBP_0,
+                1,
+                2,
+                datetime!(2024-07-01 13:30 UTC).unix_timestamp_nanos() as u64,
+            ),
+            ts_recv: UNDEF_TIMESTAMP,
+            ..Default::default()
+        };
+        let local = rec.ts_event_in(TimeZone::NewYork).unwrap();
+        assert_eq!(local, datetime!(2024-07-01 09:30 -4));
+        assert_eq!(local.offset(), offset!(-4));
+        assert!(rec.index_ts_in(TimeZone::NewYork).is_none());
+    }
+}
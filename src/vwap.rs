@@ -0,0 +1,325 @@
+//! Calculating intraday volume-weighted (VWAP) and time-weighted (TWAP) average prices
+//! from [`TradeMsg`] records received from either client.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use dbn::{TradeMsg, FIXED_PRICE_SCALE, UNDEF_PRICE, UNDEF_TIMESTAMP};
+use time::{Date, Time};
+
+use crate::timezone::{ts_in, TimeZone};
+
+/// Calculates the VWAP and TWAP of a set of instruments, updated by passing
+/// [`TradeMsg`] records to [`update()`](Self::update) in the order they're received.
+/// Trades are timestamped by their `ts_recv`.
+#[derive(Debug, Clone)]
+pub struct AveragePrices {
+    window: AverageWindow,
+    session: Option<SessionBoundary>,
+    instruments: HashMap<u32, InstrumentAverages>,
+}
+
+/// The trades included in each average.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AverageWindow {
+    /// All trades since the start of the session, or since the first trade if there
+    /// are no session boundaries.
+    Cumulative,
+    /// The trades within the duration ending at the latest trade.
+    Rolling(Duration),
+}
+
+/// The local time at which each trading session starts, after which the averages
+/// start over. For example, CME Globex sessions start at 17:00 Chicago time on the
+/// previous day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionBoundary {
+    /// The time zone of `start`.
+    pub time_zone: TimeZone,
+    /// The local time at which each session starts.
+    pub start: Time,
+}
+
+/// The averages of a single instrument as of a trade. Derived from each trade passed
+/// to [`AveragePrices::update()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AveragePrice {
+    /// The instrument ID of the trade.
+    pub instrument_id: u32,
+    /// The `ts_recv` of the trade expressed as the number of nanoseconds since the UNIX
+    /// epoch.
+    pub ts_recv: u64,
+    /// The volume-weighted average price.
+    pub vwap: f64,
+    /// The time-weighted average price, where each trade's price is weighted by the
+    /// time until the next trade. Equal to the trade price when the window spans no
+    /// time.
+    pub twap: f64,
+    /// The total size of the trades in the window.
+    pub volume: u64,
+    /// The number of trades in the window.
+    pub trade_count: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct InstrumentAverages {
+    session: Option<Date>,
+    trades: VecDeque<Trade>,
+    /// The sum of price times size of `trades`.
+    notional: i128,
+    volume: u64,
+    /// The sum of each of `trades`' price times the time until the next trade.
+    price_time: i128,
+    /// The last trade that left the window, whose price was in effect at the start of
+    /// the window.
+    evicted: Option<Trade>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Trade {
+    ts: u64,
+    price: i64,
+    size: u64,
+}
+
+impl AveragePrices {
+    /// Creates a new calculator with averages over `window` that never start over.
+    pub fn new(window: AverageWindow) -> Self {
+        Self {
+            window,
+            session: None,
+            instruments: HashMap::new(),
+        }
+    }
+
+    /// Creates a new calculator with averages over `window` that start over at each
+    /// session boundary.
+    pub fn with_session(window: AverageWindow, session: SessionBoundary) -> Self {
+        Self {
+            window,
+            session: Some(session),
+            instruments: HashMap::new(),
+        }
+    }
+
+    /// Returns the window of the averages.
+    pub fn window(&self) -> AverageWindow {
+        self.window
+    }
+
+    /// Returns the session boundary, if any.
+    pub fn session(&self) -> Option<SessionBoundary> {
+        self.session
+    }
+
+    /// Adds `trade` to the averages of its instrument and returns the updated averages,
+    /// or `None` if `trade` has an undefined price or `ts_recv`.
+    pub fn update(&mut self, trade: &TradeMsg) -> Option<AveragePrice> {
+        if trade.price == UNDEF_PRICE || trade.ts_recv == UNDEF_TIMESTAMP {
+            return None;
+        }
+        let instrument_id = trade.hd.instrument_id;
+        let session = self
+            .session
+            .and_then(|session| session.session_date(trade.ts_recv));
+        let averages = self.instruments.entry(instrument_id).or_default();
+        if averages.session != session {
+            *averages = InstrumentAverages {
+                session,
+                ..Default::default()
+            };
+        }
+        let window_start = self.window.start(trade.ts_recv);
+        averages.push(Trade {
+            ts: trade.ts_recv,
+            price: trade.price,
+            size: u64::from(trade.size),
+        });
+        if let Some(window_start) = window_start {
+            averages.evict_before(window_start);
+        }
+        Some(averages.average_price(instrument_id, trade.ts_recv, window_start))
+    }
+
+    /// Returns the averages of the instrument with `instrument_id` as of its latest
+    /// trade, if any have been received.
+    pub fn get(&self, instrument_id: u32) -> Option<AveragePrice> {
+        let averages = self.instruments.get(&instrument_id)?;
+        let ts = averages.trades.back()?.ts;
+        Some(averages.average_price(instrument_id, ts, self.window.start(ts)))
+    }
+}
+
+impl AverageWindow {
+    /// Returns the start of the window ending at `ts`, or `None` if it's cumulative.
+    fn start(&self, ts: u64) -> Option<u64> {
+        match self {
+            Self::Cumulative => None,
+            Self::Rolling(duration) => {
+                Some(ts.saturating_sub(u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)))
+            }
+        }
+    }
+}
+
+impl SessionBoundary {
+    /// Creates a new session boundary at the local time `start` in `time_zone`.
+    pub fn new(time_zone: TimeZone, start: Time) -> Self {
+        Self { time_zone, start }
+    }
+
+    /// Returns the date of the session containing `ts`, i.e. the local date on which
+    /// the session started.
+    fn session_date(&self, ts: u64) -> Option<Date> {
+        let local = ts_in(ts, self.time_zone)?;
+        Some(if local.time() < self.start {
+            local.date().previous_day()?
+        } else {
+            local.date()
+        })
+    }
+}
+
+impl InstrumentAverages {
+    fn push(&mut self, trade: Trade) {
+        if let Some(last) = self.trades.back() {
+            self.price_time +=
+                i128::from(last.price) * i128::from(trade.ts.saturating_sub(last.ts));
+        }
+        self.notional += i128::from(trade.price) * i128::from(trade.size);
+        self.volume += trade.size;
+        self.trades.push_back(trade);
+    }
+
+    fn evict_before(&mut self, window_start: u64) {
+        while let Some(&front) = self.trades.front() {
+            if front.ts >= window_start {
+                break;
+            }
+            self.trades.pop_front();
+            self.notional -= i128::from(front.price) * i128::from(front.size);
+            self.volume -= front.size;
+            if let Some(next) = self.trades.front() {
+                self.price_time -=
+                    i128::from(front.price) * i128::from(next.ts.saturating_sub(front.ts));
+            }
+            self.evicted = Some(front);
+        }
+    }
+
+    fn average_price(
+        &self,
+        instrument_id: u32,
+        ts: u64,
+        window_start: Option<u64>,
+    ) -> AveragePrice {
+        let last_price = self.trades.back().map_or(0, |trade| trade.price);
+        let first_ts = self.trades.front().map_or(ts, |trade| trade.ts);
+        // The price of the last evicted trade was in effect from the start of the window
+        // until the first trade in it
+        let (start, price_time) = match (self.evicted, window_start) {
+            (Some(evicted), Some(window_start)) => (
+                window_start,
+                self.price_time
+                    + i128::from(evicted.price) * i128::from(first_ts.saturating_sub(window_start)),
+            ),
+            _ => (first_ts, self.price_time),
+        };
+        let duration = ts.saturating_sub(start);
+        let twap = if duration == 0 {
+            last_price as f64
+        } else {
+            price_time as f64 / duration as f64
+        };
+        let vwap = if self.volume == 0 {
+            last_price as f64
+        } else {
+            self.notional as f64 / self.volume as f64
+        };
+        AveragePrice {
+            instrument_id,
+            ts_recv: ts,
+            vwap: vwap / FIXED_PRICE_SCALE as f64,
+            twap: twap / FIXED_PRICE_SCALE as f64,
+            volume: self.volume,
+            trade_count: self.trades.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbn::{enums::rtype, record::RecordHeader};
+    use time::macros::{datetime, time};
+
+    use super::*;
+
+    const SEC: u64 = 1_000_000_000;
+
+    fn trade(ts_recv: u64, price: i64, size: u32) -> TradeMsg {
+        TradeMsg {
+            hd: RecordHeader::new::<TradeMsg>(rtype::MBP_0, 1, 10, ts_recv),
+            ts_recv,
+            price: price * FIXED_PRICE_SCALE,
+            size,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cumulative() {
+        let mut target = AveragePrices::new(AverageWindow::Cumulative);
+        let res = target.update(&trade(10 * SEC, 100, 1)).unwrap();
+        assert_eq!(res.vwap, 100.0);
+        assert_eq!(res.twap, 100.0);
+        target.update(&trade(13 * SEC, 104, 3)).unwrap();
+        let res = target.update(&trade(14 * SEC, 90, 0)).unwrap();
+        // (100 * 1 + 104 * 3) / 4
+        assert_eq!(res.vwap, 103.0);
+        // (100 * 3 + 104 * 1) / 4
+        assert_eq!(res.twap, 101.0);
+        assert_eq!(res.volume, 4);
+        assert_eq!(res.trade_count, 3);
+        assert_eq!(target.get(10), Some(res));
+        let undef_price = TradeMsg {
+            price: UNDEF_PRICE,
+            ..trade(15 * SEC, 0, 1)
+        };
+        assert!(target.update(&undef_price).is_none());
+    }
+
+    #[test]
+    fn test_rolling() {
+        let mut target = AveragePrices::new(AverageWindow::Rolling(Duration::from_secs(4)));
+        target.update(&trade(10 * SEC, 100, 1)).unwrap();
+        target.update(&trade(12 * SEC, 110, 1)).unwrap();
+        let res = target.update(&trade(15 * SEC, 120, 2)).unwrap();
+        // The first trade left the window, but its price was in effect from 11 to 12
+        assert_eq!(res.trade_count, 2);
+        assert!((res.vwap - (110.0 + 120.0 * 2.0) / 3.0).abs() < 1e-9);
+        assert_eq!(res.twap, (100.0 + 110.0 * 3.0) / 4.0);
+    }
+
+    #[test]
+    fn test_session_boundary() {
+        let mut target = AveragePrices::with_session(
+            AverageWindow::Cumulative,
+            SessionBoundary::new(TimeZone::Chicago, time!(17:00)),
+        );
+        let ts = |dt: time::OffsetDateTime| dt.unix_timestamp_nanos() as u64;
+        target
+            .update(&trade(ts(datetime!(2024-07-01 15:00 -5)), 100, 1))
+            .unwrap();
+        let res = target
+            .update(&trade(ts(datetime!(2024-07-01 16:59 -5)), 102, 1))
+            .unwrap();
+        assert_eq!(res.vwap, 101.0);
+        let res = target
+            .update(&trade(ts(datetime!(2024-07-01 17:00 -5)), 110, 1))
+            .unwrap();
+        assert_eq!(res.vwap, 110.0);
+        assert_eq!(res.trade_count, 1);
+    }
+}